@@ -0,0 +1,120 @@
+//! Cost of the middleware for a request, run with `cargo bench`.
+//!
+//! To compare a change with the current code, save a baseline before the change with
+//! `cargo bench -- --save-baseline before` and compare with `cargo bench -- --baseline before`.
+//!
+//! The query string is parsed once per request into values borrowing the URL, and values are
+//! only copied when a rule needs them. The order of the fields, their policies and dependencies
+//! are planned by the first request using the same session, method and step rules, later requests
+//! go through the plan. Each request still expands patterns and wildcards, scans the headers
+//! case-insensitively and copies the URL when it has a query string.
+//!
+//! `stacked middlewares` runs two middlewares on the same route. The second one reads the
+//! normalized values of the first from the request and copies them, like its parsed values, only
+//! to merge them with values of its own. An accepted request still allocates the value of each
+//! validated field, the map of its query parameters and the fields matched by patterns and
+//! wildcards.
+//!
+//! No results are recorded in the repository since they depend on the machine, compare baselines
+//! before claiming a change is faster.
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures::executor::block_on;
+use http_service_mock::make_server;
+use tide::http::{Method, Request};
+use tide::{Server, StatusCode};
+use tide_validator::validators::{
+    is_email, is_integer, is_length_between, is_max_length, is_required, is_uuid, normalize_bool,
+    parsed, ValidationError,
+};
+use tide_validator::{HttpField, ValidatorMiddleware};
+
+use async_std::io::prelude::*;
+use async_std::io::Read;
+
+fn app() -> Server<()> {
+    let mut app = tide::new();
+
+    let mut listing = ValidatorMiddleware::new();
+    listing.add_validator(HttpField::query_param("page"), is_integer);
+    listing.add_validator(HttpField::query_param("per_page"), is_integer);
+    listing.add_validator(HttpField::query_param("q"), is_max_length(100));
+    listing.add_validator(HttpField::header("X-Request-Id"), is_uuid);
+    app.at("/users")
+        .middleware(listing)
+        .get(|_| async { Ok("users") });
+
+    let mut creation = ValidatorMiddleware::new();
+    creation.add_validator(HttpField::body_field("name"), is_required);
+    creation.add_validator(HttpField::body_field("name"), is_length_between(1, 50));
+    creation.add_validator(HttpField::body_field("email"), is_required);
+    creation.add_validator(HttpField::body_field("email"), is_email);
+    creation.add_validator(HttpField::body_field("address.city"), is_max_length(50));
+    app.at("/users")
+        .middleware(creation)
+        .post(|_| async { Ok("created") });
+
+    // Stacked middlewares, the second one adds its parsed values to the ones of the first
+    let mut filters = ValidatorMiddleware::new();
+    filters.add_normalizer(HttpField::query_param("active"), normalize_bool);
+    filters.add_parser(
+        HttpField::query_param("page"),
+        parsed::<u32, ValidationError>(),
+    );
+    let mut item = ValidatorMiddleware::new();
+    item.add_validator(HttpField::param("id"), is_required);
+    item.add_parser(HttpField::param("id"), parsed::<u64, ValidationError>());
+    app.at("/items/:id")
+        .middleware(filters)
+        .middleware(item)
+        .get(|_| async { Ok("item") });
+
+    app
+}
+
+fn read_body(mut res: impl Read + Unpin) {
+    let mut buf = Vec::new();
+    block_on(res.read_to_end(&mut buf)).unwrap();
+}
+
+fn handle(c: &mut Criterion) {
+    let mut server = make_server(app()).unwrap();
+
+    c.bench_function("query and headers", |b| {
+        b.iter(|| {
+            let url = "http://localhost/users?page=2&per_page=50&q=grib%C3%B6uille&sort=name";
+            let mut req = Request::new(Method::Get, url.parse().unwrap());
+            req.insert_header("X-Request-Id", "67e55044-10b1-426f-9247-bb680e5fe0c8")
+                .unwrap();
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), StatusCode::Ok);
+            read_body(res)
+        })
+    });
+
+    c.bench_function("json body", |b| {
+        b.iter(|| {
+            let mut req = Request::new(Method::Post, "http://localhost/users".parse().unwrap());
+            req.insert_header("Content-Type", "application/json")
+                .unwrap();
+            req.set_body(
+                r#"{"name": "Gribouille", "email": "gribouille@example.com", "address": {"city": "Paris"}}"#,
+            );
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), StatusCode::Ok);
+            read_body(res)
+        })
+    });
+
+    c.bench_function("stacked middlewares", |b| {
+        b.iter(|| {
+            let url = "http://localhost/items/42?active=yes&page=3";
+            let req = Request::new(Method::Get, url.parse().unwrap());
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), StatusCode::Ok);
+            read_body(res)
+        })
+    });
+}
+
+criterion_group!(benches, handle);
+criterion_main!(benches);
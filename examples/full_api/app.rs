@@ -0,0 +1,119 @@
+//! Routes of the `full_api` example, shared with the integration tests in `tests/full_api.rs`
+use tide::{Request, Response, Server, StatusCode};
+use tide_validator::multipart::{has_extension, is_file, is_max_size};
+use tide_validator::validators::{
+    is_email, is_integer_between, is_length_between, is_min_length, is_required, is_uuid, one_of,
+};
+use tide_validator::{ErrorShape, HttpField, Validation, ValidationError, ValidatorMiddleware};
+
+/// Tenants known by the directory, `maintenance` is there but the directory cannot answer for it
+const TENANTS: &[&str] = &["acme", "globex", "maintenance"];
+
+pub fn app() -> Server<()> {
+    let mut app = tide::new();
+
+    // Path parameter, header and cookie, errors are sent as is
+    let mut user_validators = ValidatorMiddleware::new();
+    user_validators.add_validator(HttpField::param("id"), is_uuid);
+    user_validators.add_validator(HttpField::header("X-Request-Id"), is_uuid);
+    user_validators.add_validator(HttpField::cookie("session"), is_required);
+    user_validators.add_validator(HttpField::cookie("session"), is_min_length(16));
+    app.at("/users/:id")
+        .middleware(user_validators)
+        .get(|_: Request<()>| async { Ok("user") });
+
+    // Query parameters, every error grouped by field and unknown parameters rejected
+    let mut search_validators = ValidatorMiddleware::new()
+        .error_shape(ErrorShape::GroupedByField)
+        .collect_all_errors(true)
+        .deny_unknown_query_params();
+    search_validators.add_validator(HttpField::query_param("q"), is_required);
+    search_validators.add_validator(HttpField::query_param("page"), is_integer_between(1, 100));
+    search_validators.add_validator(HttpField::query_param("sort"), one_of(&["asc", "desc"]));
+    app.at("/search")
+        .middleware(search_validators)
+        .get(|_: Request<()>| async { Ok("results") });
+
+    // Fields of a JSON body, errors sent as Problem Details and unknown fields rejected
+    let mut signup_validators = ValidatorMiddleware::new()
+        .error_shape(ErrorShape::Problem)
+        .deny_unknown_fields();
+    signup_validators.add_validator(HttpField::body_field("name"), is_required);
+    signup_validators.add_validator(HttpField::body_field("name"), is_length_between(1, 64));
+    signup_validators.add_validator(HttpField::body_field("email"), is_required);
+    signup_validators.add_validator(HttpField::body_field("email"), is_email);
+    signup_validators.add_validator(
+        HttpField::body_field("address.country"),
+        one_of(&["FR", "DE", "US"]),
+    );
+    app.at("/users")
+        .middleware(signup_validators)
+        .post(|mut req: Request<()>| async move {
+            // The body is still available once validated
+            let user: serde_json::Value = req.body_json().await?;
+            Ok(Response::new(StatusCode::Ok).body_json(&user)?)
+        });
+
+    // Matrix parameters of a path segment, e.g. `/catalog/items;limit=10`
+    let mut catalog_validators = ValidatorMiddleware::new();
+    catalog_validators.add_validator(
+        HttpField::matrix_param("items", "limit"),
+        is_integer_between(1, 50),
+    );
+    app.at("/catalog/:items")
+        .middleware(catalog_validators)
+        .get(|_: Request<()>| async { Ok("items") });
+
+    // Text fields and files of a multipart body
+    let mut upload_validators = ValidatorMiddleware::new();
+    upload_validators.add_validator(HttpField::multipart("title"), is_required);
+    upload_validators.add_file_validator(HttpField::multipart("avatar"), is_file);
+    upload_validators.add_file_validator(
+        HttpField::multipart("avatar"),
+        has_extension(&["png", "jpg"]),
+    );
+    upload_validators.add_file_validator(HttpField::multipart("avatar"), is_max_size(1024));
+    app.at("/avatars")
+        .middleware(upload_validators)
+        .post(|_: Request<()>| async { Ok("uploaded") });
+
+    // Custom field read from the subdomain, checked against a directory which can be unavailable
+    let mut tenant_validators = ValidatorMiddleware::new()
+        .value_provider(|req: &Request<()>, name: &str| match name {
+            "tenant" => req
+                .uri()
+                .host_str()
+                .and_then(|host| host.split_once('.'))
+                .map(|(subdomain, _)| subdomain.to_string()),
+            _ => None,
+        })
+        .on_validator_error(|field, err| {
+            Response::new(StatusCode::ServiceUnavailable).body_string(format!(
+                "cannot check '{}': {}",
+                field.name(),
+                err
+            ))
+        });
+    tenant_validators.add_validator(HttpField::custom("tenant"), is_required);
+    tenant_validators.add_fallible_validator(HttpField::custom("tenant"), tenant_exists);
+    app.at("/tenant")
+        .middleware(tenant_validators)
+        .get(|_: Request<()>| async { Ok("tenant") });
+
+    app
+}
+
+fn tenant_exists(
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Validation<ValidationError, String> {
+    match field_value {
+        Some("maintenance") => Validation::Error(String::from("tenant directory is unavailable")),
+        Some(tenant) if !TENANTS.contains(&tenant) => Validation::Invalid(ValidationError::new(
+            field_name,
+            "tenant",
+            format!("tenant '{}' doesn't exist", tenant),
+        )),
+        _ => Validation::Valid,
+    }
+}
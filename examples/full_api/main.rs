@@ -0,0 +1,18 @@
+//! An API using every kind of field and error format, try it with:
+//!
+//! ```sh
+//! curl -i 'http://127.0.0.1:8080/search?q=cats&page=0&sort=up'
+//! curl -i -X POST -d '{"name": "", "email": "nope"}' http://127.0.0.1:8080/users
+//! curl -i 'http://127.0.0.1:8080/catalog/items;limit=100'
+//! ```
+use async_std::io;
+use async_std::task;
+
+mod app;
+
+fn main() -> io::Result<()> {
+    task::block_on(async {
+        app::app().listen("127.0.0.1:8080").await?;
+        Ok(())
+    })
+}
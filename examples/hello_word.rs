@@ -12,7 +12,7 @@ async fn main() -> io::Result<()> {
             .map(|_| ())
             .map_err(|_| "invalid number")
     };
-    validator_middleware.add_validator(HttpField::Param("age"), is_number);
+    validator_middleware.add_validator(HttpField::param("age"), is_number);
     app.at("/test/:age")
         .middleware(validator_middleware)
         .get(|_: tide::Request<()>| async move { Ok("Hello World") });
@@ -16,10 +16,10 @@ fn main() -> io::Result<()> {
 
         let mut validator_middleware = ValidatorMiddleware::new();
         // 'age' is the parameter name inside the route '/test/:name'
-        validator_middleware.add_validator(HttpField::Param("age"), is_number);
+        validator_middleware.add_validator(HttpField::param("age"), is_number);
 
         // You can also add multiple validators on a single parameter to check different things
-        validator_middleware.add_validator(HttpField::Param("age"), is_required);
+        validator_middleware.add_validator(HttpField::param("age"), is_required);
 
         // You can assign different middleware for each routes so different validators for each routes
         app.at("/test/:age").middleware(validator_middleware).get(
@@ -0,0 +1,307 @@
+//! Authentication of requests, available with the `auth` feature: `Authorization: Bearer` headers
+//! carrying JSON Web Tokens signed with HS256 or RS256.
+//!
+//! Verified claims are stored in request extensions. Errors are built-in errors sent like other
+//! validation errors, use a [`ResponsePolicy`](../struct.ResponsePolicy.html) to answer
+//! `401 Unauthorized`.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::auth::jwt_hs256;
+//!
+//! #[derive(Deserialize)]
+//! struct Claims {
+//!     sub: String,
+//!     scope: String,
+//! }
+//!
+//! let validator_middleware = ValidatorMiddleware::new()
+//!     .response_policy(ResponsePolicy::new().for_location(Location::Header, StatusCode::Unauthorized, true))
+//!     .preset(jwt_hs256(b"secret").audience(&["api"]).claims::<Claims>());
+//! app.at("/me").middleware(validator_middleware).get(|req: Request<()>| async move {
+//!     let claims = req.local::<Claims>().unwrap();
+//!     // ...
+//! });
+//! ```
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::presets::Preset;
+use crate::validators::{self, ValidationError};
+use crate::{ConfigError, HttpField, ValidatorMiddleware};
+
+/// Token of an `Authorization: Bearer <token>` header, the scheme is case-insensitive
+pub fn bearer_token(value: &str) -> Option<&str> {
+    let mut parts = value.trim().splitn(2, ' ');
+    let scheme = parts.next()?;
+    let token = parts.next()?.trim_start();
+    if scheme.eq_ignore_ascii_case("Bearer") && is_token68(token) {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+/// Check if the field is a well-formed `Bearer <token>` credential
+pub fn is_bearer(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if bearer_token(value).is_none() => Err(ValidationError::new(
+            field_name,
+            "bearer",
+            format!("'{}' must be a bearer token", field_name),
+        )
+        .with_param(
+            "example",
+            "Bearer eyJhbGciOiJIUzI1NiJ9.e30.ZRrHA1JJJW8opsbCGfG_HACGpVUMN_a9IV7pAx_Zmeo",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Preset verifying JSON Web Tokens, see [`jwt_hs256`](fn.jwt_hs256.html) and
+/// [`jwt_rs256`](fn.jwt_rs256.html). The token is required, its signature and `exp` claim are
+/// always checked, the `nbf` claim when present, the audience and issuer when configured. Claims are
+/// deserialized into `C` and stored in request extensions, read them with `req.local::<C>()`.
+pub struct Jwt<C = serde_json::Value> {
+    field: HttpField<'static>,
+    key: DecodingKey<'static>,
+    validation: Validation,
+    claims: PhantomData<fn() -> C>,
+}
+
+impl<C> fmt::Debug for Jwt<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwt")
+            .field("field", &self.field)
+            .field("validation", &self.validation)
+            .finish()
+    }
+}
+
+/// Verify tokens signed with HMAC SHA-256 and `secret`
+pub fn jwt_hs256(secret: &[u8]) -> Jwt {
+    Jwt::new(
+        DecodingKey::from_secret(secret).into_static(),
+        Algorithm::HS256,
+    )
+}
+
+/// Verify tokens signed with RSA SHA-256, `public_key` is a PEM encoded RSA public key
+pub fn jwt_rs256(public_key: &[u8]) -> Result<Jwt, ConfigError> {
+    let key = DecodingKey::from_rsa_pem(public_key)
+        .map_err(|err| ConfigError::InvalidKey(err.to_string()))?;
+    Ok(Jwt::new(key.into_static(), Algorithm::RS256))
+}
+
+impl Jwt {
+    fn new(key: DecodingKey<'static>, algorithm: Algorithm) -> Self {
+        Jwt {
+            field: HttpField::header("Authorization"),
+            key,
+            validation: Validation::new(algorithm),
+            claims: PhantomData,
+        }
+    }
+}
+
+impl<C> Jwt<C> {
+    /// Deserialize the claims into `D`, `serde_json::Value` by default
+    pub fn claims<D>(self) -> Jwt<D> {
+        Jwt {
+            field: self.field,
+            key: self.key,
+            validation: self.validation,
+            claims: PhantomData,
+        }
+    }
+
+    /// Read the token from another field than the `Authorization` header, given without `Bearer` prefix
+    pub fn field(mut self, field: HttpField<'static>) -> Self {
+        self.field = field;
+        self
+    }
+
+    /// Require the `aud` claim to be one of `audience`
+    pub fn audience(mut self, audience: &[&str]) -> Self {
+        self.validation.set_audience(audience);
+        self
+    }
+
+    /// Require the `iss` claim to be `issuer`
+    pub fn issuer(mut self, issuer: &str) -> Self {
+        self.validation.iss = Some(issuer.to_string());
+        self
+    }
+
+    /// Accept `exp` and `nbf` claims off by `seconds`, to account for clock skew
+    pub fn leeway(mut self, seconds: u64) -> Self {
+        self.validation.leeway = seconds;
+        self
+    }
+}
+
+impl<C: DeserializeOwned> Jwt<C> {
+    /// Verify the token of the field and decode its claims
+    pub fn verify(
+        &self,
+        field_name: &str,
+        field_value: Option<&str>,
+    ) -> Result<C, ValidationError> {
+        validators::is_required(field_name, field_value)?;
+        let value = field_value.unwrap_or_default();
+        let token = if self.field.name().eq_ignore_ascii_case("Authorization") {
+            is_bearer(field_name, field_value)?;
+            bearer_token(value).unwrap_or_default()
+        } else {
+            value
+        };
+        let invalid = |reason: &str| {
+            ValidationError::new(
+                field_name,
+                "jwt",
+                format!("'{}' is not a valid token: {}", field_name, reason),
+            )
+            .with_param("reason", reason)
+        };
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &self.key, &self.validation)
+            .map_err(|err| {
+                invalid(match err.kind() {
+                    ErrorKind::ExpiredSignature => "expired",
+                    ErrorKind::InvalidAudience => "for another audience",
+                    ErrorKind::InvalidIssuer => "from another issuer",
+                    ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => {
+                        "not signed with the expected key"
+                    }
+                    _ => "malformed",
+                })
+            })?
+            .claims;
+        // jsonwebtoken rejects tokens without `nbf` when it checks it, it's optional here
+        if let Some(not_before) = claims.get("nbf").and_then(serde_json::Value::as_u64) {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or_default();
+            if not_before > now + self.validation.leeway {
+                return Err(invalid("not valid yet"));
+            }
+        }
+        serde_json::from_value(claims).map_err(|_| invalid("malformed"))
+    }
+}
+
+impl<T, State, C> Preset<T, State> for Jwt<C>
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+    C: DeserializeOwned + Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        let field = self.field.clone();
+        let field_name = field.name().to_string();
+        middleware.add_resolver(field, move |value: Option<String>| {
+            futures::future::ready(self.verify(&field_name, value.as_deref()).map_err(T::from))
+        });
+    }
+}
+
+/// Check if the token is made of the base64 and base64url alphabets followed by optional
+/// padding (RFC 7235 token68)
+fn is_token68(token: &str) -> bool {
+    let token = token.trim_end_matches('=');
+    !token.is_empty()
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._~+/".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn token(claims: serde_json::Value) -> String {
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        format!("Bearer {}", token)
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn bearer_tokens() {
+        assert_eq!(bearer_token("Bearer abc.def-_"), Some("abc.def-_"));
+        assert_eq!(bearer_token("bearer abc=="), Some("abc=="));
+        assert_eq!(bearer_token("Basic abc"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("Bearer a b"), None);
+        assert!(is_bearer("Authorization", None).is_ok());
+        assert_eq!(
+            is_bearer("Authorization", Some("abc")).unwrap_err().code,
+            "bearer"
+        );
+    }
+
+    #[test]
+    fn jwt_claims() {
+        let jwt = jwt_hs256(b"secret").audience(&["api"]);
+        let claims = jwt
+            .verify(
+                "Authorization",
+                Some(&token(
+                    serde_json::json!({"sub": "1", "aud": "api", "exp": now() + 60}),
+                )),
+            )
+            .unwrap();
+        assert_eq!(claims["sub"], "1");
+
+        let reason = |value: &str| {
+            jwt.verify("Authorization", Some(value))
+                .unwrap_err()
+                .params
+                .unwrap()["reason"]
+                .clone()
+        };
+        assert_eq!(
+            reason(&token(serde_json::json!({"aud": "api", "exp": now() - 60}))),
+            "expired"
+        );
+        assert_eq!(
+            reason(&token(
+                serde_json::json!({"aud": "api", "exp": now() + 60, "nbf": now() + 60})
+            )),
+            "not valid yet"
+        );
+        assert_eq!(
+            reason(&token(serde_json::json!({"aud": "web", "exp": now() + 60}))),
+            "for another audience"
+        );
+        let forged = jwt_hs256(b"other").verify(
+            "Authorization",
+            Some(&token(serde_json::json!({"exp": now() + 60}))),
+        );
+        assert_eq!(
+            forged.unwrap_err().params.unwrap()["reason"],
+            "not signed with the expected key"
+        );
+        assert_eq!(reason("Bearer abc"), "malformed");
+        assert_eq!(
+            jwt.verify("Authorization", None).unwrap_err().code,
+            "required"
+        );
+    }
+}
@@ -0,0 +1,766 @@
+//! Body of a request, read once and looked up by body fields
+
+use std::borrow::Cow;
+
+use serde::Serialize;
+use tide::{Request, StatusCode};
+
+#[cfg(feature = "compression")]
+use crate::compression;
+use crate::response::Rejection;
+use crate::values::header_values;
+#[cfg(feature = "xml")]
+use crate::xml;
+use crate::{multipart, FieldRule, HttpField, Location, ValidationError, ValidatorMiddleware};
+
+/// Parsed body in which body fields are looked up
+#[derive(Clone, Copy)]
+pub(crate) enum Body<'b> {
+    Json(&'b serde_json::Value),
+    #[cfg(feature = "xml")]
+    Xml(&'b xml::Element),
+}
+
+impl Body<'_> {
+    /// Value of the body field at `path`, the first selected element of an XML body
+    pub(crate) fn value(self, path: &str) -> Option<String> {
+        match self {
+            Body::Json(body) => body_field(body, path).and_then(json_value),
+            #[cfg(feature = "xml")]
+            Body::Xml(document) => xml::select(document, path)
+                .into_iter()
+                .find_map(|(_, value)| value),
+        }
+    }
+
+    /// Items of the JSON array at `path` or every element selected in an XML body
+    pub(crate) fn values(self, path: &str) -> Vec<String> {
+        match self {
+            Body::Json(body) => match body_field(body, path) {
+                Some(serde_json::Value::Array(items)) => {
+                    items.iter().filter_map(json_value).collect()
+                }
+                value => value.and_then(json_value).into_iter().collect(),
+            },
+            #[cfg(feature = "xml")]
+            Body::Xml(document) => xml::select(document, path)
+                .into_iter()
+                .filter_map(|(_, value)| value)
+                .collect(),
+        }
+    }
+
+    /// Whether the body has a field at `path`, even `null`
+    pub(crate) fn contains(self, path: &str) -> bool {
+        match self {
+            Body::Json(body) => body_field(body, path).is_some(),
+            #[cfg(feature = "xml")]
+            Body::Xml(document) => xml::select(document, path)
+                .iter()
+                .any(|(_, value)| value.is_some()),
+        }
+    }
+
+    /// Paths of the top-level fields of the body
+    pub(crate) fn names(self) -> Vec<String> {
+        match self {
+            Body::Json(serde_json::Value::Object(fields)) => fields.keys().cloned().collect(),
+            Body::Json(_) => Vec::new(),
+            #[cfg(feature = "xml")]
+            Body::Xml(document) => xml::select(document, "*")
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect(),
+        }
+    }
+
+    /// Concrete paths of the fields matched by `path`, see `expand_body_path`. Every element
+    /// selected in an XML body is a field.
+    pub(crate) fn expand(self, path: &str) -> Option<Vec<String>> {
+        match self {
+            Body::Json(body) if path.contains('*') => Some(expand_body_path(body, path)),
+            Body::Json(_) => None,
+            #[cfg(feature = "xml")]
+            Body::Xml(document) => Some(
+                xml::select(document, path)
+                    .into_iter()
+                    .map(|(path, _)| path)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Strings are taken as is, other values as JSON and `null` as a missing value
+pub(crate) fn json_value(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(value) => Some(value.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+pub(crate) fn body_field<'v>(
+    body: &'v serde_json::Value,
+    path: &str,
+) -> Option<&'v serde_json::Value> {
+    path_segments(path)
+        .iter()
+        .try_fold(body, |value, (segment, _)| child(value, segment))
+}
+
+pub(crate) fn child<'v>(
+    value: &'v serde_json::Value,
+    segment: &str,
+) -> Option<&'v serde_json::Value> {
+    match value {
+        serde_json::Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => value.get(segment),
+    }
+}
+
+/// Segments of a body path with their position in the path: a JSON Pointer when the path starts
+/// with `/`, otherwise segments separated by dots where `[n]` is the segment `n`
+pub(crate) fn path_segments(path: &str) -> Vec<(Cow<'_, str>, usize)> {
+    let mut segments = Vec::new();
+    if let Some(pointer) = path.strip_prefix('/') {
+        let mut start = 1;
+        for segment in pointer.split('/') {
+            let unescaped = if segment.contains('~') {
+                Cow::Owned(segment.replace("~1", "/").replace("~0", "~"))
+            } else {
+                Cow::Borrowed(segment)
+            };
+            segments.push((unescaped, start));
+            start += segment.len() + 1;
+        }
+        return segments;
+    }
+    let mut start = 0;
+    for part in path.split('.') {
+        // `items[0][1]` is the segments `items`, `0` and `1`
+        let name_end = part.find('[').unwrap_or(part.len());
+        if name_end > 0 || part.is_empty() {
+            segments.push((Cow::Borrowed(&part[..name_end]), start));
+        }
+        let mut offset = start + name_end;
+        for index in part[name_end..].split_terminator(']') {
+            segments.push((Cow::Borrowed(index.trim_start_matches('[')), offset + 1));
+            offset += index.len() + 1;
+        }
+        start += part.len() + 1;
+    }
+    segments
+}
+
+/// Concrete paths matched by a body path with `*` segments, e.g. `items[0].sku` and
+/// `items[1].sku` for `items[*].sku`. Paths without wildcard match themselves.
+pub(crate) fn expand_body_path(body: &serde_json::Value, path: &str) -> Vec<String> {
+    pub(crate) fn expand(
+        value: Option<&serde_json::Value>,
+        path: &str,
+        segments: &[(Cow<'_, str>, usize)],
+        indexes: &mut Vec<(usize, usize)>,
+        paths: &mut Vec<String>,
+    ) {
+        let ((segment, position), rest) = match segments.split_first() {
+            Some(first) => first,
+            None => {
+                let mut expanded = String::with_capacity(path.len());
+                let mut copied = 0;
+                for (position, index) in indexes.iter() {
+                    expanded.push_str(&path[copied..*position]);
+                    expanded.push_str(&index.to_string());
+                    copied = position + 1;
+                }
+                expanded.push_str(&path[copied..]);
+                paths.push(expanded);
+                return;
+            }
+        };
+        if segment != "*" {
+            return expand(
+                value.and_then(|value| child(value, segment)),
+                path,
+                rest,
+                indexes,
+                paths,
+            );
+        }
+        if let Some(serde_json::Value::Array(items)) = value {
+            for (index, item) in items.iter().enumerate() {
+                indexes.push((*position, index));
+                expand(Some(item), path, rest, indexes, paths);
+                indexes.pop();
+            }
+        }
+    }
+
+    let segments = path_segments(path);
+    if segments.iter().all(|(segment, _)| segment != "*") {
+        return vec![path.to_string()];
+    }
+    let mut paths = Vec::new();
+    expand(Some(body), path, &segments, &mut Vec::new(), &mut paths);
+    paths
+}
+
+/// Read the body, stopping one byte past `max_size` so streamed bodies can't grow unbounded. The
+/// body is attached back to the request once validated.
+pub(crate) async fn read_body<State>(
+    ctx: &mut Request<State>,
+    max_size: Option<usize>,
+) -> Result<Vec<u8>, ValidationError> {
+    use futures::io::AsyncReadExt;
+
+    let mut body = Vec::new();
+    let limit = max_size.map_or(u64::MAX, |max_size| max_size as u64 + 1);
+    (&mut *ctx)
+        .take(limit)
+        .read_to_end(&mut body)
+        .await
+        .map_err(|err| {
+            ValidationError::new("body", "read_body", format!("cannot read body: {}", err))
+        })?;
+    Ok(body)
+}
+
+/// Parse the JSON body, an empty body is `null`
+pub(crate) fn parse_json_body(body: &[u8]) -> Result<serde_json::Value, ValidationError> {
+    if body.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+    serde_json::from_slice(body).map_err(|err| {
+        ValidationError::new("body", "json", format!("cannot parse JSON body: {}", err))
+    })
+}
+
+/// Body read by the middleware, attached back to the request once validated
+pub(crate) struct RequestBody {
+    /// Bytes of the body, decoded when it was compressed
+    pub(crate) raw: Vec<u8>,
+    /// JSON body, `null` when no rule reads it or when it's XML
+    pub(crate) json: serde_json::Value,
+    #[cfg(feature = "xml")]
+    pub(crate) xml: Option<xml::Element>,
+    /// Whether the body was read, empty bodies included
+    pub(crate) read: bool,
+    /// Whether the body was parsed as JSON
+    pub(crate) is_json: bool,
+    #[cfg(feature = "compression")]
+    pub(crate) decoded: bool,
+}
+
+impl RequestBody {
+    /// Body in which body fields are looked up
+    pub(crate) fn fields(&self) -> Body<'_> {
+        #[cfg(feature = "xml")]
+        {
+            if let Some(document) = &self.xml {
+                return Body::Xml(document);
+            }
+        }
+        Body::Json(&self.json)
+    }
+
+    /// Attach the body back to the request, decoded bodies with their new length
+    pub(crate) fn restore<State>(self, ctx: &mut Request<State>) {
+        if !self.read {
+            return;
+        }
+        #[cfg(feature = "compression")]
+        if self.decoded {
+            ctx.insert_header("Content-Encoding", "identity");
+            ctx.insert_header("Content-Length", self.raw.len().to_string());
+        }
+        ctx.set_body(self.raw);
+    }
+}
+
+/// How the body is read, besides JSON
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BodySettings {
+    #[cfg(feature = "xml")]
+    pub(crate) xml_body: bool,
+    #[cfg(feature = "compression")]
+    pub(crate) max_decoded_body_size: Option<usize>,
+}
+
+impl BodySettings {
+    #[cfg_attr(
+        not(any(feature = "xml", feature = "compression")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn merge(&mut self, other: BodySettings) {
+        #[cfg(feature = "xml")]
+        {
+            self.xml_body |= other.xml_body;
+        }
+        #[cfg(feature = "compression")]
+        {
+            self.max_decoded_body_size = other.max_decoded_body_size.or(self.max_decoded_body_size);
+        }
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Read the body as XML, for partners which don't send JSON. Paths of
+    /// [`HttpField::BodyField`](enum.HttpField.html#variant.BodyField) are then XPath-like
+    /// selectors and validators get the text content of the selected elements or attributes:
+    ///
+    /// - `/order/customer/name` selects from the root element, `customer/name` from its children
+    /// - `item[2]` selects the second `item`, indexes start at 1
+    /// - `*` selects every child element
+    /// - `@sku` selects an attribute and `text()` the text of an element, as last step
+    ///
+    /// A step without index selects every matching element and validators run once per element,
+    /// with the path of the element in errors, e.g. `/order/item[2]/@sku`. A missing element or
+    /// attribute is a missing field. A body which isn't well-formed XML is rejected with
+    /// `400 Bad Request` and an `xml` error on the `body` field, like errors of validators, and
+    /// the body is attached back to the request for the handler.
+    ///
+    /// Requires the `xml` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut validator_middleware = ValidatorMiddleware::new().xml_body();
+    /// validator_middleware.add_validator(HttpField::body_field("/order/customer/email"), is_email);
+    /// validator_middleware.add_validator(HttpField::body_field("/order/item/@sku"), is_required);
+    /// validator_middleware.add_validator(HttpField::body_field("/order/item/quantity"), is_integer);
+    /// ```
+    #[cfg(feature = "xml")]
+    pub fn xml_body(mut self) -> Self {
+        self.body.xml_body = true;
+        self
+    }
+
+    /// Decode bodies sent with `Content-Encoding: gzip` or `deflate` before validating their
+    /// fields, up to `max_size` decoded bytes so that a small compressed body can't expand into
+    /// gigabytes. Larger bodies are rejected with `413 Payload Too Large`, invalid streams with
+    /// `400 Bad Request` and other encodings with `415 Unsupported Media Type`. The limit set by
+    /// [`max_body_size`](#method.max_body_size) still applies to the compressed body.
+    ///
+    /// Handlers get the decoded body, with `Content-Encoding: identity` and the `Content-Length`
+    /// of the decoded body. Bodies are only decoded when a validator reads them.
+    ///
+    /// Requires the `compression` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut validator_middleware = ValidatorMiddleware::new()
+    ///     .max_body_size(64 * 1024)
+    ///     .decode_bodies(1024 * 1024);
+    /// validator_middleware.add_validator(HttpField::body_field("name"), is_required);
+    /// ```
+    #[cfg(feature = "compression")]
+    pub fn decode_bodies(mut self, max_size: usize) -> Self {
+        self.body.max_decoded_body_size = Some(max_size);
+        self
+    }
+
+    /// Read the body when a rule or a limit needs it, decoding and parsing it as the rules
+    /// need
+    pub(crate) async fn read_request_body(
+        &self,
+        ctx: &mut Request<State>,
+    ) -> Result<RequestBody, Rejection> {
+        let reads_json_body = self.reads_json_body();
+        // Without `Content-Length` the size of the body is only known once read
+        let streamed_body = self.limits.max_body_size.is_some() && ctx.len().is_none();
+        let read = reads_json_body || self.reads_raw_body() || streamed_body;
+        let raw = if read {
+            read_body(ctx, self.limits.max_body_size)
+                .await
+                .map_err(|err| (StatusCode::BadRequest, HttpField::custom("body"), err))?
+        } else {
+            Vec::new()
+        };
+        if let Some(rejection) = self.limits.check_body_size(raw.len()) {
+            return Err(rejection);
+        }
+        #[cfg(feature = "compression")]
+        let (raw, decoded) = match self.body.max_decoded_body_size {
+            Some(max_size) if read => match header_values(ctx, "Content-Encoding") {
+                Some(values) => {
+                    let values: Vec<&str> = values.iter().map(|value| value.as_str()).collect();
+                    let raw = compression::decode(&values.join(","), raw, max_size)
+                        .map_err(compression::DecodeError::into_rejection)?;
+                    (raw, true)
+                }
+                None => (raw, false),
+            },
+            _ => (raw, false),
+        };
+        #[cfg(feature = "xml")]
+        let xml = if reads_json_body && self.body.xml_body {
+            match xml::parse(&raw) {
+                Some(document) => Some(document),
+                None => {
+                    let err = ValidationError::new("body", "xml", "cannot parse XML body");
+                    return Err((StatusCode::BadRequest, HttpField::custom("body"), err));
+                }
+            }
+        } else {
+            None
+        };
+        // Limits, unknown fields and array items only apply to JSON bodies
+        #[cfg(feature = "xml")]
+        let reads_json_body = reads_json_body && xml.is_none();
+        let json = if reads_json_body {
+            parse_json_body(&raw)
+                .map_err(|err| (StatusCode::BadRequest, HttpField::custom("body"), err))?
+        } else {
+            serde_json::Value::Null
+        };
+        if let Some(rejection) = self.limits.check_body(&json) {
+            return Err(rejection);
+        }
+        Ok(RequestBody {
+            raw,
+            json,
+            #[cfg(feature = "xml")]
+            xml,
+            read,
+            is_json: reads_json_body,
+            #[cfg(feature = "compression")]
+            decoded,
+        })
+    }
+
+    /// Parts of a multipart body, when a rule reads them
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn parse_parts<'b>(
+        &self,
+        ctx: &Request<State>,
+        raw: &'b [u8],
+    ) -> Result<Vec<multipart::Part<'b>>, Rejection> {
+        if !self.reads_multipart() {
+            return Ok(Vec::new());
+        }
+        let content_type = header_values(ctx, "Content-Type")
+            .and_then(|values| values.last())
+            .map(|value| value.as_str())
+            .unwrap_or_default();
+        multipart::parse(content_type, raw).ok_or_else(|| {
+            let err = ValidationError::new("body", "multipart", "cannot parse multipart body");
+            (StatusCode::BadRequest, HttpField::custom("body"), err)
+        })
+    }
+
+    /// Whether a resolver or a validator needs the JSON body
+    pub(crate) fn reads_json_body(&self) -> bool {
+        self.uses_field(|field| matches!(field, HttpField::BodyField(_)))
+            || self.scopes.each_item.is_some()
+            || self.limits.max_items.is_some()
+            || self
+                .global_validators
+                .iter()
+                .any(|(location, _)| *location == Location::Body)
+    }
+
+    /// Whether a resolver or a validator needs the parts of a multipart body
+    pub(crate) fn reads_multipart(&self) -> bool {
+        self.uses_field(|field| matches!(field, HttpField::Multipart(_)))
+    }
+
+    /// Whether a validator needs the raw body
+    pub(crate) fn reads_raw_body(&self) -> bool {
+        #[cfg(feature = "sessions")]
+        {
+            if self
+                .scopes
+                .session_rules
+                .iter()
+                .any(|(_, rules)| rules.reads_raw_body())
+            {
+                return true;
+            }
+        }
+        self.reads_multipart()
+            || self.cache.hash_json_body
+            || self
+                .scopes
+                .steps
+                .iter()
+                .any(|(_, rules)| rules.reads_raw_body())
+            || self
+                .scopes
+                .method_rules
+                .iter()
+                .any(|(_, rules)| rules.reads_raw_body())
+            || matches!(&self.scopes.each_item, Some(rules) if rules.reads_raw_body())
+            || self
+                .validators
+                .values()
+                .flatten()
+                .any(FieldRule::reads_body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    use crate::{multipart, validators};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request};
+    use tide::StatusCode;
+
+    #[test]
+    fn body_paths() {
+        let body = serde_json::json!({
+            "user": {"address": {"zip": "75001"}, "a/b": {"~c": 1}},
+            "items": [{"sku": "A1"}, {"qty": 2}, {"sku": "C3"}],
+            "matrix": [[1, 2], [3]],
+        });
+        let value = |path| body_field(&body, path).cloned();
+        assert_eq!(value("user.address.zip"), Some("75001".into()));
+        assert_eq!(value("/user/address/zip"), Some("75001".into()));
+        assert_eq!(value("/user/a~1b/~0c"), Some(1.into()));
+        assert_eq!(value("items[2].sku"), Some("C3".into()));
+        assert_eq!(value("items.2.sku"), Some("C3".into()));
+        assert_eq!(value("matrix[0][1]"), Some(2.into()));
+        assert_eq!(value("items[*].sku"), None);
+
+        assert_eq!(
+            expand_body_path(&body, "items[*].sku"),
+            vec!["items[0].sku", "items[1].sku", "items[2].sku"]
+        );
+        assert_eq!(
+            expand_body_path(&body, "/items/*/sku"),
+            vec!["/items/0/sku", "/items/1/sku", "/items/2/sku"]
+        );
+        assert_eq!(
+            expand_body_path(&body, "matrix.*.*"),
+            vec!["matrix.0.0", "matrix.0.1", "matrix.1.0"]
+        );
+        assert!(expand_body_path(&body, "missing[*].sku").is_empty());
+        assert_eq!(expand_body_path(&body, "user.name"), vec!["user.name"]);
+    }
+
+    #[test]
+    fn validator_body_wildcards() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(
+            HttpField::body_field("items[*].sku"),
+            validators::is_required,
+        );
+        validators.add_validator(
+            HttpField::body_field("/user/address/zip"),
+            validators::is_number,
+        );
+        inner
+            .at("/orders")
+            .middleware(validators)
+            .post(|_| async { Ok("created") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(Method::Post, "http://localhost/orders".parse().unwrap());
+        req.set_body(r#"{"user": {"address": {"zip": "75001"}}, "items": [{"sku": "A1"}]}"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/orders".parse().unwrap());
+        req.set_body(r#"{"items": [{"sku": "A1"}, {"qty": 2}]}"#);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let err: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(err.field, "items[1].sku");
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn validator_xml_body() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().xml_body();
+        validators.add_validator(
+            HttpField::body_field("/order/item/@sku"),
+            validators::is_required,
+        );
+        validators.add_validator(
+            HttpField::body_field("/order/customer/zip"),
+            validators::is_number,
+        );
+        inner
+            .at("/orders")
+            .middleware(validators)
+            .post(|mut req: tide::Request<()>| async move {
+                Ok(req.body_string().await?.len().to_string())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let order = r#"<order><customer><zip>75001</zip></customer><item sku="A1"/></order>"#;
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/orders".parse().unwrap());
+        req.set_body(order);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(buf, order.len().to_string().as_bytes());
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/orders".parse().unwrap());
+        req.set_body(r#"<order><item sku="A1"/><item/></order>"#);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let err: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(err.field, "/order/item[2]/@sku");
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/orders".parse().unwrap());
+        req.set_body("<order></item>");
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let err: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!((&err.field[..], &err.code[..]), ("body", "xml"));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn validator_decode_bodies() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let gzip = |body: &[u8]| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        };
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().decode_bodies(1024);
+        validators.add_validator(HttpField::body_field("age"), validators::is_number);
+        inner
+            .at("/cats")
+            .middleware(validators)
+            .post(|mut req: tide::Request<()>| async move {
+                let encoding = crate::values::header_values(&req, "Content-Encoding")
+                    .map(|values| values[0].as_str().to_string());
+                Ok(format!("{:?} {}", encoding, req.body_string().await?))
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/cats".parse().unwrap());
+        req.insert_header("Content-Encoding", "gzip").unwrap();
+        req.set_body(gzip(br#"{"age": 4}"#));
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(buf, br#"Some("identity") {"age": 4}"#);
+
+        let mut req = Request::new(Method::Post, "http://localhost/cats".parse().unwrap());
+        req.insert_header("Content-Encoding", "gzip").unwrap();
+        req.set_body(gzip(br#"{"age": "four"}"#));
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let mut req = Request::new(Method::Post, "http://localhost/cats".parse().unwrap());
+        req.insert_header("Content-Encoding", "gzip").unwrap();
+        req.set_body(gzip(&[b' '; 4096]));
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::PayloadTooLarge);
+
+        let mut req = Request::new(Method::Post, "http://localhost/cats".parse().unwrap());
+        req.insert_header("Content-Encoding", "br").unwrap();
+        req.set_body(r#"{"age": 4}"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::UnsupportedMediaType);
+    }
+
+    #[test]
+    fn validator_body_field() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(HttpField::body_field("user.ages.1"), is_number);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .post(|mut req: tide::Request<()>| async move {
+                let body: serde_json::Value = req.body_json().await?;
+                Ok(body["user"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"{"user": {"name": "Gribouille", "ages": [1, "two"]}}"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"{"user": {"name": "Gribouille", "ages": [1, 2]}}"#);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&*buf, &*b"Gribouille");
+    }
+
+    #[test]
+    fn validator_multipart() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(HttpField::multipart("title"), validators::is_required);
+        validators.add_file_validator(HttpField::multipart("avatar"), multipart::is_file);
+        validators.add_file_validator(
+            HttpField::multipart("avatar"),
+            multipart::has_extension(&["png"]),
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .post(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let body = |filename: &str| {
+            format!(
+                "--XyZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHolidays\r\n\
+                 --XyZ\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"{}\"\r\n\
+                 Content-Type: image/png\r\n\r\nPNG\r\n--XyZ--\r\n",
+                filename
+            )
+        };
+        for (filename, status) in &[
+            ("cat.png", StatusCode::Ok),
+            ("cat.gif", StatusCode::BadRequest),
+        ] {
+            let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+            req.insert_header("Content-Type", "multipart/form-data; boundary=XyZ")
+                .unwrap();
+            req.set_body(body(filename));
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.insert_header("Content-Type", "multipart/form-data; boundary=XyZ")
+            .unwrap();
+        req.set_body("not multipart");
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let err: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!((&err.field[..], &err.code[..]), ("body", "multipart"));
+    }
+}
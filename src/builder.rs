@@ -0,0 +1,164 @@
+//! Fluent builder of middlewares, see [`ValidatorMiddleware::builder`](struct.ValidatorMiddleware.html#method.builder)
+use std::borrow::Cow;
+
+use serde::Serialize;
+use tide::Response;
+
+use crate::{ConfigError, HttpField, ValidatorMiddleware};
+
+/// Builder of a [`ValidatorMiddleware`](struct.ValidatorMiddleware.html) declaring its fields in a
+/// single expression. Validators of a field run in the order they are declared.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let validator_middleware = ValidatorMiddleware::builder()
+///     .query("page", is_required)
+///     .query("page", is_number)
+///     .header("X-Key", is_uuid)
+///     .cookie("session", is_max_length(20))
+///     .on_error(|err: &CustomError| Response::new(err.status_code).body_string(err.message.clone()))
+///     .build();
+/// app.at("/items").middleware(validator_middleware).get(list_items);
+/// ```
+pub struct ValidatorMiddlewareBuilder<T, State = ()>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    middleware: ValidatorMiddleware<T, State>,
+}
+
+impl<T, State> std::fmt::Debug for ValidatorMiddlewareBuilder<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ValidatorMiddlewareBuilder")
+            .field(&self.middleware)
+            .finish()
+    }
+}
+
+impl<T, State> ValidatorMiddlewareBuilder<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    pub(crate) fn new() -> Self {
+        ValidatorMiddlewareBuilder {
+            middleware: ValidatorMiddleware::new(),
+        }
+    }
+
+    /// Add a validator of any field
+    pub fn field<F>(mut self, field: HttpField<'static>, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.middleware.add_validator(field, validator);
+        self
+    }
+
+    /// Add a validator of a path parameter
+    pub fn param<F>(self, name: impl Into<Cow<'static, str>>, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.field(HttpField::param(name), validator)
+    }
+
+    /// Add a validator of a query parameter
+    pub fn query<F>(self, name: impl Into<Cow<'static, str>>, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.field(HttpField::query_param(name), validator)
+    }
+
+    /// Add a validator of a header
+    pub fn header<F>(self, name: impl Into<Cow<'static, str>>, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.field(HttpField::header(name), validator)
+    }
+
+    /// Add a validator of a cookie
+    pub fn cookie<F>(self, name: impl Into<Cow<'static, str>>, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.field(HttpField::cookie(name), validator)
+    }
+
+    /// Add a validator of a field of a JSON body
+    pub fn body_field<F>(self, path: impl Into<Cow<'static, str>>, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.field(HttpField::body_field(path), validator)
+    }
+
+    /// Build the response of invalid requests from their error, see
+    /// [`ValidatorMiddleware::on_error`](struct.ValidatorMiddleware.html#method.on_error)
+    pub fn on_error<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&T) -> Response + Send + Sync + 'static,
+    {
+        self.middleware = self.middleware.on_error(hook);
+        self
+    }
+
+    /// Configure the middleware with the other methods of
+    /// [`ValidatorMiddleware`](struct.ValidatorMiddleware.html), e.g.
+    /// `.configure(|middleware| middleware.collect_all_errors(true))`
+    pub fn configure<F>(mut self, configure: F) -> Self
+    where
+        F: FnOnce(ValidatorMiddleware<T, State>) -> ValidatorMiddleware<T, State>,
+    {
+        self.middleware = configure(self.middleware);
+        self
+    }
+
+    /// Get the middleware
+    pub fn build(self) -> ValidatorMiddleware<T, State> {
+        self.middleware
+    }
+
+    /// Get the middleware after checking its configuration with
+    /// [`finalize`](struct.ValidatorMiddleware.html#method.finalize)
+    pub fn try_build(self) -> Result<ValidatorMiddleware<T, State>, ConfigError> {
+        self.middleware.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validators::{self, ValidationError};
+
+    #[test]
+    fn fluent_builder() {
+        let middleware = ValidatorMiddleware::<ValidationError>::builder()
+            .query("page", validators::is_required)
+            .query("page", validators::is_integer)
+            .header("X-Key", validators::is_uuid)
+            .cookie("session", validators::is_max_length(20))
+            .configure(|middleware| middleware.collect_all_errors(true))
+            .build();
+        assert_eq!(
+            middleware.validators[&HttpField::query_param("page")].len(),
+            2
+        );
+        assert_eq!(middleware.validators.len(), 3);
+        assert!(middleware.collect_all_errors);
+
+        let duplicate = ValidatorMiddleware::<ValidationError>::builder()
+            .param("id", validators::is_integer)
+            .param("id", validators::is_integer)
+            .try_build();
+        assert!(matches!(duplicate, Err(ConfigError::DuplicateValidator(_))));
+    }
+}
@@ -0,0 +1,1093 @@
+//! Caching for async validators backed by remote services, cache keys for responses and hashes
+//! of JSON bodies for idempotency layers.
+//!
+//! [`cached`](fn.cached.html) keeps the results of an async validator in a
+//! [`ResultCache`](trait.ResultCache.html), in memory with [`MemoryCache`](struct.MemoryCache.html)
+//! or in your own store. The primitives used by [`negative_cache`](fn.negative_cache.html) are
+//! available to write your own caching validators: [`TtlMap`](struct.TtlMap.html),
+//! [`LruMap`](struct.LruMap.html) and [`SingleFlight`](struct.SingleFlight.html).
+//!
+//! ```rust,no_run,compile_fail
+//! use std::time::Duration;
+//! use tide_validator::cache::{cached, negative_cache, MemoryCache};
+//!
+//! // Unknown API keys are rejected without calling the backend again for 30 seconds
+//! let check_api_key = negative_cache(Duration::from_secs(30), 10_000, check_api_key_in_db);
+//! // Introspected tokens are valid or invalid for 5 minutes
+//! let check_token = cached(MemoryCache::new(10_000), Duration::from_secs(300), introspect_token);
+//! ```
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use serde::Serialize;
+use tide::Request;
+
+use crate::body::RequestBody;
+use crate::values::RequestValues;
+use crate::{BoxedAsyncValidator, HttpField, ValidatorMiddleware};
+
+/// Cache the failures of an async validator for `ttl`, so repeated invalid values (e.g. tokens
+/// or API keys during an abuse wave) are rejected with the cached error without calling the backend.
+/// Successful lookups are never cached. At most `capacity` failures are kept, expired ones are
+/// evicted when the cache is full and new failures are not cached while it's still full.
+pub fn negative_cache<T, F>(ttl: Duration, capacity: usize, validator: F) -> BoxedAsyncValidator<T>
+where
+    T: Clone + Send + 'static,
+    F: Fn(&str, Option<&str>) -> BoxFuture<'static, Result<(), T>> + Send + Sync + 'static,
+{
+    let failures = Arc::new(TtlMap::new(ttl, capacity));
+    Box::new(move |field_name: &str, field_value: Option<&str>| {
+        let key = (field_name.to_string(), field_value.map(String::from));
+        if let Some(err) = failures.get(&key) {
+            return Box::pin(async move { Err(err) });
+        }
+
+        let lookup = validator(field_name, field_value);
+        let failures = Arc::clone(&failures);
+        Box::pin(async move {
+            let result = lookup.await;
+            if let Err(err) = &result {
+                failures.insert(key, err.clone());
+            }
+            result
+        })
+    })
+}
+
+/// Store of validator results keyed by field name and value, used by [`cached`](fn.cached.html).
+/// Implement it to share results between instances, e.g. in Redis. Values are the raw values of
+/// the request like API keys or tokens: stores shared with other services should hash them.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// struct RedisCache(redis::Client);
+///
+/// impl ResultCache<String> for RedisCache {
+///     fn get(&self, field_name: &str, field_value: &str) -> BoxFuture<'static, Option<Result<(), String>>> {
+///         let key = format!("validation:{}:{}", field_name, sha256_hex(field_value));
+///         let client = self.0.clone();
+///         Box::pin(async move {
+///             let cached: Option<String> = client.get_async_connection().await.ok()?.get(key).await.ok()?;
+///             cached.map(|err| if err.is_empty() { Ok(()) } else { Err(err) })
+///         })
+///     }
+///
+///     fn set(&self, field_name: &str, field_value: &str, result: Result<(), String>, ttl: Duration) -> BoxFuture<'static, ()> {
+///         let key = format!("validation:{}:{}", field_name, sha256_hex(field_value));
+///         let client = self.0.clone();
+///         Box::pin(async move {
+///             if let Ok(mut connection) = client.get_async_connection().await {
+///                 let _: redis::RedisResult<()> = connection.set_ex(key, result.err().unwrap_or_default(), ttl.as_secs() as usize).await;
+///             }
+///         })
+///     }
+/// }
+/// ```
+pub trait ResultCache<T>: Send + Sync + 'static {
+    /// Result cached for `field_value` of `field_name`, `None` when it's unknown or expired
+    fn get(&self, field_name: &str, field_value: &str)
+        -> BoxFuture<'static, Option<Result<(), T>>>;
+
+    /// Cache `result` for `field_value` of `field_name` during `ttl`
+    fn set(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        result: Result<(), T>,
+        ttl: Duration,
+    ) -> BoxFuture<'static, ()>;
+}
+
+impl<T, C: ResultCache<T>> ResultCache<T> for Arc<C> {
+    fn get(
+        &self,
+        field_name: &str,
+        field_value: &str,
+    ) -> BoxFuture<'static, Option<Result<(), T>>> {
+        (**self).get(field_name, field_value)
+    }
+
+    fn set(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        result: Result<(), T>,
+        ttl: Duration,
+    ) -> BoxFuture<'static, ()> {
+        (**self).set(field_name, field_value, result, ttl)
+    }
+}
+
+/// In-memory [`ResultCache`](trait.ResultCache.html) keeping at most `capacity` results: expired
+/// ones are evicted when the cache is full, and new results are not cached while it's still full.
+/// Share it between middlewares with an `Arc`.
+pub struct MemoryCache<T> {
+    capacity: usize,
+    entries: Mutex<HashMap<(String, String), (Instant, Duration, Result<(), T>)>>,
+}
+
+impl<T> fmt::Debug for MemoryCache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryCache")
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl<T> MemoryCache<T> {
+    /// Create an empty cache
+    pub fn new(capacity: usize) -> Self {
+        MemoryCache {
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> ResultCache<T> for MemoryCache<T> {
+    fn get(
+        &self,
+        field_name: &str,
+        field_value: &str,
+    ) -> BoxFuture<'static, Option<Result<(), T>>> {
+        let key = (field_name.to_string(), field_value.to_string());
+        let cached = self
+            .entries
+            .lock()
+            .ok()
+            .and_then(|entries| match entries.get(&key) {
+                Some((inserted_at, ttl, result)) if inserted_at.elapsed() < *ttl => {
+                    Some(result.clone())
+                }
+                _ => None,
+            });
+        Box::pin(futures::future::ready(cached))
+    }
+
+    fn set(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        result: Result<(), T>,
+        ttl: Duration,
+    ) -> BoxFuture<'static, ()> {
+        if let Ok(mut entries) = self.entries.lock() {
+            // Expiration is checked against the elapsed time, so the longest TTLs can't overflow
+            if entries.len() >= self.capacity {
+                entries.retain(|_, (inserted_at, ttl, _)| inserted_at.elapsed() < *ttl);
+            }
+            let key = (field_name.to_string(), field_value.to_string());
+            if entries.len() < self.capacity || entries.contains_key(&key) {
+                entries.insert(key, (Instant::now(), ttl, result));
+            }
+        }
+        Box::pin(futures::future::ready(()))
+    }
+}
+
+/// Cache the results of an async validator in `cache` for `ttl`, so repeated requests with the
+/// same credential (e.g. an API key or a token to introspect) don't call the backend again.
+/// Both successes and failures are cached, missing fields are always validated. While a value is
+/// being validated, other requests with the same value wait for its result instead of calling the
+/// backend too.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let results = Arc::new(MemoryCache::new(10_000));
+/// validator_middleware.add_async_validator(
+///     HttpField::header("X-Api-Key"),
+///     cached(Arc::clone(&results), Duration::from_secs(60), check_api_key_in_db),
+/// );
+/// ```
+pub fn cached<T, C, F>(cache: C, ttl: Duration, validator: F) -> BoxedAsyncValidator<T>
+where
+    T: Clone + Send + Sync + 'static,
+    C: ResultCache<T>,
+    F: Fn(&str, Option<&str>) -> BoxFuture<'static, Result<(), T>> + Send + Sync + 'static,
+{
+    let cache = Arc::new(cache);
+    let validator = Arc::new(validator);
+    let lookups = Arc::new(SingleFlight::new());
+    Box::new(move |field_name: &str, field_value: Option<&str>| {
+        let field_value = match field_value {
+            Some(field_value) => field_value.to_string(),
+            None => return validator(field_name, None),
+        };
+        let field_name = field_name.to_string();
+        let cache = Arc::clone(&cache);
+        let validator = Arc::clone(&validator);
+        let lookups = Arc::clone(&lookups);
+        Box::pin(async move {
+            if let Some(result) = cache.get(&field_name, &field_value).await {
+                return result;
+            }
+            let key = (field_name.clone(), field_value.clone());
+            // Only the first caller calls the validator, the others wait for its lookup
+            lookups
+                .run(key, move || {
+                    let lookup = validator(&field_name, Some(&field_value));
+                    async move {
+                        let result = lookup.await;
+                        cache
+                            .set(&field_name, &field_value, result.clone(), ttl)
+                            .await;
+                        result
+                    }
+                })
+                .await
+        })
+    })
+}
+
+/// Canonical key built from validated fields, added in request extensions by middlewares
+/// configured with [`cache_key`](../struct.ValidatorMiddleware.html#method.cache_key) so response
+/// caches key on validated input only. Retrieve it with `req.local::<CacheKey>()`.
+///
+/// Fields are sorted by location and name, e.g. `header.accept=application/json&query.page=2`.
+/// Values are the normalized ones, header names are lowercase, missing fields have no `=` and
+/// `%`, `&` and `=` are percent-encoded, so different inputs never give the same key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Build the key of fields and their values, `None` for missing fields
+    pub fn new<'f>(fields: impl IntoIterator<Item = (&'f HttpField<'f>, Option<&'f str>)>) -> Self {
+        let mut entries: Vec<String> = fields
+            .into_iter()
+            .map(|(field, value)| {
+                let name = match field {
+                    HttpField::MatrixParam(segment, name) => {
+                        Cow::Owned(format!("{};{}", segment, name))
+                    }
+                    HttpField::Header(_) | HttpField::ContentType | HttpField::Accept => {
+                        Cow::Owned(field.name().to_ascii_lowercase())
+                    }
+                    _ => Cow::Borrowed(field.name()),
+                };
+                let name = format!("{}.{}", field.location().as_str(), escape(&name));
+                match value {
+                    Some(value) => format!("{}={}", name, escape(value)),
+                    None => name,
+                }
+            })
+            .collect();
+        entries.sort();
+        CacheKey(entries.join("&"))
+    }
+
+    /// The key as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Characters percent-encoded in cache keys
+const ESCAPED: &[char] = &['%', '&', '='];
+
+fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(ESCAPED) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 6);
+    for c in value.chars() {
+        if ESCAPED.contains(&c) {
+            escaped.push_str(&format!("%{:02X}", c as u8));
+        } else {
+            escaped.push(c);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Hash of the canonical form of a JSON body, or of the raw bytes of other bodies, added in request extensions by middlewares
+/// configured with [`hash_json_body`](../struct.ValidatorMiddleware.html#method.hash_json_body) so
+/// idempotency and deduplication layers don't have to read the body again. Retrieve it with
+/// `req.local::<BodyHash>()`.
+///
+/// Bodies differing only by whitespace, key order or the notation of numbers (`1`, `1.0` and
+/// `1e0`) have the same hash. It's a 128 bits FNV-1a hash of [`canonical_json`](fn.canonical_json.html),
+/// not a cryptographic one: don't use it to authenticate bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BodyHash(u128);
+
+impl BodyHash {
+    /// Hash of the canonical form of a JSON value
+    pub fn new(body: &serde_json::Value) -> Self {
+        BodyHash::of_bytes(canonical_json(body).as_bytes())
+    }
+
+    /// Hash of a raw body: the canonical form when it's JSON, `null` when it's empty, the bytes
+    /// themselves otherwise
+    pub fn from_body(body: &[u8]) -> Self {
+        if body.is_empty() {
+            return BodyHash::new(&serde_json::Value::Null);
+        }
+        match serde_json::from_slice(body) {
+            Ok(value) => BodyHash::new(&value),
+            Err(_) => BodyHash::of_bytes(body),
+        }
+    }
+
+    fn of_bytes(bytes: &[u8]) -> Self {
+        const OFFSET_BASIS: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+        const PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+        let hash = bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u128::from(*byte)).wrapping_mul(PRIME)
+        });
+        BodyHash(hash)
+    }
+
+    /// The hash as a number
+    pub fn as_u128(&self) -> u128 {
+        self.0
+    }
+}
+
+/// Lowercase hexadecimal, 32 characters
+impl fmt::Display for BodyHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+/// Canonical form of a JSON value: no whitespace, object keys sorted, integral numbers written
+/// as integers (`1.0` and `1e0` are `1`) and other numbers in their shortest form
+pub fn canonical_json(value: &serde_json::Value) -> String {
+    let mut canonical = String::new();
+    write_canonical(value, &mut canonical);
+    canonical
+}
+
+fn write_canonical(value: &serde_json::Value, out: &mut String) {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+        Value::Number(number) => {
+            if let Some(number) = number.as_i64() {
+                out.push_str(&number.to_string());
+            } else if let Some(number) = number.as_u64() {
+                out.push_str(&number.to_string());
+            } else {
+                let number = number.as_f64().unwrap_or_default();
+                // Integral floats are written as integers, -0.0 as 0
+                if number.fract() == 0.0 && number.abs() < 9_007_199_254_740_992.0 {
+                    out.push_str(&(number as i64).to_string());
+                } else {
+                    out.push_str(&number.to_string());
+                }
+            }
+        }
+        Value::String(value) => out.push_str(&Value::String(value.clone()).to_string()),
+        Value::Array(items) => {
+            out.push('[');
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (idx, (key, value)) in entries.into_iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// Thread-safe map whose entries expire `ttl` after their insertion. At most `capacity` entries
+/// are kept: expired ones are evicted when the map is full, and new keys aren't inserted while
+/// it's still full.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let tenants = Arc::new(TtlMap::new(Duration::from_secs(60), 1_000));
+/// validator_middleware.add_async_validator(HttpField::header("X-Tenant"), move |field_name, field_value| {
+///     let tenants = Arc::clone(&tenants);
+///     let tenant = field_value.map(String::from);
+///     Box::pin(async move {
+///         let tenant = tenant.unwrap_or_default();
+///         let exists = match tenants.get(&tenant) {
+///             Some(exists) => exists,
+///             None => {
+///                 let exists = db::tenant_exists(&tenant).await;
+///                 tenants.insert(tenant, exists);
+///                 exists
+///             }
+///         };
+///         if exists { Ok(()) } else { Err(format!("'{}' is not a known tenant", field_name)) }
+///     })
+/// });
+/// ```
+pub struct TtlMap<K, V> {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K, V> fmt::Debug for TtlMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtlMap")
+            .field("ttl", &self.ttl)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> TtlMap<K, V> {
+    /// Create an empty map
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        TtlMap {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Value of `key` if it hasn't expired
+    pub fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().ok()?;
+        match entries.get(key) {
+            Some((inserted_at, value)) if inserted_at.elapsed() < self.ttl => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Insert or replace the value of `key`, unless the map is full of entries which haven't expired
+    pub fn insert(&self, key: K, value: V) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= self.capacity {
+                let ttl = self.ttl;
+                entries.retain(|_, (inserted_at, _)| inserted_at.elapsed() < ttl);
+            }
+            if entries.len() < self.capacity || entries.contains_key(&key) {
+                entries.insert(key, (Instant::now(), value));
+            }
+        }
+    }
+}
+
+/// Thread-safe map keeping the `capacity` most recently used entries, e.g. to memoize an
+/// expensive pure check of values with a bounded memory
+pub struct LruMap<K, V> {
+    capacity: usize,
+    inner: Mutex<LruEntries<K, V>>,
+}
+
+struct LruEntries<K, V> {
+    tick: u64,
+    entries: HashMap<K, (u64, V)>,
+    by_use: BTreeMap<u64, K>,
+}
+
+impl<K, V> fmt::Debug for LruMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LruMap")
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruMap<K, V> {
+    /// Create an empty map
+    pub fn new(capacity: usize) -> Self {
+        LruMap {
+            capacity,
+            inner: Mutex::new(LruEntries {
+                tick: 0,
+                entries: HashMap::new(),
+                by_use: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Value of `key`, which becomes the most recently used entry
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut inner = self.inner.lock().ok()?;
+        let tick = inner.next_tick();
+        let LruEntries {
+            entries, by_use, ..
+        } = &mut *inner;
+        let (used_at, value) = entries.get_mut(key)?;
+        by_use.remove(used_at);
+        by_use.insert(tick, key.clone());
+        *used_at = tick;
+        Some(value.clone())
+    }
+
+    /// Insert or replace the value of `key`, evicting the least recently used entry when the map is full
+    pub fn insert(&self, key: K, value: V) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(_) => return,
+        };
+        let tick = inner.next_tick();
+        if let Some((used_at, _)) = inner.entries.remove(&key) {
+            inner.by_use.remove(&used_at);
+        }
+        while inner.entries.len() >= self.capacity {
+            let oldest = match inner.by_use.keys().next() {
+                Some(oldest) => *oldest,
+                None => return,
+            };
+            if let Some(evicted) = inner.by_use.remove(&oldest) {
+                inner.entries.remove(&evicted);
+            }
+        }
+        inner.by_use.insert(tick, key.clone());
+        inner.entries.insert(key, (tick, value));
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .map(|inner| inner.entries.len())
+            .unwrap_or_default()
+    }
+
+    /// Whether the map has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, V> LruEntries<K, V> {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+/// Deduplication of concurrent lookups: while a lookup of a key is running, other lookups of the
+/// same key wait for its result instead of calling the backend again
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let lookups = Arc::new(SingleFlight::new());
+/// validator_middleware.add_async_validator(HttpField::header("X-Api-Key"), move |field_name, field_value| {
+///     let lookups = Arc::clone(&lookups);
+///     let key = field_value.unwrap_or_default().to_string();
+///     let field_name = field_name.to_string();
+///     Box::pin(async move {
+///         let valid = lookups.run(key.clone(), || db::api_key_exists(key)).await;
+///         if valid { Ok(()) } else { Err(format!("'{}' is not a valid API key", field_name)) }
+///     })
+/// });
+/// ```
+pub struct SingleFlight<K, V> {
+    running: Mutex<HashMap<K, Shared<BoxFuture<'static, V>>>>,
+}
+
+impl<K, V> fmt::Debug for SingleFlight<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SingleFlight")
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        SingleFlight::new()
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> SingleFlight<K, V> {
+    /// Create a deduplicator without running lookups
+    pub fn new() -> Self {
+        SingleFlight {
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Result of the lookup of `key`, calling `lookup` only if no lookup of `key` is running
+    pub async fn run<F, Fut>(&self, key: K, lookup: F) -> V
+    where
+        K: Clone,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V> + Send + 'static,
+    {
+        // The lock is released before awaiting, so that the future stays `Send`
+        let (flight, leader) = match self.running.lock() {
+            Ok(mut running) => match running.get(&key) {
+                Some(flight) => (flight.clone(), None),
+                None => {
+                    let flight = lookup().boxed().shared();
+                    running.insert(key.clone(), flight.clone());
+                    let leader = Leader {
+                        running: &self.running,
+                        key,
+                    };
+                    (flight, Some(leader))
+                }
+            },
+            Err(_) => (lookup().boxed().shared(), None),
+        };
+        let value = flight.await;
+        drop(leader);
+        value
+    }
+}
+
+/// Caller which started a lookup, the only one removing it once it resolved or when it's dropped.
+/// Waiters finishing later would otherwise remove a newer lookup of the same key.
+struct Leader<'a, K: Hash + Eq, V> {
+    running: &'a Mutex<HashMap<K, Shared<BoxFuture<'static, V>>>>,
+    key: K,
+}
+
+impl<K: Hash + Eq, V> Drop for Leader<'_, K, V> {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(&self.key);
+        }
+    }
+}
+
+/// Values the middleware gives the handler to cache its responses
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CacheSettings {
+    pub(crate) key_fields: Vec<HttpField<'static>>,
+    pub(crate) hash_json_body: bool,
+}
+
+impl CacheSettings {
+    pub(crate) fn merge(&mut self, other: CacheSettings) {
+        for field in other.key_fields {
+            if !self.key_fields.contains(&field) {
+                self.key_fields.push(field);
+            }
+        }
+        self.hash_json_body |= other.hash_json_body;
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Add a [`CacheKey`](cache/struct.CacheKey.html) built from `fields` in the request extensions
+    /// when the request is valid, so a response cache placed after this middleware keys on the
+    /// validated values of these fields only. A key set by a previous middleware is replaced.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new()
+    ///     .cache_key(&[HttpField::query_param("q"), HttpField::query_param("page"), HttpField::accept()]);
+    /// // ... then in your caching middleware
+    /// let key = req.local::<CacheKey>().map(|key| key.to_string());
+    /// ```
+    pub fn cache_key(mut self, fields: &[HttpField<'static>]) -> Self {
+        self.cache.key_fields = fields.to_vec();
+        self
+    }
+
+    /// Add the [`BodyHash`](cache/struct.BodyHash.html) of the body in the request extensions
+    /// when the request is valid, so idempotency or deduplication layers placed after this
+    /// middleware don't have to read the body again. The body is read even when no validator
+    /// needs it but isn't required to be JSON: JSON bodies are hashed in their canonical form,
+    /// others (forms, multipart, binary) as raw bytes, an empty body is hashed as `null`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().hash_json_body();
+    /// // ... then in your idempotency middleware
+    /// let hash = req.local::<BodyHash>().map(|hash| hash.to_string());
+    /// ```
+    pub fn hash_json_body(mut self) -> Self {
+        self.cache.hash_json_body = true;
+        self
+    }
+
+    /// Add the cache key and the body hash of the valid request in its extensions
+    pub(crate) fn store_cache_keys(
+        &self,
+        mut ctx: Request<State>,
+        values: &mut RequestValues<'_, State>,
+        body: &RequestBody,
+    ) -> Request<State> {
+        if !self.cache.key_fields.is_empty() {
+            let key_values: Vec<Option<String>> = self
+                .cache
+                .key_fields
+                .iter()
+                .map(|field| values.get(&ctx, field))
+                .collect();
+            let key = CacheKey::new(
+                self.cache
+                    .key_fields
+                    .iter()
+                    .zip(key_values.iter().map(Option::as_deref)),
+            );
+            ctx = ctx.set_local(key);
+        }
+        if self.cache.hash_json_body {
+            ctx = ctx.set_local(if body.is_json {
+                BodyHash::new(&body.json)
+            } else {
+                BodyHash::from_body(&body.raw)
+            });
+        }
+        ctx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tide::http::{Method, Request};
+
+    #[test]
+    fn failures_are_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let validator = negative_cache(Duration::from_secs(60), 10, move |_, value| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let valid = value == Some("good");
+            Box::pin(async move {
+                if valid {
+                    Ok(())
+                } else {
+                    Err(String::from("unknown key"))
+                }
+            })
+        });
+
+        assert!(block_on(validator("key", Some("bad"))).is_err());
+        assert!(block_on(validator("key", Some("bad"))).is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert!(block_on(validator("key", Some("good"))).is_ok());
+        assert!(block_on(validator("key", Some("good"))).is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn cached_results() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let results = Arc::new(MemoryCache::new(10));
+        let validator = cached(
+            Arc::clone(&results),
+            Duration::from_secs(60),
+            move |_, value| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let valid = value == Some("good");
+                Box::pin(async move {
+                    if valid {
+                        Ok(())
+                    } else {
+                        Err(String::from("unknown key"))
+                    }
+                })
+            },
+        );
+
+        assert!(block_on(validator("key", Some("good"))).is_ok());
+        assert!(block_on(validator("key", Some("good"))).is_ok());
+        assert_eq!(
+            block_on(validator("key", Some("bad"))),
+            Err(String::from("unknown key"))
+        );
+        assert!(block_on(validator("key", Some("bad"))).is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            block_on(results.get("key", "bad")),
+            Some(Err(String::from("unknown key")))
+        );
+        assert_eq!(block_on(results.get("other", "bad")), None);
+
+        // Missing fields aren't cached
+        assert!(block_on(validator("key", None)).is_err());
+        assert!(block_on(validator("key", None)).is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn memory_cache_expiration() {
+        let cache = MemoryCache::new(1);
+        block_on(cache.set("key", "a", Err(1), Duration::from_millis(0)));
+        assert_eq!(block_on(cache.get("key", "a")), None);
+        block_on(cache.set("key", "b", Ok(()), Duration::from_secs(60)));
+        assert_eq!(block_on(cache.get("key", "b")), Some(Ok(())));
+        // Full of results which haven't expired
+        block_on(cache.set("key", "c", Ok(()), Duration::from_secs(60)));
+        assert_eq!(block_on(cache.get("key", "c")), None);
+
+        let cache = MemoryCache::new(1);
+        block_on(cache.set("key", "a", Err(1), Duration::from_secs(u64::MAX)));
+        assert_eq!(block_on(cache.get("key", "a")), Some(Err(1)));
+    }
+
+    #[test]
+    fn concurrent_cached_lookups() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let (release, released) = futures::channel::oneshot::channel::<()>();
+        let released = released.shared();
+        let validator = cached(
+            MemoryCache::new(10),
+            Duration::from_secs(60),
+            move |_, _| -> BoxFuture<'static, Result<(), String>> {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let released = released.clone();
+                Box::pin(async move {
+                    let _ = released.await;
+                    Ok(())
+                })
+            },
+        );
+
+        let (first, second, _) = block_on(futures::future::join3(
+            validator("key", Some("slow")),
+            validator("key", Some("slow")),
+            async move {
+                let _ = release.send(());
+            },
+        ));
+        assert_eq!((first, second), (Ok(()), Ok(())));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn cache_keys() {
+        let (page, sort, accept, missing) = (
+            HttpField::query_param("page"),
+            HttpField::query_param("sort"),
+            HttpField::accept(),
+            HttpField::header("If-None-Match"),
+        );
+        let key = CacheKey::new(vec![
+            (&sort, Some("a&b=c")),
+            (&page, Some("2")),
+            (&accept, Some("application/json")),
+            (&missing, None),
+        ]);
+        assert_eq!(
+            key.as_str(),
+            "header.accept=application/json&header.if-none-match&query.page=2&query.sort=a%26b%3Dc"
+        );
+        assert_eq!(
+            key,
+            CacheKey::new(vec![
+                (&page, Some("2")),
+                (&missing, None),
+                (&accept, Some("application/json")),
+                (&sort, Some("a&b=c")),
+            ])
+        );
+        assert_ne!(
+            CacheKey::new(vec![(&page, Some(""))]),
+            CacheKey::new(vec![(&page, None)])
+        );
+    }
+
+    #[test]
+    fn expired_failures_are_evicted() {
+        let cache = TtlMap::new(Duration::from_millis(0), 1);
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), None);
+        cache.insert("b", 2);
+        assert_eq!(cache.entries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn body_hashes() {
+        let body: serde_json::Value =
+            serde_json::from_str(r#"{"b": [1.0, 2.5, -0.0], "a": {"y": "\u00e9", "x": 1e2}}"#)
+                .unwrap();
+        assert_eq!(
+            canonical_json(&body),
+            r#"{"a":{"x":100,"y":"é"},"b":[1,2.5,0]}"#
+        );
+
+        let same: serde_json::Value =
+            serde_json::from_str(r#"{"a":{"x":100,"y":"é"},"b":[1,2.5,0]}"#).unwrap();
+        assert_eq!(BodyHash::new(&body), BodyHash::new(&same));
+        assert_eq!(BodyHash::new(&body).to_string().len(), 32);
+
+        let other = serde_json::json!({"a": {"x": 100, "y": "é"}, "b": [1, 2.5, 1]});
+        assert_ne!(BodyHash::new(&body), BodyHash::new(&other));
+
+        assert_eq!(
+            BodyHash::from_body(br#"{ "b": [1, 2.5, 0], "a": {"x": 1e2, "y": "\u00e9"} }"#),
+            BodyHash::new(&body)
+        );
+        assert_eq!(
+            BodyHash::from_body(b""),
+            BodyHash::new(&serde_json::Value::Null)
+        );
+        assert_eq!(
+            BodyHash::from_body(b"name=bob&age=42"),
+            BodyHash::from_body(b"name=bob&age=42")
+        );
+        assert_ne!(
+            BodyHash::from_body(b"name=bob&age=42"),
+            BodyHash::from_body(b"name=bob&age=43")
+        );
+    }
+
+    #[test]
+    fn lru_evictions() {
+        let cache = LruMap::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(cache.get(&"a"), Some(1));
+        cache.insert("c", 3);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"c"), Some(3));
+        cache.insert("c", 4);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"c"), Some(4));
+    }
+
+    #[test]
+    fn single_flight() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flights = SingleFlight::new();
+        let lookup = |value: u32| {
+            let calls = Arc::clone(&calls);
+            move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                // Yield once so the second lookup starts while the first one is running
+                let mut yielded = false;
+                futures::future::poll_fn(|cx| {
+                    if yielded {
+                        std::task::Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        cx.waker().wake_by_ref();
+                        std::task::Poll::Pending
+                    }
+                })
+                .await;
+                value
+            }
+        };
+        let (first, second) = block_on(futures::future::join(
+            flights.run("key", lookup(1)),
+            flights.run("key", lookup(2)),
+        ));
+        assert_eq!((first, second), (1, 1));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(block_on(flights.run("key", lookup(3))), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn single_flight_late_waiters() {
+        use futures::channel::oneshot;
+        use std::task::{Context, Poll};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flights = SingleFlight::new();
+        let gated = |gate: oneshot::Receiver<u32>| {
+            let calls = Arc::clone(&calls);
+            move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                gate.await.unwrap_or_default()
+            }
+        };
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let (open_first, first_gate) = oneshot::channel();
+        let mut leader = flights.run("key", gated(first_gate)).boxed();
+        let mut waiter = flights.run("key", gated(oneshot::channel().1)).boxed();
+        assert!(leader.poll_unpin(&mut cx).is_pending());
+        assert!(waiter.poll_unpin(&mut cx).is_pending());
+        open_first.send(1).unwrap();
+        assert_eq!(leader.poll_unpin(&mut cx), Poll::Ready(1));
+
+        // A new lookup starts before the waiter of the first one finishes
+        let (open_second, second_gate) = oneshot::channel();
+        let mut second = flights.run("key", gated(second_gate)).boxed();
+        assert!(second.poll_unpin(&mut cx).is_pending());
+        assert_eq!(waiter.poll_unpin(&mut cx), Poll::Ready(1));
+        let mut joined = flights.run("key", gated(oneshot::channel().1)).boxed();
+        assert!(joined.poll_unpin(&mut cx).is_pending());
+        open_second.send(2).unwrap();
+        assert_eq!(second.poll_unpin(&mut cx), Poll::Ready(2));
+        assert_eq!(joined.poll_unpin(&mut cx), Poll::Ready(2));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn validator_body_hash() {
+        let mut inner = tide::new();
+        inner
+            .at("/foo")
+            .middleware(ValidatorMiddleware::<String>::new().hash_json_body())
+            .post(|req: tide::Request<()>| async move {
+                Ok(req
+                    .local::<BodyHash>()
+                    .map(|hash| hash.to_string())
+                    .unwrap_or_default())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (content_type, body, hash) in &[
+            (
+                "application/json",
+                r#"{ "b": 1.0, "a": "x" }"#,
+                BodyHash::new(&serde_json::json!({"a": "x", "b": 1})),
+            ),
+            (
+                "application/x-www-form-urlencoded",
+                "a=x&b=1",
+                BodyHash::from_body(b"a=x&b=1"),
+            ),
+            (
+                "application/octet-stream",
+                "\u{0}PNG",
+                BodyHash::from_body(b"\0PNG"),
+            ),
+        ] {
+            let mut buf = Vec::new();
+            let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+            req.insert_header("Content-Type", *content_type).unwrap();
+            req.set_body(*body);
+            let mut res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), 200);
+            block_on(res.read_to_end(&mut buf)).unwrap();
+            assert_eq!(String::from_utf8_lossy(&buf), hash.to_string());
+        }
+    }
+}
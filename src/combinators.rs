@@ -0,0 +1,314 @@
+//! Wrappers changing how other validators are run, they take built-in validators as well as
+//! closures and can be nested.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::combinators::{all, any, map_value, not, optional, required};
+//!
+//! // Fails with the standard "'age' is required" message when the field is missing
+//! validator_middleware.add_validator(HttpField::query_param("age"), required(is_number));
+//! // `is_number` never sees a missing field
+//! validator_middleware.add_validator(HttpField::query_param("page"), optional(is_number));
+//! // Log in with a UUID or an email address
+//! validator_middleware.add_validator(HttpField::query_param("login"), any(is_uuid, is_email));
+//! validator_middleware.add_validator(HttpField::query_param("name"), all(not(is_empty), is_max_length(64)));
+//! // Only accept emails of the company domain
+//! validator_middleware.add_validator(
+//!     HttpField::query_param("email"),
+//!     map_value(|email| email.rsplit('@').next().map(String::from), one_of(&["example.com"])),
+//! );
+//! ```
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+
+use crate::validators::{self, ValidationError};
+use crate::{AnyError, BoxedValidator};
+
+/// Fail early with the standard required error when the field is missing, otherwise run `inner`.
+///
+/// The error type must be buildable from a [`ValidationError`](../validators/struct.ValidationError.html),
+/// it's already the case for `String` and `ValidationError`.
+pub fn required<T, F>(inner: F) -> BoxedValidator<T>
+where
+    T: From<ValidationError> + 'static,
+    F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+            validators::is_required(field_name, field_value).map_err(T::from)?;
+            inner(field_name, field_value)
+        },
+    )
+}
+
+/// Only run `inner` when the field is present, a missing field is always valid
+pub fn optional<T, F>(inner: F) -> BoxedValidator<T>
+where
+    T: 'static,
+    F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+            match field_value {
+                Some(_) => inner(field_name, field_value),
+                None => Ok(()),
+            }
+        },
+    )
+}
+
+/// Valid when both validators are, fails with the error of the first one failing
+pub fn all<T, F, G>(first: F, second: G) -> BoxedValidator<T>
+where
+    T: 'static,
+    F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    G: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+            first(field_name, field_value)?;
+            second(field_name, field_value)
+        },
+    )
+}
+
+/// Valid when at least one of the validators is, `second` only runs when `first` fails and the
+/// error of `first` is returned when both fail
+pub fn any<T, F, G>(first: F, second: G) -> BoxedValidator<T>
+where
+    T: 'static,
+    F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    G: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+            match first(field_name, field_value) {
+                Ok(()) => Ok(()),
+                Err(err) => second(field_name, field_value).map_err(|_| err),
+            }
+        },
+    )
+}
+
+/// Valid when `inner` fails, e.g. `not(is_empty)`. The error has the code `not`, it must be
+/// buildable from a [`ValidationError`](../validators/struct.ValidationError.html).
+pub fn not<T, F>(inner: F) -> BoxedValidator<T>
+where
+    T: From<ValidationError> + 'static,
+    F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+            match inner(field_name, field_value) {
+                Ok(()) => Err(ValidationError::new(
+                    field_name,
+                    "not",
+                    format!(
+                        "field '{}' = '{}' is not allowed",
+                        field_name,
+                        field_value.unwrap_or_default()
+                    ),
+                )
+                .into()),
+                Err(_) => Ok(()),
+            }
+        },
+    )
+}
+
+/// Run `inner` on a value derived from the field, e.g. the domain of an email or the version
+/// prefix of a token. `inner` gets `None` when the field is missing or when `map` gives `None`,
+/// its errors are reported on the field.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// // "v2.abcdef" -> "v2"
+/// let version = |token: &str| token.split('.').next().map(String::from);
+/// validator_middleware.add_validator(HttpField::header("X-Token"), map_value(version, one_of(&["v1", "v2"])));
+/// ```
+pub fn map_value<T, M, F>(map: M, inner: F) -> BoxedValidator<T>
+where
+    T: 'static,
+    M: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+            let mapped = field_value.and_then(&map);
+            inner(field_name, mapped.as_deref())
+        },
+    )
+}
+
+/// Erase the error type of a validator, so validators returning different error types can be
+/// added to a `ValidatorMiddleware<AnyError>`, see [`AnyError`](../erased/struct.AnyError.html)
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let mut validator_middleware: ValidatorMiddleware<AnyError> = ValidatorMiddleware::new();
+/// validator_middleware.add_validator(HttpField::query_param("age"), erase(is_number));
+/// validator_middleware.add_validator(HttpField::query_param("age"), erase(is_adult));
+/// ```
+pub fn erase<E, F>(inner: F) -> BoxedValidator<AnyError>
+where
+    E: Serialize + Send + Sync + 'static,
+    F: Fn(&str, Option<&str>) -> Result<(), E> + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), AnyError> {
+            inner(field_name, field_value).map_err(AnyError::new)
+        },
+    )
+}
+
+/// Only run `inner` for a fraction of the requests, e.g. `0.1` for one request out of ten, other
+/// requests skip it. Made for expensive heuristic checks like injection detectors on high traffic
+/// routes: failures of the sampled runs are still counted by the
+/// [validation status route](../struct.ValidatorMiddleware.html#method.validation_status_route),
+/// skipped runs are only counted for validators added with
+/// [`add_sampled_validator`](../struct.ValidatorMiddleware.html#method.add_sampled_validator).
+///
+/// Sampling isn't random: each validator returned by `sample_rate` counts its own calls, for every
+/// request and field it validates, and runs `inner` on a regular subset of them, e.g. calls 4, 8,
+/// 12... with `0.25`. Clients sending requests in a row can predict which ones are checked, don't
+/// rely on it to enforce a rule. The rate is clamped between `0.0` (never run) and `1.0` (always
+/// run).
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// validator_middleware.add_validator(HttpField::query_param("q"), sample_rate(0.1, looks_like_sql_injection));
+/// ```
+pub fn sample_rate<T, F>(rate: f64, inner: F) -> BoxedValidator<T>
+where
+    T: 'static,
+    F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+{
+    let sampler = Sampler::new(rate);
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+            if sampler.sample() {
+                inner(field_name, field_value)
+            } else {
+                Ok(())
+            }
+        },
+    )
+}
+
+/// Deterministic sampling of the calls of a validator, see [`sample_rate`](fn.sample_rate.html)
+#[derive(Debug)]
+pub(crate) struct Sampler {
+    rate: f64,
+    calls: AtomicU64,
+}
+
+impl Sampler {
+    pub(crate) fn new(rate: f64) -> Self {
+        let rate = if rate.is_nan() {
+            0.0
+        } else {
+            rate.clamp(0.0, 1.0)
+        };
+        Sampler {
+            rate,
+            calls: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether this call is sampled
+    pub(crate) fn sample(&self) -> bool {
+        let call = self.calls.fetch_add(1, Ordering::Relaxed) as f64;
+        ((call + 1.0) * self.rate).floor() > (call * self.rate).floor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_present(_: &str, field_value: Option<&str>) -> Result<(), String> {
+        field_value
+            .map(|_| ())
+            .ok_or_else(|| String::from("missing"))
+    }
+
+    #[test]
+    fn required_and_optional() {
+        let validator = required(is_present);
+        assert_eq!(
+            validator("age", None),
+            Err(String::from("'age' is required"))
+        );
+        assert!(validator("age", Some("4")).is_ok());
+
+        let validator = optional(is_present);
+        assert!(validator("age", None).is_ok());
+    }
+
+    #[test]
+    fn logic() {
+        let validator = any(validators::is_uuid, validators::is_email);
+        assert!(validator("login", Some("john@example.com")).is_ok());
+        assert!(validator("login", Some("67e55044-10b1-426f-9247-bb680e5fe0c8")).is_ok());
+        assert_eq!(validator("login", Some("john")).unwrap_err().code, "uuid");
+
+        let validator = all(not(validators::is_empty), validators::is_max_length(4));
+        assert!(validator("name", Some("john")).is_ok());
+        assert_eq!(validator("name", Some("")).unwrap_err().code, "not");
+        assert_eq!(validator("name", None).unwrap_err().code, "not");
+        assert_eq!(validator("name", Some("johnny")).unwrap_err().code, "max");
+    }
+
+    #[test]
+    fn mapped() {
+        let domain = |email: &str| {
+            email
+                .split_once('@')
+                .map(|(_, domain)| domain)
+                .filter(|domain| !domain.is_empty())
+                .map(String::from)
+        };
+        let validator = map_value(domain, required(validators::one_of(&["example.com"])));
+        assert!(validator("email", Some("john@example.com")).is_ok());
+        assert_eq!(
+            validator("email", Some("john@example.org"))
+                .unwrap_err()
+                .code,
+            "in"
+        );
+        assert_eq!(
+            validator("email", Some("john")).unwrap_err().code,
+            "required"
+        );
+        assert_eq!(validator("email", None).unwrap_err().code, "required");
+    }
+
+    #[test]
+    fn sampled() {
+        let validator = sample_rate(0.25, is_present);
+        let failures = (0..100).filter(|_| validator("age", None).is_err()).count();
+        assert_eq!(failures, 25);
+
+        let validator = sample_rate(0.0, is_present);
+        assert!((0..10).all(|_| validator("age", None).is_ok()));
+        let validator = sample_rate(2.0, is_present);
+        assert!((0..10).all(|_| validator("age", None).is_err()));
+    }
+
+    #[test]
+    fn erased() {
+        let validators: Vec<BoxedValidator<AnyError>> =
+            vec![erase(is_present), erase(validators::is_number)];
+        assert_eq!(
+            validators[0]("age", None)
+                .unwrap_err()
+                .downcast_ref::<String>(),
+            Some(&String::from("missing"))
+        );
+        let err = validators[1]("age", Some("a")).unwrap_err();
+        assert_eq!(err.as_value()["code"], "numeric");
+    }
+}
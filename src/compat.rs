@@ -0,0 +1,17 @@
+//! Names used by previous versions, kept so existing code compiles while migrating.
+//!
+//! Fields used to be described with `ParameterType`, renamed to
+//! [`HttpField`](../enum.HttpField.html). Variants kept their names but now hold a `Cow<str>`:
+//!
+//! ```rust,no_run,compile_fail
+//! // Before
+//! validator_middleware.add_validator(ParameterType::QueryParam("age"), is_number);
+//! // After
+//! validator_middleware.add_validator(HttpField::query_param("age"), is_number);
+//! validator_middleware.add_validator(HttpField::try_from(("query", "age"))?, is_number);
+//! ```
+use crate::HttpField;
+
+/// Previous name of [`HttpField`](../enum.HttpField.html)
+#[deprecated(since = "0.8.0", note = "renamed to `HttpField`")]
+pub type ParameterType<'a> = HttpField<'a>;
@@ -0,0 +1,168 @@
+//! Compressed bodies, decoded before body fields are validated
+use std::io::Read;
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use tide::StatusCode;
+
+use crate::response::Rejection;
+use crate::validators::ValidationError;
+use crate::HttpField;
+
+/// Reason a body can't be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DecodeError {
+    /// The coding isn't gzip, deflate or identity
+    Unsupported(String),
+    /// The body isn't a valid stream of the coding
+    Invalid(String, String),
+    /// The decoded body is larger than the limit
+    TooLarge(usize),
+}
+
+impl DecodeError {
+    /// Error rejecting the request with `415 Unsupported Media Type`, `400 Bad Request` or
+    /// `413 Payload Too Large`
+    pub(crate) fn into_rejection(self) -> Rejection {
+        match self {
+            DecodeError::Unsupported(coding) => (
+                StatusCode::UnsupportedMediaType,
+                HttpField::header("Content-Encoding"),
+                ValidationError::new(
+                    "Content-Encoding",
+                    "unsupported_encoding",
+                    format!("unsupported content encoding '{}'", coding),
+                )
+                .with_param("coding", coding),
+            ),
+            DecodeError::Invalid(coding, err) => (
+                StatusCode::BadRequest,
+                HttpField::custom("body"),
+                ValidationError::new(
+                    "body",
+                    "invalid_encoding",
+                    format!("cannot decode {} body: {}", coding, err),
+                )
+                .with_param("coding", coding),
+            ),
+            DecodeError::TooLarge(max_size) => (
+                StatusCode::PayloadTooLarge,
+                HttpField::custom("body"),
+                ValidationError::new(
+                    "body",
+                    "max_decoded_body_size",
+                    format!("decoded body is larger than {} bytes", max_size),
+                )
+                .with_param("max", max_size),
+            ),
+        }
+    }
+}
+
+/// Decode `body` sent with the `Content-Encoding` header `encoding`, which lists the codings in
+/// the order they were applied. Decoding stops one byte past `max_size`, so a small compressed
+/// body can't expand unbounded.
+pub(crate) fn decode(
+    encoding: &str,
+    body: Vec<u8>,
+    max_size: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    let mut body = body;
+    for coding in encoding.rsplit(',').map(str::trim) {
+        let limit = max_size as u64 + 1;
+        let mut decoded = Vec::new();
+        let read = if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+            GzDecoder::new(&body[..])
+                .take(limit)
+                .read_to_end(&mut decoded)
+        } else if coding.eq_ignore_ascii_case("deflate") {
+            // Most clients send raw deflate streams, zlib ones are recognized by their header
+            if is_zlib(&body) {
+                ZlibDecoder::new(&body[..])
+                    .take(limit)
+                    .read_to_end(&mut decoded)
+            } else {
+                DeflateDecoder::new(&body[..])
+                    .take(limit)
+                    .read_to_end(&mut decoded)
+            }
+        } else if coding.is_empty() || coding.eq_ignore_ascii_case("identity") {
+            continue;
+        } else {
+            return Err(DecodeError::Unsupported(coding.to_string()));
+        };
+        if let Err(err) = read {
+            return Err(DecodeError::Invalid(coding.to_string(), err.to_string()));
+        }
+        if decoded.len() > max_size {
+            return Err(DecodeError::TooLarge(max_size));
+        }
+        body = decoded;
+    }
+    Ok(body)
+}
+
+/// Whether the stream starts with a zlib header: deflate method and a valid check value
+fn is_zlib(body: &[u8]) -> bool {
+    match body {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decoded_bodies() {
+        let body = br#"{"name": "Gribouille"}"#;
+        assert_eq!(decode("gzip", gzip(body), 1024).unwrap(), body);
+        assert_eq!(decode("x-gzip", gzip(body), 1024).unwrap(), body);
+        assert_eq!(decode("identity", body.to_vec(), 1024).unwrap(), body);
+
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(body).unwrap();
+        let deflate = deflate.finish().unwrap();
+        assert_eq!(decode("deflate", deflate.clone(), 1024).unwrap(), body);
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(body).unwrap();
+        assert_eq!(
+            decode("Deflate", zlib.finish().unwrap(), 1024).unwrap(),
+            body
+        );
+        // Applied in order: deflate first, gzip last
+        assert_eq!(decode("deflate, gzip", gzip(&deflate), 1024).unwrap(), body);
+
+        assert!(matches!(
+            decode("gzip", body.to_vec(), 1024),
+            Err(DecodeError::Invalid(..))
+        ));
+        assert_eq!(
+            decode("br", body.to_vec(), 1024),
+            Err(DecodeError::Unsupported(String::from("br")))
+        );
+    }
+
+    #[test]
+    fn zip_bombs() {
+        let bomb = gzip(&vec![0; 1024 * 1024]);
+        assert!(bomb.len() < 2048);
+        assert_eq!(
+            decode("gzip", bomb.clone(), 64 * 1024),
+            Err(DecodeError::TooLarge(64 * 1024))
+        );
+        assert_eq!(
+            decode("gzip", bomb, 1024 * 1024).unwrap().len(),
+            1024 * 1024
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! Checks of the configuration once every rule is added, and order of dependent fields
+
+use serde::Serialize;
+
+use crate::{HttpField, ValidatorMiddleware};
+
+/// Configuration error found by [`finalize`](struct.ValidatorMiddleware.html#method.finalize) or
+/// when building a [`Schedule`](schedule/struct.Schedule.html), a JWT preset or an IP range
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// Fields depending on each other, the first field is repeated at the end
+    DependencyCycle(Vec<HttpField<'static>>),
+    /// A field depends on a field without validators, its validators would never run after it
+    UnknownDependency {
+        /// Field declaring the dependency
+        field: HttpField<'static>,
+        /// Field without validators
+        depends_on: HttpField<'static>,
+    },
+    /// A header field is named with characters which cannot appear in a header name, e.g.
+    /// `"X-Request-Id:"`
+    InvalidHeaderName(String),
+    /// A location name isn't one of `path`, `query`, `header`, `cookie`, `body` or `custom`
+    UnknownLocation(String),
+    /// A field has an empty name, e.g. `HttpField::query_param("")`
+    EmptyFieldName(HttpField<'static>),
+    /// More validators than allowed by
+    /// [`max_validators_per_field`](struct.ValidatorMiddleware.html#method.max_validators_per_field)
+    /// are registered for a field, usually rules added in a loop by mistake
+    TooManyValidators {
+        /// Field with too many validators
+        field: HttpField<'static>,
+        /// Number of validators of the field
+        count: usize,
+        /// Maximum number of validators
+        max: usize,
+    },
+    /// The same function is added twice with `add_validator` for a field
+    DuplicateValidator(HttpField<'static>),
+    /// A time of day of a [`Schedule`](schedule/struct.Schedule.html) isn't `HH:MM` or `HH:MM:SS`
+    InvalidTimeOfDay(String),
+    /// A key given to verify signatures can't be parsed, e.g. a malformed PEM file
+    InvalidKey(String),
+    /// An IP range isn't written in CIDR notation, e.g. `10.0.0.0/8`
+    InvalidCidr(String),
+    /// Path parameters are validated but missing from the route given to
+    /// [`bind_route`](struct.ValidatorMiddleware.html#method.bind_route), usually a typo
+    UnknownRouteParams {
+        /// Route of the middleware, e.g. `/users/:id`
+        route: String,
+        /// Parameters missing from the route
+        params: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::DependencyCycle(fields) => {
+                let cycle: Vec<String> = fields
+                    .iter()
+                    .map(|field| format!("{} '{}'", field.kind(), field.name()))
+                    .collect();
+                write!(f, "dependency cycle: {}", cycle.join(" -> "))
+            }
+            ConfigError::UnknownDependency { field, depends_on } => write!(
+                f,
+                "{} '{}' depends on {} '{}' which has no validators",
+                field.kind(),
+                field.name(),
+                depends_on.kind(),
+                depends_on.name()
+            ),
+            ConfigError::InvalidHeaderName(name) => write!(f, "invalid header name '{}'", name),
+            ConfigError::UnknownLocation(location) => write!(f, "unknown location '{}'", location),
+            ConfigError::EmptyFieldName(field) => write!(f, "{} without name", field.kind()),
+            ConfigError::TooManyValidators { field, count, max } => write!(
+                f,
+                "{} '{}' has {} validators, more than {}",
+                field.kind(),
+                field.name(),
+                count,
+                max
+            ),
+            ConfigError::DuplicateValidator(field) => write!(
+                f,
+                "{} '{}' has the same validator twice",
+                field.kind(),
+                field.name()
+            ),
+            ConfigError::InvalidTimeOfDay(value) => write!(f, "invalid time of day '{}'", value),
+            ConfigError::InvalidKey(reason) => write!(f, "invalid key: {}", reason),
+            ConfigError::InvalidCidr(value) => write!(f, "invalid IP range '{}'", value),
+            ConfigError::UnknownRouteParams { route, params } => write!(
+                f,
+                "parameters '{}' aren't in the route '{}'",
+                params.join("', '"),
+                route
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Maximum number of validators of a single field accepted by `finalize` by default
+pub(crate) const DEFAULT_MAX_VALIDATORS_PER_FIELD: usize = 32;
+
+/// Check that a field can be found in a request, i.e. that it has a name and that header names
+/// are HTTP tokens
+pub(crate) fn check_field(field: &HttpField<'_>) -> Result<(), ConfigError> {
+    match field {
+        HttpField::Header(name) if !is_token(name) => {
+            Err(ConfigError::InvalidHeaderName(name.to_string()))
+        }
+        HttpField::MatrixParam(segment, _) if segment.is_empty() => {
+            Err(ConfigError::EmptyFieldName(field.clone().into_owned()))
+        }
+        HttpField::Param(name)
+        | HttpField::QueryParam(name)
+        | HttpField::QueryList(name, _)
+        | HttpField::Cookie(name)
+        | HttpField::BodyField(name)
+        | HttpField::MatrixParam(_, name)
+        | HttpField::Multipart(name)
+        | HttpField::Custom(name)
+            if name.is_empty() =>
+        {
+            Err(ConfigError::EmptyFieldName(field.clone().into_owned()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Token as defined by [RFC 7230](https://tools.ietf.org/html/rfc7230#section-3.2.6)
+pub(crate) fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Maximum number of validators of a single field accepted by
+    /// [`finalize`](#method.finalize), 32 by default
+    pub fn max_validators_per_field(mut self, max_validators: usize) -> Self {
+        self.max_validators_per_field = max_validators;
+        self
+    }
+
+    /// Run the validators of `field` after the validators of `depends_on`, and skip them when one
+    /// of these fields is invalid, e.g. to only check a coupon once the product is valid. A
+    /// dependency closing a cycle isn't added and returns
+    /// [`ConfigError::DependencyCycle`](enum.ConfigError.html#variant.DependencyCycle). Call
+    /// [`finalize`](#method.finalize) once every rule is added to check that the dependencies
+    /// point to fields with validators.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// validator_middleware.add_dependency(HttpField::query_param("coupon"), &[HttpField::param("product")])?;
+    /// let validator_middleware = validator_middleware.finalize()?;
+    /// ```
+    pub fn add_dependency(
+        &mut self,
+        field: HttpField<'static>,
+        depends_on: &[HttpField<'static>],
+    ) -> Result<(), ConfigError> {
+        let previous = self.dependencies.get(&field).cloned();
+        self.dependencies
+            .entry(field.clone())
+            .or_default()
+            .extend(depends_on.iter().cloned());
+        match self.topological_order() {
+            Ok(order) => {
+                self.order = Some(order);
+                Ok(())
+            }
+            Err(err) => {
+                match previous {
+                    Some(previous) => self.dependencies.insert(field, previous),
+                    None => self.dependencies.remove(&field),
+                };
+                Err(err)
+            }
+        }
+    }
+
+    /// Check the configuration once every rule is added: fields must have a valid name and a
+    /// reasonable number of validators, the same function must not be added twice for a field,
+    /// dependencies must point to fields with validators and must not form a cycle. Validators
+    /// then run in an order respecting the dependencies.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = validator_middleware.finalize()?;
+    /// app.at("/users").middleware(validator_middleware).get(list_users);
+    /// ```
+    pub fn finalize(mut self) -> Result<Self, ConfigError> {
+        for (field, rules) in &self.validators {
+            check_field(field)?;
+            if rules.len() > self.max_validators_per_field {
+                return Err(ConfigError::TooManyValidators {
+                    field: field.clone(),
+                    count: rules.len(),
+                    max: self.max_validators_per_field,
+                });
+            }
+        }
+        for (idx, (field, type_id)) in self.stateless_validators.iter().enumerate() {
+            if self.stateless_validators[..idx].contains(&(field.clone(), *type_id)) {
+                return Err(ConfigError::DuplicateValidator(field.clone()));
+            }
+        }
+        for (field, depends_on) in &self.dependencies {
+            if let Some(unknown) = depends_on
+                .iter()
+                .find(|depends_on| !self.validators.contains_key(depends_on))
+            {
+                return Err(ConfigError::UnknownDependency {
+                    field: field.clone(),
+                    depends_on: unknown.clone(),
+                });
+            }
+        }
+        self.order = Some(self.topological_order()?);
+        Ok(self)
+    }
+
+    /// Check that the path parameters read by the middleware appear in `route`, the route it's
+    /// mounted on written as for `app.at`, so a typo like `HttpField::param("agee")` is caught at
+    /// startup instead of rejecting every request
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut validator_middleware = ValidatorMiddleware::new();
+    /// validator_middleware.add_validator(HttpField::param("age"), is_number);
+    /// let validator_middleware = validator_middleware.bind_route("/test/:age")?;
+    /// app.at("/test/:age").middleware(validator_middleware).get(endpoint);
+    /// ```
+    pub fn bind_route(self, route: &str) -> Result<Self, ConfigError> {
+        let route_params: Vec<&str> = route
+            .split('/')
+            .filter_map(|segment| {
+                segment
+                    .strip_prefix(':')
+                    .or_else(|| segment.strip_prefix('*'))
+            })
+            .collect();
+        let mut params: Vec<String> = Vec::new();
+        for field in self.fields() {
+            if let HttpField::Param(name) = field {
+                if !route_params.contains(&name.as_ref())
+                    && !params.iter().any(|param| param == name)
+                {
+                    params.push(name.to_string());
+                }
+            }
+        }
+        if params.is_empty() {
+            Ok(self)
+        } else {
+            Err(ConfigError::UnknownRouteParams {
+                route: route.to_string(),
+                params,
+            })
+        }
+    }
+
+    /// Every field read by the middleware, including the fields of the rules it nests
+    pub(crate) fn fields(&self) -> Vec<&HttpField<'static>> {
+        let mut fields: Vec<&HttpField<'static>> = self
+            .validators
+            .keys()
+            .chain(self.normalize.transformers.keys())
+            .chain(self.normalize.codecs.keys())
+            .chain(self.normalize.defaults.keys())
+            .chain(self.normalize.percent_decoding.keys())
+            .chain(self.presence.keys())
+            .chain(self.resolvers.iter().map(|resolver| &resolver.field))
+            .chain(&self.scopes.step_field)
+            .chain(self.group_validators.iter().flat_map(|group| &group.fields))
+            .collect();
+        #[cfg(feature = "sessions")]
+        fields.extend(
+            self.scopes
+                .session_rules
+                .iter()
+                .flat_map(|(_, rules)| rules.fields()),
+        );
+        fields.extend(
+            self.scopes
+                .steps
+                .iter()
+                .flat_map(|(_, rules)| rules.fields()),
+        );
+        fields.extend(
+            self.scopes
+                .method_rules
+                .iter()
+                .flat_map(|(_, rules)| rules.fields()),
+        );
+        fields
+    }
+
+    /// Sort the fields once after the dependencies or the fields changed, instead of on every
+    /// request. A cycle leaves the fields in insertion order until
+    /// [`finalize`](#method.finalize) reports it.
+    pub(crate) fn update_order(&mut self) {
+        self.order = if self.dependencies.is_empty() || self.validators.is_empty() {
+            None
+        } else {
+            self.topological_order().ok()
+        };
+    }
+
+    /// Fields with validators or dependencies sorted so that each field comes after the fields it
+    /// depends on
+    pub(crate) fn topological_order(&self) -> Result<Vec<HttpField<'static>>, ConfigError> {
+        let mut remaining: Vec<&HttpField<'static>> = self.validators.keys().collect();
+        for (field, depends_on) in &self.dependencies {
+            for field in std::iter::once(field).chain(depends_on) {
+                if !remaining.contains(&field) {
+                    remaining.push(field);
+                }
+            }
+        }
+        let mut order: Vec<HttpField<'static>> = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let is_pending = |field: &HttpField<'static>| remaining.contains(&field);
+            let ready = remaining.iter().position(|field| {
+                self.dependencies
+                    .get(*field)
+                    .map(|depends_on| !depends_on.iter().any(is_pending))
+                    .unwrap_or(true)
+            });
+            match ready {
+                Some(ready) => order.push(remaining.remove(ready).clone()),
+                None => return Err(ConfigError::DependencyCycle(self.find_cycle(&remaining))),
+            }
+        }
+        Ok(order)
+    }
+
+    /// Follow the pending dependencies of fields which all have one until a field repeats
+    pub(crate) fn find_cycle(&self, remaining: &[&HttpField<'static>]) -> Vec<HttpField<'static>> {
+        let mut path: Vec<HttpField<'static>> = Vec::new();
+        let mut current = remaining[0];
+        loop {
+            if let Some(start) = path.iter().position(|field| field == current) {
+                let mut cycle = path.split_off(start);
+                cycle.push(current.clone());
+                return cycle;
+            }
+            path.push(current.clone());
+            current = match self.dependencies.get(current).and_then(|depends_on| {
+                depends_on
+                    .iter()
+                    .find(|depends_on| remaining.contains(depends_on))
+            }) {
+                Some(next) => next,
+                None => return path,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    use crate::{validators, ValidationError};
+
+    #[test]
+    fn header_names() {
+        let mut validators = ValidatorMiddleware::<String, ()>::new();
+        assert!(validators
+            .try_add_validator(HttpField::header("X_Legacy-Token"), is_number)
+            .is_ok());
+        assert_eq!(
+            validators.try_add_validator(HttpField::header("X-Request-Id:"), is_number),
+            Err(ConfigError::InvalidHeaderName(String::from(
+                "X-Request-Id:"
+            )))
+        );
+        assert!(validators.finalize().is_ok());
+
+        let mut validators = ValidatorMiddleware::<String, ()>::new();
+        validators.add_validator(HttpField::header("X Request Id"), is_number);
+        assert_eq!(
+            validators.finalize().unwrap_err().to_string(),
+            "invalid header name 'X Request Id'"
+        );
+    }
+
+    #[test]
+    fn guardrails() {
+        let mut validators = ValidatorMiddleware::<String, ()>::new();
+        validators.add_validator(HttpField::query_param(""), is_number);
+        assert_eq!(
+            validators.finalize().unwrap_err(),
+            ConfigError::EmptyFieldName(HttpField::query_param(""))
+        );
+
+        let mut validators =
+            ValidatorMiddleware::<ValidationError, ()>::new().max_validators_per_field(2);
+        for max in 1..4 {
+            validators.add_validator(
+                HttpField::query_param("page"),
+                validators::is_integer_between(0, max),
+            );
+        }
+        assert_eq!(
+            validators.finalize().unwrap_err().to_string(),
+            "query parameter 'page' has 3 validators, more than 2"
+        );
+
+        let mut validators = ValidatorMiddleware::<String, ()>::new();
+        validators.add_validator(HttpField::query_param("page"), is_number);
+        validators.add_validator(HttpField::query_param("size"), is_number);
+        assert!(validators.finalize().is_ok());
+
+        let mut validators = ValidatorMiddleware::<String, ()>::new();
+        validators.add_validator(HttpField::query_param("page"), is_number);
+        validators.add_validator(HttpField::query_param("page"), is_number);
+        assert_eq!(
+            validators.finalize().unwrap_err(),
+            ConfigError::DuplicateValidator(HttpField::query_param("page"))
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Signed cookies, available with the `signed-cookies` feature: the value of a cookie is followed
+//! by the HMAC-SHA256 of the cookie so clients cannot tamper with it.
+//!
+//! Set cookies with [`sign_cookie`](fn.sign_cookie.html) and verify them with the
+//! [`signed_cookie`](fn.signed_cookie.html) codec. Validators of the cookie and handlers get the
+//! unsigned payload, handlers read it from [`NormalizedValues`](../struct.NormalizedValues.html).
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::cookies::{sign_cookie, signed_cookie};
+//!
+//! validator_middleware.add_codec(HttpField::cookie("user_id"), signed_cookie(secret));
+//! validator_middleware.add_validator(HttpField::cookie("user_id"), is_integer);
+//! app.at("/me").middleware(validator_middleware).get(|req: Request<()>| async move {
+//!     let user_id = req
+//!         .local::<NormalizedValues>()
+//!         .and_then(|values| values.get(&HttpField::cookie("user_id")));
+//!     // ...
+//! });
+//!
+//! // when logging in
+//! res.set_cookie(Cookie::new("user_id", sign_cookie(secret, "user_id", "42")));
+//! ```
+use std::fmt;
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+use crate::validators::ValidationError;
+use crate::FieldCodec;
+
+/// Codec verifying signed cookies, see [`signed_cookie`](fn.signed_cookie.html)
+#[derive(Clone)]
+pub struct SignedCookie {
+    secret: Vec<u8>,
+}
+
+impl fmt::Debug for SignedCookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedCookie").finish()
+    }
+}
+
+/// Verify cookies signed with `secret` by [`sign_cookie`](fn.sign_cookie.html), to register with
+/// [`add_codec`](../struct.ValidatorMiddleware.html#method.add_codec). The error code is
+/// `signature` when the cookie isn't signed or has been modified.
+pub fn signed_cookie(secret: impl Into<Vec<u8>>) -> SignedCookie {
+    SignedCookie {
+        secret: secret.into(),
+    }
+}
+
+/// Value of the cookie `name` carrying `payload`, signed with `secret`. The signature covers the
+/// name so the value of a cookie cannot be replayed in another cookie.
+pub fn sign_cookie(secret: &[u8], name: &str, payload: &str) -> String {
+    let signature = mac(secret, name, payload)
+        .map(|mac| hex::encode(mac.finalize().into_bytes()))
+        .unwrap_or_default();
+    format!("{}.{}", payload, signature)
+}
+
+/// Payload of the signed value of the cookie `name`, `None` when the signature doesn't match
+pub fn verify_cookie<'a>(secret: &[u8], name: &str, value: &'a str) -> Option<&'a str> {
+    let dot = value.rfind('.')?;
+    let (payload, signature) = (&value[..dot], &value[dot + 1..]);
+    let signature = hex::decode(signature).ok()?;
+    mac(secret, name, payload)?
+        .verify(&signature)
+        .ok()
+        .map(|_| payload)
+}
+
+fn mac(secret: &[u8], name: &str, payload: &str) -> Option<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret).ok()?;
+    mac.update(name.as_bytes());
+    mac.update(b"=");
+    mac.update(payload.as_bytes());
+    Some(mac)
+}
+
+impl<T, State> FieldCodec<T, State> for SignedCookie
+where
+    T: From<ValidationError> + 'static,
+    State: 'static,
+{
+    fn decode(&self, _state: &State, field_name: &str, value: &str) -> Result<String, T> {
+        verify_cookie(&self.secret, field_name, value)
+            .map(String::from)
+            .ok_or_else(|| {
+                ValidationError::new(
+                    field_name,
+                    "signature",
+                    format!("'{}' has an invalid signature", field_name),
+                )
+                .into()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_cookies() {
+        let value = sign_cookie(b"secret", "user_id", "42");
+        assert_eq!(verify_cookie(b"secret", "user_id", &value), Some("42"));
+        assert_eq!(verify_cookie(b"other", "user_id", &value), None);
+        assert_eq!(verify_cookie(b"secret", "admin_id", &value), None);
+        assert_eq!(
+            verify_cookie(b"secret", "user_id", &value.replacen("42", "43", 1)),
+            None
+        );
+        assert_eq!(verify_cookie(b"secret", "user_id", "42"), None);
+
+        let value = sign_cookie(b"secret", "cart", "a.b");
+        let decoded: Result<String, ValidationError> =
+            signed_cookie("secret").decode(&(), "cart", &value);
+        assert_eq!(decoded.unwrap(), "a.b");
+        let decoded: Result<String, ValidationError> =
+            signed_cookie("secret").decode(&(), "cart", "a.b");
+        assert_eq!(decoded.unwrap_err().code, "signature");
+    }
+}
@@ -0,0 +1,329 @@
+//! Validators of dates, times and durations, e.g. for filters like `?from=2024-01-01&to=2024-02-01`.
+//! Enable the `datetime` feature to use them.
+//!
+//! - `is_date`: ISO-8601 date `YYYY-MM-DD`
+//! - `is_datetime`: RFC 3339 timestamp, e.g. `2024-01-01T08:30:00Z`
+//! - `is_time`: time of day `HH:MM`
+//! - `is_duration`: duration like `30s`, `5m` or `1h 30m`
+//! - `is_date_in_future`, `is_date_in_past`, `is_date_before`, `is_date_after` and
+//!   `is_duration_between` compare the value, `dates_in_order` compares two fields
+//!
+//! The parsers `date`, `datetime`, `time` and `duration` give the parsed value to handlers with
+//! [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser).
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::datetime::{dates_in_order, is_date, is_date_in_future, is_duration};
+//!
+//! validator_middleware.add_validator(HttpField::query_param("from"), is_date);
+//! validator_middleware.add_validator(HttpField::query_param("to"), is_date);
+//! validator_middleware.add_group_validator(
+//!     &[HttpField::query_param("from"), HttpField::query_param("to")],
+//!     dates_in_order("from", "to"),
+//! );
+//! validator_middleware.add_validator(HttpField::body_field("delivery"), is_date_in_future());
+//! validator_middleware.add_validator(HttpField::query_param("timeout"), is_duration);
+//! ```
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Utc};
+
+use crate::validators::ValidationError;
+use crate::BoxedValidator;
+
+/// Parse an ISO-8601 date `YYYY-MM-DD`
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    // chrono accepts years without padding, dates must be exactly 10 characters long
+    if value.len() != 10 {
+        return None;
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+/// Parse an RFC 3339 timestamp, e.g. `2024-01-01T08:30:00+01:00`
+pub fn parse_datetime(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(value).ok()
+}
+
+/// Parse a time of day `HH:MM`
+pub fn parse_time(value: &str) -> Option<NaiveTime> {
+    if value.len() != 5 {
+        return None;
+    }
+    NaiveTime::parse_from_str(value, "%H:%M").ok()
+}
+
+/// Parse a duration like `30s`, `5m` or `1h 30m`
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    humantime::parse_duration(value).ok()
+}
+
+/// Check if the field is an ISO-8601 date `YYYY-MM-DD`
+pub fn is_date(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    check_format(field_name, field_value, parse_date, "date", "2024-01-31")
+}
+
+/// Check if the field is an RFC 3339 timestamp, e.g. `2024-01-01T08:30:00Z`
+pub fn is_datetime(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    check_format(
+        field_name,
+        field_value,
+        parse_datetime,
+        "datetime",
+        "2024-01-31T08:30:00Z",
+    )
+}
+
+/// Check if the field is a time of day `HH:MM`
+pub fn is_time(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    check_format(field_name, field_value, parse_time, "time", "08:30")
+}
+
+/// Check if the field is a duration like `30s`, `5m` or `1h 30m`
+pub fn is_duration(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    check_format(field_name, field_value, parse_duration, "duration", "5m")
+}
+
+/// Check if the field is a date after today (UTC)
+pub fn is_date_in_future() -> BoxedValidator<ValidationError> {
+    date_check(
+        "future",
+        |date| date > Utc::now().naive_utc().date(),
+        || String::from("in the future"),
+    )
+}
+
+/// Check if the field is a date before today (UTC)
+pub fn is_date_in_past() -> BoxedValidator<ValidationError> {
+    date_check(
+        "past",
+        |date| date < Utc::now().naive_utc().date(),
+        || String::from("in the past"),
+    )
+}
+
+/// Check if the field is a date strictly before `reference`
+pub fn is_date_before(reference: NaiveDate) -> BoxedValidator<ValidationError> {
+    date_check(
+        "before",
+        move |date| date < reference,
+        move || format!("before {}", reference),
+    )
+}
+
+/// Check if the field is a date strictly after `reference`
+pub fn is_date_after(reference: NaiveDate) -> BoxedValidator<ValidationError> {
+    date_check(
+        "after",
+        move |date| date > reference,
+        move || format!("after {}", reference),
+    )
+}
+
+/// Check if the field is a duration between `min` and `max` (inclusive)
+pub fn is_duration_between(min: Duration, max: Duration) -> BoxedValidator<ValidationError> {
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            is_duration(field_name, field_value)?;
+            match field_value.and_then(parse_duration) {
+                Some(duration) if duration < min || duration > max => Err(ValidationError::new(
+                    field_name,
+                    "between",
+                    format!(
+                        "field '{}' = '{}' must be between {} and {}",
+                        field_name,
+                        field_value.unwrap_or_default(),
+                        humantime::format_duration(min),
+                        humantime::format_duration(max)
+                    ),
+                )
+                .with_param("min", humantime::format_duration(min))
+                .with_param("max", humantime::format_duration(max))),
+                _ => Ok(()),
+            }
+        },
+    )
+}
+
+/// Group validator for [`add_group_validator`](../struct.ValidatorMiddleware.html#method.add_group_validator)
+/// checking the date `start` isn't after the date `end`, when both are present and valid
+pub fn dates_in_order(
+    start: &'static str,
+    end: &'static str,
+) -> impl Fn(&HashMap<&str, Option<&str>>) -> Result<(), ValidationError> + Send + Sync + 'static {
+    move |values: &HashMap<&str, Option<&str>>| {
+        let date = |name| values.get(name).copied().flatten().and_then(parse_date);
+        match (date(start), date(end)) {
+            (Some(start_date), Some(end_date)) if start_date > end_date => {
+                Err(ValidationError::new(
+                    end,
+                    "after",
+                    format!("'{}' must not be before '{}'", end, start),
+                )
+                .with_param("other", start))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parser of ISO-8601 dates for [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser)
+pub fn date<T>() -> impl Fn(&str, Option<&str>) -> Result<Option<NaiveDate>, T> + Send + Sync
+where
+    T: From<ValidationError>,
+{
+    parser(is_date, parse_date)
+}
+
+/// Parser of RFC 3339 timestamps for [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser)
+pub fn datetime<T>(
+) -> impl Fn(&str, Option<&str>) -> Result<Option<DateTime<FixedOffset>>, T> + Send + Sync
+where
+    T: From<ValidationError>,
+{
+    parser(is_datetime, parse_datetime)
+}
+
+/// Parser of times of day for [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser)
+pub fn time<T>() -> impl Fn(&str, Option<&str>) -> Result<Option<NaiveTime>, T> + Send + Sync
+where
+    T: From<ValidationError>,
+{
+    parser(is_time, parse_time)
+}
+
+/// Parser of durations for [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser)
+pub fn duration<T>() -> impl Fn(&str, Option<&str>) -> Result<Option<Duration>, T> + Send + Sync
+where
+    T: From<ValidationError>,
+{
+    parser(is_duration, parse_duration)
+}
+
+fn parser<V, T>(
+    validator: fn(&str, Option<&str>) -> Result<(), ValidationError>,
+    parse: fn(&str) -> Option<V>,
+) -> impl Fn(&str, Option<&str>) -> Result<Option<V>, T> + Send + Sync
+where
+    T: From<ValidationError>,
+{
+    move |field_name: &str, field_value: Option<&str>| {
+        validator(field_name, field_value)?;
+        Ok(field_value.and_then(parse))
+    }
+}
+
+fn check_format<V>(
+    field_name: &str,
+    field_value: Option<&str>,
+    parse: fn(&str) -> Option<V>,
+    code: &str,
+    example: &str,
+) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if parse(value).is_none() => Err(ValidationError::new(
+            field_name,
+            code,
+            format!(
+                "field '{}' = '{}' is not a valid {}, e.g. '{}'",
+                field_name, value, code, example
+            ),
+        )
+        .with_param("example", example)),
+        _ => Ok(()),
+    }
+}
+
+fn date_check<C, D>(code: &'static str, check: C, describe: D) -> BoxedValidator<ValidationError>
+where
+    C: Fn(NaiveDate) -> bool + Send + Sync + 'static,
+    D: Fn() -> String + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            is_date(field_name, field_value)?;
+            match field_value.and_then(parse_date) {
+                Some(date) if !check(date) => Err(ValidationError::new(
+                    field_name,
+                    code,
+                    format!("field '{}' = '{}' must be {}", field_name, date, describe()),
+                )),
+                _ => Ok(()),
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        assert!(is_date("from", Some("2024-02-29")).is_ok());
+        assert!(is_date("from", Some("2023-02-29")).is_err());
+        assert!(is_date("from", Some("2024-1-01")).is_err());
+        assert!(is_datetime("at", Some("2024-01-01T08:30:00Z")).is_ok());
+        assert!(is_datetime("at", Some("2024-01-01T08:30:00.250+01:00")).is_ok());
+        assert!(is_datetime("at", Some("2024-01-01 08:30")).is_err());
+        assert!(is_time("opens", Some("08:30")).is_ok());
+        assert!(is_time("opens", Some("24:00")).is_err());
+        assert!(is_time("opens", Some("8:30")).is_err());
+        assert!(is_duration("timeout", Some("30s")).is_ok());
+        assert!(is_duration("timeout", Some("1h 30m")).is_ok());
+        assert_eq!(
+            is_duration("timeout", Some("soon")).unwrap_err().code,
+            "duration"
+        );
+        assert!(is_date("from", None).is_ok());
+    }
+
+    #[test]
+    fn comparisons() {
+        let reference = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert!(is_date_before(reference)("to", Some("2023-12-31")).is_ok());
+        assert_eq!(
+            is_date_before(reference)("to", Some("2024-01-01"))
+                .unwrap_err()
+                .code,
+            "before"
+        );
+        assert!(is_date_after(reference)("from", Some("2024-01-02")).is_ok());
+        assert!(is_date_in_future()("delivery", Some("9999-12-31")).is_ok());
+        assert_eq!(
+            is_date_in_future()("delivery", Some("2000-01-01"))
+                .unwrap_err()
+                .code,
+            "future"
+        );
+        assert!(is_date_in_past()("birthday", Some("2000-01-01")).is_ok());
+
+        let timeout = is_duration_between(Duration::from_secs(1), Duration::from_secs(60));
+        assert!(timeout("timeout", Some("30s")).is_ok());
+        assert_eq!(timeout("timeout", Some("5m")).unwrap_err().code, "between");
+
+        let in_order = dates_in_order("from", "to");
+        let mut values = HashMap::new();
+        values.insert("from", Some("2024-01-01"));
+        values.insert("to", Some("2024-02-01"));
+        assert!(in_order(&values).is_ok());
+        values.insert("to", Some("2023-12-01"));
+        assert_eq!(in_order(&values).unwrap_err().field, "to");
+        values.insert("to", None);
+        assert!(in_order(&values).is_ok());
+    }
+
+    #[test]
+    fn parsers() {
+        assert_eq!(
+            date::<ValidationError>()("from", Some("2024-01-01")),
+            Ok(Some(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()))
+        );
+        assert_eq!(
+            duration::<ValidationError>()("timeout", Some("5m")),
+            Ok(Some(Duration::from_secs(300)))
+        );
+        assert!(time::<ValidationError>()("opens", Some("noon")).is_err());
+        assert_eq!(datetime::<ValidationError>()("at", None), Ok(None));
+    }
+}
@@ -0,0 +1,115 @@
+//! Errors of any serializable type, to mix validators returning different error types in a
+//! single middleware.
+//!
+//! A middleware is generic over a single error type, use `ValidatorMiddleware<AnyError>` and wrap
+//! validators with [`erase`](../combinators/fn.erase.html):
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::{combinators::erase, AnyError};
+//!
+//! let mut validator_middleware: ValidatorMiddleware<AnyError> = ValidatorMiddleware::new();
+//! // Built-in validators return a `ValidationError`
+//! validator_middleware.add_validator(HttpField::query_param("email"), erase(is_email));
+//! // Your validators return a `CustomError`
+//! validator_middleware.add_validator(HttpField::query_param("email"), erase(is_company_email));
+//! ```
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Serialize, Serializer};
+
+use crate::validators::ValidationError;
+
+/// Error of any serializable type, serialized like the original error. The original error can be
+/// read back with [`downcast_ref`](#method.downcast_ref), e.g. in an
+/// [`on_error`](../struct.ValidatorMiddleware.html#method.on_error) hook.
+#[derive(Clone)]
+pub struct AnyError {
+    value: serde_json::Value,
+    source: Arc<dyn Any + Send + Sync>,
+}
+
+impl AnyError {
+    /// Erase the type of an error. Errors which cannot be serialized as JSON, e.g. maps with
+    /// non-string keys, are serialized as the message of the serialization error.
+    pub fn new<E>(err: E) -> Self
+    where
+        E: Serialize + Send + Sync + 'static,
+    {
+        let value = serde_json::to_value(&err).unwrap_or_else(|serialization_err| {
+            serde_json::Value::String(format!(
+                "cannot serialize validation error : {}",
+                serialization_err
+            ))
+        });
+        AnyError {
+            value,
+            source: Arc::new(err),
+        }
+    }
+
+    /// The error serialized as JSON
+    pub fn as_value(&self) -> &serde_json::Value {
+        &self.value
+    }
+
+    /// The original error, `None` if it's not an `E`
+    pub fn downcast_ref<E: 'static>(&self) -> Option<&E> {
+        self.source.downcast_ref()
+    }
+}
+
+impl fmt::Debug for AnyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AnyError").field(&self.value).finish()
+    }
+}
+
+impl PartialEq for AnyError {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Serialize for AnyError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+/// Built-in validators and combinators requiring `From<ValidationError>` work with `AnyError`
+impl From<ValidationError> for AnyError {
+    fn from(err: ValidationError) -> Self {
+        AnyError::new(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize, PartialEq)]
+    struct CustomError {
+        status_code: usize,
+    }
+
+    #[test]
+    fn erased_errors() {
+        let err = AnyError::new(CustomError { status_code: 422 });
+        assert_eq!(err.as_value(), &serde_json::json!({"status_code": 422}));
+        assert_eq!(
+            err.downcast_ref::<CustomError>(),
+            Some(&CustomError { status_code: 422 })
+        );
+        assert!(err.downcast_ref::<String>().is_none());
+
+        let err = AnyError::new(String::from("'age' is required"));
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            "\"'age' is required\""
+        );
+        let err = AnyError::from(ValidationError::new("age", "required", "'age' is required"));
+        assert_eq!(err.as_value()["code"], "required");
+    }
+}
@@ -0,0 +1,245 @@
+//! Events emitted on the server side when a request is rejected, with the `log` or `tracing`
+//! features. Both are emitted when both features are enabled.
+use tide::Request;
+
+use crate::HttpField;
+
+/// A field of a request is invalid, logged at the `info` level
+pub(crate) fn field_rejected<State>(req: &Request<State>, field: &HttpField<'_>, error: &str) {
+    #[cfg(feature = "log")]
+    log::info!(
+        "{} {} rejected, invalid {} '{}': {}",
+        req.method(),
+        req.uri().path(),
+        field.kind(),
+        field.name(),
+        error
+    );
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        method = %req.method(),
+        path = req.uri().path(),
+        field = field.name(),
+        kind = field.kind(),
+        location = field.location().as_str(),
+        error,
+        "request rejected"
+    );
+}
+
+/// A validator couldn't check a field, logged at the `warn` level
+pub(crate) fn validator_errored<State>(
+    req: &Request<State>,
+    field: &HttpField<'_>,
+    error: &(dyn std::error::Error + Send + Sync),
+) {
+    #[cfg(feature = "log")]
+    log::warn!(
+        "{} {} cannot validate {} '{}': {}",
+        req.method(),
+        req.uri().path(),
+        field.kind(),
+        field.name(),
+        error
+    );
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        method = %req.method(),
+        path = req.uri().path(),
+        field = field.name(),
+        kind = field.kind(),
+        location = field.location().as_str(),
+        error = %error,
+        "cannot validate field"
+    );
+}
+
+#[cfg(all(test, any(feature = "log", feature = "tracing")))]
+mod tests {
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request};
+    use tide::StatusCode;
+
+    use crate::{HttpField, ValidatorMiddleware};
+
+    /// Send a request to a route validating the `age` query parameter
+    fn send(url: &str) -> StatusCode {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(
+            HttpField::query_param("age"),
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some(age) if age.parse::<u8>().is_err() => {
+                    Err(format!("'{}' is not a valid age", field_name))
+                }
+                _ => Ok(()),
+            },
+        );
+        inner
+            .at("/users")
+            .middleware(validators)
+            .get(|_| async { Ok("users") });
+        let mut server = make_server(inner).unwrap();
+        let req = Request::new(Method::Get, url.parse().unwrap());
+        server.simulate(req).unwrap().status()
+    }
+
+    #[cfg(feature = "log")]
+    // A `const` initializer of the thread local needs a newer compiler than the one supported
+    #[allow(clippy::missing_const_for_thread_local)]
+    mod capture_log {
+        use std::cell::RefCell;
+
+        thread_local! {
+            static RECORDS: RefCell<Vec<(log::Level, String)>> = RefCell::new(Vec::new());
+        }
+
+        /// Keeps the records of this crate on the thread which emitted them, tests run in parallel
+        struct Capture;
+
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+                metadata.target().starts_with("tide_validator")
+            }
+
+            fn log(&self, record: &log::Record<'_>) {
+                if self.enabled(record.metadata()) {
+                    let message = record.args().to_string();
+                    RECORDS.with(|records| records.borrow_mut().push((record.level(), message)));
+                }
+            }
+
+            fn flush(&self) {}
+        }
+
+        static CAPTURE: Capture = Capture;
+
+        /// Records emitted by `f` on this thread
+        pub(super) fn records(f: impl FnOnce()) -> Vec<(log::Level, String)> {
+            // Another test may have installed it already
+            let _ = log::set_logger(&CAPTURE);
+            log::set_max_level(log::LevelFilter::Trace);
+            RECORDS.with(|records| records.borrow_mut().clear());
+            f();
+            RECORDS.with(|records| records.borrow_mut().drain(..).collect())
+        }
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn log_records() {
+        let records = capture_log::records(|| {
+            assert_eq!(send("http://localhost/users?age=42"), StatusCode::Ok);
+        });
+        assert!(records.is_empty(), "{:?}", records);
+
+        let records = capture_log::records(|| {
+            assert_eq!(
+                send("http://localhost/users?age=old"),
+                StatusCode::BadRequest
+            );
+        });
+        assert_eq!(
+            records,
+            vec![(
+                log::Level::Info,
+                String::from(
+                    r#"GET /users rejected, invalid query parameter 'age': "'age' is not a valid age""#
+                )
+            )]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    mod capture_tracing {
+        use std::fmt::Debug;
+        use std::sync::{Arc, Mutex};
+
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Level, Metadata, Subscriber};
+
+        /// An event of this crate with its fields formatted as `name=value`
+        pub(super) type Captured = (Level, Vec<String>);
+
+        #[derive(Default)]
+        struct Capture {
+            events: Arc<Mutex<Vec<Captured>>>,
+        }
+
+        struct Fields(Vec<String>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+                metadata.target().starts_with("tide_validator")
+            }
+
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields(Vec::new());
+                event.record(&mut fields);
+                if let Ok(mut events) = self.events.lock() {
+                    events.push((*event.metadata().level(), fields.0));
+                }
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        /// Events emitted by `f` on this thread
+        pub(super) fn events(f: impl FnOnce()) -> Vec<Captured> {
+            let capture = Capture::default();
+            let events = Arc::clone(&capture.events);
+            tracing::subscriber::with_default(capture, f);
+            let events = events.lock().unwrap();
+            events.clone()
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_events() {
+        let events = capture_tracing::events(|| {
+            assert_eq!(send("http://localhost/users?age=42"), StatusCode::Ok);
+        });
+        assert!(events.is_empty(), "{:?}", events);
+
+        let events = capture_tracing::events(|| {
+            assert_eq!(
+                send("http://localhost/users?age=old"),
+                StatusCode::BadRequest
+            );
+        });
+        assert_eq!(events.len(), 1);
+        let (level, fields) = &events[0];
+        assert_eq!(*level, tracing::Level::INFO);
+        let mut fields = fields.clone();
+        fields.sort();
+        let mut expected = vec![
+            String::from("message=request rejected"),
+            String::from("method=GET"),
+            String::from(r#"path="/users""#),
+            String::from(r#"field="age""#),
+            String::from(r#"kind="query parameter""#),
+            String::from(r#"location="query""#),
+            String::from(r#"error="\"'age' is not a valid age\"""#),
+        ];
+        expected.sort();
+        assert_eq!(fields, expected);
+    }
+}
@@ -0,0 +1,422 @@
+//! Fields of a request validated by the middleware and where they're located
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ConfigError;
+
+/// Enum to indicate on which HTTP field you want to make validations. Names are borrowed
+/// literals or owned `String`s, e.g. header names loaded from a config file.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum HttpField<'a> {
+    /// To validate a path parameter. Example in URL `/test/:name` you can use `HttpField::param("name")`
+    Param(Cow<'a, str>),
+    /// To validate a query parameter. Example in URL `/test?name=test` you can use `HttpField::query_param("name")`
+    QueryParam(Cow<'a, str>),
+    /// To validate a query parameter sent as a list, written as given by the
+    /// [`ListStyle`](enum.ListStyle.html). Example `HttpField::query_list("ids", ListStyle::Brackets)`
+    /// for `?ids[]=1&ids[]=2`. Every item is a value of the field, see
+    /// [`add_each_validator`](struct.ValidatorMiddleware.html#method.add_each_validator),
+    /// [`add_multi_validator`](struct.ValidatorMiddleware.html#method.add_multi_validator) and
+    /// [`add_list_parser`](struct.ValidatorMiddleware.html#method.add_list_parser).
+    QueryList(Cow<'a, str>, ListStyle),
+    /// To validate a header. Example `HttpField::header("X-My-Custom-Header")`. The name is compared
+    /// case-insensitively to the raw names of the request headers, so any name sent by clients can
+    /// be validated. Names which cannot be header names are reported by
+    /// [`try_add_validator`](struct.ValidatorMiddleware.html#method.try_add_validator) and
+    /// [`finalize`](struct.ValidatorMiddleware.html#method.finalize).
+    Header(Cow<'a, str>),
+    /// To validate a cookie. Example `HttpField::cookie("session")`
+    Cookie(Cow<'a, str>),
+    /// To validate a field of a JSON body, with a path separated by dots where numbers are array
+    /// indexes. Example `HttpField::body_field("user.emails.0")`, indexes can also be written
+    /// `user.emails[0]`. Paths starting with `/` are JSON Pointers, e.g. `/user/emails/0`.
+    /// A `*` segment matches every item of an array, e.g. `items[*].sku` or `/items/*/sku`:
+    /// validators run once per item with the path of the item, like `items[1].sku`.
+    /// Strings are validated as is, other values as JSON and `null` as a missing field. The body
+    /// is read by the middleware and attached back to the request, `req.body_json()` still works
+    /// in your endpoint.
+    BodyField(Cow<'a, str>),
+    /// To validate a matrix parameter of a path segment, for legacy APIs using URLs like
+    /// `/items;limit=10;offset=20`. The first value is the segment without its parameters, the
+    /// second the parameter name. Example `HttpField::matrix_param("items", "limit")`. Tide routes
+    /// match whole segments, declare the route with a parameter like `/:items`.
+    MatrixParam(Cow<'a, str>, Cow<'a, str>),
+    /// To validate a field of a `multipart/form-data` body, e.g. a file upload. Example
+    /// `HttpField::multipart("avatar")`. Validators receive the text of text fields and the
+    /// filename of files, see the [`multipart`](multipart/index.html) module to check files.
+    /// The body is read by the middleware and attached back to the request.
+    Multipart(Cow<'a, str>),
+    /// To validate a value given by the [`ValueProvider`](trait.ValueProvider.html)s of the
+    /// middleware, e.g. claims of a JWT parsed by an earlier middleware. Example
+    /// `HttpField::custom("jwt.sub")`.
+    Custom(Cow<'a, str>),
+    /// To validate the media type of the body given by the `Content-Type` header, see
+    /// [`require_content_type`](validators/fn.require_content_type.html)
+    ContentType,
+    /// To validate the media types accepted by the client in the `Accept` header, see
+    /// [`require_accepts`](validators/fn.require_accepts.html). Several `Accept` lines are
+    /// joined into a single value.
+    Accept,
+    /// To validate the address of the client as given by the server, e.g. `127.0.0.1:52114`, see
+    /// the [`network`](network/index.html) module to check IP ranges and forwarding headers. Its
+    /// location is `custom`.
+    PeerAddr,
+    /// To validate every header whose name matches a regular expression, built with
+    /// [`header_pattern`](#method.header_pattern) of the `regex` feature. Example
+    /// `HttpField::header_pattern(Regex::new("^(?i)x-internal-").unwrap())`. Validators run once
+    /// per matching header with its name, and don't run when no header matches.
+    HeaderPattern(FieldPattern),
+    /// To validate every query parameter whose name matches a regular expression, built with
+    /// [`query_param_pattern`](#method.query_param_pattern) of the `regex` feature. Example
+    /// `HttpField::query_param_pattern(Regex::new("^filter_").unwrap())`.
+    QueryParamPattern(FieldPattern),
+}
+
+/// Regular expression matching field names, compared and hashed by its source. Patterns are
+/// built from a `regex::Regex` with the `regex` feature, without it no pattern can be built.
+#[derive(Debug, Clone)]
+pub struct FieldPattern(PatternRegex);
+
+#[cfg(feature = "regex")]
+type PatternRegex = regex::Regex;
+#[cfg(not(feature = "regex"))]
+type PatternRegex = std::convert::Infallible;
+
+impl FieldPattern {
+    /// Source of the regular expression
+    #[cfg(feature = "regex")]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Source of the regular expression
+    #[cfg(not(feature = "regex"))]
+    pub fn as_str(&self) -> &str {
+        match self.0 {}
+    }
+
+    /// Whether `name` matches the pattern
+    #[cfg(feature = "regex")]
+    pub(crate) fn is_match(&self, name: &str) -> bool {
+        self.0.is_match(name)
+    }
+}
+
+impl PartialEq for FieldPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for FieldPattern {}
+
+impl std::hash::Hash for FieldPattern {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl From<regex::Regex> for FieldPattern {
+    fn from(regex: regex::Regex) -> Self {
+        FieldPattern(regex)
+    }
+}
+
+/// How the items of a [`HttpField::QueryList`](enum.HttpField.html#variant.QueryList) are written
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum ListStyle {
+    /// Repeated parameter, e.g. `?ids=1&ids=2`
+    Repeated,
+    /// Repeated parameter with brackets after its name, e.g. `?ids[]=1&ids[]=2`
+    Brackets,
+    /// Items separated by commas, e.g. `?ids=1,2`. Repeated parameters are joined and an empty
+    /// parameter is an empty list.
+    Comma,
+}
+
+impl ListStyle {
+    /// Name of the query parameter holding the items of the list `name`
+    pub(crate) fn param_name<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        match self {
+            ListStyle::Brackets => Cow::Owned(format!("{}[]", name)),
+            ListStyle::Repeated | ListStyle::Comma => Cow::Borrowed(name),
+        }
+    }
+}
+
+/// Where a field is located in the request, as in OpenAPI's `in`
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Location {
+    /// Path parameter
+    Path,
+    /// Query parameter
+    Query,
+    /// Header
+    Header,
+    /// Cookie
+    Cookie,
+    /// Request body
+    Body,
+    /// Value given by a [`ValueProvider`](trait.ValueProvider.html)
+    Custom,
+}
+
+impl Location {
+    /// Name of the location as serialized, e.g. `query`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Location::Path => "path",
+            Location::Query => "query",
+            Location::Header => "header",
+            Location::Cookie => "cookie",
+            Location::Body => "body",
+            Location::Custom => "custom",
+        }
+    }
+}
+
+impl FromStr for Location {
+    type Err = ConfigError;
+
+    fn from_str(location: &str) -> Result<Self, Self::Err> {
+        match location {
+            "path" => Ok(Location::Path),
+            "query" => Ok(Location::Query),
+            "header" => Ok(Location::Header),
+            "cookie" => Ok(Location::Cookie),
+            "body" => Ok(Location::Body),
+            "custom" => Ok(Location::Custom),
+            other => Err(ConfigError::UnknownLocation(other.to_string())),
+        }
+    }
+}
+
+/// Field named `name` at a location, e.g. `HttpField::from((Location::Query, "age"))`. Path
+/// locations give path parameters and body locations JSON body fields.
+impl<'a, N: Into<Cow<'a, str>>> From<(Location, N)> for HttpField<'a> {
+    fn from((location, name): (Location, N)) -> Self {
+        match location {
+            Location::Path => HttpField::param(name),
+            Location::Query => HttpField::query_param(name),
+            Location::Header => HttpField::header(name),
+            Location::Cookie => HttpField::cookie(name),
+            Location::Body => HttpField::body_field(name),
+            Location::Custom => HttpField::custom(name),
+        }
+    }
+}
+
+/// Field named `name` at a location given by its name, e.g. `HttpField::try_from(("query", "age"))`,
+/// for fields loaded from config files
+impl<'a> TryFrom<(&str, &'a str)> for HttpField<'a> {
+    type Error = ConfigError;
+
+    fn try_from((location, name): (&str, &'a str)) -> Result<Self, Self::Error> {
+        Ok(HttpField::from((location.parse::<Location>()?, name)))
+    }
+}
+
+impl<'a> HttpField<'a> {
+    /// Path parameter
+    pub fn param(name: impl Into<Cow<'a, str>>) -> Self {
+        HttpField::Param(name.into())
+    }
+
+    /// Query parameter
+    pub fn query_param(name: impl Into<Cow<'a, str>>) -> Self {
+        HttpField::QueryParam(name.into())
+    }
+
+    /// Query parameter sent as a list
+    pub fn query_list(name: impl Into<Cow<'a, str>>, style: ListStyle) -> Self {
+        HttpField::QueryList(name.into(), style)
+    }
+
+    /// Header
+    pub fn header(name: impl Into<Cow<'a, str>>) -> Self {
+        HttpField::Header(name.into())
+    }
+
+    /// Cookie
+    pub fn cookie(name: impl Into<Cow<'a, str>>) -> Self {
+        HttpField::Cookie(name.into())
+    }
+
+    /// Field of a JSON body
+    pub fn body_field(path: impl Into<Cow<'a, str>>) -> Self {
+        HttpField::BodyField(path.into())
+    }
+
+    /// Matrix parameter of a path segment
+    pub fn matrix_param(segment: impl Into<Cow<'a, str>>, name: impl Into<Cow<'a, str>>) -> Self {
+        HttpField::MatrixParam(segment.into(), name.into())
+    }
+
+    /// Field of a `multipart/form-data` body
+    pub fn multipart(name: impl Into<Cow<'a, str>>) -> Self {
+        HttpField::Multipart(name.into())
+    }
+
+    /// Value given by a value provider
+    pub fn custom(name: impl Into<Cow<'a, str>>) -> Self {
+        HttpField::Custom(name.into())
+    }
+
+    /// `Content-Type` header
+    pub fn content_type() -> Self {
+        HttpField::ContentType
+    }
+
+    /// `Accept` header
+    pub fn accept() -> Self {
+        HttpField::Accept
+    }
+
+    /// Address of the client
+    pub fn peer_addr() -> Self {
+        HttpField::PeerAddr
+    }
+
+    /// Headers whose name matches `pattern`
+    #[cfg(feature = "regex")]
+    pub fn header_pattern(pattern: regex::Regex) -> Self {
+        HttpField::HeaderPattern(pattern.into())
+    }
+
+    /// Query parameters whose name matches `pattern`
+    #[cfg(feature = "regex")]
+    pub fn query_param_pattern(pattern: regex::Regex) -> Self {
+        HttpField::QueryParamPattern(pattern.into())
+    }
+
+    /// Copy borrowed names to get a field which can be registered
+    pub fn into_owned(self) -> HttpField<'static> {
+        match self {
+            HttpField::Param(name) => HttpField::Param(Cow::Owned(name.into_owned())),
+            HttpField::QueryParam(name) => HttpField::QueryParam(Cow::Owned(name.into_owned())),
+            HttpField::QueryList(name, style) => {
+                HttpField::QueryList(Cow::Owned(name.into_owned()), style)
+            }
+            HttpField::Header(name) => HttpField::Header(Cow::Owned(name.into_owned())),
+            HttpField::Cookie(name) => HttpField::Cookie(Cow::Owned(name.into_owned())),
+            HttpField::BodyField(path) => HttpField::BodyField(Cow::Owned(path.into_owned())),
+            HttpField::MatrixParam(segment, name) => HttpField::MatrixParam(
+                Cow::Owned(segment.into_owned()),
+                Cow::Owned(name.into_owned()),
+            ),
+            HttpField::Multipart(name) => HttpField::Multipart(Cow::Owned(name.into_owned())),
+            HttpField::Custom(name) => HttpField::Custom(Cow::Owned(name.into_owned())),
+            HttpField::ContentType => HttpField::ContentType,
+            HttpField::Accept => HttpField::Accept,
+            HttpField::PeerAddr => HttpField::PeerAddr,
+            HttpField::HeaderPattern(pattern) => HttpField::HeaderPattern(pattern),
+            HttpField::QueryParamPattern(pattern) => HttpField::QueryParamPattern(pattern),
+        }
+    }
+
+    /// Name of the field, e.g. `age` for `HttpField::query_param("age")`, or the regular
+    /// expression of a pattern
+    pub fn name(&self) -> &str {
+        match self {
+            HttpField::Param(name)
+            | HttpField::QueryParam(name)
+            | HttpField::QueryList(name, _)
+            | HttpField::Header(name)
+            | HttpField::Cookie(name)
+            | HttpField::BodyField(name)
+            | HttpField::MatrixParam(_, name)
+            | HttpField::Multipart(name)
+            | HttpField::Custom(name) => name,
+            HttpField::ContentType => "Content-Type",
+            HttpField::Accept => "Accept",
+            HttpField::PeerAddr => "peer_addr",
+            HttpField::HeaderPattern(pattern) | HttpField::QueryParamPattern(pattern) => {
+                pattern.as_str()
+            }
+        }
+    }
+
+    /// Where the field is located in the request
+    pub fn location(&self) -> Location {
+        match self {
+            HttpField::Param(_) | HttpField::MatrixParam(_, _) => Location::Path,
+            HttpField::QueryParam(_) | HttpField::QueryList(_, _) => Location::Query,
+            HttpField::Header(_) | HttpField::ContentType | HttpField::Accept => Location::Header,
+            HttpField::QueryParamPattern(_) => Location::Query,
+            HttpField::HeaderPattern(_) => Location::Header,
+            HttpField::Cookie(_) => Location::Cookie,
+            HttpField::BodyField(_) | HttpField::Multipart(_) => Location::Body,
+            HttpField::Custom(_) | HttpField::PeerAddr => Location::Custom,
+        }
+    }
+
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            HttpField::Param(_) => "parameter",
+            HttpField::QueryParam(_) => "query parameter",
+            HttpField::QueryList(_, _) => "query list",
+            HttpField::Header(_) | HttpField::ContentType | HttpField::Accept => "header",
+            HttpField::Cookie(_) => "cookie",
+            HttpField::BodyField(_) => "body field",
+            HttpField::MatrixParam(_, _) => "matrix parameter",
+            HttpField::Multipart(_) => "multipart field",
+            HttpField::Custom(_) => "custom field",
+            HttpField::PeerAddr => "peer address",
+            HttpField::HeaderPattern(_) => "header",
+            HttpField::QueryParamPattern(_) => "query parameter",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    #[allow(deprecated)]
+    use crate::ParameterType;
+    use crate::ValidatorMiddleware;
+
+    #[test]
+    fn field_conversions() {
+        assert_eq!(
+            HttpField::from((Location::Query, "age")),
+            HttpField::query_param("age")
+        );
+        assert_eq!(
+            HttpField::from((Location::Body, String::from("user.name"))),
+            HttpField::body_field("user.name")
+        );
+        assert_eq!(
+            HttpField::try_from(("path", "id")),
+            Ok(HttpField::param("id"))
+        );
+        assert_eq!(
+            HttpField::try_from(("form", "id")),
+            Err(ConfigError::UnknownLocation(String::from("form")))
+        );
+
+        #[allow(deprecated)]
+        let field: ParameterType<'_> = ParameterType::Header("X-Request-Id".into());
+        assert_eq!(field, HttpField::header("X-Request-Id"));
+    }
+
+    #[test]
+    fn owned_field_names() {
+        let header_name = String::from("X-Tenant");
+        let mut validators: ValidatorMiddleware<String> = ValidatorMiddleware::new();
+        validators.add_validator(HttpField::header(header_name.clone()), is_number);
+        assert!(validators
+            .validators
+            .contains_key(&HttpField::header("X-Tenant")));
+        assert_eq!(
+            HttpField::header(&header_name[..]).into_owned(),
+            HttpField::header(header_name)
+        );
+    }
+}
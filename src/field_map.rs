@@ -0,0 +1,144 @@
+//! Map of fields keeping the order in which they were added
+use std::collections::HashMap;
+use std::ops::Index;
+
+use crate::HttpField;
+
+/// Values by field, iterated in the order fields were first added so that validators of
+/// different fields always run, and report errors, in registration order
+#[derive(Debug, Clone)]
+pub(crate) struct FieldMap<V> {
+    entries: Vec<(HttpField<'static>, V)>,
+    positions: HashMap<HttpField<'static>, usize>,
+}
+
+impl<V> Default for FieldMap<V> {
+    fn default() -> Self {
+        FieldMap {
+            entries: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl<V> FieldMap<V> {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn get(&self, field: &HttpField<'_>) -> Option<&V> {
+        let position = *self.positions.get(field)?;
+        Some(&self.entries[position].1)
+    }
+
+    pub(crate) fn contains_key(&self, field: &HttpField<'_>) -> bool {
+        self.positions.contains_key(field)
+    }
+
+    /// Value of `field`, added at the end with `default` when the field is new
+    pub(crate) fn get_or_insert_with(
+        &mut self,
+        field: HttpField<'static>,
+        default: impl FnOnce() -> V,
+    ) -> &mut V {
+        let position = match self.positions.get(&field) {
+            Some(position) => *position,
+            None => {
+                self.positions.insert(field.clone(), self.entries.len());
+                self.entries.push((field, default()));
+                self.entries.len() - 1
+            }
+        };
+        &mut self.entries[position].1
+    }
+
+    /// Value of `field`, added at the end when the field is new
+    pub(crate) fn entry(&mut self, field: HttpField<'static>) -> &mut V
+    where
+        V: Default,
+    {
+        self.get_or_insert_with(field, V::default)
+    }
+
+    /// Set the value of `field`, a field already present keeps its position
+    pub(crate) fn insert(&mut self, field: HttpField<'static>, value: V) {
+        let mut value = Some(value);
+        let current = self.get_or_insert_with(field, || value.take().unwrap());
+        if let Some(value) = value {
+            *current = value;
+        }
+    }
+
+    /// Field and value at `index` in the order fields were added
+    pub(crate) fn entry_at(&self, index: usize) -> (&HttpField<'static>, &V) {
+        let (field, value) = &self.entries[index];
+        (field, value)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[cfg_attr(not(feature = "openapi"), allow(dead_code))]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&HttpField<'static>, &V)> {
+        self.entries.iter().map(|(field, value)| (field, value))
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &HttpField<'static>> {
+        self.entries.iter().map(|(field, _)| field)
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl<'m, V> IntoIterator for &'m FieldMap<V> {
+    type Item = (&'m HttpField<'static>, &'m V);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'m, (HttpField<'static>, V)>,
+        fn(&'m (HttpField<'static>, V)) -> (&'m HttpField<'static>, &'m V),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(field, value)| (field, value))
+    }
+}
+
+impl<V> Index<&HttpField<'_>> for FieldMap<V> {
+    type Output = V;
+
+    fn index(&self, field: &HttpField<'_>) -> &V {
+        self.get(field).expect("no value for the field")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registration_order() {
+        let mut map: FieldMap<Vec<u8>> = FieldMap::new();
+        assert!(map.is_empty());
+        let fields: Vec<HttpField<'static>> = (0..20)
+            .map(|n| HttpField::query_param(format!("field{}", n)))
+            .collect();
+        for (n, field) in fields.iter().enumerate().rev() {
+            map.entry(field.clone()).push(n as u8);
+        }
+        map.entry(fields[19].clone()).push(0);
+        map.insert(fields[0].clone(), vec![42]);
+        let keys: Vec<&HttpField<'static>> = map.keys().collect();
+        let expected: Vec<&HttpField<'static>> = fields.iter().rev().collect();
+        assert_eq!(keys, expected);
+        assert_eq!(map[&fields[19]], vec![19, 0]);
+        assert_eq!(map.get(&fields[0]), Some(&vec![42]));
+        assert_eq!(map.len(), 20);
+        assert!(!map.is_empty());
+    }
+}
@@ -0,0 +1,592 @@
+//! Rules registered for a field and how they run
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use tide::{Request, StatusCode};
+
+use crate::combinators::Sampler;
+use crate::{multipart, HttpField, Validator};
+
+/// Result of a validator which can fail for reasons unrelated to the request, e.g. when the
+/// store it queries is down, added with
+/// [`add_fallible_validator`](struct.ValidatorMiddleware.html#method.add_fallible_validator)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Validation<T, E> {
+    /// The value is valid
+    Valid,
+    /// The value is invalid, reported like the errors of other validators
+    Invalid(T),
+    /// The validator couldn't check the value, handled by
+    /// [`on_validator_error`](struct.ValidatorMiddleware.html#method.on_validator_error)
+    Error(E),
+}
+
+impl<T, E> From<Result<(), T>> for Validation<T, E> {
+    fn from(result: Result<(), T>) -> Self {
+        match result {
+            Ok(()) => Validation::Valid,
+            Err(err) => Validation::Invalid(err),
+        }
+    }
+}
+
+/// Internal error of a validator, see [`Validation::Error`](enum.Validation.html#variant.Error)
+pub type ValidatorError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// How the failure of a validator added with
+/// [`add_validator_with_severity`](struct.ValidatorMiddleware.html#method.add_validator_with_severity)
+/// affects the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The request is rejected, like with other validators
+    Error,
+    /// The request proceeds and the error is recorded in
+    /// [`ValidationWarnings`](struct.ValidationWarnings.html), e.g. to flag a deprecated parameter
+    Warning,
+}
+
+/// Cost of running a validator, used by
+/// [`order_by_cost`](struct.ValidatorMiddleware.html#method.order_by_cost) to run the cheap ones
+/// first. Async validators are expensive, other validators cheap unless added with
+/// [`add_validator_with_cost`](struct.ValidatorMiddleware.html#method.add_validator_with_cost).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Cost {
+    /// Syntactic check of the value, e.g. its length or format
+    Cheap,
+    /// Check calling another service or doing heavy work, e.g. a database lookup
+    Expensive,
+}
+
+pub(crate) type ValidatorFn<T> =
+    Arc<dyn Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static>;
+pub(crate) type RequestValidatorFn<T, State> =
+    Arc<dyn Fn(&Request<State>, &str, Option<&str>) -> Result<(), T> + Send + Sync + 'static>;
+pub(crate) type BodyValidatorFn<T, State> = Arc<
+    dyn Fn(&Request<State>, &[u8], &str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+>;
+pub(crate) type MultiValidatorFn<T> =
+    Arc<dyn Fn(&str, Option<&[&str]>) -> Result<(), T> + Send + Sync + 'static>;
+pub(crate) type NormalizerFn<T> =
+    Arc<dyn Fn(&str, Option<&str>) -> Result<Option<String>, T> + Send + Sync + 'static>;
+pub(crate) type FileValidatorFn<T> =
+    Arc<dyn Fn(&str, Option<&multipart::Part<'_>>) -> Result<(), T> + Send + Sync + 'static>;
+pub(crate) type FallibleValidatorFn<T> =
+    Arc<dyn Fn(&str, Option<&str>) -> Validation<T, ValidatorError> + Send + Sync + 'static>;
+pub(crate) type AsyncFallibleValidatorFn<T> = Arc<
+    dyn Fn(&str, Option<&str>) -> BoxFuture<'static, Validation<T, ValidatorError>>
+        + Send
+        + Sync
+        + 'static,
+>;
+pub(crate) type AsyncValidatorFn<T> =
+    Arc<dyn Fn(&str, Option<&str>) -> BoxFuture<'static, Result<(), T>> + Send + Sync + 'static>;
+pub(crate) type TransformerFn = Arc<dyn Fn(&str) -> String + Send + Sync + 'static>;
+pub(crate) type ParsedValue = Arc<dyn Any + Send + Sync + 'static>;
+pub(crate) type ParserFn<T> =
+    Arc<dyn Fn(&str, Option<&str>) -> Result<Option<ParsedValue>, T> + Send + Sync + 'static>;
+pub(crate) type ListParserFn<T> =
+    Arc<dyn Fn(&str, &[String]) -> Result<Option<ParsedValue>, T> + Send + Sync + 'static>;
+
+pub(crate) enum FieldRule<T, State> {
+    Static(fn(&str, Option<&str>) -> Result<(), T>),
+    Value(ValidatorFn<T>),
+    Async(AsyncValidatorFn<T>),
+    Fallible(FallibleValidatorFn<T>),
+    AsyncFallible(AsyncFallibleValidatorFn<T>),
+    Request(RequestValidatorFn<T, State>),
+    Body(BodyValidatorFn<T, State>),
+    Multi(MultiValidatorFn<T>),
+    Each(ValidatorFn<T>),
+    File(FileValidatorFn<T>),
+    Normalize(NormalizerFn<T>),
+    Parse(ParserFn<T>),
+    ParseEach(ListParserFn<T>),
+    Described(String, Arc<FieldRule<T, State>>),
+    Named(Arc<dyn Validator<T>>),
+    Warning(Arc<FieldRule<T, State>>),
+    Status(StatusCode, Arc<FieldRule<T, State>>),
+    Costed(Cost, Arc<FieldRule<T, State>>),
+    Sampled(Arc<Sampler>, Arc<FieldRule<T, State>>),
+}
+
+// Derived implementations would require `T: Clone`
+impl<T, State> Clone for FieldRule<T, State> {
+    fn clone(&self) -> Self {
+        match self {
+            FieldRule::Static(validator) => FieldRule::Static(*validator),
+            FieldRule::Value(validator) => FieldRule::Value(Arc::clone(validator)),
+            FieldRule::Async(validator) => FieldRule::Async(Arc::clone(validator)),
+            FieldRule::Fallible(validator) => FieldRule::Fallible(Arc::clone(validator)),
+            FieldRule::AsyncFallible(validator) => FieldRule::AsyncFallible(Arc::clone(validator)),
+            FieldRule::Request(validator) => FieldRule::Request(Arc::clone(validator)),
+            FieldRule::Body(validator) => FieldRule::Body(Arc::clone(validator)),
+            FieldRule::Multi(validator) => FieldRule::Multi(Arc::clone(validator)),
+            FieldRule::Each(validator) => FieldRule::Each(Arc::clone(validator)),
+            FieldRule::File(validator) => FieldRule::File(Arc::clone(validator)),
+            FieldRule::Normalize(normalizer) => FieldRule::Normalize(Arc::clone(normalizer)),
+            FieldRule::Parse(parser) => FieldRule::Parse(Arc::clone(parser)),
+            FieldRule::ParseEach(parser) => FieldRule::ParseEach(Arc::clone(parser)),
+            FieldRule::Described(description, rule) => {
+                FieldRule::Described(description.clone(), Arc::clone(rule))
+            }
+            FieldRule::Named(validator) => FieldRule::Named(Arc::clone(validator)),
+            FieldRule::Warning(rule) => FieldRule::Warning(Arc::clone(rule)),
+            FieldRule::Status(status, rule) => FieldRule::Status(*status, Arc::clone(rule)),
+            FieldRule::Costed(cost, rule) => FieldRule::Costed(*cost, Arc::clone(rule)),
+            FieldRule::Sampled(sampler, rule) => {
+                FieldRule::Sampled(Arc::clone(sampler), Arc::clone(rule))
+            }
+        }
+    }
+}
+
+/// Value produced by a rule which passed
+pub(crate) enum RuleOutput {
+    Normalized(String),
+    Parsed(ParsedValue),
+}
+
+/// Why a rule didn't pass
+pub(crate) enum Failure<T> {
+    Invalid(T),
+    Errored(ValidatorError),
+}
+
+impl<T> From<Validation<T, ValidatorError>> for Result<Option<RuleOutput>, Failure<T>> {
+    fn from(validation: Validation<T, ValidatorError>) -> Self {
+        match validation {
+            Validation::Valid => Ok(None),
+            Validation::Invalid(err) => Err(Failure::Invalid(err)),
+            Validation::Error(err) => Err(Failure::Errored(err)),
+        }
+    }
+}
+
+/// Result of a rule, async rules give a future which doesn't borrow the request
+pub(crate) enum Applied<T> {
+    Ready(Result<Option<RuleOutput>, Failure<T>>),
+    Pending(BoxFuture<'static, Result<(), T>>),
+    PendingFallible(BoxFuture<'static, Validation<T, ValidatorError>>),
+}
+
+impl<T> Applied<T> {
+    pub(crate) async fn resolve(self) -> Result<Option<RuleOutput>, Failure<T>> {
+        match self {
+            Applied::Ready(result) => result,
+            Applied::Pending(validation) => {
+                validation.await.map(|_| None).map_err(Failure::Invalid)
+            }
+            Applied::PendingFallible(validation) => validation.await.into(),
+        }
+    }
+}
+
+impl<T, State> FieldRule<T, State> {
+    /// Run the rule, returning the normalized value if the rule replaces the field value or the
+    /// parsed value if the rule parses it
+    pub(crate) fn apply(
+        &self,
+        req: &Request<State>,
+        body: &[u8],
+        parts: &[multipart::Part<'_>],
+        field_name: &str,
+        field_value: Option<&str>,
+        all_values: &[String],
+    ) -> Applied<T> {
+        let result = match self {
+            FieldRule::Static(validator) => validator(field_name, field_value).map(|_| None),
+            FieldRule::Value(validator) => validator(field_name, field_value).map(|_| None),
+            FieldRule::Async(validator) => {
+                return Applied::Pending(validator(field_name, field_value))
+            }
+            FieldRule::Fallible(validator) => {
+                return Applied::Ready(validator(field_name, field_value).into())
+            }
+            FieldRule::AsyncFallible(validator) => {
+                return Applied::PendingFallible(validator(field_name, field_value))
+            }
+            FieldRule::Request(validator) => validator(req, field_name, field_value).map(|_| None),
+            FieldRule::Body(validator) => {
+                validator(req, body, field_name, field_value).map(|_| None)
+            }
+            FieldRule::Multi(validator) => {
+                let values: Vec<&str> = all_values.iter().map(String::as_str).collect();
+                let values = if values.is_empty() {
+                    None
+                } else {
+                    Some(&values[..])
+                };
+                validator(field_name, values).map(|_| None)
+            }
+            FieldRule::Each(validator) => {
+                if all_values.is_empty() {
+                    validator(field_name, None).map(|_| None)
+                } else {
+                    all_values
+                        .iter()
+                        .try_for_each(|value| validator(field_name, Some(value)))
+                        .map(|_| None)
+                }
+            }
+            FieldRule::File(validator) => {
+                let part = parts.iter().find(|part| part.name == field_name);
+                validator(field_name, part).map(|_| None)
+            }
+            FieldRule::Normalize(normalizer) => {
+                normalizer(field_name, field_value).map(|value| value.map(RuleOutput::Normalized))
+            }
+            FieldRule::Parse(parser) => {
+                parser(field_name, field_value).map(|value| value.map(RuleOutput::Parsed))
+            }
+            FieldRule::ParseEach(parser) => {
+                parser(field_name, all_values).map(|value| value.map(RuleOutput::Parsed))
+            }
+            FieldRule::Described(_, rule)
+            | FieldRule::Warning(rule)
+            | FieldRule::Status(_, rule)
+            | FieldRule::Costed(_, rule)
+            | FieldRule::Sampled(_, rule) => {
+                return rule.apply(req, body, parts, field_name, field_value, all_values)
+            }
+            FieldRule::Named(validator) => {
+                validator.validate(field_name, field_value).map(|_| None)
+            }
+        };
+        Applied::Ready(result.map_err(Failure::Invalid))
+    }
+
+    pub(crate) fn reads_body(&self) -> bool {
+        match self {
+            FieldRule::Body(_) => true,
+            FieldRule::Described(_, rule)
+            | FieldRule::Warning(rule)
+            | FieldRule::Status(_, rule)
+            | FieldRule::Costed(_, rule)
+            | FieldRule::Sampled(_, rule) => rule.reads_body(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn reads_all_values(&self) -> bool {
+        match self {
+            FieldRule::Multi(_) | FieldRule::Each(_) | FieldRule::ParseEach(_) => true,
+            FieldRule::Described(_, rule)
+            | FieldRule::Warning(rule)
+            | FieldRule::Status(_, rule)
+            | FieldRule::Costed(_, rule)
+            | FieldRule::Sampled(_, rule) => rule.reads_all_values(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn description(&self) -> Option<&str> {
+        match self {
+            FieldRule::Described(description, _) => Some(description),
+            FieldRule::Warning(rule)
+            | FieldRule::Status(_, rule)
+            | FieldRule::Costed(_, rule)
+            | FieldRule::Sampled(_, rule) => rule.description(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        match self {
+            FieldRule::Named(validator) => Some(validator.name()),
+            FieldRule::Described(_, rule)
+            | FieldRule::Warning(rule)
+            | FieldRule::Status(_, rule)
+            | FieldRule::Costed(_, rule)
+            | FieldRule::Sampled(_, rule) => rule.name(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn severity(&self) -> Severity {
+        match self {
+            FieldRule::Warning(_) => Severity::Warning,
+            FieldRule::Described(_, rule)
+            | FieldRule::Status(_, rule)
+            | FieldRule::Costed(_, rule)
+            | FieldRule::Sampled(_, rule) => rule.severity(),
+            _ => Severity::Error,
+        }
+    }
+
+    /// Status code of the error response when the rule fails, instead of the response policy's
+    pub(crate) fn status(&self) -> Option<StatusCode> {
+        match self {
+            FieldRule::Status(status, _) => Some(*status),
+            FieldRule::Described(_, rule)
+            | FieldRule::Warning(rule)
+            | FieldRule::Costed(_, rule)
+            | FieldRule::Sampled(_, rule) => rule.status(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn cost(&self) -> Cost {
+        match self {
+            FieldRule::Costed(cost, _) => *cost,
+            FieldRule::Async(_) | FieldRule::AsyncFallible(_) => Cost::Expensive,
+            FieldRule::Described(_, rule)
+            | FieldRule::Warning(rule)
+            | FieldRule::Status(_, rule)
+            | FieldRule::Sampled(_, rule) => rule.cost(),
+            _ => Cost::Cheap,
+        }
+    }
+
+    /// Whether the rule runs on this call, `false` when a sampled rule skips it
+    pub(crate) fn sample(&self) -> bool {
+        match self {
+            FieldRule::Sampled(sampler, rule) => sampler.sample() && rule.sample(),
+            FieldRule::Described(_, rule)
+            | FieldRule::Warning(rule)
+            | FieldRule::Status(_, rule)
+            | FieldRule::Costed(_, rule) => rule.sample(),
+            _ => true,
+        }
+    }
+}
+
+pub(crate) type GroupValidatorFn<T> =
+    Arc<dyn Fn(&HashMap<&str, Option<&str>>) -> Result<(), T> + Send + Sync + 'static>;
+
+/// Validator seeing the values of several fields
+pub(crate) struct GroupRule<T> {
+    pub(crate) fields: Vec<HttpField<'static>>,
+    pub(crate) validate: GroupValidatorFn<T>,
+}
+
+impl<T> Clone for GroupRule<T> {
+    fn clone(&self) -> Self {
+        GroupRule {
+            fields: self.fields.clone(),
+            validate: Arc::clone(&self.validate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    use crate::{validators, ValidationError, ValidatorMiddleware};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use std::sync::Mutex;
+    use tide::http::{Method, Request};
+    use tide::Response;
+
+    #[test]
+    fn validator_with_status() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(HttpField::query_param("page"), is_number);
+        validators.add_validator_with_status(
+            HttpField::header("X-Api-Key"),
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some("secret") => Ok(()),
+                _ => Err(format!("'{}' is not a valid key", field_name)),
+            },
+            StatusCode::Unauthorized,
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (url, key, status) in &[
+            ("/foo?page=1", Some("secret"), StatusCode::Ok),
+            ("/foo?page=one", Some("secret"), StatusCode::BadRequest),
+            ("/foo?page=1", Some("guess"), StatusCode::Unauthorized),
+            ("/foo?page=1", None, StatusCode::Unauthorized),
+        ] {
+            let url = format!("http://localhost{}", url);
+            let mut req = Request::new(Method::Get, url.parse().unwrap());
+            if let Some(key) = key {
+                req.insert_header("X-Api-Key", *key).unwrap();
+            }
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+    }
+
+    #[test]
+    fn rule_costs() {
+        let cheap: FieldRule<String, ()> = FieldRule::Static(is_number);
+        assert_eq!(cheap.cost(), Cost::Cheap);
+        let lookup: FieldRule<String, ()> =
+            FieldRule::Async(Arc::new(|_: &str, _: Option<&str>| {
+                Box::pin(async { Ok(()) })
+            }));
+        assert_eq!(lookup.cost(), Cost::Expensive);
+        let described = FieldRule::Described(String::from("lookup"), Arc::new(lookup));
+        assert_eq!(described.cost(), Cost::Expensive);
+        let costed = FieldRule::Costed(Cost::Expensive, Arc::new(cheap));
+        assert_eq!(costed.cost(), Cost::Expensive);
+    }
+
+    #[test]
+    fn sampled_rules() {
+        let sampled: FieldRule<String, ()> = FieldRule::Sampled(
+            Arc::new(Sampler::new(0.5)),
+            Arc::new(FieldRule::Static(is_number)),
+        );
+        let described = FieldRule::Described(String::from("sampled"), Arc::new(sampled));
+        let runs: Vec<bool> = (0..4).map(|_| described.sample()).collect();
+        assert_eq!(runs, vec![false, true, false, true]);
+        assert!(FieldRule::<String, ()>::Static(is_number).sample());
+    }
+
+    #[test]
+    fn validator_order_by_cost() {
+        let lookups = Arc::new(Mutex::new(Vec::new()));
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().order_by_cost();
+        let validator_lookups = lookups.clone();
+        validators.add_async_validator(
+            HttpField::header("X-Api-Key"),
+            move |_: &str, field_value: Option<&str>| {
+                let key = field_value.unwrap_or_default().to_string();
+                validator_lookups.lock().unwrap().push(key);
+                Box::pin(async { Ok(()) })
+            },
+        );
+        validators.add_validator(HttpField::query_param("page"), is_number);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(
+            Method::Get,
+            "http://localhost/foo?page=one".parse().unwrap(),
+        );
+        req.insert_header("X-Api-Key", "secret").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert!(lookups.lock().unwrap().is_empty());
+
+        let mut req = Request::new(Method::Get, "http://localhost/foo?page=1".parse().unwrap());
+        req.insert_header("X-Api-Key", "secret").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(*lookups.lock().unwrap(), vec![String::from("secret")]);
+    }
+
+    #[test]
+    fn validator_descriptions() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_described_validator(
+            HttpField::query_param("page"),
+            "must be an integer",
+            validators::is_integer,
+        );
+        validators.add_validator(HttpField::query_param("page"), validators::is_required);
+        assert_eq!(
+            validators.descriptions(&HttpField::query_param("page")),
+            vec!["must be an integer"]
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/foo?page=a".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let err: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(err.description.as_deref(), Some("must be an integer"));
+    }
+
+    #[test]
+    fn validator_fallible() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .on_validator_error(|_, _| Response::new(StatusCode::ServiceUnavailable));
+        validators.add_fallible_validator(
+            HttpField::query_param("tenant"),
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some("down") => Validation::Error("tenant store is down"),
+                Some("acme") | None => Validation::Valid,
+                Some(_) => Validation::Invalid(format!("'{}' is not a known tenant", field_name)),
+            },
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (url, status) in &[
+            ("http://localhost/foo?tenant=acme", StatusCode::Ok),
+            ("http://localhost/foo?tenant=other", StatusCode::BadRequest),
+            (
+                "http://localhost/foo?tenant=down",
+                StatusCode::ServiceUnavailable,
+            ),
+        ] {
+            let req = Request::new(Method::Get, url.parse().unwrap());
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+    }
+
+    #[test]
+    fn validator_async() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_async_validator(
+            HttpField::header("X-Api-Key"),
+            |field_name: &str, field_value: Option<&str>| {
+                let field_name = field_name.to_string();
+                let valid = field_value == Some("secret");
+                Box::pin(async move {
+                    if valid {
+                        Ok(())
+                    } else {
+                        Err(format!("'{}' is not a valid API key", field_name))
+                    }
+                })
+            },
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+        req.insert_header("X-Api-Key", "secret").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+        req.insert_header("X-Api-Key", "guess").unwrap();
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf[..]),
+            String::from(r#""'X-Api-Key' is not a valid API key""#)
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Adversarial inputs to fuzz your validators, available with the `fuzzing` feature.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::fuzzing::panics;
+//!
+//! #[test]
+//! fn is_slug_never_panics() {
+//!     assert!(panics("slug", &is_slug).is_empty());
+//! }
+//! ```
+use crate::{validate_one, Outcome};
+
+/// Values known to break naive validators: missing and empty values, control characters,
+/// unicode edge cases, numbers at the limits and very long strings
+pub fn corpus() -> Vec<Option<String>> {
+    let values = [
+        "",
+        " ",
+        "\t\r\n",
+        "\0",
+        "abc\0def",
+        "\u{7f}",
+        "\u{1b}[31m",
+        // BOM, zero width space and right-to-left override
+        "\u{feff}value",
+        "\u{200b}",
+        "\u{202e}txt.exe",
+        // Combining characters, emoji sequence, replacement character
+        "e\u{301}\u{301}\u{301}",
+        "👩‍👩‍👧‍👦",
+        "\u{fffd}",
+        // Digits which aren't ASCII
+        "１２３",
+        "٣",
+        "NaN",
+        "inf",
+        "-inf",
+        "-0",
+        "1e309",
+        "9223372036854775808",
+        "-9223372036854775809",
+        "0x10",
+        "1_000",
+        "%00",
+        "%ZZ",
+        "../../etc/passwd",
+        "' OR 1=1 --",
+        "<script>alert(1)</script>",
+        "${jndi:ldap://x}",
+    ];
+
+    let mut corpus: Vec<Option<String>> = vec![None];
+    corpus.extend(values.iter().map(|value| Some(value.to_string())));
+    corpus.push(Some("a".repeat(64 * 1024)));
+    corpus.push(Some("9".repeat(4096)));
+    corpus.push(Some("é".repeat(16 * 1024)));
+    corpus
+}
+
+/// Run `rule` against every value of the [`corpus`](fn.corpus.html)
+pub fn run<T, F>(field_name: &str, rule: &F) -> Vec<(Option<String>, Outcome<T>)>
+where
+    F: Fn(&str, Option<&str>) -> Result<(), T>,
+{
+    corpus()
+        .into_iter()
+        .map(|value| {
+            let outcome = validate_one(rule, field_name, value.as_deref());
+            (value, outcome)
+        })
+        .collect()
+}
+
+/// Values of the [`corpus`](fn.corpus.html) making `rule` panic, with the panic message
+pub fn panics<T, F>(field_name: &str, rule: &F) -> Vec<(Option<String>, String)>
+where
+    F: Fn(&str, Option<&str>) -> Result<(), T>,
+{
+    run(field_name, rule)
+        .into_iter()
+        .filter_map(|(value, outcome)| match outcome {
+            Outcome::Panicked(message) => Some((value, message)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validators;
+
+    #[test]
+    fn built_in_validators_never_panic() {
+        assert!(panics("n", &validators::is_number).is_empty());
+        assert!(panics("n", &validators::is_between(1.0, 10.0)).is_empty());
+        assert!(panics("s", &validators::is_max_length(3)).is_empty());
+    }
+
+    #[test]
+    fn panics_are_caught() {
+        let rule = |_: &str, value: Option<&str>| -> Result<(), String> {
+            value.map(|value| &value[..1]);
+            Ok(())
+        };
+        assert!(!panics("s", &rule).is_empty());
+    }
+}
@@ -0,0 +1,858 @@
+//! Validation of GraphQL requests, available with the `graphql` feature.
+//!
+//! The [`graphql_request`](fn.graphql_request.html) preset checks the JSON envelope posted to a
+//! GraphQL endpoint (`query`, `operationName`, `variables` and `extensions`) before the resolvers
+//! run: the query is parsed, its depth and number of fields are limited, the variables are checked
+//! against the types declared by the operation and the SHA-256 hash of an
+//! [automatic persisted query](https://www.apollographql.com/docs/apollo-server/performance/apq/)
+//! has to match the query sent with it.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::graphql::graphql_request;
+//!
+//! let validator_middleware = ValidatorMiddleware::new().preset(
+//!     graphql_request().max_depth(8).max_complexity(200),
+//! );
+//! app.at("/graphql").middleware(validator_middleware).post(graphql_handler);
+//! ```
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::presets::Preset;
+use crate::validators::ValidationError;
+use crate::{HttpField, ValidatorMiddleware};
+
+/// Nesting of selection sets and values above which a query isn't parsed, whatever the limits
+const MAX_NESTING: usize = 128;
+
+/// Preset validating the requests of a GraphQL endpoint, see
+/// [`graphql_request`](fn.graphql_request.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphqlRequest {
+    max_depth: Option<usize>,
+    max_complexity: Option<usize>,
+}
+
+/// Validate the JSON body of GraphQL requests:
+///
+/// - the body is an object with a `query` string, unless it has the hash of a persisted query
+/// - the query is a valid GraphQL document, and `operationName` names one of its operations when
+///   it has several
+/// - the selected operation is at most [`max_depth`](struct.GraphqlRequest.html#method.max_depth)
+///   fields deep and selects at most
+///   [`max_complexity`](struct.GraphqlRequest.html#method.max_complexity) fields, fragments
+///   included
+/// - `variables` have the built-in types `Int`, `Float`, `String`, `Boolean` and `ID` declared by
+///   the operation and required variables are given, values of other types aren't checked
+/// - `extensions.persistedQuery.sha256Hash` is the hash of the query sent with it
+///
+/// Errors are reported on the body field `query` with the codes `graphql`, `operation`, `depth`,
+/// `complexity`, `variable` and `persisted_query`.
+pub fn graphql_request() -> GraphqlRequest {
+    GraphqlRequest {
+        max_depth: None,
+        max_complexity: None,
+    }
+}
+
+impl GraphqlRequest {
+    /// Maximum number of nested fields, e.g. `{ user { friends { name } } }` is 3 fields deep
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Maximum number of fields selected by the operation, counting the fields of fragments each
+    /// time they're spread
+    pub fn max_complexity(mut self, max_complexity: usize) -> Self {
+        self.max_complexity = Some(max_complexity);
+        self
+    }
+
+    /// Validate a request body
+    pub fn validate(&self, body: &[u8]) -> Result<(), ValidationError> {
+        let envelope: Envelope = serde_json::from_slice(body)
+            .map_err(|err| invalid("graphql", format!("body isn't a GraphQL request: {}", err)))?;
+        let query = match (&envelope.query, envelope.persisted_hash()) {
+            (Some(query), Some(hash)) => {
+                if hex::encode(Sha256::digest(query.as_bytes())) != hash.to_lowercase() {
+                    return Err(invalid(
+                        "persisted_query",
+                        String::from("the hash of the persisted query doesn't match the query"),
+                    ));
+                }
+                query
+            }
+            (Some(query), None) => query,
+            // The server looks the query up by its hash
+            (None, Some(_)) => return Ok(()),
+            (None, None) => return Err(invalid("graphql", String::from("'query' is required"))),
+        };
+
+        let document = parse(query).map_err(|err| invalid("graphql", err))?;
+        let operation = document.operation(envelope.operation_name.as_deref())?;
+        let mut cost = Cost {
+            fragments: &document.fragments,
+            measured: HashMap::new(),
+            spread: Vec::new(),
+            max_depth: self.max_depth,
+            max_complexity: self.max_complexity,
+        };
+        cost.measure(&operation.selections, 0)?;
+
+        let variables = match &envelope.variables {
+            Some(Value::Object(variables)) => Some(variables),
+            None | Some(Value::Null) => None,
+            Some(_) => {
+                return Err(invalid(
+                    "variable",
+                    String::from("'variables' must be an object"),
+                ))
+            }
+        };
+        for definition in &operation.variables {
+            let value = variables.and_then(|variables| variables.get(&definition.name));
+            let valid = match value {
+                None => definition.has_default || !definition.ty.is_non_null(),
+                Some(value) => definition.ty.accepts(value),
+            };
+            if !valid {
+                return Err(invalid(
+                    "variable",
+                    format!(
+                        "variable '${}' must be a {}",
+                        definition.name, definition.ty
+                    ),
+                )
+                .with_param("variable", &definition.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T, State> Preset<T, State> for GraphqlRequest
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        middleware.add_body_validator(
+            HttpField::body_field("query"),
+            move |body: &[u8], _: &str, _: Option<&str>| self.validate(body).map_err(T::from),
+        );
+    }
+}
+
+fn invalid(code: &str, message: String) -> ValidationError {
+    ValidationError::new("query", code, message)
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    query: Option<String>,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+    variables: Option<Value>,
+    extensions: Option<Value>,
+}
+
+impl Envelope {
+    fn persisted_hash(&self) -> Option<&str> {
+        self.extensions.as_ref()?["persistedQuery"]["sha256Hash"].as_str()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punct(char),
+    Spread,
+    Name(String),
+    /// Number or string, their content doesn't matter to the checks
+    Literal,
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() || c == ',' || c == '\u{feff}' => {}
+            '#' => {
+                while matches!(chars.peek(), Some(c) if *c != '\n' && *c != '\r') {
+                    chars.next();
+                }
+            }
+            '{' | '}' | '(' | ')' | '[' | ']' | ':' | '=' | '!' | '$' | '@' | '|' | '&' => {
+                tokens.push(Token::Punct(c))
+            }
+            '.' => {
+                if chars.next() != Some('.') || chars.next() != Some('.') {
+                    return Err(String::from("unexpected '.'"));
+                }
+                tokens.push(Token::Spread);
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(c) = chars
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+                {
+                    name.push(*c);
+                    chars.next();
+                }
+                tokens.push(Token::Name(name));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                while matches!(chars.peek(), Some(c) if c.is_ascii_alphanumeric() || "+-.".contains(*c))
+                {
+                    chars.next();
+                }
+                tokens.push(Token::Literal);
+            }
+            '"' => {
+                let block = chars.peek() == Some(&'"') && {
+                    chars.next();
+                    if chars.peek() == Some(&'"') {
+                        chars.next();
+                        true
+                    } else {
+                        // Empty string
+                        tokens.push(Token::Literal);
+                        continue;
+                    }
+                };
+                let mut quotes = 0;
+                loop {
+                    match chars.next() {
+                        None => return Err(String::from("unterminated string")),
+                        Some('\\') => {
+                            chars.next();
+                            quotes = 0;
+                        }
+                        Some('"') if !block => break,
+                        Some('"') => {
+                            quotes += 1;
+                            if quotes == 3 {
+                                break;
+                            }
+                        }
+                        Some('\n') | Some('\r') if !block => {
+                            return Err(String::from("unterminated string"))
+                        }
+                        Some(_) => quotes = 0,
+                    }
+                }
+                tokens.push(Token::Literal);
+            }
+            c => return Err(format!("unexpected character '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Type {
+    Named(String),
+    List(Box<Type>),
+    NonNull(Box<Type>),
+}
+
+impl Type {
+    fn is_non_null(&self) -> bool {
+        matches!(self, Type::NonNull(_))
+    }
+
+    /// Whether the value can be coerced to the type, values of custom types are accepted
+    fn accepts(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Type::NonNull(_), Value::Null) => false,
+            (Type::NonNull(ty), value) => ty.accepts(value),
+            (_, Value::Null) => true,
+            (Type::List(ty), Value::Array(items)) => items.iter().all(|item| ty.accepts(item)),
+            // A single value is coerced to a list of one item
+            (Type::List(ty), value) => ty.accepts(value),
+            (Type::Named(name), value) => match name.as_str() {
+                "Int" => matches!(
+                    value.as_i64(),
+                    Some(n) if n >= i64::from(i32::MIN) && n <= i64::from(i32::MAX)
+                ),
+                "Float" => value.is_number(),
+                "String" => value.is_string(),
+                "Boolean" => value.is_boolean(),
+                "ID" => value.is_string() || value.is_i64() || value.is_u64(),
+                _ => true,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Named(name) => f.write_str(name),
+            Type::List(ty) => write!(f, "[{}]", ty),
+            Type::NonNull(ty) => write!(f, "{}!", ty),
+        }
+    }
+}
+
+struct VariableDefinition {
+    name: String,
+    ty: Type,
+    has_default: bool,
+}
+
+enum Selection {
+    /// Field with its sub-selections
+    Field(Vec<Selection>),
+    /// Spread of a named fragment
+    Spread(String),
+    Inline(Vec<Selection>),
+}
+
+struct Operation {
+    name: Option<String>,
+    variables: Vec<VariableDefinition>,
+    selections: Vec<Selection>,
+}
+
+struct Document {
+    operations: Vec<Operation>,
+    fragments: HashMap<String, Vec<Selection>>,
+}
+
+impl Document {
+    /// Operation run by the request
+    fn operation(&self, name: Option<&str>) -> Result<&Operation, ValidationError> {
+        match name {
+            Some(name) => self
+                .operations
+                .iter()
+                .find(|operation| operation.name.as_deref() == Some(name))
+                .ok_or_else(|| {
+                    invalid(
+                        "operation",
+                        format!("the query has no operation named '{}'", name),
+                    )
+                }),
+            None if self.operations.len() == 1 => Ok(&self.operations[0]),
+            None => Err(invalid(
+                "operation",
+                String::from("'operationName' is required when the query has several operations"),
+            )),
+        }
+    }
+}
+
+fn parse(query: &str) -> Result<Document, String> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+        nesting: 0,
+    };
+    let mut document = Document {
+        operations: Vec::new(),
+        fragments: HashMap::new(),
+    };
+    while let Some(token) = parser.peek() {
+        match token {
+            Token::Punct('{') => document.operations.push(Operation {
+                name: None,
+                variables: Vec::new(),
+                selections: parser.selection_set()?,
+            }),
+            Token::Name(keyword) if keyword == "fragment" => {
+                parser.next();
+                let name = parser.name()?;
+                if parser.name()? != "on" {
+                    return Err(format!("fragment '{}' has no type condition", name));
+                }
+                parser.name()?;
+                parser.directives()?;
+                let selections = parser.selection_set()?;
+                document.fragments.insert(name, selections);
+            }
+            Token::Name(keyword)
+                if keyword == "query" || keyword == "mutation" || keyword == "subscription" =>
+            {
+                parser.next();
+                let name = match parser.peek() {
+                    Some(Token::Name(_)) => Some(parser.name()?),
+                    _ => None,
+                };
+                let variables = if parser.peek() == Some(&Token::Punct('(')) {
+                    parser.variable_definitions()?
+                } else {
+                    Vec::new()
+                };
+                parser.directives()?;
+                document.operations.push(Operation {
+                    name,
+                    variables,
+                    selections: parser.selection_set()?,
+                });
+            }
+            _ => return Err(String::from("expected an operation or a fragment")),
+        }
+    }
+    if document.operations.is_empty() {
+        return Err(String::from("the query has no operation"));
+    }
+    Ok(document)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// Current nesting of selection sets, lists and objects
+    nesting: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Punct(c)) if c == punct => Ok(()),
+            _ => Err(format!("expected '{}'", punct)),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(name),
+            _ => Err(String::from("expected a name")),
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            return Err(String::from("the query is nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn variable_definitions(&mut self) -> Result<Vec<VariableDefinition>, String> {
+        self.expect('(')?;
+        let mut definitions = Vec::new();
+        while self.peek() != Some(&Token::Punct(')')) {
+            self.expect('$')?;
+            let name = self.name()?;
+            self.expect(':')?;
+            let ty = self.ty()?;
+            let has_default = self.peek() == Some(&Token::Punct('='));
+            if has_default {
+                self.next();
+                self.value()?;
+            }
+            self.directives()?;
+            definitions.push(VariableDefinition {
+                name,
+                ty,
+                has_default,
+            });
+        }
+        self.expect(')')?;
+        Ok(definitions)
+    }
+
+    fn ty(&mut self) -> Result<Type, String> {
+        let ty = if self.peek() == Some(&Token::Punct('[')) {
+            self.next();
+            self.enter()?;
+            let ty = self.ty()?;
+            self.nesting -= 1;
+            self.expect(']')?;
+            Type::List(Box::new(ty))
+        } else {
+            Type::Named(self.name()?)
+        };
+        if self.peek() == Some(&Token::Punct('!')) {
+            self.next();
+            return Ok(Type::NonNull(Box::new(ty)));
+        }
+        Ok(ty)
+    }
+
+    fn value(&mut self) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Punct('$')) => self.name().map(|_| ()),
+            Some(Token::Literal) | Some(Token::Name(_)) => Ok(()),
+            Some(Token::Punct('[')) => {
+                self.enter()?;
+                while self.peek() != Some(&Token::Punct(']')) {
+                    self.value()?;
+                }
+                self.nesting -= 1;
+                self.expect(']')
+            }
+            Some(Token::Punct('{')) => {
+                self.enter()?;
+                while self.peek() != Some(&Token::Punct('}')) {
+                    self.name()?;
+                    self.expect(':')?;
+                    self.value()?;
+                }
+                self.nesting -= 1;
+                self.expect('}')
+            }
+            _ => Err(String::from("expected a value")),
+        }
+    }
+
+    fn arguments(&mut self) -> Result<(), String> {
+        if self.peek() != Some(&Token::Punct('(')) {
+            return Ok(());
+        }
+        self.next();
+        while self.peek() != Some(&Token::Punct(')')) {
+            self.name()?;
+            self.expect(':')?;
+            self.value()?;
+        }
+        self.expect(')')
+    }
+
+    fn directives(&mut self) -> Result<(), String> {
+        while self.peek() == Some(&Token::Punct('@')) {
+            self.next();
+            self.name()?;
+            self.arguments()?;
+        }
+        Ok(())
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Selection>, String> {
+        self.expect('{')?;
+        self.enter()?;
+        let mut selections = Vec::new();
+        while self.peek() != Some(&Token::Punct('}')) {
+            if self.peek().is_none() {
+                return Err(String::from("expected '}'"));
+            }
+            selections.push(self.selection()?);
+        }
+        self.nesting -= 1;
+        self.expect('}')?;
+        if selections.is_empty() {
+            return Err(String::from("empty selection set"));
+        }
+        Ok(selections)
+    }
+
+    fn selection(&mut self) -> Result<Selection, String> {
+        if self.peek() == Some(&Token::Spread) {
+            self.next();
+            return match self.peek() {
+                Some(Token::Name(on)) if on == "on" => {
+                    self.next();
+                    self.name()?;
+                    self.directives()?;
+                    Ok(Selection::Inline(self.selection_set()?))
+                }
+                Some(Token::Name(_)) => {
+                    let name = self.name()?;
+                    self.directives()?;
+                    Ok(Selection::Spread(name))
+                }
+                _ => {
+                    self.directives()?;
+                    Ok(Selection::Inline(self.selection_set()?))
+                }
+            };
+        }
+        self.name()?;
+        if self.peek() == Some(&Token::Punct(':')) {
+            self.next();
+            self.name()?;
+        }
+        self.arguments()?;
+        self.directives()?;
+        let children = if self.peek() == Some(&Token::Punct('{')) {
+            self.selection_set()?
+        } else {
+            Vec::new()
+        };
+        Ok(Selection::Field(children))
+    }
+}
+
+/// Fields of a spread fragment, failing on unknown fragments and cycles
+fn fragment<'d>(
+    name: &'d str,
+    fragments: &'d HashMap<String, Vec<Selection>>,
+    spread: &[&'d str],
+) -> Result<&'d [Selection], ValidationError> {
+    if spread.contains(&name) {
+        return Err(invalid(
+            "graphql",
+            format!("fragment '{}' spreads itself", name),
+        ));
+    }
+    fragments
+        .get(name)
+        .map(Vec::as_slice)
+        .ok_or_else(|| invalid("graphql", format!("unknown fragment '{}'", name)))
+}
+
+/// Depth and complexity of selections. The ones of each fragment are computed once, so fragments
+/// spreading other fragments several times can't make the walk exponential, and the walk stops
+/// as soon as a limit is passed.
+struct Cost<'d> {
+    fragments: &'d HashMap<String, Vec<Selection>>,
+    /// Depth and complexity of the fragments already measured
+    measured: HashMap<&'d str, (usize, usize)>,
+    /// Fragments being measured, to find cycles
+    spread: Vec<&'d str>,
+    max_depth: Option<usize>,
+    max_complexity: Option<usize>,
+}
+
+impl<'d> Cost<'d> {
+    /// Depth and complexity of selections nested in `level` fields
+    fn measure(
+        &mut self,
+        selections: &'d [Selection],
+        level: usize,
+    ) -> Result<(usize, usize), ValidationError> {
+        let mut max_depth = 0;
+        let mut total = 0usize;
+        for selection in selections {
+            let (depth, complexity) = match selection {
+                Selection::Field(children) => {
+                    let (depth, complexity) = self.measure(children, level + 1)?;
+                    (depth + 1, complexity.saturating_add(1))
+                }
+                Selection::Inline(children) => self.measure(children, level)?,
+                Selection::Spread(name) => self.fragment(name, level)?,
+            };
+            max_depth = max_depth.max(depth);
+            total = total.saturating_add(complexity);
+            if let Some(max) = self.max_depth.filter(|max| level + max_depth > *max) {
+                return Err(invalid(
+                    "depth",
+                    format!("the query is more than {} fields deep", max),
+                )
+                .with_param("max", max));
+            }
+            // The fields selected so far are a part of the fields of the whole query
+            if let Some(max) = self.max_complexity.filter(|max| total > *max) {
+                return Err(invalid(
+                    "complexity",
+                    format!("the query selects more than {} fields", max),
+                )
+                .with_param("max", max));
+            }
+        }
+        Ok((max_depth, total))
+    }
+
+    /// Depth and complexity of a spread fragment
+    fn fragment(&mut self, name: &'d str, level: usize) -> Result<(usize, usize), ValidationError> {
+        if let Some(measured) = self.measured.get(name) {
+            return Ok(*measured);
+        }
+        let children = fragment(name, self.fragments, &self.spread)?;
+        self.spread.push(name);
+        let measured = self.measure(children, level)?;
+        self.spread.pop();
+        self.measured.insert(name, measured);
+        Ok(measured)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn validate(preset: GraphqlRequest, body: Value) -> Result<(), String> {
+        preset
+            .validate(body.to_string().as_bytes())
+            .map_err(|err| err.code)
+    }
+
+    #[test]
+    fn queries() {
+        let preset = graphql_request().max_depth(3).max_complexity(6);
+        let query = r#"
+            # Friends of a user
+            query Friends($id: ID!, $first: Int = 10) {
+                user(id: $id) {
+                    name
+                    friends(first: $first, filter: {tags: ["a", "b"]}) @include(if: true) {
+                        ...person
+                    }
+                }
+            }
+            fragment person on User { name, avatar(size: 64) }
+        "#;
+        assert_eq!(
+            validate(preset, json!({"query": query, "variables": {"id": "42"}})),
+            Ok(())
+        );
+        assert_eq!(
+            validate(
+                preset.max_depth(2),
+                json!({"query": query, "variables": {"id": 42}})
+            ),
+            Err(String::from("depth"))
+        );
+        assert_eq!(
+            validate(
+                preset.max_complexity(4),
+                json!({"query": query, "variables": {"id": 42}})
+            ),
+            Err(String::from("complexity"))
+        );
+        assert_eq!(
+            validate(preset, json!({"query": "{ a { b { c { d } } } }"})),
+            Err(String::from("depth"))
+        );
+        assert_eq!(
+            validate(
+                preset,
+                json!({"query": "{ user(id: \"\"\"block \"quoted\" \"\"\") { name ...on User { id } } }"})
+            ),
+            Ok(())
+        );
+
+        for query in &[
+            "{ user { name }",
+            "query { }",
+            "{ user(id: ) { name } }",
+            "fragment f on User { id }",
+            "{ ...f } fragment f on User { ...f }",
+            "{ ...missing }",
+        ] {
+            assert_eq!(
+                validate(preset, json!({ "query": query })),
+                Err(String::from("graphql")),
+                "{}",
+                query
+            );
+        }
+        let deep = format!("{}{}", "{ a ".repeat(200), "}".repeat(200));
+        assert_eq!(
+            validate(graphql_request(), json!({ "query": deep })),
+            Err(String::from("graphql"))
+        );
+        assert_eq!(
+            validate(preset, json!({"variables": {}})),
+            Err(String::from("graphql"))
+        );
+    }
+
+    #[test]
+    fn fragment_chains() {
+        // Each fragment spreads the next one twice, the query selects 2^64 fields
+        let mut query = String::from("{ ...f0 }");
+        for i in 0..64 {
+            query.push_str(&format!(
+                " fragment f{} on Query {{ a {{ ...f{} }} b {{ ...f{} }} }}",
+                i,
+                i + 1,
+                i + 1
+            ));
+        }
+        query.push_str(" fragment f64 on Query { id }");
+        assert_eq!(
+            validate(graphql_request(), json!({ "query": query })),
+            Ok(())
+        );
+        assert_eq!(
+            validate(
+                graphql_request().max_complexity(1000),
+                json!({ "query": query })
+            ),
+            Err(String::from("complexity"))
+        );
+        assert_eq!(
+            validate(graphql_request().max_depth(10), json!({ "query": query })),
+            Err(String::from("depth"))
+        );
+    }
+
+    #[test]
+    fn operations_and_variables() {
+        let preset = graphql_request();
+        let query =
+            "query A($n: Int!, $tags: [String!]) { a(n: $n, tags: $tags) } mutation B { b }";
+        assert_eq!(
+            validate(
+                preset,
+                json!({"query": query, "operationName": "A", "variables": {"n": 1, "tags": ["x"]}})
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            validate(preset, json!({"query": query, "operationName": "B"})),
+            Ok(())
+        );
+        assert_eq!(
+            validate(preset, json!({"query": query, "variables": {"n": 1}})),
+            Err(String::from("operation"))
+        );
+        assert_eq!(
+            validate(preset, json!({"query": query, "operationName": "C"})),
+            Err(String::from("operation"))
+        );
+        for variables in &[
+            json!({}),
+            json!({"n": "1"}),
+            json!({"n": 3_000_000_000u64}),
+            json!({"n": 1, "tags": [null]}),
+            json!([1]),
+        ] {
+            assert_eq!(
+                validate(
+                    preset,
+                    json!({"query": query, "operationName": "A", "variables": variables})
+                ),
+                Err(String::from("variable")),
+                "{}",
+                variables
+            );
+        }
+        assert_eq!(
+            validate(
+                preset,
+                json!({"query": query, "operationName": "A", "variables": {"n": 1, "tags": "x"}})
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn persisted_queries() {
+        let preset = graphql_request();
+        let query = "{ me { name } }";
+        let hash = hex::encode(Sha256::digest(query.as_bytes()));
+        let extensions = json!({"persistedQuery": {"version": 1, "sha256Hash": hash}});
+        assert_eq!(
+            validate(preset, json!({"query": query, "extensions": extensions})),
+            Ok(())
+        );
+        assert_eq!(validate(preset, json!({"extensions": extensions})), Ok(()));
+        assert_eq!(
+            validate(
+                preset,
+                json!({"query": "{ me { email } }", "extensions": extensions})
+            ),
+            Err(String::from("persisted_query"))
+        );
+    }
+}
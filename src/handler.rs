@@ -0,0 +1,68 @@
+//! Helpers to write handlers of validated routes.
+use std::future::Future;
+
+use tide::{Endpoint, Request, Response};
+
+use crate::ValidationReport;
+
+/// Wrap a handler to receive the [`ValidationReport`](../struct.ValidationReport.html) of the
+/// request as second argument, with the parsed values ready to use
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// use tide_validator::handler::with_validation;
+///
+/// validator_middleware.add_parser(HttpField::param("age"), parsed::<u32, String>());
+/// app.at("/age/:age").middleware(validator_middleware).get(with_validation(
+///     |_req: Request<()>, report: ValidationReport| async move {
+///         let age: u32 = *report.validated::<u32>("age").unwrap();
+///         Ok(format!("{}", age))
+///     },
+/// ));
+/// ```
+pub fn with_validation<State, F, Fut, Res>(handler: F) -> impl Endpoint<State>
+where
+    State: Send + Sync + 'static,
+    F: Fn(Request<State>, ValidationReport) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = tide::Result<Res>> + Send + 'static,
+    Res: Into<Response>,
+{
+    move |req: Request<State>| {
+        let report = ValidationReport::from_request(&req);
+        handler(req, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{validators, HttpField, ValidatorMiddleware};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request as HttpRequest};
+
+    #[test]
+    fn handler_with_validation() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::<String, ()>::new();
+        validators.add_parser(HttpField::param("age"), validators::parsed::<u32, String>());
+        inner
+            .at("/age/:age")
+            .middleware(validators)
+            .get(with_validation(|_, report: ValidationReport| async move {
+                let age = report.validated::<u32>("age").copied().unwrap_or_default();
+                Ok(format!("{}", age))
+            }));
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = HttpRequest::new(Method::Get, "http://localhost/age/42".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&*buf, &*b"42");
+    }
+}
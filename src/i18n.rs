@@ -0,0 +1,144 @@
+//! Translated error messages, rendered in the language asked by the `Accept-Language` header.
+//!
+//! Built-in validators give a `code` and parameters in their errors, the message of a code is
+//! looked up in the translation table of the best locale of the request and its placeholders
+//! filled like [message overrides](../struct.ValidatorMiddleware.html#method.with_message_override).
+//! Codes without a translation keep their message, requests without a supported locale get the
+//! messages of the validators.
+//!
+//! ```rust,no_run,compile_fail
+//! let validator_middleware = ValidatorMiddleware::new()
+//!     .with_translations("fr", vec![
+//!         ("required", "{field} est obligatoire"),
+//!         ("between", "{field} doit être entre {min} et {max}"),
+//!     ])
+//!     .with_translations("de", serde_json::from_str::<HashMap<String, String>>(include_str!("de.json"))?);
+//! // Accept-Language: fr-CH, fr;q=0.9, en;q=0.8
+//! // {"field": "age", "code": "between", "message": "age doit être entre 1 et 120", ...}
+//! ```
+use std::collections::HashMap;
+
+/// Translation tables of error messages by locale, message templates are indexed by error code
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Translations {
+    tables: Vec<(String, HashMap<String, String>)>,
+}
+
+impl Translations {
+    /// Create empty translations
+    pub fn new() -> Self {
+        Translations { tables: Vec::new() }
+    }
+
+    /// Add messages of a locale given as a BCP 47 tag, e.g. `fr` or `pt-BR`, adding to the
+    /// messages already added for this locale
+    pub fn add<I, K, V>(&mut self, locale: &str, messages: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let messages = messages
+            .into_iter()
+            .map(|(code, template)| (code.into(), template.into()));
+        match self
+            .tables
+            .iter_mut()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(locale))
+        {
+            Some((_, table)) => table.extend(messages),
+            None => self.tables.push((locale.to_string(), messages.collect())),
+        }
+        self
+    }
+
+    /// Check if no locale has been added
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Locale and messages to answer a request with the `Accept-Language` header `accept_language`.
+    /// Languages are tried by preference, a tag without translations falls back on less specific
+    /// tags then on a locale of the same language: `fr-CA` is answered with `fr`, then `fr-FR`.
+    pub fn negotiate(&self, accept_language: &str) -> Option<(&str, &HashMap<String, String>)> {
+        accepted_languages(accept_language)
+            .into_iter()
+            .find_map(|requested| self.find(requested))
+            .map(|(tag, table)| (tag.as_str(), table))
+    }
+
+    fn find(&self, requested: &str) -> Option<&(String, HashMap<String, String>)> {
+        let mut tag = requested;
+        loop {
+            if let Some(table) = self
+                .tables
+                .iter()
+                .find(|(locale, _)| locale.eq_ignore_ascii_case(tag))
+            {
+                return Some(table);
+            }
+            match tag.rfind('-') {
+                Some(end) => tag = &tag[..end],
+                None => break,
+            }
+        }
+        self.tables.iter().find(|(locale, _)| {
+            matches!(locale.split('-').next(), Some(language) if language.eq_ignore_ascii_case(tag))
+        })
+    }
+}
+
+/// Language tags of an `Accept-Language` header by decreasing preference, without the tags
+/// refused with `q=0` and the wildcard `*`
+pub fn accepted_languages(value: &str) -> Vec<&str> {
+    let mut languages: Vec<(&str, f32)> = value
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let tag = params.next()?.trim();
+            let weight = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |weight| weight.trim().parse::<f32>().ok())?;
+            if tag.is_empty() || tag == "*" || weight <= 0.0 {
+                None
+            } else {
+                Some((tag, weight))
+            }
+        })
+        .collect();
+    // Stable sort, tags of the same weight keep the order of the header
+    languages.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    languages.into_iter().map(|(tag, _)| tag).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_language() {
+        assert_eq!(
+            accepted_languages("fr-CH, fr;q=0.9, en;q=0.8, de;q=0.7, *;q=0.5"),
+            vec!["fr-CH", "fr", "en", "de"]
+        );
+        assert_eq!(
+            accepted_languages("en;q=0.5, de, it;q=0, es;q=x"),
+            vec!["de", "en"]
+        );
+        assert!(accepted_languages("").is_empty());
+    }
+
+    #[test]
+    fn negotiation() {
+        let mut translations = Translations::new();
+        translations
+            .add("fr", vec![("required", "{field} est obligatoire")])
+            .add("pt-BR", vec![("required", "{field} é obrigatório")])
+            .add("FR", vec![("min", "{field} doit être au moins {min}")]);
+        let locale = |header: &str| translations.negotiate(header).map(|(tag, _)| tag);
+        assert_eq!(locale("fr-CA"), Some("fr"));
+        assert_eq!(locale("de, pt;q=0.5"), Some("pt-BR"));
+        assert_eq!(locale("en, fr;q=0"), None);
+        assert_eq!(translations.negotiate("fr").unwrap().1.len(), 2);
+    }
+}
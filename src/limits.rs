@@ -0,0 +1,354 @@
+//! Limits on the size and content of requests, checked before any validator runs.
+use serde::Serialize;
+use tide::{Request, StatusCode};
+
+use crate::response::Rejection;
+use crate::validators::ValidationError;
+use crate::{HttpField, ValidatorMiddleware};
+
+/// Limits applied to the whole request, every limit is disabled by default.
+/// [`RequestLimits::hardened`](#method.hardened) gives sane defaults to protect your services.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RequestLimits {
+    /// Maximum length of the URL, rejected with `414 URI Too Long`
+    pub max_url_length: Option<usize>,
+    /// Maximum number of query parameters, rejected with `400 Bad Request`
+    pub max_query_params: Option<usize>,
+    /// Maximum size of a single header (name and values), rejected with `431 Request Header Fields Too Large`
+    pub max_header_size: Option<usize>,
+    /// Maximum body size, rejected with `413 Payload Too Large`. Bodies sent without
+    /// `Content-Length` are read by the middleware up to the limit.
+    pub max_body_size: Option<usize>,
+    /// Maximum number of items of a JSON array body, rejected with `413 Payload Too Large`
+    pub max_items: Option<usize>,
+    /// Reject control characters in the URL (percent-encoded or not) and in header values,
+    /// tabs in header values are allowed
+    pub reject_control_characters: bool,
+}
+
+impl RequestLimits {
+    /// Sane defaults: URLs up to 8 KiB, 100 query parameters, headers up to 8 KiB,
+    /// bodies up to 1 MiB and no control characters
+    pub fn hardened() -> Self {
+        RequestLimits {
+            max_url_length: Some(8 * 1024),
+            max_query_params: Some(100),
+            max_header_size: Some(8 * 1024),
+            max_body_size: Some(1024 * 1024),
+            max_items: None,
+            reject_control_characters: true,
+        }
+    }
+
+    /// Error rejecting the request if it exceeds a limit
+    pub(crate) fn check<State>(&self, req: &Request<State>) -> Option<Rejection> {
+        let url = req.uri().as_str();
+        if let Some(max_url_length) = self.max_url_length {
+            if url.len() > max_url_length {
+                return Some(reject(
+                    StatusCode::UriTooLong,
+                    HttpField::custom("url"),
+                    "max_url_length",
+                    format!("URL is longer than {} bytes", max_url_length),
+                    max_url_length,
+                ));
+            }
+        }
+
+        if let Some(max_query_params) = self.max_query_params {
+            let query_params = req
+                .uri()
+                .query()
+                .map(|query| query.split('&').filter(|pair| !pair.is_empty()).count())
+                .unwrap_or(0);
+            if query_params > max_query_params {
+                return Some(reject(
+                    StatusCode::BadRequest,
+                    HttpField::custom("query"),
+                    "max_query_params",
+                    format!("more than {} query parameters", max_query_params),
+                    max_query_params,
+                ));
+            }
+        }
+
+        if let Some(max_header_size) = self.max_header_size {
+            for (name, values) in req.iter() {
+                let size = name.as_str().len()
+                    + values
+                        .iter()
+                        .map(|value| value.as_str().len())
+                        .sum::<usize>();
+                if size > max_header_size {
+                    return Some(reject(
+                        StatusCode::RequestHeaderFieldsTooLarge,
+                        HttpField::header(name.as_str().to_string()),
+                        "max_header_size",
+                        format!("header '{}' is larger than {} bytes", name, max_header_size),
+                        max_header_size,
+                    ));
+                }
+            }
+        }
+
+        if let Some(len) = req.len() {
+            if let Some(rejection) = self.check_body_size(len) {
+                return Some(rejection);
+            }
+        }
+
+        if self.reject_control_characters {
+            if has_encoded_control_character(url) || url.chars().any(char::is_control) {
+                return Some((
+                    StatusCode::BadRequest,
+                    HttpField::custom("url"),
+                    ValidationError::new(
+                        "url",
+                        "control_characters",
+                        "URL contains control characters",
+                    ),
+                ));
+            }
+            for (name, values) in req.iter() {
+                if values
+                    .iter()
+                    .any(|value| value.as_str().chars().any(|c| c.is_control() && c != '\t'))
+                {
+                    return Some((
+                        StatusCode::BadRequest,
+                        HttpField::header(name.as_str().to_string()),
+                        ValidationError::new(
+                            name.as_str(),
+                            "control_characters",
+                            format!("header '{}' contains control characters", name),
+                        ),
+                    ));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Limits of `self` overridden by the limits set in `other`
+    pub(crate) fn merge(self, other: RequestLimits) -> Self {
+        RequestLimits {
+            max_url_length: other.max_url_length.or(self.max_url_length),
+            max_query_params: other.max_query_params.or(self.max_query_params),
+            max_header_size: other.max_header_size.or(self.max_header_size),
+            max_body_size: other.max_body_size.or(self.max_body_size),
+            max_items: other.max_items.or(self.max_items),
+            reject_control_characters: self.reject_control_characters
+                || other.reject_control_characters,
+        }
+    }
+
+    /// Error rejecting the request if its body is larger than `max_body_size`
+    pub(crate) fn check_body_size(&self, size: usize) -> Option<Rejection> {
+        match self.max_body_size {
+            Some(max_body_size) if size > max_body_size => Some(reject(
+                StatusCode::PayloadTooLarge,
+                HttpField::custom("body"),
+                "max_body_size",
+                format!("body is larger than {} bytes", max_body_size),
+                max_body_size,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Error rejecting the request if its JSON body exceeds a limit
+    pub(crate) fn check_body(&self, body: &serde_json::Value) -> Option<Rejection> {
+        match (self.max_items, body) {
+            (Some(max_items), serde_json::Value::Array(items)) if items.len() > max_items => {
+                Some(reject(
+                    StatusCode::PayloadTooLarge,
+                    HttpField::custom("body"),
+                    "max_items",
+                    format!("body has more than {} items", max_items),
+                    max_items,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Error of a limit, with the limit as `max` parameter
+fn reject(
+    status: StatusCode,
+    field: HttpField<'static>,
+    code: &str,
+    message: String,
+    max: usize,
+) -> Rejection {
+    let err = ValidationError::new(field.name(), code, message).with_param("max", max);
+    (status, field, err)
+}
+
+/// Look for `%00` to `%1F` and `%7F`
+fn has_encoded_control_character(url: &str) -> bool {
+    url.as_bytes().windows(3).any(|window| {
+        window[0] == b'%'
+            && matches!(
+                (window[1], window[2].to_ascii_uppercase()),
+                (b'0' | b'1', b'0'..=b'9' | b'A'..=b'F') | (b'7', b'F')
+            )
+    })
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Create a middleware protecting against oversized or malformed requests with the
+    /// [`RequestLimits::hardened`](limits/struct.RequestLimits.html#method.hardened) defaults,
+    /// each limit can be overridden afterwards
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::hardened()
+    ///     .max_body_size(10 * 1024 * 1024)
+    ///     .max_query_params(20);
+    /// ```
+    pub fn hardened() -> Self {
+        Self::new().request_limits(RequestLimits::hardened())
+    }
+
+    /// Set every limit applied to the whole request before validators run
+    pub fn request_limits(mut self, limits: RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Reject requests whose URL is longer than `max_length` bytes
+    pub fn max_url_length(mut self, max_length: usize) -> Self {
+        self.limits.max_url_length = Some(max_length);
+        self
+    }
+
+    /// Reject requests with more than `max_params` query parameters
+    pub fn max_query_params(mut self, max_params: usize) -> Self {
+        self.limits.max_query_params = Some(max_params);
+        self
+    }
+
+    /// Reject requests with a header (name and values) larger than `max_size` bytes
+    pub fn max_header_size(mut self, max_size: usize) -> Self {
+        self.limits.max_header_size = Some(max_size);
+        self
+    }
+
+    /// Reject requests whose body is larger than `max_size` bytes with `413 Payload Too Large`,
+    /// as announced by `Content-Length` or once read for streamed bodies
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// // Uploads get 10 MiB, the rest of the API 64 KiB
+    /// app.at("/uploads").middleware(ValidatorMiddleware::new().max_body_size(10 * 1024 * 1024)).post(upload);
+    /// app.at("/users").middleware(ValidatorMiddleware::new().max_body_size(64 * 1024)).post(create_user);
+    /// ```
+    pub fn max_body_size(mut self, max_size: usize) -> Self {
+        self.limits.max_body_size = Some(max_size);
+        self
+    }
+
+    /// Reject JSON array bodies with more than `max_items` items
+    pub fn max_items(mut self, max_items: usize) -> Self {
+        self.limits.max_items = Some(max_items);
+        self
+    }
+
+    /// Reject control characters in the URL and in header values
+    pub fn reject_control_characters(mut self, reject: bool) -> Self {
+        self.limits.reject_control_characters = reject;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResponseFormat;
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request};
+
+    #[test]
+    fn encoded_control_characters() {
+        assert!(has_encoded_control_character("/foo?bar=%00"));
+        assert!(has_encoded_control_character("/foo?bar=a%0d%0aSet-Cookie"));
+        assert!(has_encoded_control_character("/foo?bar=%7f"));
+        assert!(!has_encoded_control_character("/foo?bar=%20%2F"));
+    }
+
+    #[test]
+    fn max_body_size() {
+        let limits = RequestLimits {
+            max_body_size: Some(4),
+            ..RequestLimits::default()
+        };
+        assert!(limits.check_body_size(4).is_none());
+        let (status, field, err) = limits.check_body_size(5).unwrap();
+        assert_eq!(status, StatusCode::PayloadTooLarge);
+        assert_eq!(field, HttpField::custom("body"));
+        assert_eq!(err.code, "max_body_size");
+        assert_eq!(err.param("max"), Some("4"));
+        assert!(RequestLimits::default()
+            .check_body_size(usize::MAX)
+            .is_none());
+    }
+
+    #[test]
+    fn max_items() {
+        let limits = RequestLimits {
+            max_items: Some(2),
+            ..RequestLimits::default()
+        };
+        assert!(limits.check_body(&serde_json::json!([1, 2])).is_none());
+        assert!(limits
+            .check_body(&serde_json::json!({"a": 1, "b": 2, "c": 3}))
+            .is_none());
+        let (status, _, err) = limits.check_body(&serde_json::json!([1, 2, 3])).unwrap();
+        assert_eq!(status, StatusCode::PayloadTooLarge);
+        assert_eq!(err.message, "body has more than 2 items");
+    }
+
+    #[test]
+    fn validator_streamed_body_size() {
+        let mut inner = tide::new();
+        inner
+            .at("/foo")
+            .middleware(
+                ValidatorMiddleware::<ValidationError, ()>::new()
+                    .max_body_size(16)
+                    .response_format(ResponseFormat::Text),
+            )
+            .post(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (body, status, message) in &[
+            ("0123456789", StatusCode::Ok, "foo"),
+            (
+                "0123456789abcdefghij",
+                StatusCode::PayloadTooLarge,
+                "body is larger than 16 bytes",
+            ),
+        ] {
+            let mut buf = Vec::new();
+            let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+            // Without a length the body is sent without `Content-Length`
+            let reader = async_std::io::Cursor::new(body.as_bytes().to_vec());
+            req.set_body(tide::http::Body::from_reader(reader, None));
+            let mut res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+            block_on(res.read_to_end(&mut buf)).unwrap();
+            assert!(String::from_utf8_lossy(&buf).starts_with(message));
+        }
+    }
+}
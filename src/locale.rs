@@ -0,0 +1,438 @@
+//! Validators of locale-sensitive inputs, found in query parameters and headers of most APIs.
+//!
+//! - `is_language_tag`: BCP 47 language tag, e.g. `en-GB` or `zh-Hant-TW`
+//! - `is_accept_language`: `Accept-Language` header, e.g. `fr-CH, fr;q=0.9, *;q=0.5`
+//! - `is_currency_code`: ISO 4217 currency code, e.g. `EUR`
+//! - `is_country_code`: ISO 3166-1 alpha-2 country code, e.g. `FR`
+//! - `is_timezone`: IANA time zone name, e.g. `Europe/Paris`, with the `datetime` feature
+//!
+//! Currency and country codes are upper case, as written by the standards.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::locale::{is_accept_language, is_currency_code, is_language_tag};
+//!
+//! validator_middleware.add_validator(HttpField::query_param("lang"), is_language_tag);
+//! validator_middleware.add_validator(HttpField::header("Accept-Language"), is_accept_language);
+//! validator_middleware.add_validator(HttpField::body_field("price.currency"), is_currency_code);
+//! ```
+use crate::validators::ValidationError;
+
+/// Tags registered before RFC 4646 which don't follow the syntax of language tags
+const IRREGULAR_TAGS: &[&str] = &[
+    "en-GB-oed",
+    "i-ami",
+    "i-bnn",
+    "i-default",
+    "i-enochian",
+    "i-hak",
+    "i-klingon",
+    "i-lux",
+    "i-mingo",
+    "i-navajo",
+    "i-pwn",
+    "i-tao",
+    "i-tay",
+    "i-tsu",
+    "sgn-BE-FR",
+    "sgn-BE-NL",
+    "sgn-CH-DE",
+];
+
+/// Active ISO 4217 codes, sorted, without `XTS` reserved for tests and `XXX` for transactions
+/// without currency
+const CURRENCIES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD",
+    "CAD", "CDF", "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUC", "CUP",
+    "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP",
+    "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS",
+    "INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW",
+    "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD",
+    "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MXV", "MYR", "MZN", "NAD", "NGN",
+    "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR",
+    "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SLL",
+    "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY",
+    "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS", "VED", "VES",
+    "VND", "VUV", "WST", "XAF", "XAG", "XAU", "XBA", "XBB", "XBC", "XBD", "XCD", "XCG", "XDR",
+    "XOF", "XPD", "XPF", "XPT", "XSU", "XUA", "YER", "ZAR", "ZMW", "ZWG", "ZWL",
+];
+
+/// Officially assigned ISO 3166-1 alpha-2 codes, sorted
+const COUNTRIES: &[&str] = &[
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS",
+    "BT", "BV", "BW", "BY", "BZ", "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN",
+    "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ", "DE", "DJ", "DK", "DM", "DO", "DZ", "EC", "EE",
+    "EG", "EH", "ER", "ES", "ET", "FI", "FJ", "FK", "FM", "FO", "FR", "GA", "GB", "GD", "GE", "GF",
+    "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY", "HK", "HM",
+    "HN", "HR", "HT", "HU", "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT", "JE", "JM",
+    "JO", "JP", "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ", "LA", "LB", "LC",
+    "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY", "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK",
+    "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA",
+    "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ", "OM", "PA", "PE", "PF", "PG",
+    "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY", "QA", "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS",
+    "ST", "SV", "SX", "SY", "SZ", "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO",
+    "TR", "TT", "TV", "TW", "TZ", "UA", "UG", "UM", "US", "UY", "UZ", "VA", "VC", "VE", "VG", "VI",
+    "VN", "VU", "WF", "WS", "YE", "YT", "ZA", "ZM", "ZW",
+];
+
+/// Check if `tag` is a well-formed BCP 47 language tag (RFC 5646): a language, then optional
+/// script, region, variants, extensions and private use subtags. Subtags are compared
+/// case-insensitively and variants or extensions can't be repeated.
+pub fn is_well_formed_language_tag(tag: &str) -> bool {
+    if IRREGULAR_TAGS
+        .iter()
+        .any(|irregular| irregular.eq_ignore_ascii_case(tag))
+    {
+        return true;
+    }
+    let subtags: Vec<&str> = tag.split('-').collect();
+    if subtags.iter().any(|subtag| {
+        !(1..=8).contains(&subtag.len()) || !subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    }) {
+        return false;
+    }
+    let alpha = |subtag: &str| subtag.chars().all(|c| c.is_ascii_alphabetic());
+    let digit = |subtag: &str| subtag.chars().all(|c| c.is_ascii_digit());
+    let private_use = |rest: &[&str]| !rest.is_empty();
+
+    let (language, mut rest) = match subtags.split_first() {
+        Some(first) => first,
+        None => return false,
+    };
+    if language.eq_ignore_ascii_case("x") {
+        return private_use(rest);
+    }
+    if language.len() < 2 || !alpha(language) {
+        return false;
+    }
+    if language.len() <= 3 {
+        let extlangs = rest
+            .iter()
+            .take(3)
+            .take_while(|subtag| subtag.len() == 3 && alpha(subtag))
+            .count();
+        rest = &rest[extlangs..];
+    }
+    if let Some(script) = rest.first() {
+        if script.len() == 4 && alpha(script) {
+            rest = &rest[1..];
+        }
+    }
+    if let Some(region) = rest.first() {
+        if (region.len() == 2 && alpha(region)) || (region.len() == 3 && digit(region)) {
+            rest = &rest[1..];
+        }
+    }
+    let mut variants: Vec<String> = Vec::new();
+    while let Some(variant) = rest.first() {
+        let is_variant = variant.len() >= 5
+            || (variant.len() == 4 && variant.starts_with(|c: char| c.is_ascii_digit()));
+        if !is_variant {
+            break;
+        }
+        let variant = variant.to_ascii_lowercase();
+        if variants.contains(&variant) {
+            return false;
+        }
+        variants.push(variant);
+        rest = &rest[1..];
+    }
+    let mut singletons: Vec<String> = Vec::new();
+    while let Some(singleton) = rest.first() {
+        if singleton.len() != 1 {
+            return false;
+        }
+        if singleton.eq_ignore_ascii_case("x") {
+            return private_use(&rest[1..]);
+        }
+        let singleton = singleton.to_ascii_lowercase();
+        if singletons.contains(&singleton) {
+            return false;
+        }
+        singletons.push(singleton);
+        rest = &rest[1..];
+        let extension = rest.iter().take_while(|subtag| subtag.len() >= 2).count();
+        if extension == 0 {
+            return false;
+        }
+        rest = &rest[extension..];
+    }
+    true
+}
+
+/// Check if the field is a well-formed BCP 47 language tag like `en-GB`, see
+/// [`is_well_formed_language_tag`](fn.is_well_formed_language_tag.html). Tags written with
+/// underscores like `en_GB` are rejected.
+pub fn is_language_tag(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if !is_well_formed_language_tag(value) => Err(ValidationError::new(
+            field_name,
+            "language_tag",
+            format!(
+                "field '{}' = '{}' is not a valid language tag",
+                field_name, value
+            ),
+        )
+        .with_param("example", "en-GB")),
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is a valid `Accept-Language` header (RFC 9110): language ranges like
+/// `fr-CH` or `*` separated by commas, each with an optional weight `;q=` between 0 and 1 with at
+/// most 3 decimals
+pub fn is_accept_language(
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<(), ValidationError> {
+    let value = match field_value {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    let is_range = |range: &str| {
+        let mut params = range.split(';').map(str::trim);
+        let tag = params.next().unwrap_or_default();
+        let is_tag = tag == "*"
+            || tag.split('-').enumerate().all(|(i, subtag)| {
+                (1..=8).contains(&subtag.len())
+                    && if i == 0 {
+                        subtag.chars().all(|c| c.is_ascii_alphabetic())
+                    } else {
+                        subtag.chars().all(|c| c.is_ascii_alphanumeric())
+                    }
+            });
+        let weights: Vec<&str> = params.collect();
+        is_tag
+            && match weights[..] {
+                [] => true,
+                [weight] => matches!(
+                    weight.strip_prefix("q=").or_else(|| weight.strip_prefix("Q=")),
+                    Some(qvalue) if is_qvalue(qvalue)
+                ),
+                _ => false,
+            }
+    };
+    let ranges: Vec<&str> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+        .collect();
+    if ranges.is_empty() || !ranges.iter().all(|range| is_range(range)) {
+        return Err(ValidationError::new(
+            field_name,
+            "accept_language",
+            format!(
+                "field '{}' = '{}' is not a valid list of languages",
+                field_name, value
+            ),
+        )
+        .with_param("example", "fr-CH, fr;q=0.9, *;q=0.5"));
+    }
+    Ok(())
+}
+
+/// Weight of a language range: `0` to `1` with at most 3 decimals
+fn is_qvalue(weight: &str) -> bool {
+    let (integer, decimals) = match weight.find('.') {
+        Some(dot) => (&weight[..dot], &weight[dot + 1..]),
+        None => (weight, ""),
+    };
+    decimals.len() <= 3
+        && decimals.chars().all(|c| c.is_ascii_digit())
+        && match integer {
+            "0" => true,
+            "1" => decimals.chars().all(|c| c == '0'),
+            _ => false,
+        }
+}
+
+/// Check if the field is an active ISO 4217 currency code in upper case, e.g. `EUR` or `JPY`
+pub fn is_currency_code(
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if CURRENCIES.binary_search(&value).is_err() => Err(ValidationError::new(
+            field_name,
+            "currency",
+            format!(
+                "field '{}' = '{}' is not a valid ISO 4217 currency code",
+                field_name, value
+            ),
+        )
+        .with_param("example", "EUR")),
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is an ISO 3166-1 alpha-2 country code in upper case, e.g. `FR` or `GB`.
+/// Reserved codes like `UK` or `EU` aren't countries.
+pub fn is_country_code(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if COUNTRIES.binary_search(&value).is_err() => Err(ValidationError::new(
+            field_name,
+            "country",
+            format!(
+                "field '{}' = '{}' is not a valid ISO 3166-1 country code",
+                field_name, value
+            ),
+        )
+        .with_param("example", "FR")),
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is the name of a time zone of the IANA database, e.g. `Europe/Paris` or
+/// `UTC`. Names are case-sensitive and the old names kept as links, like `US/Eastern`, are
+/// accepted. Available with the `datetime` feature.
+#[cfg(feature = "datetime")]
+pub fn is_timezone(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if value.parse::<chrono_tz::Tz>().is_err() => Err(ValidationError::new(
+            field_name,
+            "timezone",
+            format!(
+                "field '{}' = '{}' is not a valid time zone",
+                field_name, value
+            ),
+        )
+        .with_param("example", "Europe/Paris")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_tags() {
+        for tag in &[
+            "en",
+            "en-GB",
+            "zh-Hant-TW",
+            "es-419",
+            "sr-Latn-RS",
+            "zh-yue-HK",
+            "de-CH-1901",
+            "sl-rozaj-biske",
+            "en-US-u-ca-gregory",
+            "x-whatever",
+            "en-x-private",
+            "i-klingon",
+        ] {
+            assert!(is_language_tag("lang", Some(tag)).is_ok(), "{}", tag);
+        }
+        for tag in &[
+            "",
+            "e",
+            "en_GB",
+            "en-",
+            "1en",
+            "en-GB-",
+            "de-1901-1901",
+            "en-u",
+            "en-a-bbb-a-ccc",
+            "en-verylongsubtag",
+            "x",
+        ] {
+            assert_eq!(
+                is_language_tag("lang", Some(tag)).unwrap_err().code,
+                "language_tag",
+                "{}",
+                tag
+            );
+        }
+        assert!(is_language_tag("lang", None).is_ok());
+    }
+
+    #[test]
+    fn accept_language() {
+        for value in &[
+            "fr",
+            "fr-CH, fr;q=0.9, en;q=0.8, de;q=0.7, *;q=0.5",
+            "en-US,en;q=0.5",
+            "da, en-gb;q=0.8, en;q=0.7",
+            "*",
+            "en;q=1.000",
+            "en;q=0",
+        ] {
+            assert!(
+                is_accept_language("Accept-Language", Some(value)).is_ok(),
+                "{}",
+                value
+            );
+        }
+        for value in &[
+            "",
+            ",",
+            "en;q=2",
+            "en;q=0.1234",
+            "en;q=1.5",
+            "en;level=1",
+            "en_US",
+        ] {
+            assert_eq!(
+                is_accept_language("Accept-Language", Some(value))
+                    .unwrap_err()
+                    .code,
+                "accept_language",
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn currencies_and_countries() {
+        assert!(CURRENCIES.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(COUNTRIES.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(COUNTRIES.len(), 249);
+
+        assert!(is_currency_code("currency", Some("EUR")).is_ok());
+        assert!(is_currency_code("currency", Some("JPY")).is_ok());
+        assert!(is_currency_code("currency", None).is_ok());
+        for value in &["eur", "EURO", "XXX", "FRF", ""] {
+            assert_eq!(
+                is_currency_code("currency", Some(value)).unwrap_err().code,
+                "currency",
+                "{}",
+                value
+            );
+        }
+
+        assert!(is_country_code("country", Some("FR")).is_ok());
+        assert!(is_country_code("country", Some("GB")).is_ok());
+        for value in &["fr", "UK", "EU", "FRA", ""] {
+            assert_eq!(
+                is_country_code("country", Some(value)).unwrap_err().code,
+                "country",
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[cfg(feature = "datetime")]
+    #[test]
+    fn timezones() {
+        for value in &[
+            "Europe/Paris",
+            "America/Argentina/Buenos_Aires",
+            "UTC",
+            "US/Eastern",
+        ] {
+            assert!(is_timezone("tz", Some(value)).is_ok(), "{}", value);
+        }
+        for value in &["europe/paris", "Europe/Lyon", "GMT+25", ""] {
+            assert_eq!(
+                is_timezone("tz", Some(value)).unwrap_err().code,
+                "timezone",
+                "{}",
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,47 @@
+/// Declare the validators of a route in a block expanded into a static rule table. Field names are
+/// `&'static str` and validators plain functions, so services defining hundreds of routes in code
+/// don't box anything at startup. The error type comes first, followed by each field and its validators.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// use tide_validator::validators::{is_integer, is_required, ValidationError};
+///
+/// let validator_middleware = validators! {
+///     ValidationError;
+///     Param("id") => [is_integer],
+///     QueryParam("page") => [is_required, is_integer],
+///     Header("X-Request-Id") => [is_required],
+/// };
+/// app.at("/items/:id").middleware(validator_middleware).get(endpoint);
+/// ```
+#[macro_export]
+macro_rules! validators {
+    ($error:ty; $($kind:ident($($name:expr),+) => [$($validator:expr),* $(,)?]),* $(,)?) => {{
+        static RULES: &[$crate::StaticRules<$error>] = &[
+            $(($crate::HttpField::$kind($(::std::borrow::Cow::Borrowed($name)),+), &[$($validator),*])),*
+        ];
+        $crate::ValidatorMiddleware::from_static_rules(RULES)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::validators::{is_integer, is_required, ValidationError};
+    use crate::{HttpField, ValidatorMiddleware};
+
+    #[test]
+    fn static_rules() {
+        let middleware: ValidatorMiddleware<ValidationError> = validators! {
+            ValidationError;
+            Param("id") => [is_integer],
+            QueryParam("page") => [is_required, is_integer],
+            MatrixParam("items", "limit") => [is_integer],
+        };
+        assert_eq!(middleware.validators.len(), 3);
+        assert_eq!(
+            middleware.validators[&HttpField::query_param("page")].len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Validation of `multipart/form-data` bodies, e.g. file uploads.
+//!
+//! Fields of the body are validated with [`HttpField::Multipart`](../enum.HttpField.html#variant.Multipart):
+//! validators added with `add_validator` receive the text of text fields and the filename of files,
+//! validators added with [`add_file_validator`](../struct.ValidatorMiddleware.html#method.add_file_validator)
+//! receive the whole [`Part`](struct.Part.html) to check the metadata of files.
+//!
+//! Available validators: `is_file`, `is_content_type`, `has_extension` and `is_max_size`.
+//!
+//! The whole body is read in memory before its parts are validated, files included: set a
+//! [`max_body_size`](../struct.ValidatorMiddleware.html#method.max_body_size) on routes accepting
+//! uploads so that a client can't make the middleware buffer an unbounded body. A body which
+//! isn't valid `multipart/form-data` is rejected with `400 Bad Request` and a `multipart` error
+//! on the `body` field.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::multipart::{has_extension, is_file, is_max_size};
+//!
+//! let mut validator_middleware = ValidatorMiddleware::new().max_body_size(10 * 1024 * 1024);
+//! validator_middleware.add_validator(HttpField::multipart("title"), is_required);
+//! validator_middleware.add_file_validator(HttpField::multipart("avatar"), is_file);
+//! validator_middleware.add_file_validator(HttpField::multipart("avatar"), has_extension(&["png", "jpg"]));
+//! validator_middleware.add_file_validator(HttpField::multipart("avatar"), is_max_size(2 * 1024 * 1024));
+//! ```
+use crate::validators::ValidationError;
+
+/// Validator of a part of a multipart body, `None` when the part is missing
+pub type FileValidator =
+    Box<dyn Fn(&str, Option<&Part<'_>>) -> Result<(), ValidationError> + Send + Sync + 'static>;
+
+/// Part of a `multipart/form-data` body, borrowed from the body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Part<'a> {
+    /// Name of the form field
+    pub name: &'a str,
+    /// Name of the uploaded file, `None` for text fields
+    pub filename: Option<&'a str>,
+    /// Media type given by the client, `None` when it's missing
+    pub content_type: Option<&'a str>,
+    /// Content of the part
+    pub data: &'a [u8],
+}
+
+impl<'a> Part<'a> {
+    /// Text of a text field or filename of a file, as given to validators added with `add_validator`
+    pub fn value(&self) -> Option<&'a str> {
+        match self.filename {
+            Some(filename) => Some(filename),
+            None => std::str::from_utf8(self.data).ok(),
+        }
+    }
+}
+
+/// Parse a `multipart/form-data` body with the boundary given by `content_type`, `None` when
+/// the body isn't multipart or is malformed
+pub fn parse<'a>(content_type: &str, body: &'a [u8]) -> Option<Vec<Part<'a>>> {
+    let mut params = content_type.split(';');
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    let boundary = params.find_map(|param| {
+        let mut name_value = param.splitn(2, '=');
+        match (name_value.next(), name_value.next()) {
+            (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("boundary") => {
+                Some(value.trim().trim_matches('"'))
+            }
+            _ => None,
+        }
+    })?;
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let separator = format!("\r\n--{}", boundary).into_bytes();
+
+    let mut rest = &body[find(body, &delimiter)? + delimiter.len()..];
+    let mut parts = Vec::new();
+    loop {
+        if rest.starts_with(b"--") {
+            return Some(parts);
+        }
+        rest = rest.strip_prefix(b"\r\n")?;
+        let end = find(rest, &separator)?;
+        parts.push(parse_part(&rest[..end])?);
+        rest = &rest[end + separator.len()..];
+    }
+}
+
+fn parse_part(part: &[u8]) -> Option<Part<'_>> {
+    let headers_end = find(part, b"\r\n\r\n")?;
+    let headers = std::str::from_utf8(&part[..headers_end]).ok()?;
+    let (mut name, mut filename, mut content_type) = (None, None, None);
+    for header in headers.split("\r\n") {
+        let mut name_value = header.splitn(2, ':');
+        let (header_name, value) = (name_value.next()?.trim(), name_value.next()?.trim());
+        if header_name.eq_ignore_ascii_case("Content-Disposition") {
+            for param in value.split(';').skip(1) {
+                let mut param = param.splitn(2, '=');
+                let (param_name, param_value) = (param.next()?.trim(), param.next()?.trim());
+                let param_value = param_value.trim_matches('"');
+                match param_name {
+                    "name" => name = Some(param_value),
+                    "filename" => filename = Some(param_value),
+                    _ => {}
+                }
+            }
+        } else if header_name.eq_ignore_ascii_case("Content-Type") {
+            content_type = Some(value);
+        }
+    }
+    Some(Part {
+        name: name?,
+        filename,
+        content_type,
+        data: &part[headers_end + 4..],
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Force the field to be an uploaded file
+pub fn is_file(field_name: &str, part: Option<&Part<'_>>) -> Result<(), ValidationError> {
+    match part {
+        Some(Part {
+            filename: Some(_), ..
+        }) => Ok(()),
+        _ => Err(ValidationError::new(
+            field_name,
+            "file",
+            format!("'{}' must be a file", field_name),
+        )),
+    }
+}
+
+/// Check if the media type of the file is one of the allowed ones, e.g. `image/png`
+pub fn is_content_type(allowed: &[&str]) -> FileValidator {
+    let allowed: Vec<String> = allowed.iter().map(|value| value.to_string()).collect();
+    Box::new(move |field_name: &str, part: Option<&Part<'_>>| {
+        let content_type = match part {
+            Some(Part {
+                filename: Some(_),
+                content_type,
+                ..
+            }) => content_type.unwrap_or_default(),
+            _ => return Ok(()),
+        };
+        let media_type = content_type.split(';').next().unwrap_or_default().trim();
+        if allowed
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(media_type))
+        {
+            Ok(())
+        } else {
+            Err(ValidationError::new(
+                field_name,
+                "content_type",
+                format!(
+                    "file '{}' of type '{}' must be one of: {}",
+                    field_name,
+                    content_type,
+                    allowed.join(", ")
+                ),
+            )
+            .with_param("allowed", allowed.join(", ")))
+        }
+    })
+}
+
+/// Check if the filename of the file ends with one of the allowed extensions, given without dot
+pub fn has_extension(allowed: &[&str]) -> FileValidator {
+    let allowed: Vec<String> = allowed.iter().map(|value| value.to_string()).collect();
+    Box::new(move |field_name: &str, part: Option<&Part<'_>>| {
+        let filename = match part.and_then(|part| part.filename) {
+            Some(filename) => filename,
+            None => return Ok(()),
+        };
+        let extension = match filename.rfind('.') {
+            Some(dot) => &filename[dot + 1..],
+            None => "",
+        };
+        if allowed
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(extension))
+        {
+            Ok(())
+        } else {
+            Err(ValidationError::new(
+                field_name,
+                "extension",
+                format!(
+                    "file '{}' = '{}' must have one of the extensions: {}",
+                    field_name,
+                    filename,
+                    allowed.join(", ")
+                ),
+            )
+            .with_param("allowed", allowed.join(", ")))
+        }
+    })
+}
+
+/// Check if the part is at most `max_size` bytes
+pub fn is_max_size(max_size: usize) -> FileValidator {
+    Box::new(
+        move |field_name: &str, part: Option<&Part<'_>>| match part {
+            Some(part) if part.data.len() > max_size => Err(ValidationError::new(
+                field_name,
+                "file_size",
+                format!("file '{}' is larger than {} bytes", field_name, max_size),
+            )
+            .with_param("max", max_size)),
+            _ => Ok(()),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"--XyZ\r\n\
+Content-Disposition: form-data; name=\"title\"\r\n\
+\r\n\
+Holidays\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"avatar\"; filename=\"cat.png\"\r\n\
+Content-Type: image/png\r\n\
+\r\n\
+\x89PNG\r\n\
+--XyZ--\r\n";
+
+    #[test]
+    fn parts() {
+        let parts = parse("multipart/form-data; boundary=XyZ", BODY).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "title");
+        assert_eq!(parts[0].value(), Some("Holidays"));
+        assert_eq!(parts[1].filename, Some("cat.png"));
+        assert_eq!(parts[1].content_type, Some("image/png"));
+        assert_eq!(parts[1].data, b"\x89PNG");
+
+        assert!(parse("application/json", BODY).is_none());
+        assert!(parse("multipart/form-data; boundary=other", BODY).is_none());
+    }
+
+    #[test]
+    fn file_checks() {
+        let parts = parse("multipart/form-data; boundary=\"XyZ\"", BODY).unwrap();
+        assert!(is_file("avatar", Some(&parts[1])).is_ok());
+        assert!(is_file("title", Some(&parts[0])).is_err());
+        assert!(is_content_type(&["image/png"])("avatar", Some(&parts[1])).is_ok());
+        assert!(is_content_type(&["image/jpeg"])("avatar", Some(&parts[1])).is_err());
+        assert!(has_extension(&["PNG"])("avatar", Some(&parts[1])).is_ok());
+        assert!(has_extension(&["jpg"])("avatar", Some(&parts[1])).is_err());
+        assert!(is_max_size(4)("avatar", Some(&parts[1])).is_ok());
+        assert!(is_max_size(3)("avatar", Some(&parts[1])).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+//! Validation of the address of clients, for internal-only endpoints.
+//!
+//! The address of the peer is validated with
+//! [`HttpField::PeerAddr`](../enum.HttpField.html#variant.PeerAddr). Behind reverse proxies the
+//! peer is the proxy, the address of the client is the first hop of the `X-Forwarded-For` or
+//! `Forwarded` header which can only be trusted when every proxy after it is yours.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::network::{is_forwarded_by, is_ip_in, Cidr};
+//!
+//! let proxies: Vec<Cidr> = vec!["10.0.0.0/8".parse()?];
+//! let office: Vec<Cidr> = vec!["192.0.2.0/24".parse()?, "2001:db8::/32".parse()?];
+//! // Requests come through our proxies
+//! validator_middleware.add_validator(HttpField::peer_addr(), is_ip_in(&proxies));
+//! // which add the client to the chain, other hops are our proxies
+//! validator_middleware.add_validator(HttpField::header("X-Forwarded-For"), is_forwarded_by(&proxies));
+//! validator_middleware.add_validator(HttpField::header("X-Forwarded-For"), is_client_in(&office, &proxies));
+//! ```
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::validators::ValidationError;
+use crate::{BoxedValidator, ConfigError};
+
+/// Range of IP addresses in CIDR notation, e.g. `10.0.0.0/8` or `2001:db8::/32`. A single address
+/// is a range of one address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Check if `ip` is in the range, IPv4-mapped IPv6 addresses are compared as IPv4 addresses
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, unmap(*ip)) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => mask(
+                u32::from(range).into(),
+                u32::from(ip).into(),
+                32,
+                self.prefix,
+            ),
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                mask(u128::from(range), u128::from(ip), 128, self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConfigError::InvalidCidr(value.to_string());
+        let mut parts = value.trim().splitn(2, '/');
+        let addr = unmap(
+            parts
+                .next()
+                .unwrap_or_default()
+                .parse()
+                .map_err(|_| invalid())?,
+        );
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match parts.next() {
+            Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+fn mask(range: u128, ip: u128, bits: u8, prefix: u8) -> bool {
+    let shift = bits - prefix;
+    shift == bits || (range >> shift) == (ip >> shift)
+}
+
+/// IPv4 address of an IPv4-mapped IPv6 address, e.g. `::ffff:10.0.0.1`
+fn unmap(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) if v6.segments()[..6] == [0, 0, 0, 0, 0, 0xffff] => {
+            let [.., a, b, c, d] = v6.octets();
+            IpAddr::from([a, b, c, d])
+        }
+        ip => ip,
+    }
+}
+
+/// IP of an address with an optional port, e.g. `192.0.2.1`, `192.0.2.1:4711`, `2001:db8::1`
+/// or `[2001:db8::1]:4711`, quotes of `Forwarded` values are removed
+pub fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Ok(ip) = value.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = value.strip_prefix('[') {
+        return rest[..rest.find(']')?].parse().ok();
+    }
+    let colon = value.rfind(':')?;
+    value[..colon]
+        .parse()
+        .ok()
+        .filter(|ip: &IpAddr| ip.is_ipv4())
+}
+
+/// Hops of an `X-Forwarded-For` header from the client to the last proxy, e.g.
+/// `["203.0.113.7", "10.0.0.1"]` for `203.0.113.7, 10.0.0.1`
+pub fn x_forwarded_for(value: &str) -> Vec<&str> {
+    value.split(',').map(str::trim).collect()
+}
+
+/// `for` hops of a `Forwarded` header ([RFC 7239](https://tools.ietf.org/html/rfc7239)) from the
+/// client to the last proxy, e.g. `["192.0.2.60", "\"[2001:db8::1]\""]` for
+/// `for=192.0.2.60;proto=http, for="[2001:db8::1]"`. Hops without `for` are empty strings.
+pub fn forwarded(value: &str) -> Vec<&str> {
+    value
+        .split(',')
+        .map(|hop| {
+            hop.split(';')
+                .find_map(|pair| {
+                    let mut pair = pair.splitn(2, '=');
+                    match (pair.next(), pair.next()) {
+                        (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("for") => {
+                            Some(value.trim())
+                        }
+                        _ => None,
+                    }
+                })
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Hops of a forwarding header in either format
+fn hops(value: &str) -> Vec<&str> {
+    if value.to_ascii_lowercase().contains("for=") {
+        forwarded(value)
+    } else {
+        x_forwarded_for(value)
+    }
+}
+
+/// Address of the client of a forwarding chain: the last hop which isn't one of the
+/// `trusted_proxies`, earlier hops may have been written by the client. `None` when a hop
+/// isn't an IP address.
+pub fn client_ip(hops: &[&str], trusted_proxies: &[Cidr]) -> Option<IpAddr> {
+    let mut client = None;
+    for hop in hops.iter().rev() {
+        let ip = parse_ip(hop)?;
+        client = Some(ip);
+        if !trusted_proxies.iter().any(|range| range.contains(&ip)) {
+            break;
+        }
+    }
+    client
+}
+
+/// Check if the field is an address (with an optional port) in one of the `ranges`, e.g. the
+/// peer address or an `X-Real-IP` header
+pub fn is_ip_in(ranges: &[Cidr]) -> BoxedValidator<ValidationError> {
+    let ranges = ranges.to_vec();
+    Box::new(move |field_name: &str, field_value: Option<&str>| {
+        let value = match field_value {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        match parse_ip(value) {
+            Some(ip) if ranges.iter().any(|range| range.contains(&ip)) => Ok(()),
+            _ => Err(ValidationError::new(
+                field_name,
+                "ip_range",
+                format!("'{}' = '{}' isn't an allowed address", field_name, value),
+            )),
+        }
+    })
+}
+
+/// Check if the field is a well-formed `X-Forwarded-For` or `Forwarded` chain whose hops after
+/// the client are all `trusted_proxies`, rejecting chains made up by clients
+pub fn is_forwarded_by(trusted_proxies: &[Cidr]) -> BoxedValidator<ValidationError> {
+    let trusted_proxies = trusted_proxies.to_vec();
+    Box::new(move |field_name: &str, field_value: Option<&str>| {
+        let value = match field_value {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        let hops = hops(value);
+        let mut ips = hops.iter().map(|hop| parse_ip(hop));
+        let client = ips.next().flatten();
+        let proxies: Option<Vec<IpAddr>> = ips.collect();
+        match (client, proxies) {
+            (Some(_), Some(proxies))
+                if proxies
+                    .iter()
+                    .all(|ip| trusted_proxies.iter().any(|range| range.contains(ip))) =>
+            {
+                Ok(())
+            }
+            _ => Err(ValidationError::new(
+                field_name,
+                "forwarded",
+                format!("'{}' isn't a chain of trusted proxies", field_name),
+            )),
+        }
+    })
+}
+
+/// Check if the client of an `X-Forwarded-For` or `Forwarded` chain, found with
+/// [`client_ip`](fn.client_ip.html), is in one of the `ranges`
+pub fn is_client_in(ranges: &[Cidr], trusted_proxies: &[Cidr]) -> BoxedValidator<ValidationError> {
+    let ranges = ranges.to_vec();
+    let trusted_proxies = trusted_proxies.to_vec();
+    Box::new(move |field_name: &str, field_value: Option<&str>| {
+        let value = match field_value {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        match client_ip(&hops(value), &trusted_proxies) {
+            Some(ip) if ranges.iter().any(|range| range.contains(&ip)) => Ok(()),
+            _ => Err(ValidationError::new(
+                field_name,
+                "ip_range",
+                format!("'{}' doesn't come from an allowed address", field_name),
+            )),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidrs(ranges: &[&str]) -> Vec<Cidr> {
+        ranges.iter().map(|range| range.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn ranges() {
+        let private = cidrs(&["10.0.0.0/8", "2001:db8::/32", "192.0.2.1", "0.0.0.0/0"]);
+        let ip = |value: &str| parse_ip(value).unwrap();
+        assert!(private[0].contains(&ip("10.1.2.3")));
+        assert!(!private[0].contains(&ip("11.1.2.3")));
+        assert!(private[0].contains(&ip("[::ffff:10.0.0.1]:80")));
+        assert!(private[1].contains(&ip("[2001:db8::1]:4711")));
+        assert!(!private[1].contains(&ip("10.1.2.3")));
+        assert!(private[2].contains(&ip("192.0.2.1:443")));
+        assert!(!private[2].contains(&ip("192.0.2.2")));
+        assert!(private[3].contains(&ip("203.0.113.7")));
+        assert_eq!(private[0].to_string(), "10.0.0.0/8");
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+        assert_eq!(parse_ip("2001:db8::1:80"), "2001:db8::1:80".parse().ok());
+        assert_eq!(parse_ip("localhost:80"), None);
+    }
+
+    #[test]
+    fn forwarding_chains() {
+        assert_eq!(
+            x_forwarded_for("203.0.113.7, 10.0.0.1"),
+            vec!["203.0.113.7", "10.0.0.1"]
+        );
+        assert_eq!(
+            forwarded("for=192.0.2.60;proto=http, For=\"[2001:db8::1]\";by=10.0.0.1, by=x"),
+            vec!["192.0.2.60", "\"[2001:db8::1]\"", ""]
+        );
+
+        let proxies = cidrs(&["10.0.0.0/8"]);
+        assert_eq!(
+            client_ip(&["198.51.100.1", "203.0.113.7", "10.0.0.2"], &proxies),
+            parse_ip("203.0.113.7")
+        );
+        assert_eq!(client_ip(&["10.0.0.1"], &proxies), parse_ip("10.0.0.1"));
+        assert_eq!(client_ip(&["unknown", "10.0.0.1"], &proxies), None);
+
+        let validator = is_forwarded_by(&proxies);
+        assert!(validator("X-Forwarded-For", Some("203.0.113.7, 10.0.0.1")).is_ok());
+        assert!(validator("Forwarded", Some("for=203.0.113.7, for=10.0.0.1")).is_ok());
+        assert!(validator("X-Forwarded-For", Some("10.0.0.1, 203.0.113.7")).is_err());
+        assert!(validator("X-Forwarded-For", Some("evil")).is_err());
+
+        let validator = is_client_in(&cidrs(&["192.0.2.0/24"]), &proxies);
+        assert!(validator("X-Forwarded-For", Some("192.0.2.9, 10.0.0.1")).is_ok());
+        assert!(validator("X-Forwarded-For", Some("192.0.2.9, 203.0.113.7, 10.0.0.1")).is_err());
+        assert!(is_ip_in(&proxies)("peer_addr", Some("10.0.0.1:52114")).is_ok());
+        assert_eq!(
+            is_ip_in(&proxies)("peer_addr", Some("203.0.113.7:52114"))
+                .unwrap_err()
+                .code,
+            "ip_range"
+        );
+    }
+}
@@ -0,0 +1,882 @@
+//! Values changed before validators run: percent decoding, codecs, defaults and transformers
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tide::http::headers::HeaderValue;
+use tide::{Request, Response, StatusCode};
+
+use crate::field_map::FieldMap;
+use crate::field_rule::TransformerFn;
+use crate::values::{header_values, RequestValues};
+use crate::{HttpField, Location, ValidationError, ValidatorMiddleware};
+
+/// Decoder of fields sent encoded or encrypted by clients, e.g. an encrypted query token or
+/// cookie, registered with [`add_codec`](struct.ValidatorMiddleware.html#method.add_codec).
+/// Closures taking the application state, the field name and the value are codecs.
+///
+/// Validators of the field get the decoded value and handlers read it from
+/// [`NormalizedValues`](struct.NormalizedValues.html). Errors are sent as validation errors, don't
+/// put the encoded value in them. Validators may echo the value they check: in error responses,
+/// warnings and logs, messages of the errors of the field containing the decoded value as a word
+/// are replaced, e.g. by `'token' is invalid`, and parameters equal to it by `[redacted]`. Rule
+/// traces show `[redacted]` as the input of the field. Hooks like
+/// [`on_error`](struct.ValidatorMiddleware.html#method.on_error) receive the errors unchanged.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// struct Decrypt;
+///
+/// impl FieldCodec<String, AppState> for Decrypt {
+///     fn decode(&self, state: &AppState, field_name: &str, value: &str) -> Result<String, String> {
+///         state
+///             .cipher
+///             .decrypt(value)
+///             .map_err(|_| format!("'{}' cannot be decrypted", field_name))
+///     }
+/// }
+///
+/// validator_middleware.add_codec(HttpField::cookie("session"), Decrypt);
+/// validator_middleware.add_validator(HttpField::cookie("session"), is_uuid);
+/// ```
+pub trait FieldCodec<T, State>: Send + Sync + 'static {
+    /// Decoded value of the field `field_name`
+    fn decode(&self, state: &State, field_name: &str, value: &str) -> Result<String, T>;
+}
+
+impl<T, State, F> FieldCodec<T, State> for F
+where
+    F: Fn(&State, &str, &str) -> Result<String, T> + Send + Sync + 'static,
+{
+    fn decode(&self, state: &State, field_name: &str, value: &str) -> Result<String, T> {
+        self(state, field_name, value)
+    }
+}
+
+/// Replaces the decoded values of codecs in errors, warnings and traces
+pub(crate) const REDACTED: &str = "[redacted]";
+
+/// Values decoded by the codecs of a middleware, kept out of error responses, warnings, traces
+/// and logs
+#[derive(Clone)]
+pub(crate) struct DecodedValues(pub(crate) Vec<(HttpField<'static>, String)>);
+
+impl DecodedValues {
+    /// Value decoded by the codec of `field`
+    pub(crate) fn get(&self, field: &HttpField<'_>) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(decoded_field, _)| decoded_field == field)
+            .map(|(_, decoded)| decoded.as_str())
+    }
+
+    /// Replace the message of an error of `field` which contains its decoded value, and the
+    /// parameters equal to it. Messages are free text: one containing the value as a whole word,
+    /// not within a longer word or number, is replaced as a whole.
+    pub(crate) fn redact(&self, field: &HttpField<'_>, error: &mut serde_json::Value) {
+        let decoded = match self.get(field) {
+            Some(decoded) => decoded,
+            None => return,
+        };
+        if let serde_json::Value::String(message) = error {
+            if carries(message, decoded) {
+                *error = serde_json::Value::from(redacted_message(field));
+            }
+        } else if let Some(object) = error.as_object_mut() {
+            if let Some(message) = object.get_mut("message") {
+                if matches!(message, serde_json::Value::String(text) if carries(text, decoded)) {
+                    *message = serde_json::Value::from(redacted_message(field));
+                }
+            }
+            if let Some(params) = object.get_mut("params") {
+                redact_equal(params, decoded);
+            }
+        }
+    }
+
+    /// Replace a message about `field`, like the error of a validator which couldn't validate,
+    /// when it contains the decoded value
+    pub(crate) fn redact_text(&self, field: &HttpField<'_>, text: String) -> String {
+        match self.get(field) {
+            Some(decoded) if carries(&text, decoded) => redacted_message(field),
+            _ => text,
+        }
+    }
+}
+
+fn redacted_message(field: &HttpField<'_>) -> String {
+    format!("'{}' is invalid", field.name())
+}
+
+/// Whether `text` contains `decoded` as a whole word: `1` is in `'1' is too small` but not in
+/// `at most 10`
+fn carries(text: &str, decoded: &str) -> bool {
+    text.match_indices(decoded).any(|(start, _)| {
+        let end = start + decoded.len();
+        !matches!(text[..start].chars().next_back(), Some(c) if c.is_alphanumeric())
+            && !matches!(text[end..].chars().next(), Some(c) if c.is_alphanumeric())
+    })
+}
+
+/// Replace the strings equal to `decoded` in parameters, nested ones included
+fn redact_equal(value: &mut serde_json::Value, decoded: &str) {
+    match value {
+        serde_json::Value::String(text) if text == decoded => {
+            *value = serde_json::Value::from(REDACTED);
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                redact_equal(item, decoded);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for field in fields.values_mut() {
+                redact_equal(field, decoded);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// How the percent-encoded bytes of a field, like `%20` or `%C3%A9`, are decoded before its
+/// validators run, see [`decode_percent`](struct.ValidatorMiddleware.html#method.decode_percent)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PercentDecoding {
+    /// Values which don't decode to valid UTF-8 are rejected with `400 Bad Request`
+    Strict,
+    /// Invalid UTF-8 sequences are replaced by `U+FFFD`
+    Lossy,
+}
+
+/// Values replaced by normalizers and defaults of missing fields, added in request extensions when
+/// at least one field has been normalized.
+/// Tide doesn't allow to rewrite the request URL so handlers have to read canonical values from here.
+/// When several middlewares are stacked, validators of the later ones see the normalized values
+/// and the values of every middleware end up here.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let email = req
+///     .local::<NormalizedValues>()
+///     .and_then(|values| values.get(&HttpField::query_param("email")));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NormalizedValues(pub(crate) HashMap<HttpField<'static>, String>);
+
+impl NormalizedValues {
+    /// Normalized value of a field, `None` if the field hasn't been normalized
+    pub fn get(&self, field: &HttpField<'static>) -> Option<&str> {
+        self.0.get(field).map(|value| &value[..])
+    }
+}
+
+/// Write the normalized values of headers and cookies into the request
+pub(crate) fn rewrite_headers<State>(req: &mut Request<State>, normalized: &NormalizedValues) {
+    let mut cookies = Vec::new();
+    for (field, value) in &normalized.0 {
+        match field {
+            HttpField::Header(_) | HttpField::ContentType | HttpField::Accept => {
+                req.insert_header(field.name(), value.as_str());
+            }
+            HttpField::Cookie(name) => cookies.push((name.as_ref(), value.as_str())),
+            _ => {}
+        }
+    }
+    if cookies.is_empty() {
+        return;
+    }
+    let header = header_values(req, "Cookie")
+        .and_then(|values| values.last())
+        .map(HeaderValue::as_str)
+        .unwrap_or_default();
+    let header = rewrite_cookies(header, &cookies);
+    req.insert_header("Cookie", header);
+}
+
+/// `Cookie` header with the values of `cookies` replaced, cookies missing from the header are
+/// added at the end
+pub(crate) fn rewrite_cookies(header: &str, cookies: &[(&str, &str)]) -> String {
+    let mut pairs: Vec<String> = Vec::new();
+    let mut rewritten = Vec::new();
+    for pair in header
+        .split(';')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let name = pair.split('=').next().unwrap_or_default().trim();
+        match cookies.iter().find(|(cookie, _)| *cookie == name) {
+            Some((cookie, value)) => {
+                rewritten.push(*cookie);
+                pairs.push(format!("{}={}", cookie, value));
+            }
+            None => pairs.push(pair.to_string()),
+        }
+    }
+    for (cookie, value) in cookies {
+        if !rewritten.contains(cookie) {
+            pairs.push(format!("{}={}", cookie, value));
+        }
+    }
+    pairs.join("; ")
+}
+
+/// Bytes of a percent-encoded value, `%` not followed by two hexadecimal digits is kept as is
+pub(crate) fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Normalization of the field values before the validators run
+pub(crate) struct Normalization<T, State> {
+    pub(crate) transformers: FieldMap<Vec<TransformerFn>>,
+    pub(crate) codecs: FieldMap<Arc<dyn FieldCodec<T, State>>>,
+    pub(crate) defaults: FieldMap<String>,
+    pub(crate) percent_decoding: FieldMap<PercentDecoding>,
+    pub(crate) rewrite_headers: bool,
+}
+
+impl<T, State> Default for Normalization<T, State> {
+    fn default() -> Self {
+        Normalization {
+            transformers: FieldMap::new(),
+            codecs: FieldMap::new(),
+            defaults: FieldMap::new(),
+            percent_decoding: FieldMap::new(),
+            rewrite_headers: false,
+        }
+    }
+}
+
+impl<T, State> Clone for Normalization<T, State> {
+    fn clone(&self) -> Self {
+        Normalization {
+            transformers: self.transformers.clone(),
+            codecs: self.codecs.clone(),
+            defaults: self.defaults.clone(),
+            percent_decoding: self.percent_decoding.clone(),
+            rewrite_headers: self.rewrite_headers,
+        }
+    }
+}
+
+impl<T, State> Normalization<T, State> {
+    /// Transformers of `other` run after these ones, its codecs, defaults and decodings replace
+    /// these ones
+    pub(crate) fn merge(&mut self, other: Normalization<T, State>) {
+        for (field, transformers) in &other.transformers {
+            self.transformers
+                .entry(field.clone())
+                .extend(transformers.iter().cloned());
+        }
+        for (field, codec) in &other.codecs {
+            self.codecs.insert(field.clone(), Arc::clone(codec));
+        }
+        for (field, default) in &other.defaults {
+            self.defaults.insert(field.clone(), default.clone());
+        }
+        for (field, decoding) in &other.percent_decoding {
+            self.percent_decoding.insert(field.clone(), *decoding);
+        }
+        self.rewrite_headers |= other.rewrite_headers;
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Write the values of headers and cookies changed by normalizers, transformers, defaults or
+    /// codecs back into the request, so that downstream middleware and handlers reading the header
+    /// see the canonical form, e.g. `X-Dry-Run: yes` becomes `X-Dry-Run: true` with
+    /// [`normalize_bool`](validators/fn.normalize_bool.html). Cookies are rewritten in the `Cookie`
+    /// header. Other fields can't be changed in the request, handlers read their values from
+    /// [`NormalizedValues`](struct.NormalizedValues.html).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut validator_middleware = ValidatorMiddleware::new().rewrite_headers();
+    /// validator_middleware.add_normalizer(HttpField::header("X-Dry-Run"), normalize_bool);
+    /// ```
+    pub fn rewrite_headers(mut self) -> Self {
+        self.normalize.rewrite_headers = true;
+        self
+    }
+
+    /// Add a transformer sanitizing the field value before any validator of the field runs, e.g. to
+    /// trim whitespace. Transformers of a field run in the order they have been added, validators
+    /// see the transformed value and handlers can read it from
+    /// [`NormalizedValues`](struct.NormalizedValues.html).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// validator_middleware.add_validator(HttpField::query_param("email"), is_email);
+    /// validator_middleware.add_transformer(HttpField::query_param("email"), |value: &str| value.trim().to_string());
+    /// validator_middleware.add_transformer(HttpField::query_param("email"), |value: &str| value.to_lowercase());
+    /// ```
+    pub fn add_transformer<F>(&mut self, param_name: HttpField<'static>, transformer: F)
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.normalize
+            .transformers
+            .entry(param_name)
+            .push(Arc::new(transformer));
+    }
+
+    /// Decode the percent-encoded bytes of a field before any codec, transformer or validator of the
+    /// field runs, e.g. so that `caf%C3%A9` is 4 characters long for `is_max_length`. Path
+    /// parameters and headers are given as they appear in the request while query parameters are
+    /// already decoded, they aren't decoded twice. Handlers read the decoded value from
+    /// [`NormalizedValues`](struct.NormalizedValues.html). `%` not followed by two hexadecimal
+    /// digits is kept as is. With [`PercentDecoding::Strict`](enum.PercentDecoding.html), bytes
+    /// which aren't UTF-8 are rejected with a `percent_encoding` error, like errors of validators.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// validator_middleware.decode_percent(HttpField::param("name"), PercentDecoding::Strict);
+    /// validator_middleware.add_validator(HttpField::param("name"), is_max_length(32));
+    /// ```
+    pub fn decode_percent(&mut self, param_name: HttpField<'static>, decoding: PercentDecoding) {
+        self.normalize.percent_decoding.insert(param_name, decoding);
+    }
+
+    /// Give a default value to a field missing from the request. Validators and parsers of the
+    /// field see the default, handlers read it from [`NormalizedValues`](struct.NormalizedValues.html)
+    /// or, when the field has a parser, with
+    /// [`req.validated::<V>(name)`](trait.ValidatedRequestExt.html#tymethod.validated).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// validator_middleware.add_default(HttpField::query_param("per_page"), "20");
+    /// validator_middleware.add_parser(HttpField::query_param("per_page"), parsed::<u32, String>());
+    /// // ... then in the endpoint, 20 when the query parameter is missing
+    /// let per_page: u32 = *req.validated::<u32>("per_page").unwrap();
+    /// ```
+    pub fn add_default(&mut self, param_name: HttpField<'static>, value: &str) {
+        self.normalize
+            .defaults
+            .insert(param_name, value.to_string());
+    }
+
+    /// Add a codec decoding the field before its transformers and validators run, see
+    /// [`FieldCodec`](trait.FieldCodec.html). A field has at most one codec, adding another one
+    /// replaces it.
+    pub fn add_codec<C>(&mut self, param_name: HttpField<'static>, codec: C)
+    where
+        C: FieldCodec<T, State>,
+    {
+        self.normalize.codecs.insert(param_name, Arc::new(codec));
+    }
+
+    /// Replace the values of the fields with their percent-decoded, decoded, default and
+    /// transformed values, in this order. A value which can't be decoded gives the response.
+    pub(crate) fn normalize(
+        &self,
+        mut ctx: Request<State>,
+        values: &mut RequestValues<'_, State>,
+    ) -> Result<Request<State>, Response> {
+        for (field, decoding) in &self.normalize.percent_decoding {
+            // Query parameters are already decoded, `%2525` must stay `%25`
+            if field.location() == Location::Query {
+                continue;
+            }
+            let value = match values.raw(&ctx, field) {
+                Some(value) if value.contains('%') => value,
+                _ => continue,
+            };
+            let decoded = percent_decode(&value);
+            let decoded = match decoding {
+                PercentDecoding::Strict => match String::from_utf8(decoded) {
+                    Ok(decoded) => decoded,
+                    Err(_) => {
+                        let err = ValidationError::new(
+                            field.name(),
+                            "percent_encoding",
+                            format!("{} '{}' isn't valid UTF-8", field.kind(), field.name()),
+                        );
+                        let errors = vec![(field.clone(), err)];
+                        return Err(self.reject(&mut ctx, Some(StatusCode::BadRequest), errors));
+                    }
+                },
+                PercentDecoding::Lossy => String::from_utf8_lossy(&decoded).into_owned(),
+            };
+            values.normalized.0.insert(field.clone(), decoded);
+        }
+        let mut decoded_values = Vec::new();
+        for (field, codec) in &self.normalize.codecs {
+            if let Some(value) = values.raw(&ctx, field) {
+                match codec.decode(ctx.state(), field.name(), &value) {
+                    Ok(decoded) => {
+                        if !decoded.is_empty() {
+                            decoded_values.push((field.clone(), decoded.clone()));
+                        }
+                        values.normalized.0.insert(field.clone(), decoded);
+                    }
+                    Err(err) => {
+                        self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+                        return Err(self.error_response(&ctx, vec![(field, None, err)], None));
+                    }
+                }
+            }
+        }
+        if !decoded_values.is_empty() {
+            ctx = ctx.set_local(DecodedValues(decoded_values));
+        }
+        for (field, default) in &self.normalize.defaults {
+            if values.normalized(&ctx, field).is_none() && values.raw(&ctx, field).is_none() {
+                values.normalized.0.insert(field.clone(), default.clone());
+            }
+        }
+        for (field, transformers) in &self.normalize.transformers {
+            if let Some(value) = values.get(&ctx, field) {
+                let transformed = transformers
+                    .iter()
+                    .fold(value, |value, transformer| transformer(&value));
+                values.normalized.0.insert(field.clone(), transformed);
+            }
+        }
+        Ok(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    use crate::{
+        validators, Severity, ValidatedRequestExt, ValidationError, ValidationWarnings, Warning,
+    };
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request};
+    use tide::StatusCode;
+
+    #[test]
+    fn validator_normalizer() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_normalizer(HttpField::query_param("email"), |_, value: Option<&str>| {
+            Ok(value.map(|email| email.to_lowercase()))
+        });
+        validators.add_validator(
+            HttpField::query_param("email"),
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some(email) if email.chars().any(char::is_uppercase) => {
+                    Err(format!("'{}' is not normalized", field_name))
+                }
+                _ => Ok(()),
+            },
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                Ok(req
+                    .local::<NormalizedValues>()
+                    .and_then(|values| values.get(&HttpField::query_param("email")))
+                    .unwrap_or_default()
+                    .to_string())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?email=John@Example.com"
+                .parse()
+                .unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&*buf, &*b"john@example.com");
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("caf%C3%A9"), "café".as_bytes());
+        assert_eq!(percent_decode("a%2Fb%20c"), b"a/b c");
+        assert_eq!(percent_decode("100%"), b"100%");
+        assert_eq!(percent_decode("%zz%+f%4"), b"%zz%+f%4");
+        assert!(String::from_utf8(percent_decode("%FF")).is_err());
+    }
+
+    #[test]
+    fn validator_decode_percent() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.decode_percent(HttpField::param("name"), PercentDecoding::Strict);
+        validators.add_validator(HttpField::param("name"), validators::is_max_length(4));
+        validators.decode_percent(HttpField::query_param("q"), PercentDecoding::Strict);
+        validators.add_validator(HttpField::query_param("q"), validators::is_min_length(3));
+        inner
+            .at("/users/:name")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                let values = req.local::<NormalizedValues>().cloned().unwrap_or_default();
+                Ok(values
+                    .get(&HttpField::param("name"))
+                    .unwrap_or_default()
+                    .to_string())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/users/caf%C3%A9".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), "café");
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/users/caf%FF".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let err: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(
+            (&err.field[..], &err.code[..]),
+            ("name", "percent_encoding")
+        );
+
+        // `%2525` is `%25` once the query is decoded, not `%`
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/users/bob?q=%2525".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+    }
+
+    #[test]
+    fn rewritten_cookies() {
+        assert_eq!(
+            rewrite_cookies("lang=FR; session=abc", &[("lang", "fr")]),
+            "lang=fr; session=abc"
+        );
+        assert_eq!(
+            rewrite_cookies("session=abc", &[("lang", "fr")]),
+            "session=abc; lang=fr"
+        );
+        assert_eq!(rewrite_cookies("", &[("lang", "fr")]), "lang=fr");
+    }
+
+    #[test]
+    fn validator_rewrite_headers() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().rewrite_headers();
+        validators.add_normalizer(HttpField::header("X-Dry-Run"), validators::normalize_bool);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                Ok(req
+                    .header(&"X-Dry-Run".into())
+                    .map(|values| values[0].as_str().to_string())
+                    .unwrap_or_default())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+        req.insert_header("X-Dry-Run", "Yes").unwrap();
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), "true");
+
+        let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+        req.insert_header("X-Dry-Run", "maybe").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn validator_transformers() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(HttpField::query_param("email"), validators::is_email);
+        validators.add_transformer(HttpField::query_param("email"), |value: &str| {
+            value.trim().to_string()
+        });
+        validators.add_transformer(HttpField::query_param("email"), |value: &str| {
+            value.to_lowercase()
+        });
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                Ok(req
+                    .local::<NormalizedValues>()
+                    .and_then(|values| values.get(&HttpField::query_param("email")))
+                    .unwrap_or_default()
+                    .to_string())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?email=%20Cat@Example.com%20"
+                .parse()
+                .unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&*buf, &*b"cat@example.com");
+    }
+
+    #[test]
+    fn validator_codec() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_codec(
+            HttpField::query_param("token"),
+            |_: &(), field_name: &str, value: &str| match value.strip_prefix("rot1:") {
+                Some(encoded) => Ok(encoded.chars().map(|c| (c as u8 - 1) as char).collect()),
+                None => Err(format!("'{}' cannot be decoded", field_name)),
+            },
+        );
+        validators.add_validator(HttpField::query_param("token"), is_number);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                let token = req
+                    .local::<NormalizedValues>()
+                    .and_then(|values| values.get(&HttpField::query_param("token")))
+                    .unwrap_or_default()
+                    .to_string();
+                Ok(token)
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?token=rot1:23".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        let mut buf = Vec::new();
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&buf[..], b"12");
+
+        for (url, message) in &[
+            // The decoded value isn't echoed
+            ("http://localhost/foo?token=rot1:b", "'token' is invalid"),
+            ("http://localhost/foo?token=12", "'token' cannot be decoded"),
+        ] {
+            let req = Request::new(Method::Get, url.parse().unwrap());
+            let mut res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), StatusCode::BadRequest);
+            let mut buf = Vec::new();
+            block_on(res.read_to_end(&mut buf)).unwrap();
+            let err: String = serde_json::from_slice(&buf[..]).unwrap();
+            assert_eq!(&err, message);
+        }
+    }
+
+    #[test]
+    fn validator_codec_items() {
+        let mut inner = tide::new();
+        let mut operation = ValidatorMiddleware::new();
+        operation.add_validator(HttpField::body_field("token"), is_number);
+        operation.add_validator_with_severity(
+            HttpField::body_field("token"),
+            Severity::Warning,
+            |_: &str, field_value: Option<&str>| match field_value {
+                Some(token) if token.len() < 8 => Err(format!("'{}' is too short", token)),
+                _ => Ok(()),
+            },
+        );
+        let mut validators = ValidatorMiddleware::new().each_item(operation);
+        validators.add_codec(
+            HttpField::body_field("0.token"),
+            |_: &(), field_name: &str, value: &str| match value.strip_prefix("rev:") {
+                Some(encoded) => Ok(encoded.chars().rev().collect()),
+                None => Err(format!("'{}' cannot be decoded", field_name)),
+            },
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .post(|req: tide::Request<()>| async move {
+                let messages: Vec<String> = req
+                    .local::<ValidationWarnings>()
+                    .map(|warnings| warnings.iter().map(Warning::message).collect())
+                    .unwrap_or_default();
+                Ok(messages.join(", "))
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        // Warnings and errors of the items don't echo the decoded value
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"[{"token": "rev:54321"}]"#);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        let mut buf = Vec::new();
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&buf[..], b"'0.token' is invalid");
+
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"[{"token": "rev:terc3s"}]"#);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        let mut buf = Vec::new();
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"0.token": ["'0.token' is invalid"]})
+        );
+    }
+
+    #[test]
+    fn decoded_values_redaction() {
+        let token = HttpField::query_param("token");
+        let decoded = DecodedValues(vec![(token.clone(), String::from("s3cret"))]);
+        let redact = |field: &HttpField<'_>, mut error: serde_json::Value| {
+            decoded.redact(field, &mut error);
+            error
+        };
+        assert_eq!(
+            redact(&token, serde_json::json!("'s3cret' is not a number")),
+            serde_json::json!("'token' is invalid")
+        );
+        assert_eq!(
+            redact(&token, serde_json::json!("'token' is too long")),
+            serde_json::json!("'token' is too long")
+        );
+        assert_eq!(
+            redact(
+                &token,
+                serde_json::json!({
+                    "field": "token",
+                    "code": "pattern",
+                    "message": "'s3cret' doesn't match",
+                    "params": {"value": "s3cret", "pattern": "[0-9]+"}
+                })
+            ),
+            serde_json::json!({
+                "field": "token",
+                "code": "pattern",
+                "message": "'token' is invalid",
+                "params": {"value": "[redacted]", "pattern": "[0-9]+"}
+            })
+        );
+        // Short values are only redacted where they are a whole word or parameter
+        let code = HttpField::query_param("code");
+        let decoded = DecodedValues(vec![(code.clone(), String::from("1"))]);
+        let mut error = serde_json::json!({
+            "field": "code",
+            "message": "'code' must be at most 10 characters",
+            "params": {"value": "1", "max": 10, "pattern": "[0-9]{1,3}", "values": ["1", "12"]}
+        });
+        decoded.redact(&code, &mut error);
+        assert_eq!(
+            error,
+            serde_json::json!({
+                "field": "code",
+                "message": "'code' must be at most 10 characters",
+                "params": {
+                    "value": "[redacted]",
+                    "max": 10,
+                    "pattern": "[0-9]{1,3}",
+                    "values": ["[redacted]", "12"]
+                }
+            })
+        );
+        let mut error = serde_json::json!("field 'code' = '1' is not allowed");
+        decoded.redact(&code, &mut error);
+        assert_eq!(error, serde_json::json!("'code' is invalid"));
+        assert_eq!(
+            decoded.redact_text(&code, String::from("lookup 10 failed")),
+            "lookup 10 failed"
+        );
+        // Errors of other fields are kept
+        assert_eq!(
+            redact(
+                &HttpField::query_param("other"),
+                serde_json::json!("'s3cret' is not a number")
+            ),
+            serde_json::json!("'s3cret' is not a number")
+        );
+    }
+
+    #[test]
+    fn validator_defaults() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_default(HttpField::query_param("per_page"), "20");
+        validators.add_parser(
+            HttpField::query_param("per_page"),
+            validators::parsed::<u32, String>(),
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                let per_page = req
+                    .validated::<u32>("per_page")
+                    .copied()
+                    .unwrap_or_default();
+                Ok(format!("{}", per_page))
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (url, body) in &[
+            ("http://localhost/foo", "20"),
+            ("http://localhost/foo?per_page=50", "50"),
+        ] {
+            let req = Request::new(Method::Get, url.parse().unwrap());
+            let mut res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), StatusCode::Ok);
+            let mut buf = Vec::new();
+            block_on(res.read_to_end(&mut buf)).unwrap();
+            assert_eq!(&buf[..], body.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Typed numeric validators, which check the value fits the requested Rust type and range.
+//!
+//! Unlike [`validators::is_integer`](../validators/fn.is_integer.html), values must be written in
+//! canonical form: no sign `+`, no leading zeros like `01` and no `-0`, decimals are written
+//! without exponent. A value which doesn't fit the type, e.g. `300` for `u8`, fails with the code
+//! `overflow`.
+//!
+//! A [`Numeric`](struct.Numeric.html) is added with
+//! [`add_named_validator`](../struct.ValidatorMiddleware.html#method.add_named_validator), or with
+//! [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser) through
+//! [`parser`](struct.Numeric.html#method.parser) so handlers get the parsed number.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::numeric::{is_float_between, is_integer, is_u32_in};
+//!
+//! validator_middleware.add_named_validator(HttpField::param("id"), is_integer::<i64>());
+//! validator_middleware.add_named_validator(HttpField::query_param("ratio"), is_float_between(0.0, 1.0));
+//! validator_middleware.add_parser(HttpField::query_param("page"), is_u32_in(1..=100).parser());
+//! ```
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+use std::str::FromStr;
+
+use crate::validators::ValidationError;
+use crate::Validator;
+
+/// Number type supported by the typed validators
+pub trait Number: FromStr + Copy + PartialOrd + fmt::Display + Send + Sync + 'static {
+    /// Name of the type, e.g. `u32`
+    const NAME: &'static str;
+    /// Smallest value of the type
+    const MIN: Self;
+    /// Largest value of the type
+    const MAX: Self;
+    /// Whether the type only holds integers
+    const INTEGER: bool;
+}
+
+macro_rules! impl_number {
+    ($integer:expr; $($ty:ident),*) => {
+        $(impl Number for $ty {
+            const NAME: &'static str = stringify!($ty);
+            const MIN: Self = $ty::MIN;
+            const MAX: Self = $ty::MAX;
+            const INTEGER: bool = $integer;
+        })*
+    };
+}
+
+impl_number!(true; i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_number!(false; f32, f64);
+
+/// Validator and parser of a number of type `N` in a range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Numeric<N> {
+    start: Bound<N>,
+    end: Bound<N>,
+}
+
+/// Check if the field is an integer of type `N`, e.g. `is_integer::<u16>()` rejects `-1` and `70000`
+pub fn is_integer<N: Number>() -> Numeric<N> {
+    is_in::<N, _>(..)
+}
+
+/// Check if the field is a decimal number of type `N`
+pub fn is_float<N: Number>() -> Numeric<N> {
+    is_in::<N, _>(..)
+}
+
+/// Check if the field is a number of type `N` in `range`, e.g. `is_in::<u8, _>(1..=10)`
+pub fn is_in<N: Number, R: RangeBounds<N>>(range: R) -> Numeric<N> {
+    let copied = |bound: Bound<&N>| match bound {
+        Bound::Included(n) => Bound::Included(*n),
+        Bound::Excluded(n) => Bound::Excluded(*n),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    Numeric {
+        start: copied(range.start_bound()),
+        end: copied(range.end_bound()),
+    }
+}
+
+/// Check if the field is a decimal number between `min` and `max` (inclusive)
+pub fn is_float_between(min: f64, max: f64) -> Numeric<f64> {
+    is_in(min..=max)
+}
+
+/// Check if the field is an `i32` in `range`
+pub fn is_i32_in<R: RangeBounds<i32>>(range: R) -> Numeric<i32> {
+    is_in(range)
+}
+
+/// Check if the field is an `i64` in `range`
+pub fn is_i64_in<R: RangeBounds<i64>>(range: R) -> Numeric<i64> {
+    is_in(range)
+}
+
+/// Check if the field is a `u32` in `range`, e.g. `is_u32_in(1..=100)`
+pub fn is_u32_in<R: RangeBounds<u32>>(range: R) -> Numeric<u32> {
+    is_in(range)
+}
+
+/// Check if the field is a `u64` in `range`
+pub fn is_u64_in<R: RangeBounds<u64>>(range: R) -> Numeric<u64> {
+    is_in(range)
+}
+
+/// Check if the field is a `usize` in `range`
+pub fn is_usize_in<R: RangeBounds<usize>>(range: R) -> Numeric<usize> {
+    is_in(range)
+}
+
+impl<N: Number> Numeric<N> {
+    /// Parse the field, `Ok(None)` when it's missing
+    pub fn parse(
+        &self,
+        field_name: &str,
+        field_value: Option<&str>,
+    ) -> Result<Option<N>, ValidationError> {
+        let value = match field_value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let (code, kind, example) = if N::INTEGER {
+            ("integer", "integer", "42")
+        } else {
+            ("numeric", "number", "4.2")
+        };
+        if !is_canonical(value, N::INTEGER) {
+            return Err(ValidationError::new(
+                field_name,
+                code,
+                format!(
+                    "field '{}' = '{}' is not a valid {}",
+                    field_name, value, kind
+                ),
+            )
+            .with_param("example", example));
+        }
+        let n = match value.parse::<N>() {
+            // Floats overflow to infinity instead of failing
+            Ok(n) if n >= N::MIN && n <= N::MAX => n,
+            _ => {
+                return Err(ValidationError::new(
+                    field_name,
+                    "overflow",
+                    format!(
+                        "field '{}' = '{}' doesn't fit in {}",
+                        field_name,
+                        value,
+                        N::NAME
+                    ),
+                )
+                .with_param("type", N::NAME)
+                .with_param("min", N::MIN)
+                .with_param("max", N::MAX))
+            }
+        };
+        if self.contains(n) {
+            return Ok(Some(n));
+        }
+        let code = match (self.start, self.end) {
+            (Bound::Unbounded, _) => "max",
+            (_, Bound::Unbounded) => "min",
+            _ => "between",
+        };
+        let mut err = ValidationError::new(
+            field_name,
+            code,
+            format!("field '{}' = '{}' must be {}", field_name, value, self),
+        );
+        err = match self.start {
+            Bound::Included(min) => err.with_param("min", min).with_param("example", min),
+            Bound::Excluded(min) => err.with_param("greater_than", min),
+            Bound::Unbounded => err,
+        };
+        err = match self.end {
+            Bound::Included(max) => err.with_param("max", max),
+            Bound::Excluded(max) => err.with_param("less_than", max),
+            Bound::Unbounded => err,
+        };
+        Err(err)
+    }
+
+    /// Parser for [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser), giving
+    /// the number to handlers
+    pub fn parser<T>(self) -> impl Fn(&str, Option<&str>) -> Result<Option<N>, T> + Send + Sync
+    where
+        T: From<ValidationError>,
+    {
+        move |field_name: &str, field_value: Option<&str>| {
+            self.parse(field_name, field_value).map_err(T::from)
+        }
+    }
+
+    fn contains(&self, n: N) -> bool {
+        let above = match self.start {
+            Bound::Included(min) => n >= min,
+            Bound::Excluded(min) => n > min,
+            Bound::Unbounded => true,
+        };
+        let below = match self.end {
+            Bound::Included(max) => n <= max,
+            Bound::Excluded(max) => n < max,
+            Bound::Unbounded => true,
+        };
+        above && below
+    }
+}
+
+impl<N: Number> fmt::Display for Numeric<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.start, self.end) {
+            (Bound::Included(min), Bound::Included(max)) => {
+                write!(f, "between {} and {}", min, max)
+            }
+            (start, end) => {
+                let start = match start {
+                    Bound::Included(min) => Some(format!("at least {}", min)),
+                    Bound::Excluded(min) => Some(format!("greater than {}", min)),
+                    Bound::Unbounded => None,
+                };
+                let end = match end {
+                    Bound::Included(max) => Some(format!("at most {}", max)),
+                    Bound::Excluded(max) => Some(format!("lower than {}", max)),
+                    Bound::Unbounded => None,
+                };
+                match (start, end) {
+                    (Some(start), Some(end)) => write!(f, "{} and {}", start, end),
+                    (Some(bound), None) | (None, Some(bound)) => f.write_str(&bound),
+                    (None, None) => write!(f, "a valid {}", N::NAME),
+                }
+            }
+        }
+    }
+}
+
+impl<T, N> Validator<T> for Numeric<N>
+where
+    T: From<ValidationError>,
+    N: Number,
+{
+    fn name(&self) -> &str {
+        N::NAME
+    }
+
+    fn validate(&self, field_name: &str, field_value: Option<&str>) -> Result<(), T> {
+        self.parse(field_name, field_value)
+            .map(|_| ())
+            .map_err(T::from)
+    }
+}
+
+/// Check the value is written `-?(0|[1-9][0-9]*)`, followed by `.[0-9]+` for decimals
+fn is_canonical(value: &str, integer: bool) -> bool {
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    let (whole, fraction) = match unsigned.find('.') {
+        Some(dot) if !integer => (&unsigned[..dot], Some(&unsigned[dot + 1..])),
+        _ => (unsigned, None),
+    };
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !digits(whole) || (whole.len() > 1 && whole.starts_with('0')) {
+        return false;
+    }
+    if fraction.filter(|fraction| !digits(fraction)).is_some() {
+        return false;
+    }
+    // `-0` and `-0.0` are zero written differently
+    !(value.starts_with('-') && unsigned.bytes().all(|b| b == b'0' || b == b'.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_integers() {
+        let validator = is_integer::<i64>();
+        assert_eq!(validator.parse("n", Some("42")), Ok(Some(42)));
+        assert_eq!(validator.parse("n", Some("-7")), Ok(Some(-7)));
+        assert_eq!(validator.parse("n", Some("0")), Ok(Some(0)));
+        assert_eq!(validator.parse("n", None), Ok(None));
+        for value in &["+1", "01", "-0", "", "-", "4.2", "1e3", " 1"] {
+            assert_eq!(
+                validator.parse("n", Some(value)).unwrap_err().code,
+                "integer",
+                "{}",
+                value
+            );
+        }
+
+        let err = is_integer::<u8>().parse("n", Some("300")).unwrap_err();
+        assert_eq!(err.code, "overflow");
+        assert_eq!(err.param("max"), Some("255"));
+        assert_eq!(
+            is_integer::<u8>().parse("n", Some("-1")).unwrap_err().code,
+            "overflow"
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        let validator = is_u32_in(1..=100);
+        assert_eq!(validator.parse("page", Some("100")), Ok(Some(100)));
+        let err = validator.parse("page", Some("101")).unwrap_err();
+        assert_eq!(err.code, "between");
+        assert_eq!(
+            err.message,
+            "field 'page' = '101' must be between 1 and 100"
+        );
+        assert_eq!(
+            is_i64_in(..0).parse("n", Some("0")).unwrap_err().code,
+            "max"
+        );
+        assert_eq!(
+            is_usize_in(1..).parse("n", Some("0")).unwrap_err().message,
+            "field 'n' = '0' must be at least 1"
+        );
+
+        let ratio = is_float_between(0.0, 1.0);
+        assert_eq!(ratio.parse("ratio", Some("0.25")), Ok(Some(0.25)));
+        assert_eq!(
+            ratio.parse("ratio", Some("1.5")).unwrap_err().code,
+            "between"
+        );
+        assert_eq!(
+            ratio.parse("ratio", Some(".5")).unwrap_err().code,
+            "numeric"
+        );
+        assert_eq!(
+            ratio.parse("ratio", Some("-0.0")).unwrap_err().code,
+            "numeric"
+        );
+        assert_eq!(
+            is_float::<f32>()
+                .parse("n", Some("1000000000000000000000000000000000000000"))
+                .unwrap_err()
+                .code,
+            "overflow"
+        );
+    }
+
+    #[test]
+    fn validator_and_parser() {
+        let validator = is_u32_in(1..=100);
+        assert_eq!(Validator::<ValidationError>::name(&validator), "u32");
+        assert!(Validator::<ValidationError>::validate(&validator, "page", Some("2")).is_ok());
+        assert!(Validator::<String>::validate(&validator, "page", Some("0")).is_err());
+        assert_eq!(
+            validator.parser::<ValidationError>()("page", Some("7")),
+            Ok(Some(7))
+        );
+    }
+}
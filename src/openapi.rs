@@ -0,0 +1,284 @@
+//! Validators generated from the `parameters` of an OpenAPI 3 operation, available with the
+//! `openapi` feature, so constraints declared in your spec aren't declared again in Rust.
+//!
+//! Each parameter gives a field from its `name` and `in` (`path`, `query`, `header` or `cookie`),
+//! `required: true` adds `is_required` and its `schema` supports the keywords of the
+//! [`schema`](../schema/index.html) module: `type`, `format`, `minimum`, `maximum`, `minLength`,
+//! `maxLength`, `enum` and `pattern` with the `regex` feature. References (`$ref`) must be
+//! resolved beforehand.
+//!
+//! ```rust,no_run,compile_fail
+//! let spec: serde_json::Value = serde_yaml::from_str(include_str!("../openapi.yaml"))?;
+//! let parameters = &spec["paths"]["/users/{id}"]["get"]["parameters"];
+//! app.at("/users/:id")
+//!     .middleware(ValidatorMiddleware::from_openapi_parameters(parameters)?)
+//!     .get(get_user);
+//! ```
+//!
+//! The other way around, [`to_openapi_parameters`](../struct.ValidatorMiddleware.html#method.to_openapi_parameters)
+//! documents the fields of a middleware so the spec never drifts from the actual validation.
+use std::any::TypeId;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::schema::{keywords, SchemaError};
+use crate::validators::{self, ValidationError};
+use crate::{
+    BoxedValidator, FieldRule, HttpField, ListStyle, Location, Presence, ValidatorMiddleware,
+};
+
+impl<State> ValidatorMiddleware<ValidationError, State>
+where
+    State: Send + Sync + 'static,
+{
+    /// Build a middleware from the `parameters` array of an OpenAPI 3 operation, see the
+    /// [`openapi`](openapi/index.html) module. Errors point into the array, e.g. `/0/schema/minimum`.
+    pub fn from_openapi_parameters(parameters: &Value) -> Result<Self, SchemaError> {
+        let mut middleware = ValidatorMiddleware::new();
+        for (field, validator) in fields(parameters)? {
+            middleware.add_validator(field, validator);
+        }
+        Ok(middleware)
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// OpenAPI 3 `parameters` of the path, query, header and cookie fields of the middleware, to
+    /// put in the operation of the route. Rules only enforced for some methods or steps aren't
+    /// listed.
+    ///
+    /// Path parameters and fields validated by `is_required` are required. Schemas are inferred
+    /// from `is_integer`, `is_number`, `is_bool`, `is_uuid` and `is_email` and from the typed
+    /// validators of the [`numeric`](../numeric/index.html) module, fields are strings otherwise.
+    /// Descriptions of the validators added with
+    /// [`add_described_validator`](../struct.ValidatorMiddleware.html#method.add_described_validator)
+    /// are joined in the `description`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut validator_middleware = ValidatorMiddleware::new();
+    /// validator_middleware.add_validator(HttpField::param("id"), is_uuid);
+    /// validator_middleware.add_named_validator(HttpField::query_param("page"), is_u32_in(1..=100));
+    /// // [{"name": "id", "in": "path", "required": true, "schema": {"type": "string", "format": "uuid"}},
+    /// //  {"name": "page", "in": "query", "required": false, "schema": {"type": "integer", "format": "int64", "minimum": 0}}]
+    /// let parameters = validator_middleware.to_openapi_parameters();
+    /// ```
+    pub fn to_openapi_parameters(&self) -> Value {
+        let parameters: Vec<Value> = self
+            .validators
+            .iter()
+            .filter_map(|(field, rules)| self.openapi_parameter(field, rules))
+            .collect();
+        Value::Array(parameters)
+    }
+
+    fn openapi_parameter(
+        &self,
+        field: &HttpField<'static>,
+        rules: &[FieldRule<T, State>],
+    ) -> Option<Value> {
+        let location = match field {
+            HttpField::Param(_)
+            | HttpField::QueryParam(_)
+            | HttpField::QueryList(_, _)
+            | HttpField::Header(_)
+            | HttpField::ContentType
+            | HttpField::Accept
+            | HttpField::Cookie(_) => field.location(),
+            _ => return None,
+        };
+        let stateless: Vec<TypeId> = self
+            .stateless_validators
+            .iter()
+            .filter(|(validated, _)| validated == field)
+            .map(|(_, validator)| *validator)
+            .collect();
+        let uses = |validator: TypeId| stateless.contains(&validator);
+        let names: Vec<&str> = rules.iter().filter_map(FieldRule::name).collect();
+
+        let required = location == Location::Path
+            || uses(type_id(&validators::is_required))
+            || self.presence.get(field) == Some(&Presence::Required);
+        let schema = if uses(type_id(&validators::is_integer)) {
+            json!({"type": "integer"})
+        } else if uses(type_id(&validators::is_number)) {
+            json!({"type": "number"})
+        } else if uses(type_id(&validators::is_bool)) {
+            json!({"type": "boolean"})
+        } else if uses(type_id(&validators::is_uuid)) {
+            json!({"type": "string", "format": "uuid"})
+        } else if uses(type_id(&validators::is_email)) {
+            json!({"type": "string", "format": "email"})
+        } else {
+            names
+                .iter()
+                .find_map(|name| numeric_schema(name))
+                .unwrap_or_else(|| json!({"type": "string"}))
+        };
+
+        let mut parameter = json!({
+            "name": field.name(),
+            "in": location,
+            "required": required,
+            "schema": schema,
+        });
+        if let HttpField::QueryList(name, style) = field {
+            parameter["name"] = json!(style.param_name(name));
+            parameter["schema"] = json!({"type": "array", "items": parameter["schema"]});
+            parameter["style"] = json!("form");
+            parameter["explode"] = json!(*style != ListStyle::Comma);
+        }
+        let descriptions: Vec<&str> = rules.iter().filter_map(FieldRule::description).collect();
+        if !descriptions.is_empty() {
+            parameter["description"] = json!(descriptions.join("; "));
+        }
+        Some(parameter)
+    }
+}
+
+fn type_id<F: 'static>(_: &F) -> TypeId {
+    TypeId::of::<F>()
+}
+
+/// Schema of the numbers validated by the typed validators, named after their type
+fn numeric_schema(name: &str) -> Option<Value> {
+    let schema = match name {
+        "i8" | "i16" | "i32" => json!({"type": "integer", "format": "int32"}),
+        "i64" | "i128" | "isize" => json!({"type": "integer", "format": "int64"}),
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => {
+            json!({"type": "integer", "format": "int64", "minimum": 0})
+        }
+        "f32" => json!({"type": "number", "format": "float"}),
+        "f64" => json!({"type": "number", "format": "double"}),
+        _ => return None,
+    };
+    Some(schema)
+}
+
+fn fields(
+    parameters: &Value,
+) -> Result<Vec<(HttpField<'static>, BoxedValidator<ValidationError>)>, SchemaError> {
+    let parameters = parameters
+        .as_array()
+        .ok_or_else(|| SchemaError::new("", "expected an array of parameters"))?;
+    parameters
+        .iter()
+        .enumerate()
+        .map(|(index, parameter)| {
+            let pointer = format!("/{}", index);
+            if parameter.get("$ref").is_some() {
+                return Err(SchemaError::new(
+                    &format!("{}/$ref", pointer),
+                    "references aren't supported, resolve them beforehand",
+                ));
+            }
+            let member = |name: &str| {
+                parameter.get(name).and_then(Value::as_str).ok_or_else(|| {
+                    SchemaError::new(&format!("{}/{}", pointer, name), "expected a string")
+                })
+            };
+            let name = member("name")?.to_string();
+            let field = match member("in")? {
+                "path" => HttpField::param(name),
+                "query" => HttpField::query_param(name),
+                "header" => HttpField::header(name),
+                "cookie" => HttpField::cookie(name),
+                other => {
+                    return Err(SchemaError::new(
+                        &format!("{}/in", pointer),
+                        format!("unknown location '{}'", other),
+                    ))
+                }
+            };
+            let mut chain: Vec<BoxedValidator<ValidationError>> = Vec::new();
+            if parameter.get("required") == Some(&Value::Bool(true)) {
+                chain.push(Box::new(validators::is_required));
+            }
+            if let Some(schema) = parameter.get("schema") {
+                chain.extend(keywords(schema, &format!("{}/schema", pointer))?);
+            }
+            let validator: BoxedValidator<ValidationError> = Box::new(
+                move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+                    for validator in &chain {
+                        validator(field_name, field_value)?;
+                    }
+                    Ok(())
+                },
+            );
+            Ok((field, validator))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openapi_parameters() {
+        let parameters = serde_json::json!([
+            {"name": "id", "in": "path", "required": true, "schema": {"type": "string", "format": "uuid"}},
+            {"name": "limit", "in": "query", "schema": {"type": "integer", "minimum": 1, "maximum": 100}},
+            {"name": "X-Trace", "in": "header", "description": "Trace id", "schema": {"type": "string"}},
+        ]);
+        let fields = fields(&parameters).unwrap();
+        assert_eq!(fields.len(), 3);
+        let (field, validator) = &fields[0];
+        assert_eq!(field, &HttpField::param("id"));
+        assert_eq!(validator("id", None).unwrap_err().code, "required");
+        assert_eq!(validator("id", Some("1")).unwrap_err().code, "uuid");
+        let (field, validator) = &fields[1];
+        assert_eq!(field, &HttpField::query_param("limit"));
+        assert!(validator("limit", None).is_ok());
+        assert_eq!(validator("limit", Some("101")).unwrap_err().code, "max");
+
+        let parameters = serde_json::json!([{"name": "id", "in": "body"}]);
+        assert_eq!(super::fields(&parameters).err().unwrap().pointer, "/0/in");
+        let parameters = serde_json::json!([{"$ref": "#/components/parameters/Id"}]);
+        assert!(super::fields(&parameters).is_err());
+    }
+
+    #[test]
+    fn parameters_of_middleware() {
+        let mut middleware: ValidatorMiddleware<ValidationError> = ValidatorMiddleware::new();
+        middleware.add_validator(HttpField::param("id"), validators::is_uuid);
+        middleware.add_validator(HttpField::query_param("q"), validators::is_required);
+        middleware.add_described_validator(
+            HttpField::query_param("q"),
+            "at most 64 characters",
+            validators::is_max_length(64),
+        );
+        middleware.add_named_validator(
+            HttpField::query_param("page"),
+            crate::numeric::is_u32_in(1..=100),
+        );
+        middleware.add_validator(
+            HttpField::query_list("ids", ListStyle::Brackets),
+            validators::is_integer,
+        );
+        middleware.add_validator(HttpField::body_field("name"), validators::is_required);
+
+        assert_eq!(
+            middleware.to_openapi_parameters(),
+            json!([
+                {"name": "id", "in": "path", "required": true, "schema": {"type": "string", "format": "uuid"}},
+                {"name": "q", "in": "query", "required": true, "schema": {"type": "string"},
+                    "description": "at most 64 characters"},
+                {"name": "page", "in": "query", "required": false,
+                    "schema": {"type": "integer", "format": "int64", "minimum": 0}},
+                {"name": "ids[]", "in": "query", "required": false, "style": "form", "explode": true,
+                    "schema": {"type": "array", "items": {"type": "integer"}}},
+            ])
+        );
+
+        let mut middleware: ValidatorMiddleware<ValidationError> = ValidatorMiddleware::new();
+        middleware.require(HttpField::header("X-Token"));
+        assert_eq!(middleware.to_openapi_parameters()[0]["required"], true);
+    }
+}
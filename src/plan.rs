@@ -0,0 +1,351 @@
+//! Execution plans: the fields validated by the requests using the same session, method and step
+//! rules, worked out once instead of on every request
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+use tide::Request;
+
+use crate::values::{matching_fields, present_fields, RequestValues};
+use crate::{ExecutionPolicy, FieldRule, HttpField, Presence, ValidatorMiddleware};
+
+/// Rules a planned field comes from: the middleware itself or one of its scopes, by position
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Scope {
+    Own,
+    #[cfg(feature = "sessions")]
+    Session(usize),
+    Method(usize),
+    Step(usize),
+}
+
+/// What a planned field validates
+#[derive(Debug)]
+pub(crate) enum Target {
+    /// Field at `index` in the validators of the scope, with how its rules run. Patterns, body
+    /// paths with wildcards and XML paths `expand` to the fields they match in the request.
+    Field {
+        index: usize,
+        policy: ExecutionPolicy,
+        presence: Option<Presence>,
+        depends_on: Vec<HttpField<'static>>,
+        expands: bool,
+    },
+    /// Global validators at `index` in the scope, run for the fields present at their location
+    Global(usize),
+}
+
+#[derive(Debug)]
+pub(crate) struct PlannedField {
+    pub(crate) scope: Scope,
+    pub(crate) target: Target,
+}
+
+/// Fields with validators in the order they run, then global validators
+#[derive(Debug)]
+pub(crate) struct Plan {
+    pub(crate) fields: Vec<PlannedField>,
+}
+
+/// Session, method and step rules used by a request, by position in the scopes of the middleware
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct PlanKey {
+    session: Option<usize>,
+    method: Option<usize>,
+    step: Option<usize>,
+}
+
+/// Plans of a middleware, each built by the first request using its rules. A middleware doesn't
+/// change once it handles requests, clones start without plans.
+#[derive(Debug, Default)]
+pub(crate) struct Plans(RwLock<HashMap<PlanKey, Arc<Plan>>>);
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Plan of the request, `step` is the active step of a multi-step form
+    pub(crate) fn plan(&self, ctx: &Request<State>, step: Option<&str>) -> Arc<Plan> {
+        let method = ctx.method();
+        self.cached_plan(PlanKey {
+            session: self.session_position(ctx),
+            method: self
+                .scopes
+                .method_rules
+                .iter()
+                .position(|(rules_method, _)| *rules_method == method),
+            step: step.and_then(|step| self.scopes.steps.iter().position(|(name, _)| name == step)),
+        })
+    }
+
+    /// Plan of the rules of the items of a JSON array body, which don't depend on the method or
+    /// the step
+    pub(crate) fn item_plan(&self, ctx: &Request<State>) -> Arc<Plan> {
+        self.cached_plan(PlanKey {
+            session: self.session_position(ctx),
+            method: None,
+            step: None,
+        })
+    }
+
+    fn cached_plan(&self, key: PlanKey) -> Arc<Plan> {
+        let cached = match self.plans.0.read() {
+            Ok(plans) => plans.get(&key).cloned(),
+            Err(_) => None,
+        };
+        if let Some(plan) = cached {
+            return plan;
+        }
+        let plan = Arc::new(self.build_plan(key));
+        match self.plans.0.write() {
+            Ok(mut plans) => Arc::clone(plans.entry(key).or_insert(plan)),
+            Err(_) => plan,
+        }
+    }
+
+    #[cfg(feature = "sessions")]
+    fn session_position(&self, ctx: &Request<State>) -> Option<usize> {
+        self.scopes
+            .session_rules
+            .iter()
+            .position(|(predicate, _)| predicate(ctx))
+    }
+
+    #[cfg(not(feature = "sessions"))]
+    fn session_position(&self, _ctx: &Request<State>) -> Option<usize> {
+        None
+    }
+
+    /// Validators of the middleware not replaced by the session rules, then those of the session
+    #[cfg(feature = "sessions")]
+    fn session_validators(&self, session: Option<usize>) -> Option<Vec<(Scope, usize)>> {
+        let position = session?;
+        let rules = &self.scopes.session_rules[position].1;
+        let own = (0..self.validators.len())
+            .filter(|&index| {
+                !rules
+                    .validators
+                    .contains_key(self.validators.entry_at(index).0)
+            })
+            .map(|index| (Scope::Own, index));
+        let session = (0..rules.validators.len()).map(|index| (Scope::Session(position), index));
+        Some(own.chain(session).collect())
+    }
+
+    #[cfg(not(feature = "sessions"))]
+    fn session_validators(&self, _session: Option<usize>) -> Option<Vec<(Scope, usize)>> {
+        None
+    }
+
+    /// Validators run in the order of the dependencies, unless session rules replace some of them
+    fn build_plan(&self, key: PlanKey) -> Plan {
+        let mut scoped: Vec<(Scope, &Self)> = Vec::new();
+        if let Some(position) = key.method {
+            let method = &self.scopes.method_rules[position].0;
+            scoped.extend(
+                self.scopes
+                    .method_rules
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (rules_method, _))| rules_method == method)
+                    .map(|(position, (_, rules))| (Scope::Method(position), rules)),
+            );
+        }
+        if let Some(position) = key.step {
+            let step = &self.scopes.steps[position].0;
+            scoped.extend(
+                self.scopes
+                    .steps
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (name, _))| name == step)
+                    .map(|(position, (_, rules))| (Scope::Step(position), rules)),
+            );
+        }
+        let scoped_rules: Vec<&Self> = scoped.iter().map(|(_, rules)| *rules).collect();
+
+        let scoped_validators = scoped.iter().flat_map(|&(scope, rules)| {
+            (0..rules.validators.len()).map(move |index| (scope, index))
+        });
+        let validators: Vec<(Scope, usize)> = match self.session_validators(key.session) {
+            Some(mut validators) => {
+                validators.extend(scoped_validators);
+                validators
+            }
+            None => {
+                let mut validators: Vec<(Scope, usize)> = (0..self.validators.len())
+                    .map(|index| (Scope::Own, index))
+                    .chain(scoped_validators)
+                    .collect();
+                if let Some(order) = &self.order {
+                    validators.sort_by_key(|&(scope, index)| {
+                        let field = self.scope_rules(scope).validators.entry_at(index).0;
+                        order
+                            .iter()
+                            .position(|ordered| ordered == field)
+                            .unwrap_or(usize::MAX)
+                    });
+                }
+                validators
+            }
+        };
+
+        let mut fields: Vec<PlannedField> = validators
+            .into_iter()
+            .map(|(scope, index)| {
+                let field = self.scope_rules(scope).validators.entry_at(index).0;
+                PlannedField {
+                    scope,
+                    target: Target::Field {
+                        index,
+                        policy: self.execution_policy_for(field, &scoped_rules),
+                        presence: self.presence_for(field, &scoped_rules),
+                        depends_on: self.dependencies.get(field).cloned().unwrap_or_default(),
+                        expands: match field {
+                            HttpField::BodyField(path) => {
+                                path.contains('*') || cfg!(feature = "xml")
+                            }
+                            HttpField::HeaderPattern(_) | HttpField::QueryParamPattern(_) => true,
+                            _ => false,
+                        },
+                    },
+                }
+            })
+            .collect();
+        for (scope, rules) in std::iter::once((Scope::Own, self)).chain(scoped) {
+            fields.extend(
+                (0..rules.global_validators.len()).map(|index| PlannedField {
+                    scope,
+                    target: Target::Global(index),
+                }),
+            );
+        }
+        Plan { fields }
+    }
+
+    fn scope_rules(&self, scope: Scope) -> &Self {
+        match scope {
+            Scope::Own => self,
+            #[cfg(feature = "sessions")]
+            Scope::Session(position) => &self.scopes.session_rules[position].1,
+            Scope::Method(position) => &self.scopes.method_rules[position].1,
+            Scope::Step(position) => &self.scopes.steps[position].1,
+        }
+    }
+
+    /// Field and rules of a planned field, without field for global validators
+    pub(crate) fn planned(
+        &self,
+        planned: &PlannedField,
+    ) -> (Option<&HttpField<'static>>, &[FieldRule<T, State>]) {
+        let rules = self.scope_rules(planned.scope);
+        match planned.target {
+            Target::Field { index, .. } => {
+                let (field, validators) = rules.validators.entry_at(index);
+                (Some(field), validators)
+            }
+            Target::Global(index) => (None, &rules.global_validators[index].1),
+        }
+    }
+
+    /// Fields with validators of the plan, in the order they run
+    pub(crate) fn planned_fields<'s>(
+        &'s self,
+        plan: &'s Plan,
+    ) -> impl Iterator<Item = &'s HttpField<'static>> {
+        plan.fields
+            .iter()
+            .filter_map(move |planned| self.planned(planned).0)
+    }
+
+    /// Fields of the request a planned field runs for: the field itself, or the fields it
+    /// matches for patterns, wildcards and global validators
+    pub(crate) fn planned_matches<'p>(
+        &'p self,
+        planned: &PlannedField,
+        ctx: &Request<State>,
+        values: &mut RequestValues<'_, State>,
+    ) -> impl Iterator<Item = Cow<'p, HttpField<'static>>> {
+        let rules = self.scope_rules(planned.scope);
+        let (field, matches) = match planned.target {
+            Target::Field { index, expands, .. } => {
+                let field = rules.validators.entry_at(index).0;
+                if expands {
+                    let matches = matching_fields(ctx, field, &mut values.query, values.body);
+                    (None, matches)
+                } else {
+                    (Some(Cow::Borrowed(field)), Vec::new())
+                }
+            }
+            Target::Global(index) => {
+                let location = rules.global_validators[index].0;
+                let fields = present_fields(ctx, location, &mut values.query, values.body);
+                (None, fields.into_iter().map(Cow::Owned).collect())
+            }
+        };
+        field.into_iter().chain(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    use crate::Location;
+
+    #[test]
+    fn plans() {
+        let mut step_rules = ValidatorMiddleware::new();
+        step_rules.add_validator(HttpField::body_field("address.city"), is_number);
+        let mut validators = ValidatorMiddleware::<String, ()>::new()
+            .step_field(HttpField::body_field("step"))
+            .step("address", step_rules);
+        validators.add_validator(HttpField::query_param("coupon"), is_number);
+        validators.add_validator(HttpField::query_param("product"), is_number);
+        validators.add_global_validator(Location::Header, is_number);
+        validators
+            .add_dependency(
+                HttpField::query_param("coupon"),
+                &[HttpField::query_param("product")],
+            )
+            .unwrap();
+
+        let key = PlanKey {
+            session: None,
+            method: None,
+            step: Some(0),
+        };
+        let plan = validators.cached_plan(key);
+        let fields: Vec<&HttpField<'static>> = validators.planned_fields(&plan).collect();
+        assert_eq!(
+            fields,
+            vec![
+                &HttpField::query_param("product"),
+                &HttpField::query_param("coupon"),
+                &HttpField::body_field("address.city"),
+            ]
+        );
+        match &plan.fields[2].target {
+            Target::Field { expands, .. } => assert!(*expands),
+            other => panic!("expected a field, got {:?}", other),
+        }
+        match &plan.fields[1].target {
+            Target::Field { depends_on, .. } => {
+                assert_eq!(depends_on, &vec![HttpField::query_param("product")])
+            }
+            other => panic!("expected a field, got {:?}", other),
+        }
+        assert!(matches!(plan.fields[3].target, Target::Global(0)));
+        assert_eq!(plan.fields.len(), 4);
+
+        // Requests using the same rules share the plan, clones build their own
+        assert!(Arc::ptr_eq(&plan, &validators.cached_plan(key)));
+        let unscoped = validators.cached_plan(PlanKey { step: None, ..key });
+        assert_eq!(validators.planned_fields(&unscoped).count(), 2);
+        let clone = validators.clone();
+        assert!(!Arc::ptr_eq(&plan, &clone.cached_plan(key)));
+    }
+}
@@ -0,0 +1,1746 @@
+//! Ready-made sets of validators for common routes, added to a middleware with
+//! [`ValidatorMiddleware::preset`](../struct.ValidatorMiddleware.html#method.preset).
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::presets::id_param;
+//!
+//! let validator_middleware = ValidatorMiddleware::new().preset(id_param("id"));
+//! app.at("/users/:id").middleware(validator_middleware).get(|req: Request<()>| async move {
+//!     let id = id_param("id").extract(&req);
+//!     // ...
+//! });
+//! ```
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::future::{self, FutureExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tide::http::Method;
+use tide::Request;
+
+use crate::locale;
+use crate::validators::{self, ValidationError};
+use crate::{
+    BoxedValidator, HttpField, Location, ValidationContext, ValidatorFn, ValidatorMiddleware,
+};
+
+/// A set of validators added at once to a middleware
+pub trait Preset<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Add the validators of the preset to the middleware
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>);
+}
+
+/// Named set of validators defined once and added to many middlewares with
+/// [`add_group`](../struct.ValidatorMiddleware.html#method.add_group), e.g. the rules of the
+/// pagination parameters of every listing route. Validators are shared, cloning a group is cheap.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let mut pagination = RuleGroup::new("pagination");
+/// pagination.add_validator(HttpField::query_param("page"), is_integer);
+/// pagination.add_validator(HttpField::query_param("per_page"), is_integer_between(1, 100));
+///
+/// users_middleware.add_group(&pagination);
+/// orders_middleware.add_group(&pagination);
+/// ```
+pub struct RuleGroup<T> {
+    name: String,
+    pub(crate) rules: Vec<(HttpField<'static>, ValidatorFn<T>)>,
+}
+
+impl<T> Clone for RuleGroup<T> {
+    fn clone(&self) -> Self {
+        RuleGroup {
+            name: self.name.clone(),
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for RuleGroup<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields: Vec<&HttpField<'static>> = self.rules.iter().map(|(field, _)| field).collect();
+        f.debug_struct("RuleGroup")
+            .field("name", &self.name)
+            .field("fields", &fields)
+            .finish()
+    }
+}
+
+impl<T> RuleGroup<T> {
+    /// Create an empty group
+    pub fn new(name: impl Into<String>) -> Self {
+        RuleGroup {
+            name: name.into(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Name of the group
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Add a validator to the group, validators of a field run in the order they're added
+    pub fn add_validator<F>(&mut self, param_name: HttpField<'static>, validator: F)
+    where
+        F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.rules
+            .push((param_name, std::sync::Arc::new(validator)));
+    }
+
+    /// Add the validators of another group, e.g. to build a `listing` group from `pagination` and `sorting`
+    pub fn add_group(&mut self, group: &RuleGroup<T>) {
+        self.rules.extend(group.rules.iter().cloned());
+    }
+}
+
+impl<T, State> Preset<T, State> for &RuleGroup<T>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        middleware.add_group(self);
+    }
+}
+
+/// Kind of identifiers accepted by [`id_param`](fn.id_param.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdKind {
+    /// Positive integer in canonical form: digits only, no sign and no leading zero
+    PositiveInteger,
+    /// Hyphenated UUID, in any case
+    Uuid,
+}
+
+/// Identifier parsed from a path parameter
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Id {
+    /// Positive integer identifier
+    Integer(u64),
+    /// UUID identifier, lowercased
+    Uuid(String),
+}
+
+/// Preset for the path parameter of the ubiquitous `/:id` pattern, see [`id_param`](fn.id_param.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdParam {
+    name: &'static str,
+    kind: IdKind,
+}
+
+/// Require the path parameter `name` to be a positive integer in canonical form,
+/// call [`uuid`](struct.IdParam.html#method.uuid) to accept UUIDs instead
+pub fn id_param(name: &'static str) -> IdParam {
+    IdParam {
+        name,
+        kind: IdKind::PositiveInteger,
+    }
+}
+
+impl IdParam {
+    /// Accept UUIDs instead of positive integers
+    pub fn uuid(mut self) -> Self {
+        self.kind = IdKind::Uuid;
+        self
+    }
+
+    /// Parsed identifier of a request which passed validation
+    pub fn extract<State>(&self, req: &Request<State>) -> Option<Id> {
+        let value = req.param::<String>(self.name).ok()?;
+        parse_id(self.kind, &value)
+    }
+}
+
+impl<T, State> Preset<T, State> for IdParam
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        let kind = self.kind;
+        middleware.add_validator(
+            HttpField::param(self.name),
+            move |field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+                validators::is_required(field_name, field_value)?;
+                match field_value {
+                    Some(value) if parse_id(kind, value).is_none() => {
+                        let expected = match kind {
+                            IdKind::PositiveInteger => "a positive integer",
+                            IdKind::Uuid => "a UUID",
+                        };
+                        Err(ValidationError::new(
+                            field_name,
+                            "id",
+                            format!("field '{}' = '{}' must be {}", field_name, value, expected),
+                        )
+                        .into())
+                    }
+                    _ => Ok(()),
+                }
+            },
+        );
+    }
+}
+
+/// Preset for search endpoints, see [`search_params`](fn.search_params.html)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchParams {
+    max_query_length: usize,
+    max_limit: i64,
+    sort: Vec<String>,
+}
+
+/// Validate the usual query parameters of a search endpoint:
+///
+/// - `q`: not blank, at most 256 characters and without control characters
+/// - `page`: integer starting at 1
+/// - `limit`: integer between 1 and 100
+/// - `sort`: one of the values given to [`sort`](struct.SearchParams.html#method.sort), not validated otherwise
+///
+/// Every parameter is optional.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let validator_middleware = ValidatorMiddleware::new().preset(
+///     search_params()
+///         .max_limit(50)
+///         .sort(&["name", "-created_at"]),
+/// );
+/// ```
+pub fn search_params() -> SearchParams {
+    SearchParams {
+        max_query_length: 256,
+        max_limit: 100,
+        sort: Vec::new(),
+    }
+}
+
+impl SearchParams {
+    /// Maximum length of `q` in characters
+    pub fn max_query_length(mut self, max_length: usize) -> Self {
+        self.max_query_length = max_length;
+        self
+    }
+
+    /// Maximum value of `limit`
+    pub fn max_limit(mut self, max_limit: i64) -> Self {
+        self.max_limit = max_limit;
+        self
+    }
+
+    /// Allowed values of `sort`
+    pub fn sort(mut self, allowed: &[&str]) -> Self {
+        self.sort = allowed.iter().map(|value| value.to_string()).collect();
+        self
+    }
+}
+
+impl<T, State> Preset<T, State> for SearchParams
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        let max_query_length = self.max_query_length;
+        add_converted(
+            middleware,
+            HttpField::query_param("q"),
+            Box::new(move |field_name: &str, field_value: Option<&str>| {
+                is_search_query(max_query_length, field_name, field_value)
+            }),
+        );
+        add_converted(
+            middleware,
+            HttpField::query_param("page"),
+            validators::is_integer_between(1, i64::MAX),
+        );
+        add_converted(
+            middleware,
+            HttpField::query_param("limit"),
+            validators::is_integer_between(1, self.max_limit),
+        );
+        if !self.sort.is_empty() {
+            let allowed: Vec<&str> = self.sort.iter().map(String::as_str).collect();
+            add_converted(
+                middleware,
+                HttpField::query_param("sort"),
+                validators::one_of(&allowed),
+            );
+        }
+    }
+}
+
+fn is_search_query(
+    max_length: usize,
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<(), ValidationError> {
+    let reason = match field_value {
+        None => return Ok(()),
+        Some(value) if value.trim().is_empty() => "must not be blank",
+        Some(value) if value.chars().any(char::is_control) => "must not contain control characters",
+        Some(_) => return validators::is_max_length(max_length)(field_name, field_value),
+    };
+    Err(ValidationError::new(
+        field_name,
+        "search",
+        format!("field '{}' {}", field_name, reason),
+    ))
+}
+
+/// Preset for file download endpoints, see [`download_params`](fn.download_params.html)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadParams {
+    max_ranges: usize,
+    dispositions: Vec<String>,
+}
+
+/// Validate the parameters of endpoints streaming files based on user supplied names:
+///
+/// - `filename` query parameter: required and safe, see below
+/// - `Range` header: optional, `bytes=` followed by at most 1 valid range
+/// - `disposition` query parameter: optional, `inline` or `attachment`
+///
+/// A safe filename is at most 255 bytes long, has no path separator, no control character, no
+/// leading dot or space, no trailing dot or space and isn't a reserved name on Windows like `CON`.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let validator_middleware = ValidatorMiddleware::new().preset(download_params().max_ranges(4));
+/// app.at("/download").middleware(validator_middleware).get(download);
+/// ```
+pub fn download_params() -> DownloadParams {
+    DownloadParams {
+        max_ranges: 1,
+        dispositions: vec![String::from("inline"), String::from("attachment")],
+    }
+}
+
+impl DownloadParams {
+    /// Maximum number of ranges in the `Range` header
+    pub fn max_ranges(mut self, max_ranges: usize) -> Self {
+        self.max_ranges = max_ranges;
+        self
+    }
+
+    /// Allowed values of `disposition`
+    pub fn dispositions(mut self, allowed: &[&str]) -> Self {
+        self.dispositions = allowed.iter().map(|value| value.to_string()).collect();
+        self
+    }
+}
+
+impl<T, State> Preset<T, State> for DownloadParams
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        add_converted(
+            middleware,
+            HttpField::query_param("filename"),
+            Box::new(is_safe_filename),
+        );
+        let max_ranges = self.max_ranges;
+        add_converted(
+            middleware,
+            HttpField::header("Range"),
+            Box::new(move |field_name: &str, field_value: Option<&str>| {
+                is_byte_range(max_ranges, field_name, field_value)
+            }),
+        );
+        let allowed: Vec<&str> = self.dispositions.iter().map(String::as_str).collect();
+        add_converted(
+            middleware,
+            HttpField::query_param("disposition"),
+            validators::one_of(&allowed),
+        );
+    }
+}
+
+fn is_safe_filename(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    validators::is_required(field_name, field_value)?;
+    let filename = field_value.unwrap_or_default();
+    let stem = filename.split('.').next().unwrap_or_default();
+    let reserved = ["CON", "PRN", "AUX", "NUL"]
+        .iter()
+        .any(|name| stem.eq_ignore_ascii_case(name))
+        || match (stem.len(), stem.get(..3)) {
+            (4, Some(device)) => {
+                (device.eq_ignore_ascii_case("COM") || device.eq_ignore_ascii_case("LPT"))
+                    && matches!(stem.as_bytes()[3], b'1'..=b'9')
+            }
+            _ => false,
+        };
+    let safe = !filename.is_empty()
+        && filename.len() <= 255
+        && !filename.starts_with('.')
+        && !filename.starts_with(' ')
+        && !filename.ends_with('.')
+        && !filename.ends_with(' ')
+        && !filename
+            .chars()
+            .any(|c| c.is_control() || c == '/' || c == '\\' || c == ':')
+        && !reserved;
+    if safe {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            field_name,
+            "filename",
+            format!(
+                "field '{}' = '{}' is not a safe filename",
+                field_name, filename
+            ),
+        ))
+    }
+}
+
+fn is_byte_range(
+    max_ranges: usize,
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<(), ValidationError> {
+    let value = match field_value {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    let parse = |bound: &str| -> Option<Option<u64>> {
+        match bound.trim() {
+            "" => Some(None),
+            bound if bound.bytes().all(|b| b.is_ascii_digit()) => bound.parse().ok().map(Some),
+            _ => None,
+        }
+    };
+    let valid = match value.strip_prefix("bytes=") {
+        Some(ranges) => {
+            let ranges: Vec<&str> = ranges.split(',').collect();
+            ranges.len() <= max_ranges
+                && ranges.iter().all(|range| {
+                    let mut bounds = range.splitn(2, '-');
+                    match (bounds.next().and_then(parse), bounds.next().and_then(parse)) {
+                        (Some(Some(start)), Some(Some(end))) => start <= end,
+                        (Some(Some(_)), Some(None)) | (Some(None), Some(Some(_))) => true,
+                        _ => false,
+                    }
+                })
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            field_name,
+            "range",
+            format!(
+                "field '{}' = '{}' is not a valid byte range",
+                field_name, value
+            ),
+        ))
+    }
+}
+
+/// Preset for the locale switch of a page, see [`locale_param`](fn.locale_param.html)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleParam {
+    name: &'static str,
+    supported: Vec<String>,
+    default: Option<String>,
+}
+
+/// Locale resolved by [`locale_param`](fn.locale_param.html), stored in request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    /// Value of the parameter, `None` when the request didn't ask for a locale
+    pub requested: Option<String>,
+    /// Supported tag to render the page with
+    pub resolved: String,
+}
+
+/// Validate the optional query parameter `name`, e.g. `lang` or `locale`, against supported
+/// BCP 47 tags. Tags are compared case-insensitively and a tag which isn't supported falls back
+/// on less specific tags: `fr-CA` resolves to `fr`, then to the first supported `fr-*` tag.
+/// Requests without the parameter get the default locale, the first supported tag unless
+/// [`default_locale`](struct.LocaleParam.html#method.default_locale) is called.
+///
+/// The resolved locale is stored in request extensions as a [`Locale`](struct.Locale.html).
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let validator_middleware = ValidatorMiddleware::new().preset(locale_param("lang", &["en-US", "fr", "de"]));
+/// app.at("/").middleware(validator_middleware).get(|req: Request<()>| async move {
+///     let locale = req.local::<Locale>().map(|locale| locale.resolved.as_str());
+///     // ...
+/// });
+/// ```
+pub fn locale_param(name: &'static str, supported: &[&str]) -> LocaleParam {
+    LocaleParam {
+        name,
+        supported: supported.iter().map(|tag| tag.to_string()).collect(),
+        default: None,
+    }
+}
+
+impl LocaleParam {
+    /// Locale of requests without the parameter
+    pub fn default_locale(mut self, tag: &str) -> Self {
+        self.default = Some(tag.to_string());
+        self
+    }
+
+    /// Supported tag matching `requested`, following the fallback rules of
+    /// [`locale_param`](fn.locale_param.html)
+    pub fn resolve(&self, requested: &str) -> Option<&str> {
+        if !locale::is_well_formed_language_tag(requested) {
+            return None;
+        }
+        let find = |tag: &str| {
+            self.supported
+                .iter()
+                .find(|supported| supported.eq_ignore_ascii_case(tag))
+        };
+        let mut tag = requested;
+        loop {
+            if let Some(supported) = find(tag) {
+                return Some(supported);
+            }
+            match tag.rfind('-') {
+                Some(end) => tag = &tag[..end],
+                None => break,
+            }
+        }
+        self.supported
+            .iter()
+            .find(|supported| {
+                matches!(supported.split('-').next(), Some(language) if language.eq_ignore_ascii_case(tag))
+            })
+            .map(String::as_str)
+    }
+}
+
+impl<T, State> Preset<T, State> for LocaleParam
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        let name = self.name;
+        middleware.add_resolver(
+            HttpField::query_param(name),
+            move |requested: Option<String>| {
+                let locale = match requested {
+                    Some(requested) => match self.resolve(&requested) {
+                        Some(resolved) => Ok(Locale {
+                            resolved: resolved.to_string(),
+                            requested: Some(requested),
+                        }),
+                        None => Err(ValidationError::new(
+                            name,
+                            "locale",
+                            format!(
+                                "field '{}' = '{}' must be one of: {}",
+                                name,
+                                requested,
+                                self.supported.join(", ")
+                            ),
+                        )
+                        .into()),
+                    },
+                    None => Ok(Locale {
+                        requested: None,
+                        resolved: self
+                            .default
+                            .clone()
+                            .or_else(|| self.supported.first().cloned())
+                            .unwrap_or_default(),
+                    }),
+                };
+                future::ready(locale)
+            },
+        );
+    }
+}
+
+/// Well-formed BCP 47 tag: a language of 2 to 3 or 5 to 8 letters followed by subtags of 1 to 8
+/// letters or digits, extensions and private use subtags aren't checked further
+/// Add a built-in validator to a middleware using another error type
+fn add_converted<T, State>(
+    middleware: &mut ValidatorMiddleware<T, State>,
+    field: HttpField<'static>,
+    validator: BoxedValidator<ValidationError>,
+) where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    middleware.add_validator(field, move |field_name: &str, field_value: Option<&str>| {
+        validator(field_name, field_value).map_err(T::from)
+    });
+}
+
+/// Preset for JSON Patch bodies, see [`json_patch`](fn.json_patch.html)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct JsonPatch {
+    allowed_paths: Vec<String>,
+}
+
+/// Validate `application/json-patch+json` bodies (RFC 6902) of `PATCH` endpoints:
+///
+/// - the `Content-Type` header is required and must be `application/json-patch+json`
+/// - the body is an array of operations whose `op` is `add`, `remove`, `replace`, `move`,
+///   `copy` or `test`
+/// - `path` is a valid JSON Pointer, as well as `from` for `move` and `copy`
+/// - `value` is given for `add`, `replace` and `test`
+/// - `path`, and `from` for `move`, are below a path given to
+///   [`allow_paths`](struct.JsonPatch.html#method.allow_paths) when it's called
+///
+/// Invalid bodies are reported in a single `json_patch` error on the body, whose parameters give
+/// the reason for each invalid operation by index.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let validator_middleware = ValidatorMiddleware::new().preset(json_patch().allow_paths(&["/name", "/tags"]));
+/// app.at("/users/:id").middleware(validator_middleware).patch(patch_user);
+/// // {"in": "body", "code": "json_patch", "params": {"1": "path '/role' can't be patched"}, ...}
+/// ```
+pub fn json_patch() -> JsonPatch {
+    JsonPatch::default()
+}
+
+impl JsonPatch {
+    /// Only allow patching these paths and their children, e.g. `/tags` allows `/tags/-`
+    pub fn allow_paths(mut self, paths: &[&str]) -> Self {
+        self.allowed_paths = paths.iter().map(|path| path.to_string()).collect();
+        self
+    }
+}
+
+impl<T, State> Preset<T, State> for JsonPatch
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        let allowed_paths = self.allowed_paths;
+        middleware.add_body_validator(
+            HttpField::header("Content-Type"),
+            move |body: &[u8], field_name: &str, field_value: Option<&str>| -> Result<(), T> {
+                validators::is_required(field_name, field_value)?;
+                let media_type = field_value
+                    .and_then(|value| value.split(';').next())
+                    .unwrap_or_default();
+                if !media_type
+                    .trim()
+                    .eq_ignore_ascii_case("application/json-patch+json")
+                {
+                    return Err(ValidationError::new(
+                        field_name,
+                        "content_type",
+                        format!("'{}' must be application/json-patch+json", field_name),
+                    )
+                    .into());
+                }
+                let patch = match serde_json::from_slice(body) {
+                    Ok(serde_json::Value::Array(operations)) => operations,
+                    _ => {
+                        return Err(ValidationError::new(
+                            "body",
+                            "json_patch",
+                            "body must be an array of JSON Patch operations",
+                        )
+                        .with_location(Location::Body)
+                        .into())
+                    }
+                };
+                let errors = json_patch_errors(&patch, &allowed_paths);
+                match errors.first() {
+                    None => Ok(()),
+                    Some((index, reason)) => {
+                        let err = ValidationError::new(
+                            "body",
+                            "json_patch",
+                            format!(
+                                "operation {} of the JSON Patch is invalid: {}",
+                                index, reason
+                            ),
+                        )
+                        .with_location(Location::Body);
+                        Err(errors
+                            .iter()
+                            .fold(err, |err, (index, reason)| {
+                                err.with_param(&index.to_string(), reason)
+                            })
+                            .into())
+                    }
+                }
+            },
+        );
+    }
+}
+
+/// Reason why each invalid operation of a JSON Patch is invalid, by index
+fn json_patch_errors(
+    patch: &[serde_json::Value],
+    allowed_paths: &[String],
+) -> Vec<(usize, String)> {
+    let is_allowed = |path: &str| {
+        allowed_paths.is_empty()
+            || allowed_paths.iter().any(|allowed| {
+                path == allowed
+                    || (path.starts_with(allowed.as_str())
+                        && path[allowed.len()..].starts_with('/'))
+            })
+    };
+    let pointer = |operation: &serde_json::Value, member: &str| -> Result<String, String> {
+        match operation.get(member) {
+            Some(serde_json::Value::String(pointer)) if is_json_pointer(pointer) => {
+                Ok(pointer.clone())
+            }
+            Some(_) => Err(format!("'{}' must be a JSON Pointer", member)),
+            None => Err(format!("'{}' is required", member)),
+        }
+    };
+
+    patch
+        .iter()
+        .enumerate()
+        .filter_map(|(index, operation)| {
+            let op = match operation.get("op").and_then(serde_json::Value::as_str) {
+                Some(op) => op,
+                None => return Some((index, String::from("'op' is required"))),
+            };
+            let check = || -> Result<(), String> {
+                let path = pointer(operation, "path")?;
+                match op {
+                    "add" | "replace" | "test" if operation.get("value").is_none() => {
+                        return Err(format!("'value' is required for '{}'", op))
+                    }
+                    "add" | "replace" | "test" | "remove" => {}
+                    "move" | "copy" => {
+                        let from = pointer(operation, "from")?;
+                        if op == "move" && !is_allowed(&from) {
+                            return Err(format!("path '{}' can't be patched", from));
+                        }
+                    }
+                    other => return Err(format!("'{}' isn't a JSON Patch operation", other)),
+                }
+                if op != "test" && !is_allowed(&path) {
+                    return Err(format!("path '{}' can't be patched", path));
+                }
+                Ok(())
+            };
+            check().err().map(|reason| (index, reason))
+        })
+        .collect()
+}
+
+/// Empty or made of `/` prefixed tokens where `~` only escapes `0` or `1`
+fn is_json_pointer(pointer: &str) -> bool {
+    (pointer.is_empty() || pointer.starts_with('/'))
+        && pointer
+            .split('~')
+            .skip(1)
+            .all(|escaped| escaped.starts_with('0') || escaped.starts_with('1'))
+}
+
+/// Preset for fetch metadata policies, see [`fetch_metadata`](fn.fetch_metadata.html)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchMetadata {
+    sites: Vec<String>,
+    modes: Vec<String>,
+    dests: Vec<String>,
+    cross_site_navigations: bool,
+    required: bool,
+}
+
+/// Check the fetch metadata headers sent by browsers (`Sec-Fetch-Site`, `Sec-Fetch-Mode` and
+/// `Sec-Fetch-Dest`) against a resource isolation policy, a complement to `Origin` checks:
+///
+/// - the headers must have values defined by the Fetch Metadata specification
+/// - `Sec-Fetch-Site` must be `same-origin`, `same-site` or `none` (typed URLs and bookmarks),
+///   change it with [`allow_sites`](struct.FetchMetadata.html#method.allow_sites) or
+///   [`same_origin_only`](struct.FetchMetadata.html#method.same_origin_only)
+/// - cross-site `GET` and `HEAD` navigations are allowed, except from `<object>` and `<embed>`,
+///   so links to the route keep working
+/// - requests without the headers are allowed since older browsers and other clients don't send
+///   them, call [`required`](struct.FetchMetadata.html#method.required) to reject them
+///
+/// Requests rejected by the policy get a `fetch_metadata` error on `Sec-Fetch-Site`.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let validator_middleware = ValidatorMiddleware::new().preset(fetch_metadata().same_origin_only());
+/// app.at("/api/transfers").middleware(validator_middleware).post(create_transfer);
+/// ```
+pub fn fetch_metadata() -> FetchMetadata {
+    FetchMetadata {
+        sites: vec![
+            String::from("same-origin"),
+            String::from("same-site"),
+            String::from("none"),
+        ],
+        modes: Vec::new(),
+        dests: Vec::new(),
+        cross_site_navigations: true,
+        required: false,
+    }
+}
+
+impl FetchMetadata {
+    /// Allowed values of `Sec-Fetch-Site`
+    pub fn allow_sites(mut self, sites: &[&str]) -> Self {
+        self.sites = sites.iter().map(|site| site.to_string()).collect();
+        self
+    }
+
+    /// Only allow requests made by pages of the same origin, without navigation exception
+    pub fn same_origin_only(self) -> Self {
+        self.allow_sites(&["same-origin"])
+            .allow_cross_site_navigations(false)
+    }
+
+    /// Allowed values of `Sec-Fetch-Mode`, any value is allowed by default
+    pub fn allow_modes(mut self, modes: &[&str]) -> Self {
+        self.modes = modes.iter().map(|mode| mode.to_string()).collect();
+        self
+    }
+
+    /// Allowed values of `Sec-Fetch-Dest`, any value is allowed by default
+    pub fn allow_dests(mut self, dests: &[&str]) -> Self {
+        self.dests = dests.iter().map(|dest| dest.to_string()).collect();
+        self
+    }
+
+    /// Whether cross-site `GET` and `HEAD` navigations are allowed, `true` by default
+    pub fn allow_cross_site_navigations(mut self, allow: bool) -> Self {
+        self.cross_site_navigations = allow;
+        self
+    }
+
+    /// Reject requests without `Sec-Fetch-Site`
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Why the policy rejects a request with these headers, `None` when it's allowed
+    fn rejection(
+        &self,
+        method: Method,
+        site: Option<&str>,
+        mode: Option<&str>,
+        dest: Option<&str>,
+    ) -> Option<String> {
+        let allowed = |allowed: &[String], value: Option<&str>| {
+            allowed.is_empty()
+                || value
+                    .into_iter()
+                    .all(|value| allowed.iter().any(|a| a == value))
+        };
+        if !allowed(&self.modes, mode) {
+            return Some(format!(
+                "fetch mode '{}' isn't allowed",
+                mode.unwrap_or_default()
+            ));
+        }
+        if !allowed(&self.dests, dest) {
+            return Some(format!(
+                "fetch destination '{}' isn't allowed",
+                dest.unwrap_or_default()
+            ));
+        }
+        let site = match site {
+            Some(site) => site,
+            None if self.required => return Some(String::from("fetch metadata is required")),
+            None => return None,
+        };
+        let navigation = self.cross_site_navigations
+            && (method == Method::Get || method == Method::Head)
+            && mode == Some("navigate")
+            && dest != Some("object")
+            && dest != Some("embed");
+        if self.sites.iter().any(|allowed| allowed == site) || navigation {
+            None
+        } else {
+            Some(format!("{} requests aren't allowed", site))
+        }
+    }
+}
+
+impl<T, State> Preset<T, State> for FetchMetadata
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        add_converted(
+            middleware,
+            HttpField::header("Sec-Fetch-Mode"),
+            Box::new(validators::is_fetch_mode),
+        );
+        add_converted(
+            middleware,
+            HttpField::header("Sec-Fetch-Dest"),
+            Box::new(validators::is_fetch_dest),
+        );
+        add_converted(
+            middleware,
+            HttpField::header("Sec-Fetch-Site"),
+            Box::new(validators::is_fetch_site),
+        );
+        middleware.add_context_validator(
+            HttpField::header("Sec-Fetch-Site"),
+            move |ctx: &ValidationContext<'_, State>,
+                  field_name: &str,
+                  field_value: Option<&str>| {
+                let rejection = self.rejection(
+                    ctx.method(),
+                    field_value,
+                    ctx.header("Sec-Fetch-Mode"),
+                    ctx.header("Sec-Fetch-Dest"),
+                );
+                match rejection {
+                    None => Ok(()),
+                    Some(reason) => Err(ValidationError::new(
+                        field_name,
+                        "fetch_metadata",
+                        format!("request blocked by the fetch metadata policy: {}", reason),
+                    )
+                    .with_param("allowed", self.sites.join(", "))
+                    .into()),
+                }
+            },
+        );
+    }
+}
+
+/// Preset for signed webhook receivers, see [`webhook`](fn.webhook.html)
+#[cfg(feature = "webhook")]
+#[derive(Clone)]
+pub struct Webhook {
+    secret: Vec<u8>,
+    signature_header: &'static str,
+    timestamp_header: &'static str,
+    tolerance: std::time::Duration,
+    content_type: &'static str,
+    max_body_size: usize,
+}
+
+#[cfg(feature = "webhook")]
+impl std::fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Webhook")
+            .field("signature_header", &self.signature_header)
+            .field("timestamp_header", &self.timestamp_header)
+            .field("tolerance", &self.tolerance)
+            .field("content_type", &self.content_type)
+            .field("max_body_size", &self.max_body_size)
+            .finish()
+    }
+}
+
+/// Verify signed webhooks, available with the `webhook` feature:
+///
+/// - `timestamp_header` must be a unix timestamp (in seconds) at most `tolerance` away from now
+/// - `signature_header` must be the hex encoded HMAC-SHA256 of `{timestamp}.{body}` with `secret`,
+///   optionally prefixed by `sha256=`
+/// - `Content-Type` must be `application/json`
+/// - the body must be at most 1 MiB
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let validator_middleware = ValidatorMiddleware::new().preset(webhook(
+///     std::env::var("WEBHOOK_SECRET")?,
+///     "X-Signature",
+///     "X-Timestamp",
+///     Duration::from_secs(5 * 60),
+/// ));
+/// app.at("/webhooks").middleware(validator_middleware).post(receive_webhook);
+/// ```
+#[cfg(feature = "webhook")]
+pub fn webhook(
+    secret: impl Into<Vec<u8>>,
+    signature_header: &'static str,
+    timestamp_header: &'static str,
+    tolerance: std::time::Duration,
+) -> Webhook {
+    Webhook {
+        secret: secret.into(),
+        signature_header,
+        timestamp_header,
+        tolerance,
+        content_type: "application/json",
+        max_body_size: 1024 * 1024,
+    }
+}
+
+#[cfg(feature = "webhook")]
+impl Webhook {
+    /// Expected media type of the body, `application/json` by default
+    pub fn content_type(mut self, content_type: &'static str) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    /// Maximum size of the body in bytes, 1 MiB by default
+    pub fn max_body_size(mut self, max_size: usize) -> Self {
+        self.max_body_size = max_size;
+        self
+    }
+}
+
+#[cfg(feature = "webhook")]
+impl<T, State> Preset<T, State> for Webhook
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        use std::str::FromStr;
+        use tide::http::headers::HeaderName;
+
+        middleware.limits.max_body_size = Some(self.max_body_size);
+
+        let content_type = self.content_type;
+        add_converted(
+            middleware,
+            HttpField::header("Content-Type"),
+            Box::new(move |field_name: &str, field_value: Option<&str>| {
+                validators::is_required(field_name, field_value)?;
+                match field_value {
+                    Some(value)
+                        if !value
+                            .split(';')
+                            .next()
+                            .unwrap_or_default()
+                            .trim()
+                            .eq_ignore_ascii_case(content_type) =>
+                    {
+                        Err(ValidationError::new(
+                            field_name,
+                            "content_type",
+                            format!("'{}' must be {}", field_name, content_type),
+                        ))
+                    }
+                    _ => Ok(()),
+                }
+            }),
+        );
+
+        let tolerance = self.tolerance;
+        add_converted(
+            middleware,
+            HttpField::header(self.timestamp_header),
+            Box::new(move |field_name: &str, field_value: Option<&str>| {
+                validators::is_required(field_name, field_value)?;
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|now| now.as_secs())
+                    .unwrap_or_default();
+                match field_value.and_then(|value| value.parse::<u64>().ok()) {
+                    Some(timestamp)
+                        if timestamp.max(now) - timestamp.min(now) <= tolerance.as_secs() =>
+                    {
+                        Ok(())
+                    }
+                    _ => Err(ValidationError::new(
+                        field_name,
+                        "timestamp",
+                        format!(
+                            "'{}' must be a unix timestamp less than {} seconds away",
+                            field_name,
+                            tolerance.as_secs()
+                        ),
+                    )),
+                }
+            }),
+        );
+
+        let secret = self.secret;
+        let timestamp_header = HeaderName::from_str(self.timestamp_header).ok();
+        middleware.add_request_body_validator(
+            HttpField::header(self.signature_header),
+            move |req: &Request<State>,
+                  body: &[u8],
+                  field_name: &str,
+                  field_value: Option<&str>| {
+                validators::is_required(field_name, field_value)?;
+                let timestamp = timestamp_header
+                    .as_ref()
+                    .and_then(|header| req.header(header))
+                    .and_then(|values| values.last())
+                    .map(|value| value.as_str())
+                    .unwrap_or_default();
+                if is_signed(&secret, timestamp, body, field_value.unwrap_or_default()) {
+                    Ok(())
+                } else {
+                    Err(ValidationError::new(
+                        field_name,
+                        "signature",
+                        format!("'{}' doesn't match the body", field_name),
+                    )
+                    .into())
+                }
+            },
+        );
+    }
+}
+
+#[cfg(feature = "webhook")]
+fn is_signed(secret: &[u8], timestamp: &str, body: &[u8], signature: &str) -> bool {
+    use hmac::{Hmac, Mac, NewMac};
+
+    let signature = signature.trim();
+    let signature = match hex::decode(signature.strip_prefix("sha256=").unwrap_or(signature)) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let mut mac = match Hmac::<sha2::Sha256>::new_varkey(secret) {
+        Ok(mac) => mac,
+        Err(_) => return false,
+    };
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.verify(&signature).is_ok()
+}
+
+/// Preset for `Authorization: Basic` credentials, see [`basic_auth`](fn.basic_auth.html)
+#[derive(Clone)]
+pub struct BasicAuth<V> {
+    verify: V,
+}
+
+impl<V> std::fmt::Debug for BasicAuth<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BasicAuth").finish()
+    }
+}
+
+/// Require `Authorization: Basic` credentials accepted by `verify`, an async function receiving
+/// the username and the password so it can check them against a store. The header is required,
+/// the error code is `basic_auth` when it's malformed and `credentials` when `verify` returns
+/// `false`.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let users = users.clone();
+/// let validator_middleware = ValidatorMiddleware::new().preset(basic_auth(move |username: String, password: String| {
+///     let users = users.clone();
+///     async move { users.check_password(&username, &password).await }
+/// }));
+/// ```
+pub fn basic_auth<V, Fut>(verify: V) -> BasicAuth<V>
+where
+    V: Fn(String, String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    BasicAuth { verify }
+}
+
+impl<T, State, V, Fut> Preset<T, State> for BasicAuth<V>
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+    V: Fn(String, String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        let verify = self.verify;
+        middleware.add_async_validator(
+            HttpField::header("Authorization"),
+            move |field_name: &str, field_value: Option<&str>| {
+                if let Err(err) = validators::is_required(field_name, field_value) {
+                    return future::ready(Err(err.into())).boxed();
+                }
+                let (username, password) = match basic_credentials(field_value.unwrap_or_default())
+                {
+                    Some(credentials) => credentials,
+                    None => {
+                        return future::ready(Err(ValidationError::new(
+                            field_name,
+                            "basic_auth",
+                            format!("'{}' must be basic credentials", field_name),
+                        )
+                        .into()))
+                        .boxed()
+                    }
+                };
+                let error =
+                    ValidationError::new(field_name, "credentials", "invalid username or password");
+                verify(username, password)
+                    .map(move |valid| if valid { Ok(()) } else { Err(error.into()) })
+                    .boxed()
+            },
+        );
+    }
+}
+
+/// Username and password of an `Authorization: Basic` header, `None` when it's malformed
+pub fn basic_credentials(value: &str) -> Option<(String, String)> {
+    let mut parts = value.trim().splitn(2, ' ');
+    if !parts.next()?.eq_ignore_ascii_case("Basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(decode_base64(parts.next()?.trim())?).ok()?;
+    let colon = decoded.find(':')?;
+    Some((
+        decoded[..colon].to_string(),
+        decoded[colon + 1..].to_string(),
+    ))
+}
+
+/// Decode standard base64 with optional padding, padding must complete the last group of four
+/// characters
+fn decode_base64(value: &str) -> Option<Vec<u8>> {
+    let padded = value;
+    let value = value.trim_end_matches('=');
+    let padding = padded.len() - value.len();
+    match (value.len() % 4, padding) {
+        (1, _) => return None,
+        (_, 0) | (2, 2) | (3, 1) => {}
+        _ => return None,
+    }
+    let mut bytes = Vec::with_capacity(value.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in value.bytes() {
+        let digit = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | u32::from(digit);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // Bits left after the last byte must be zero, or several encodings would decode the same
+    if buffer != 0 {
+        return None;
+    }
+    Some(bytes)
+}
+
+/// Preset for API keys sent in a header, see [`api_key`](fn.api_key.html)
+#[derive(Clone)]
+pub struct ApiKey<L> {
+    header: &'static str,
+    lookup: L,
+}
+
+impl<L> std::fmt::Debug for ApiKey<L> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("header", &self.header)
+            .finish()
+    }
+}
+
+/// Require an API key in `header` accepted by `lookup`, an async function so it can look the key
+/// up in a store. The header is required, the error code is `api_key` when `lookup` returns
+/// `false`.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let validator_middleware = ValidatorMiddleware::new().preset(api_key("X-Api-Key", move |key: String| {
+///     let redis = redis.clone();
+///     async move { redis.sismember("api_keys", key).await.unwrap_or(false) }
+/// }));
+/// ```
+pub fn api_key<L, Fut>(header: &'static str, lookup: L) -> ApiKey<L>
+where
+    L: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    ApiKey { header, lookup }
+}
+
+impl<T, State, L, Fut> Preset<T, State> for ApiKey<L>
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+    L: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        let lookup = self.lookup;
+        middleware.add_async_validator(
+            HttpField::header(self.header),
+            move |field_name: &str, field_value: Option<&str>| {
+                if let Err(err) = validators::is_required(field_name, field_value) {
+                    return future::ready(Err(err.into())).boxed();
+                }
+                let key = field_value.unwrap_or_default().trim().to_string();
+                let error = ValidationError::new(
+                    field_name,
+                    "api_key",
+                    format!("'{}' is not a valid API key", field_name),
+                );
+                lookup(key)
+                    .map(move |valid| if valid { Ok(()) } else { Err(error.into()) })
+                    .boxed()
+            },
+        );
+    }
+}
+
+/// Preset for cookies carrying a session payload, see [`session_cookie`](fn.session_cookie.html)
+pub struct SessionCookie<S> {
+    name: &'static str,
+    expiry_claim: Option<&'static str>,
+    checks: Vec<(&'static str, ValidatorFn<ValidationError>)>,
+    _session: PhantomData<fn() -> S>,
+}
+
+impl<S> Clone for SessionCookie<S> {
+    fn clone(&self) -> Self {
+        SessionCookie {
+            name: self.name,
+            expiry_claim: self.expiry_claim,
+            checks: self.checks.clone(),
+            _session: PhantomData,
+        }
+    }
+}
+
+impl<S> std::fmt::Debug for SessionCookie<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionCookie")
+            .field("name", &self.name)
+            .field("expiry_claim", &self.expiry_claim)
+            .field(
+                "checks",
+                &self.checks.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Validate the cookie `name` carrying a JSON session payload and give it to handlers as an `S`
+/// with [`req.validated::<S>(name)`](../trait.ValidatedRequestExt.html#tymethod.validated). The
+/// payload is JSON, percent-encoded or not, or JSON encoded in base64 or base64url. Its fields
+/// are checked with [`expires_at`](struct.SessionCookie.html#method.expires_at) and
+/// [`check`](struct.SessionCookie.html#method.check) before it's deserialized.
+///
+/// Error codes are `session` when the payload can't be decoded or deserialized and `expired`
+/// when the session has expired, checks give their own errors on the field `<name>.<path>`. A
+/// missing cookie is accepted, declare it with
+/// [`require`](../struct.ValidatorMiddleware.html#method.require) otherwise. Add the
+/// [`signed_cookie`](../cookies/fn.signed_cookie.html) codec to the cookie so clients can't forge
+/// sessions.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// #[derive(Deserialize)]
+/// struct Session {
+///     user_id: String,
+///     roles: Vec<String>,
+/// }
+///
+/// let mut validator_middleware = ValidatorMiddleware::new().preset(
+///     session_cookie::<Session>("session")
+///         .expires_at("exp")
+///         .check("user_id", is_uuid),
+/// );
+/// validator_middleware.require(HttpField::cookie("session"));
+/// app.at("/me").middleware(validator_middleware).get(|req: Request<()>| async move {
+///     let session = req.validated::<Session>("session").unwrap();
+///     // ...
+/// });
+/// ```
+pub fn session_cookie<S>(name: &'static str) -> SessionCookie<S>
+where
+    S: DeserializeOwned + Send + Sync + 'static,
+{
+    SessionCookie {
+        name,
+        expiry_claim: None,
+        checks: Vec::new(),
+        _session: PhantomData,
+    }
+}
+
+impl<S> SessionCookie<S>
+where
+    S: DeserializeOwned + Send + Sync + 'static,
+{
+    /// Require the field `claim` of the payload, a Unix timestamp in seconds, to be in the future
+    pub fn expires_at(mut self, claim: &'static str) -> Self {
+        self.expiry_claim = Some(claim);
+        self
+    }
+
+    /// Check the field at `path` of the payload with a validator, nested fields are separated by
+    /// dots like `user.id`. Strings are given as is, `null` and missing fields as `None` and other
+    /// values as JSON.
+    pub fn check<F>(mut self, path: &'static str, validator: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> Result<(), ValidationError> + Send + Sync + 'static,
+    {
+        self.checks.push((path, Arc::new(validator)));
+        self
+    }
+
+    /// Decode, check and deserialize the value of the cookie
+    pub fn parse(&self, value: &str) -> Result<S, ValidationError> {
+        let name = self.name;
+        let invalid = |reason: String| {
+            ValidationError::new(name, "session", format!("cookie '{}' {}", name, reason))
+        };
+        let value = value.trim();
+        let json = if value.starts_with('{') {
+            value.as_bytes().to_vec()
+        } else if value.starts_with("%7B") || value.starts_with("%7b") {
+            crate::percent_decode(value)
+        } else {
+            let standard = value.replace('-', "+").replace('_', "/");
+            decode_base64(&standard).ok_or_else(|| {
+                invalid(String::from(
+                    "must be a JSON or base64-encoded JSON payload",
+                ))
+            })?
+        };
+        let payload: serde_json::Value = serde_json::from_slice(&json).map_err(|_| {
+            invalid(String::from(
+                "must be a JSON or base64-encoded JSON payload",
+            ))
+        })?;
+
+        if let Some(claim) = self.expiry_claim {
+            let expires_at = payload
+                .get(claim)
+                .and_then(serde_json::Value::as_f64)
+                .ok_or_else(|| invalid(format!("must have a numeric '{}' field", claim)))?;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs_f64())
+                .unwrap_or_default();
+            if expires_at <= now {
+                return Err(ValidationError::new(
+                    name,
+                    "expired",
+                    format!("cookie '{}' has expired", name),
+                ));
+            }
+        }
+
+        for (path, validator) in &self.checks {
+            let pointer = format!("/{}", path.replace('.', "/"));
+            let field_value = match payload.pointer(&pointer) {
+                None | Some(serde_json::Value::Null) => None,
+                Some(serde_json::Value::String(value)) => Some(value.clone()),
+                Some(value) => Some(value.to_string()),
+            };
+            validator(&format!("{}.{}", name, path), field_value.as_deref())?;
+        }
+
+        serde_json::from_value(payload)
+            .map_err(|err| invalid(format!("has an invalid payload: {}", err)))
+    }
+}
+
+impl<T, State, S> Preset<T, State> for SessionCookie<S>
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+    S: DeserializeOwned + Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        middleware.add_parser(
+            HttpField::cookie(self.name),
+            move |_: &str, field_value: Option<&str>| match field_value {
+                Some(value) => self.parse(value).map(Some).map_err(T::from),
+                None => Ok(None),
+            },
+        );
+    }
+}
+
+fn parse_id(kind: IdKind, value: &str) -> Option<Id> {
+    match kind {
+        IdKind::PositiveInteger => {
+            if value.starts_with('0') || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            value.parse().ok().map(Id::Integer)
+        }
+        IdKind::Uuid => validators::is_uuid("", Some(value))
+            .ok()
+            .map(|_| Id::Uuid(value.to_lowercase())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[test]
+    fn session_cookies() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Session {
+            user_id: String,
+            exp: u64,
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let session = session_cookie::<Session>("session")
+            .expires_at("exp")
+            .check("user_id", validators::is_uuid);
+        let user_id = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let payload = format!(r#"{{"user_id": "{}", "exp": {}}}"#, user_id, now + 60);
+        let expected = Session {
+            user_id: user_id.to_string(),
+            exp: now + 60,
+        };
+        assert_eq!(session.parse(&payload), Ok(expected));
+        // {"user_id":"67e5…","exp":4102444800} percent-encoded, then in base64url without padding
+        let encoded = "%7B%22user_id%22%3A%2267e55044-10b1-426f-9247-bb680e5fe0c8%22%2C%22exp%22%3A4102444800%7D";
+        assert_eq!(session.parse(encoded).unwrap().exp, 4_102_444_800);
+        let base64 = "eyJ1c2VyX2lkIjoiNjdlNTUwNDQtMTBiMS00MjZmLTkyNDctYmI2ODBlNWZlMGM4IiwiZXhwIjo0MTAyNDQ0ODAwfQ";
+        assert_eq!(session.parse(base64).unwrap().exp, 4_102_444_800);
+
+        let expired = format!(r#"{{"user_id": "{}", "exp": {}}}"#, user_id, now - 60);
+        assert_eq!(session.parse(&expired).unwrap_err().code, "expired");
+        let err = session
+            .parse(&format!(r#"{{"user_id": "42", "exp": {}}}"#, now + 60))
+            .unwrap_err();
+        assert_eq!(err.field, "session.user_id");
+        assert_eq!(
+            session.parse(r#"{"user_id": "42"}"#).unwrap_err().code,
+            "session"
+        );
+        assert_eq!(session.parse("not a session!").unwrap_err().code, "session");
+        let err = session_cookie::<Session>("session")
+            .parse(r#"{"user_id": 42, "exp": 1}"#)
+            .unwrap_err();
+        assert_eq!(err.code, "session");
+    }
+
+    #[test]
+    fn fetch_metadata_policy() {
+        let policy = fetch_metadata();
+        assert_eq!(policy.rejection(Method::Post, None, None, None), None);
+        assert_eq!(
+            policy.rejection(Method::Post, Some("same-site"), Some("cors"), Some("empty")),
+            None
+        );
+        let navigation = (Some("navigate"), Some("document"));
+        assert_eq!(
+            policy.rejection(Method::Get, Some("cross-site"), navigation.0, navigation.1),
+            None
+        );
+        assert!(policy
+            .rejection(Method::Post, Some("cross-site"), navigation.0, navigation.1)
+            .is_some());
+        assert!(policy
+            .rejection(Method::Get, Some("cross-site"), navigation.0, Some("embed"))
+            .is_some());
+
+        let policy = fetch_metadata().same_origin_only().required();
+        assert!(policy.rejection(Method::Get, None, None, None).is_some());
+        assert!(policy
+            .rejection(Method::Get, Some("cross-site"), navigation.0, navigation.1)
+            .is_some());
+        let policy = fetch_metadata().allow_modes(&["cors"]);
+        assert_eq!(
+            policy.rejection(Method::Get, Some("same-origin"), Some("no-cors"), None),
+            Some(String::from("fetch mode 'no-cors' isn't allowed"))
+        );
+    }
+
+    #[test]
+    fn json_patches() {
+        let patch = serde_json::json!([
+            {"op": "add", "path": "/tags/-", "value": "cat"},
+            {"op": "replace", "path": "/name"},
+            {"op": "remove", "path": "/role"},
+            {"op": "move", "from": "/role", "path": "/name"},
+            {"op": "copy", "from": "/role", "path": "/name"},
+            {"op": "test", "path": "/role", "value": "admin"},
+            {"op": "add", "path": "name~2", "value": 1},
+            {"op": "delete", "path": "/name"},
+            {"path": "/name"},
+        ]);
+        let allowed_paths = vec![String::from("/name"), String::from("/tags")];
+        let errors = json_patch_errors(patch.as_array().unwrap(), &allowed_paths);
+        assert_eq!(
+            errors,
+            vec![
+                (1, String::from("'value' is required for 'replace'")),
+                (2, String::from("path '/role' can't be patched")),
+                (3, String::from("path '/role' can't be patched")),
+                (6, String::from("'path' must be a JSON Pointer")),
+                (7, String::from("'delete' isn't a JSON Patch operation")),
+                (8, String::from("'op' is required")),
+            ]
+        );
+        assert_eq!(json_patch_errors(patch.as_array().unwrap(), &[]).len(), 4);
+        assert!(is_json_pointer("/a~1b/~0c"));
+        assert!(!is_json_pointer("/a~b"));
+    }
+
+    #[test]
+    fn ids() {
+        assert_eq!(
+            parse_id(IdKind::PositiveInteger, "42"),
+            Some(Id::Integer(42))
+        );
+        assert_eq!(parse_id(IdKind::PositiveInteger, "042"), None);
+        assert_eq!(parse_id(IdKind::PositiveInteger, "0"), None);
+        assert_eq!(parse_id(IdKind::PositiveInteger, "+42"), None);
+        assert_eq!(parse_id(IdKind::PositiveInteger, ""), None);
+        assert_eq!(
+            parse_id(IdKind::Uuid, "67E55044-10B1-426F-9247-BB680E5FE0C8"),
+            Some(Id::Uuid(String::from(
+                "67e55044-10b1-426f-9247-bb680e5fe0c8"
+            )))
+        );
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(decode_base64("").as_deref(), Some(&b""[..]));
+        assert_eq!(decode_base64("QQ==").as_deref(), Some(&b"A"[..]));
+        assert_eq!(decode_base64("QQ").as_deref(), Some(&b"A"[..]));
+        assert_eq!(decode_base64("QUI=").as_deref(), Some(&b"AB"[..]));
+        assert_eq!(decode_base64("QUI").as_deref(), Some(&b"AB"[..]));
+        assert_eq!(decode_base64("QUJD").as_deref(), Some(&b"ABC"[..]));
+        assert_eq!(
+            decode_base64("+/+/").as_deref(),
+            Some(&[0xfb, 0xff, 0xbf][..])
+        );
+        // Padding must complete the last group
+        assert_eq!(decode_base64("QQ="), None);
+        assert_eq!(decode_base64("QQ==="), None);
+        assert_eq!(decode_base64("QUJD===="), None);
+        assert_eq!(decode_base64("QQ==QQ=="), None);
+        // A single character left can't encode a byte
+        assert_eq!(decode_base64("QUJDR"), None);
+        assert_eq!(decode_base64("Q"), None);
+        // Bits left after the last byte must be zero
+        assert_eq!(decode_base64("YQ==").as_deref(), Some(&b"a"[..]));
+        assert_eq!(decode_base64("YR=="), None);
+        assert_eq!(decode_base64("YWI=").as_deref(), Some(&b"ab"[..]));
+        assert_eq!(decode_base64("YWJ="), None);
+        // Only the standard alphabet is accepted
+        assert_eq!(decode_base64("QU-_"), None);
+        assert_eq!(decode_base64("QU I="), None);
+        assert_eq!(decode_base64("é"), None);
+    }
+
+    #[test]
+    fn basic_auth_credentials() {
+        assert_eq!(
+            basic_credentials("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="),
+            Some((String::from("Aladdin"), String::from("open sesame")))
+        );
+        assert_eq!(
+            basic_credentials("basic dXNlcjo6cGFzcw"),
+            Some((String::from("user"), String::from(":pass")))
+        );
+        assert_eq!(
+            basic_credentials("Bearer QWxhZGRpbjpvcGVuIHNlc2FtZQ=="),
+            None
+        );
+        assert_eq!(basic_credentials("Basic bm8gY29sb24="), None);
+        assert_eq!(basic_credentials("Basic not base64!"), None);
+    }
+
+    #[cfg(feature = "webhook")]
+    #[test]
+    fn webhook_signature() {
+        // echo -n '1589373000.{"event":"paid"}' | openssl dgst -sha256 -hmac secret
+        let signature = "sha256=0378d3198c37874b06049c156ce8bd9f3ae7d7b72017aae007b7bf9384956fa1";
+        assert!(is_signed(
+            b"secret",
+            "1589373000",
+            br#"{"event":"paid"}"#,
+            signature
+        ));
+        assert!(!is_signed(
+            b"secret",
+            "1589373001",
+            br#"{"event":"paid"}"#,
+            signature
+        ));
+        assert!(!is_signed(
+            b"other",
+            "1589373000",
+            br#"{"event":"paid"}"#,
+            signature
+        ));
+        assert!(!is_signed(b"secret", "1589373000", b"", "not hex"));
+    }
+
+    #[test]
+    fn locales() {
+        let locale = locale_param("lang", &["en-US", "fr", "pt-BR"]);
+        assert_eq!(locale.resolve("fr"), Some("fr"));
+        assert_eq!(locale.resolve("FR-ca"), Some("fr"));
+        assert_eq!(locale.resolve("en"), Some("en-US"));
+        assert_eq!(locale.resolve("pt-br"), Some("pt-BR"));
+        assert_eq!(locale.resolve("de"), None);
+        assert_eq!(locale.resolve("fr_FR"), None);
+        assert_eq!(locale.resolve("f"), None);
+        assert_eq!(locale.resolve(""), None);
+    }
+
+    #[test]
+    fn download() {
+        assert!(is_safe_filename("filename", Some("report 2020.pdf")).is_ok());
+        assert!(is_safe_filename("filename", None).is_err());
+        assert!(is_safe_filename("filename", Some("../etc/passwd")).is_err());
+        assert!(is_safe_filename("filename", Some(".htaccess")).is_err());
+        assert!(is_safe_filename("filename", Some("com1.txt")).is_err());
+        assert!(is_safe_filename("filename", Some("éé")).is_ok());
+        assert!(is_byte_range(1, "Range", Some("bytes=0-499")).is_ok());
+        assert!(is_byte_range(1, "Range", Some("bytes=-500")).is_ok());
+        assert!(is_byte_range(1, "Range", Some("bytes=500-")).is_ok());
+        assert!(is_byte_range(1, "Range", Some("bytes=0-1,4-5")).is_err());
+        assert!(is_byte_range(1, "Range", Some("bytes=5-1")).is_err());
+        assert!(is_byte_range(1, "Range", Some("bytes=-")).is_err());
+        assert!(is_byte_range(1, "Range", Some("items=0-1")).is_err());
+    }
+
+    #[test]
+    fn search_query() {
+        assert!(is_search_query(5, "q", Some("cats")).is_ok());
+        assert!(is_search_query(5, "q", None).is_ok());
+        assert!(is_search_query(5, "q", Some("  ")).is_err());
+        assert!(is_search_query(5, "q", Some("ca\nts")).is_err());
+        assert_eq!(
+            is_search_query(5, "q", Some("kittens")).unwrap_err().code,
+            "max"
+        );
+    }
+}
@@ -0,0 +1,761 @@
+//! What the middleware tells downstream middlewares and handlers about a valid request
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tide::Request;
+
+use crate::field_rule::ParsedValue;
+use crate::plan::Plan;
+use crate::{trace, HttpField, NormalizedValues, ValidatorMiddleware};
+
+/// Name and version of this crate, stamped on validated requests
+pub(crate) const VALIDATED_BY: &str = concat!("tide-validator/", env!("CARGO_PKG_VERSION"));
+
+/// Stamp added in request extensions when a request passed validation and stamping is enabled
+/// with [`stamp_validated_requests`](struct.ValidatorMiddleware.html#method.stamp_validated_requests).
+/// Retrieve it with `req.local::<Validated>()`. Stacked middlewares add their fields to the stamp
+/// of the previous ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validated {
+    /// Validator which validated the request, e.g. `tide-validator/0.8.0`
+    pub by: &'static str,
+    /// Fields which have been validated
+    pub fields: Vec<HttpField<'static>>,
+    /// Outcome of each validated field, with its final value and the validators which admitted it
+    pub outcomes: Vec<FieldOutcome>,
+}
+
+/// Values parsed by the parsers added with [`add_parser`](struct.ValidatorMiddleware.html#method.add_parser),
+/// added in request extensions when at least one field has been parsed. Read them with
+/// [`ValidatedRequestExt::validated`](trait.ValidatedRequestExt.html#tymethod.validated).
+/// Values parsed by stacked middlewares are merged.
+#[derive(Clone, Default)]
+pub struct ParsedValues(pub(crate) HashMap<HttpField<'static>, ParsedValue>);
+
+impl ParsedValues {
+    /// Parsed value of a field, `None` if the field hasn't been parsed or has been parsed into another type
+    pub fn get<V: 'static>(&self, field: &HttpField<'static>) -> Option<&V> {
+        self.0.get(field).and_then(|value| value.downcast_ref())
+    }
+
+    /// Parsed value of the first field named `name`, whatever its location
+    pub fn get_by_name<V: 'static>(&self, name: &str) -> Option<&V> {
+        self.0
+            .iter()
+            .filter(|(field, _)| field.name() == name)
+            .find_map(|(_, value)| value.downcast_ref())
+    }
+}
+
+impl std::fmt::Debug for ParsedValues {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Failure of a validator added with [`Severity::Warning`](enum.Severity.html#variant.Warning)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// Field which failed the validator
+    pub field: HttpField<'static>,
+    /// Serialized error of the validator
+    pub error: serde_json::Value,
+}
+
+impl Warning {
+    /// Message of the error: the error itself when it's a string, its `message` member when
+    /// it's an object, the error as JSON otherwise
+    pub fn message(&self) -> String {
+        match (&self.error, self.error.get("message")) {
+            (serde_json::Value::String(message), _)
+            | (_, Some(serde_json::Value::String(message))) => message.clone(),
+            (error, _) => error.to_string(),
+        }
+    }
+
+    /// Value of the warning header: a warn-value for the `Warning` header, the bare message for
+    /// other headers, with the characters which can't be sent in headers escaped
+    pub(crate) fn header_value(&self, warn_value: bool) -> String {
+        if warn_value {
+            // Quoted and escaped like a JSON string
+            let message = serde_json::to_string(&self.message()).unwrap_or_default();
+            trace::escape_header_value(&format!("299 - {}", message))
+        } else {
+            trace::escape_header_value(&self.message())
+        }
+    }
+}
+
+/// Warnings of the validators added with [`Severity::Warning`](enum.Severity.html#variant.Warning),
+/// added in request extensions when at least one validator warned. Warnings of stacked
+/// middlewares are merged.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// if let Some(warnings) = req.local::<ValidationWarnings>() {
+///     for warning in warnings.iter() {
+///         log::warn!("deprecated usage: {}", warning.message());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationWarnings(pub(crate) Vec<Warning>);
+
+impl ValidationWarnings {
+    /// Warnings in the order validators ran
+    pub fn iter(&self) -> impl Iterator<Item = &Warning> {
+        self.0.iter()
+    }
+
+    /// Warnings of a field
+    pub fn get<'w>(&'w self, field: &'w HttpField<'_>) -> impl Iterator<Item = &'w Warning> {
+        self.0.iter().filter(move |warning| warning.field == *field)
+    }
+
+    /// Whether no validator warned
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Read values parsed during validation from a request
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// validator_middleware.add_parser(HttpField::param("age"), parsed::<u32, String>());
+/// app.at("/age/:age").middleware(validator_middleware).get(|req: Request<()>| async move {
+///     let age: u32 = *req.validated::<u32>("age").unwrap();
+///     // ...
+/// });
+/// ```
+pub trait ValidatedRequestExt {
+    /// Value of the field named `name` parsed into `V`, `None` if the field hasn't been parsed
+    /// into this type
+    fn validated<V: Send + Sync + 'static>(&self, name: &str) -> Option<&V>;
+}
+
+impl<State> ValidatedRequestExt for Request<State> {
+    fn validated<V: Send + Sync + 'static>(&self, name: &str) -> Option<&V> {
+        self.local::<ParsedValues>()?.get_by_name(name)
+    }
+}
+
+/// What the middlewares validated for a request, given to handlers wrapped with
+/// [`handler::with_validation`](handler/fn.with_validation.html)
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    /// Fields which have been validated, empty unless
+    /// [`stamp_validated_requests`](struct.ValidatorMiddleware.html#method.stamp_validated_requests) is enabled
+    pub fields: Vec<HttpField<'static>>,
+    /// Values replaced by normalizers
+    pub normalized: NormalizedValues,
+    /// Values parsed by parsers
+    pub parsed: ParsedValues,
+    /// Warnings of validators added with [`Severity::Warning`](enum.Severity.html#variant.Warning)
+    pub warnings: ValidationWarnings,
+    /// Outcome of each field validated by the middleware, in the order fields were validated.
+    /// Given to [`after_validation`](struct.ValidatorMiddleware.html#method.after_validation)
+    /// hooks, and to handlers when
+    /// [`stamp_validated_requests`](struct.ValidatorMiddleware.html#method.stamp_validated_requests)
+    /// is enabled.
+    pub outcomes: Vec<FieldOutcome>,
+}
+
+/// Outcome of the validators of a field, see [`ValidationReport`](struct.ValidationReport.html)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldOutcome {
+    /// Validated field, a concrete path for body fields with wildcards
+    pub field: HttpField<'static>,
+    /// Whether the field passed its validators
+    pub status: FieldStatus,
+    /// Value of the field once normalized, `None` when it's missing or for body fields validated
+    /// with [`each_item`](struct.ValidatorMiddleware.html#method.each_item)
+    pub value: Option<String>,
+    /// Names or descriptions of the validators which passed, e.g. to audit which authentication
+    /// validator admitted the request. Validators without name nor description aren't listed.
+    pub passed: Vec<String>,
+}
+
+/// Whether a field passed its validators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldStatus {
+    /// Every validator passed
+    Passed,
+    /// A validator failed, the request is rejected
+    Failed,
+    /// Only validators added with [`Severity::Warning`](enum.Severity.html#variant.Warning) failed
+    Warned,
+}
+
+impl ValidationReport {
+    /// Collect the report from the request extensions
+    pub fn from_request<State>(req: &Request<State>) -> Self {
+        ValidationReport {
+            fields: req
+                .local::<Validated>()
+                .map(|validated| validated.fields.clone())
+                .unwrap_or_default(),
+            normalized: req.local::<NormalizedValues>().cloned().unwrap_or_default(),
+            parsed: req.local::<ParsedValues>().cloned().unwrap_or_default(),
+            warnings: req
+                .local::<ValidationWarnings>()
+                .cloned()
+                .unwrap_or_default(),
+            outcomes: req
+                .local::<Validated>()
+                .map(|validated| validated.outcomes.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Outcome of a field validated by the middleware, `None` if it hasn't been validated
+    pub fn outcome(&self, field: &HttpField<'_>) -> Option<&FieldOutcome> {
+        self.outcomes.iter().find(|outcome| outcome.field == *field)
+    }
+
+    /// Status of a field validated by the middleware, `None` if it hasn't been validated
+    pub fn status(&self, field: &HttpField<'_>) -> Option<FieldStatus> {
+        self.outcomes
+            .iter()
+            .find(|outcome| outcome.field == *field)
+            .map(|outcome| outcome.status)
+    }
+
+    /// Whether a field failed its validators
+    pub fn is_valid(&self) -> bool {
+        self.outcomes
+            .iter()
+            .all(|outcome| outcome.status != FieldStatus::Failed)
+    }
+
+    /// Value of the field named `name` parsed into `V`, like
+    /// [`ValidatedRequestExt::validated`](trait.ValidatedRequestExt.html#tymethod.validated)
+    pub fn validated<V: Send + Sync + 'static>(&self, name: &str) -> Option<&V> {
+        self.parsed.get_by_name(name)
+    }
+}
+
+pub(crate) type AfterValidationHook<State> =
+    Arc<dyn Fn(&ValidationReport, &mut Request<State>) + Send + Sync + 'static>;
+
+/// Outcome of each validated field, then of the failed fields of group validators and array items
+pub(crate) fn field_outcomes(
+    validated: &[&HttpField<'static>],
+    failed: &[&HttpField<'static>],
+    warnings: &[Warning],
+) -> Vec<FieldOutcome> {
+    let mut outcomes: Vec<FieldOutcome> = Vec::new();
+    let warned = warnings.iter().map(|warning| &warning.field);
+    for field in validated
+        .iter()
+        .chain(failed)
+        .copied()
+        .chain(warned.clone())
+    {
+        if outcomes.iter().any(|outcome| outcome.field == *field) {
+            continue;
+        }
+        let status = if failed.contains(&field) {
+            FieldStatus::Failed
+        } else if warned.clone().any(|warned| warned == field) {
+            FieldStatus::Warned
+        } else {
+            FieldStatus::Passed
+        };
+        outcomes.push(FieldOutcome {
+            field: field.clone(),
+            status,
+            value: None,
+            passed: Vec::new(),
+        });
+    }
+    outcomes
+}
+
+/// What the middleware reports about the validation, to the handler and in the response
+pub(crate) struct ReportSettings<State> {
+    pub(crate) after_validation: Option<AfterValidationHook<State>>,
+    pub(crate) stamp_validated_requests: bool,
+    pub(crate) warning_header: Option<String>,
+    pub(crate) validated_header: Option<String>,
+}
+
+impl<State> Default for ReportSettings<State> {
+    fn default() -> Self {
+        ReportSettings {
+            after_validation: None,
+            stamp_validated_requests: false,
+            warning_header: None,
+            validated_header: None,
+        }
+    }
+}
+
+impl<State> Clone for ReportSettings<State> {
+    fn clone(&self) -> Self {
+        ReportSettings {
+            after_validation: self.after_validation.clone(),
+            stamp_validated_requests: self.stamp_validated_requests,
+            warning_header: self.warning_header.clone(),
+            validated_header: self.validated_header.clone(),
+        }
+    }
+}
+
+impl<State> ReportSettings<State> {
+    pub(crate) fn merge(&mut self, other: ReportSettings<State>) {
+        self.after_validation = other.after_validation.or(self.after_validation.take());
+        self.stamp_validated_requests |= other.stamp_validated_requests;
+        self.warning_header = other.warning_header.or(self.warning_header.take());
+        self.validated_header = other.validated_header.or(self.validated_header.take());
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Send the warnings of validators added with [`Severity::Warning`](enum.Severity.html#variant.Warning)
+    /// in the header `name` of responses to valid requests, one value per warning. The `Warning`
+    /// header gets warn-values as defined by [RFC 7234](https://tools.ietf.org/html/rfc7234#section-5.5),
+    /// e.g. `299 - "'page_size' is deprecated, use 'per_page'"`, other headers the bare messages.
+    /// Non-ASCII and control characters are escaped like in JSON strings, e.g. `\u00e9`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().warning_header("Warning");
+    /// ```
+    pub fn warning_header(mut self, name: &str) -> Self {
+        self.report.warning_header = Some(name.to_string());
+        self
+    }
+
+    /// Call `hook` once the fields of a request are validated, with the
+    /// [`ValidationReport`](struct.ValidationReport.html) of the fields which passed, failed or
+    /// warned, before the handler runs or the error response is sent. It's the place to attach
+    /// audit metadata, annotate spans or add extensions for the handler.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().after_validation(|report, req| {
+    ///     tracing::Span::current().record("valid", &report.is_valid());
+    ///     if let Some(audit) = req.local_mut::<AuditLog>() {
+    ///         audit.validated = report.outcomes.len();
+    ///     }
+    /// });
+    /// ```
+    pub fn after_validation<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ValidationReport, &mut Request<State>) + Send + Sync + 'static,
+    {
+        self.report.after_validation = Some(Arc::new(hook));
+        self
+    }
+
+    /// Add a [`Validated`](struct.Validated.html) stamp in the request extensions when the request is valid,
+    /// so downstream middlewares and endpoints can trust that validation happened
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().stamp_validated_requests();
+    /// // ... then in an endpoint or a following middleware
+    /// let validated = req.local::<Validated>().is_some();
+    /// ```
+    pub fn stamp_validated_requests(mut self) -> Self {
+        self.report.stamp_validated_requests = true;
+        self
+    }
+
+    /// Set the header `name` to the name and version of this crate, e.g.
+    /// `X-Validated: tide-validator/0.8.0`, on valid requests before they are given to the next
+    /// middlewares and the endpoint, and on their responses. Services the request is forwarded to
+    /// and clients can see that validation happened. A value sent by the client is overwritten.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().validated_header("X-Validated");
+    /// ```
+    pub fn validated_header(mut self, name: &str) -> Self {
+        self.report.validated_header = Some(name.to_string());
+        self
+    }
+
+    /// Mark the request as validated by the middleware, with the fields it validated and their
+    /// outcomes added to those of the middlewares stacked before it
+    pub(crate) fn stamp(
+        &self,
+        ctx: Request<State>,
+        plan: &Plan,
+        outcomes: &[FieldOutcome],
+    ) -> Request<State> {
+        let (mut fields, mut all_outcomes) = ctx
+            .local::<Validated>()
+            .map(|validated| (validated.fields.clone(), validated.outcomes.clone()))
+            .unwrap_or_default();
+        for field in self.planned_fields(plan) {
+            if !fields.contains(field) {
+                fields.push(field.clone());
+            }
+        }
+        all_outcomes.extend(outcomes.iter().cloned());
+        ctx.set_local(Validated {
+            by: VALIDATED_BY,
+            fields,
+            outcomes: all_outcomes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    use crate::{validators, Severity, ValidationError};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use std::sync::Mutex;
+    use tide::http::{Method, Request};
+    use tide::StatusCode;
+
+    #[test]
+    fn validator_warnings() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().warning_header("Warning");
+        validators.add_validator_with_severity(
+            HttpField::query_param("page_size"),
+            Severity::Warning,
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some(_) => Err(format!("'{}' is deprecated, use 'per_page'", field_name)),
+                None => Ok(()),
+            },
+        );
+        validators.add_validator(HttpField::query_param("page_size"), is_number);
+        inner
+            .at("/items")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                let warnings = req
+                    .local::<ValidationWarnings>()
+                    .cloned()
+                    .unwrap_or_default();
+                Ok(warnings.iter().count().to_string())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/items?page_size=20".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(
+            res.header(&"Warning".parse().unwrap()).unwrap()[0].as_str(),
+            r#"299 - "'page_size' is deprecated, use 'per_page'""#
+        );
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&*buf, &*b"1");
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/items?page_size=a".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let req = Request::new(Method::Get, "http://localhost/items".parse().unwrap());
+        let res = server.simulate(req).unwrap();
+        assert!(res.header(&"Warning".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn warning_messages() {
+        let warning = |error| Warning {
+            field: HttpField::query_param("page_size"),
+            error,
+        };
+        assert_eq!(
+            warning(serde_json::json!("deprecated")).message(),
+            "deprecated"
+        );
+        assert_eq!(
+            warning(serde_json::json!({"code": "deprecated", "message": "use per_page"})).message(),
+            "use per_page"
+        );
+        assert_eq!(warning(serde_json::json!(42)).message(), "42");
+
+        let accented = warning(serde_json::json!(
+            "« page_size » est obsolète\nutilisez per_page"
+        ));
+        assert_eq!(
+            accented.header_value(true),
+            r#"299 - "\u00ab page_size \u00bb est obsol\u00e8te\nutilisez per_page""#
+        );
+        assert_eq!(
+            accented.header_value(false),
+            r#"\u00ab page_size \u00bb est obsol\u00e8te\u000autilisez per_page"#
+        );
+        assert!(accented
+            .header_value(false)
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() || byte == b' '));
+    }
+
+    #[test]
+    fn validator_after_validation() {
+        let audit = Arc::new(Mutex::new(Vec::new()));
+        let mut inner = tide::new();
+        let hook_audit = audit.clone();
+        let mut validators = ValidatorMiddleware::new()
+            .collect_all_errors(true)
+            .after_validation(move |report, _| {
+                hook_audit.lock().unwrap().extend(
+                    report
+                        .outcomes
+                        .iter()
+                        .map(|outcome| (outcome.field.name().to_string(), outcome.status)),
+                );
+            });
+        validators.add_validator(HttpField::query_param("page"), is_number);
+        validators.add_validator(HttpField::query_param("size"), is_number);
+        inner
+            .at("/items")
+            .middleware(validators)
+            .get(|_| async { Ok("items") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/items?page=2".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/items?size=a".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert_eq!(
+            *audit.lock().unwrap(),
+            vec![
+                (String::from("page"), FieldStatus::Passed),
+                (String::from("size"), FieldStatus::Passed),
+                (String::from("page"), FieldStatus::Passed),
+                (String::from("size"), FieldStatus::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn validator_presence() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.require(HttpField::query_param("token"));
+        validators.optional(HttpField::query_param("page"));
+        validators.add_validator(
+            HttpField::query_param("page"),
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some(value) if value.parse::<u32>().is_ok() => Ok(()),
+                _ => Err(ValidationError::new(
+                    field_name,
+                    "page",
+                    format!("'{}' must be a page number", field_name),
+                )),
+            },
+        );
+        inner
+            .at("/items")
+            .middleware(validators)
+            .get(|_| async { Ok("items") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (url, status) in &[
+            ("/items?token=abc", StatusCode::Ok),
+            ("/items?token=abc&page=2", StatusCode::Ok),
+            ("/items?token=abc&page=two", StatusCode::BadRequest),
+            ("/items?page=2", StatusCode::BadRequest),
+        ] {
+            let url = format!("http://localhost{}", url);
+            let req = Request::new(Method::Get, url.parse().unwrap());
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/items".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let errors: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(errors[0]["code"], "required");
+    }
+
+    #[test]
+    fn validator_report_in_handler() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().stamp_validated_requests();
+        validators.add_described_validator(
+            HttpField::header("X-Api-Key"),
+            "partner api key",
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some("secret") => Ok(()),
+                _ => Err(format!("'{}' is not a valid key", field_name)),
+            },
+        );
+        validators.add_normalizer(HttpField::query_param("email"), |_, value: Option<&str>| {
+            Ok(value.map(|email| email.trim().to_lowercase()))
+        });
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                let report = ValidationReport::from_request(&req);
+                let key = report.outcome(&HttpField::header("X-Api-Key")).unwrap();
+                let email = report.outcome(&HttpField::query_param("email")).unwrap();
+                Ok(format!(
+                    "{} {}",
+                    key.passed.join(","),
+                    email.value.as_deref().unwrap_or_default()
+                ))
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(
+            Method::Get,
+            "http://localhost/foo?email=Foo@Bar.com".parse().unwrap(),
+        );
+        req.insert_header("X-Api-Key", "secret").unwrap();
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), "partner api key foo@bar.com");
+    }
+
+    #[test]
+    fn validator_stamp() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .stamp_validated_requests()
+            .validated_header("X-Validated");
+        validators.add_validator(HttpField::query_param("age"), is_number);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                let validated = req.local::<Validated>().unwrap();
+                let header = req.header(&"X-Validated".parse().unwrap()).unwrap();
+                Ok(format!(
+                    "{} {} {:?}",
+                    validated.by,
+                    header[0].as_str(),
+                    validated.fields
+                ))
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Get, "http://localhost/foo?age=4".parse().unwrap());
+        req.insert_header("X-Validated", "spoofed").unwrap();
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(
+            res.header(&"X-Validated".parse().unwrap()).unwrap()[0].as_str(),
+            VALIDATED_BY
+        );
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            format!(
+                "{} {} {:?}",
+                VALIDATED_BY,
+                VALIDATED_BY,
+                vec![HttpField::query_param("age")]
+            )
+        );
+
+        let req = Request::new(Method::Get, "http://localhost/foo?age=a".parse().unwrap());
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert!(res.header(&"X-Validated".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn outcomes_of_fields() {
+        let (page, size, sort) = (
+            HttpField::query_param("page"),
+            HttpField::query_param("size"),
+            HttpField::query_param("sort"),
+        );
+        let items = HttpField::body_field("items[1].sku");
+        let warnings = vec![Warning {
+            field: size.clone(),
+            error: serde_json::json!("deprecated"),
+        }];
+        let outcomes = field_outcomes(&[&page, &size, &sort], &[&sort, &items], &warnings);
+        let statuses: Vec<(&str, FieldStatus)> = outcomes
+            .iter()
+            .map(|outcome| (outcome.field.name(), outcome.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("page", FieldStatus::Passed),
+                ("size", FieldStatus::Warned),
+                ("sort", FieldStatus::Failed),
+                ("items[1].sku", FieldStatus::Failed),
+            ]
+        );
+
+        let report = ValidationReport {
+            outcomes,
+            ..ValidationReport::default()
+        };
+        assert_eq!(report.status(&size), Some(FieldStatus::Warned));
+        assert_eq!(report.status(&HttpField::header("X-Tenant")), None);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn validator_parsed_values() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_parser(HttpField::param("age"), validators::parsed::<u32, String>());
+        inner
+            .at("/age/:age")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                let age = req.validated::<u32>("age").copied().unwrap_or_default();
+                Ok(format!("{}", age + 1))
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/age/41".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&*buf, &*b"42");
+
+        let req = Request::new(Method::Get, "http://localhost/age/-1".parse().unwrap());
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+    }
+}
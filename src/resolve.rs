@@ -0,0 +1,341 @@
+//! Async lookups run once per request before validators, like the tenant
+
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+use tide::{Request, Response};
+
+use crate::values::RequestValues;
+use crate::{FieldRule, HttpField, ValidatorMiddleware};
+
+pub(crate) type LocalStore<State> =
+    Box<dyn FnOnce(Request<State>) -> Request<State> + Send + 'static>;
+pub(crate) type ResolverFn<T, State> = Arc<
+    dyn Fn(Option<String>) -> BoxFuture<'static, Result<LocalStore<State>, T>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+/// Async lookup run once per request, its result is stored in request extensions
+pub(crate) struct SharedResolver<T, State> {
+    pub(crate) field: HttpField<'static>,
+    pub(crate) resolve: ResolverFn<T, State>,
+    /// Resolver of the tenant, set with `tenant_field`
+    tenant: bool,
+}
+
+impl<T, State> Clone for SharedResolver<T, State> {
+    fn clone(&self) -> Self {
+        SharedResolver {
+            field: self.field.clone(),
+            resolve: Arc::clone(&self.resolve),
+            tenant: self.tenant,
+        }
+    }
+}
+
+impl<T: 'static, State: 'static> SharedResolver<T, State> {
+    pub(crate) fn new<R, Res, Fut>(field: HttpField<'static>, resolver: Res) -> Self
+    where
+        R: Send + Sync + 'static,
+        Res: Fn(Option<String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, T>> + Send + 'static,
+    {
+        let resolve: ResolverFn<T, State> = Arc::new(move |field_value: Option<String>| {
+            let resolved = resolver(field_value);
+            Box::pin(async move {
+                let resolved = resolved.await?;
+                let store: LocalStore<State> =
+                    Box::new(move |req: Request<State>| req.set_local(resolved));
+                Ok(store)
+            })
+        });
+        SharedResolver {
+            field,
+            resolve,
+            tenant: false,
+        }
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Resolve the tenant of the request from `field` before running any validator. The resolver
+    /// receives the field value and validates it asynchronously, an error rejects the request.
+    /// The resolved tenant is stored in request extensions (`req.local::<Tenant>()`) and validators
+    /// added with [`add_tenant_validator`](#method.add_tenant_validator) can use it. Calling it
+    /// again replaces the previous tenant field and resolver.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut validator_middleware = ValidatorMiddleware::new().tenant_field(
+    ///     HttpField::header("X-Tenant-Id"),
+    ///     |tenant_id: Option<String>| async move {
+    ///         match tenant_id {
+    ///             Some(tenant_id) => tenants::find(&tenant_id).await.ok_or_else(|| format!("unknown tenant '{}'", tenant_id)),
+    ///             None => Err(String::from("'X-Tenant-Id' is required")),
+    ///         }
+    ///     },
+    /// );
+    /// validator_middleware.add_tenant_validator(HttpField::query_param("limit"), |tenant: &Tenant, field_name: &str, field_value: Option<&str>| {
+    ///     is_max(tenant.max_limit as f64)(field_name, field_value).map_err(String::from)
+    /// });
+    /// ```
+    pub fn tenant_field<Ten, R, Fut>(mut self, field: HttpField<'static>, resolver: R) -> Self
+    where
+        Ten: Send + Sync + 'static,
+        R: Fn(Option<String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Ten, T>> + Send + 'static,
+    {
+        // The tenant is resolved before any other shared resolver
+        self.resolvers.retain(|resolver| !resolver.tenant);
+        let mut tenant = SharedResolver::new(field, resolver);
+        tenant.tenant = true;
+        self.resolvers.insert(0, tenant);
+        self
+    }
+
+    /// Add a validator using the tenant resolved with [`tenant_field`](#method.tenant_field),
+    /// e.g. to apply per-tenant limits. The validator is skipped when no tenant has been resolved.
+    pub fn add_tenant_validator<Ten, F>(&mut self, param_name: HttpField<'static>, validator: F)
+    where
+        Ten: Send + Sync + 'static,
+        F: Fn(&Ten, &str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.add_resolved_validator(param_name, validator);
+    }
+
+    /// Add an async lookup shared by several validators, e.g. one token introspection call. The
+    /// resolver receives the value of `field` and runs once per request before any validator, an
+    /// error rejects the request. The resolved value is stored in request extensions and passed to
+    /// the validators added with [`add_resolved_validator`](#method.add_resolved_validator).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// validator_middleware.add_resolver(HttpField::header("Authorization"), |token: Option<String>| async move {
+    ///     introspect(token).await.map_err(|err| format!("invalid token: {}", err))
+    /// });
+    /// validator_middleware.add_resolved_validator(HttpField::param("account"), |token: &TokenInfo, field_name: &str, field_value: Option<&str>| {
+    ///     match field_value {
+    ///         Some(account) if !token.accounts.iter().any(|a| a == account) => Err(format!("'{}' is not accessible", field_name)),
+    ///         _ => Ok(()),
+    ///     }
+    /// });
+    /// ```
+    pub fn add_resolver<R, Res, Fut>(&mut self, field: HttpField<'static>, resolver: Res)
+    where
+        R: Send + Sync + 'static,
+        Res: Fn(Option<String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, T>> + Send + 'static,
+    {
+        self.resolvers.push(SharedResolver::new(field, resolver));
+    }
+
+    /// Add a validator using the value of a resolver added with [`add_resolver`](#method.add_resolver).
+    /// The validator is skipped when the value hasn't been resolved.
+    pub fn add_resolved_validator<R, F>(&mut self, param_name: HttpField<'static>, validator: F)
+    where
+        R: Send + Sync + 'static,
+        F: Fn(&R, &str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        self.field_rules(param_name)
+            .push(FieldRule::Request(Arc::new(
+                move |req: &Request<State>, field_name: &str, field_value: Option<&str>| match req
+                    .local::<R>()
+                {
+                    Some(resolved) => validator(resolved, field_name, field_value),
+                    None => Ok(()),
+                },
+            )));
+    }
+
+    /// Run the resolvers in order, each one stores its value in the request for the next ones. A
+    /// resolver error gives the response.
+    pub(crate) async fn resolve(
+        &self,
+        mut ctx: Request<State>,
+        values: &mut RequestValues<'_, State>,
+    ) -> Result<Request<State>, Response> {
+        for resolver in &self.resolvers {
+            let resolver_input = values.raw(&ctx, &resolver.field);
+            match (resolver.resolve)(resolver_input).await {
+                Ok(store) => ctx = store(ctx),
+                Err(err) => {
+                    self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(self.error_response(
+                        &ctx,
+                        vec![(&resolver.field, None, err)],
+                        None,
+                    ));
+                }
+            }
+        }
+        Ok(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{validators, ValidationError};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+    use tide::http::{Method, Request};
+    use tide::StatusCode;
+
+    #[test]
+    fn validator_shared_resolver() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_resolver(HttpField::header("X-Max"), move |max: Option<String>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                max.and_then(|max| max.parse::<usize>().ok())
+                    .ok_or_else(|| String::from("'X-Max' is invalid"))
+            }
+        });
+        for field in &[HttpField::query_param("a"), HttpField::query_param("b")] {
+            validators.add_resolved_validator(
+                field.clone(),
+                |max: &usize, field_name: &str, field_value: Option<&str>| match field_value {
+                    Some(value) if value.len() > *max => {
+                        Err(format!("'{}' is too long", field_name))
+                    }
+                    _ => Ok(()),
+                },
+            );
+        }
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(
+            Method::Get,
+            "http://localhost/foo?a=ab&b=abc".parse().unwrap(),
+        );
+        req.insert_header("X-Max", "2").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn validator_tenants() {
+        struct Tenant {
+            max_limit: f64,
+        }
+
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::<ValidationError>::new().tenant_field(
+            HttpField::header("X-Tenant-Id"),
+            |tenant_id: Option<String>| async move {
+                match tenant_id.as_deref() {
+                    Some("free") => Ok(Tenant { max_limit: 10.0 }),
+                    Some("pro") => Ok(Tenant { max_limit: 100.0 }),
+                    _ => Err(ValidationError::new(
+                        "X-Tenant-Id",
+                        "tenant",
+                        "unknown tenant",
+                    )),
+                }
+            },
+        );
+        validators.add_tenant_validator(
+            HttpField::query_param("limit"),
+            |tenant: &Tenant, field_name: &str, field_value: Option<&str>| {
+                validators::is_max(tenant.max_limit)(field_name, field_value)
+            },
+        );
+        inner
+            .at("/items")
+            .middleware(validators)
+            .get(|_| async { Ok("items") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let cases = [
+            ("free", 10, StatusCode::Ok),
+            ("free", 50, StatusCode::BadRequest),
+            ("pro", 50, StatusCode::Ok),
+            ("pro", 150, StatusCode::BadRequest),
+            ("other", 1, StatusCode::BadRequest),
+        ];
+        for (tenant, limit, status) in cases.iter() {
+            let url = format!("http://localhost/items?limit={}", limit);
+            let mut req = Request::new(Method::Get, url.parse().unwrap());
+            req.insert_header("X-Tenant-Id", *tenant).unwrap();
+            let mut res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status, "{} {}", tenant, limit);
+            if *status == StatusCode::BadRequest {
+                let mut buf = Vec::new();
+                block_on(res.read_to_end(&mut buf)).unwrap();
+                let err: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+                let code = if *tenant == "other" { "tenant" } else { "max" };
+                assert_eq!(err.code, code);
+            }
+        }
+    }
+
+    #[test]
+    fn validator_tenant_field_replaced() {
+        #[derive(Debug)]
+        struct Tenant(String);
+
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .tenant_field(HttpField::header("X-Tenant"), |_: Option<String>| async {
+                Err::<Tenant, _>(String::from("replaced resolver"))
+            })
+            .tenant_field(
+                HttpField::query_param("tenant"),
+                |tenant: Option<String>| async {
+                    tenant
+                        .map(Tenant)
+                        .ok_or_else(|| String::from("'tenant' is required"))
+                },
+            );
+        validators.add_resolver(HttpField::header("X-Max"), |max: Option<String>| async {
+            Ok(max.unwrap_or_default())
+        });
+        assert_eq!(validators.resolvers.len(), 2);
+        assert_eq!(
+            validators.resolvers[0].field,
+            HttpField::query_param("tenant")
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|req: tide::Request<()>| async move {
+                Ok(req.local::<Tenant>().unwrap().0.clone())
+            });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?tenant=acme".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(&*buf, &*b"acme");
+    }
+}
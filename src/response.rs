@@ -0,0 +1,1429 @@
+//! Error responses: their status, shape, format and messages
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tide::{Request, Response, StatusCode};
+
+#[cfg(any(feature = "log", feature = "tracing"))]
+use crate::events;
+use crate::i18n;
+use crate::normalize::DecodedValues;
+use crate::report::field_outcomes;
+use crate::values::header_values;
+use crate::{
+    validators, AnyError, HttpField, Location, ValidationError, ValidationReport, ValidatorError,
+    ValidatorMiddleware,
+};
+
+/// Shape of the body of error responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorShape {
+    /// The serialized error itself, or an array of errors when several are reported (default)
+    Plain,
+    /// An object mapping field names to the array of their errors, e.g.
+    /// `{"age": ["'age' is required"]}`, the format expected by many client form libraries
+    GroupedByField,
+    /// Problem Details as defined by [RFC 7807](https://tools.ietf.org/html/rfc7807), sent as
+    /// `application/problem+json` with an `invalid-params` member listing every error, e.g.
+    /// `{"type": "about:blank", "title": "Your request parameters didn't validate.", "status": 400,
+    /// "detail": "'age' is required", "invalid-params": [{"name": "age", "in": "query", "reason": "'age' is required"}]}`.
+    /// Reasons are the serialized errors, or their `message` when they're objects.
+    Problem,
+}
+
+// `#[default]` on variants needs a newer compiler than the one supported
+#[allow(clippy::derivable_impls)]
+impl Default for ErrorShape {
+    fn default() -> Self {
+        ErrorShape::Plain
+    }
+}
+
+/// Serialization format of the body of error responses, sent with its `Content-Type`, see
+/// [`response_format`](struct.ValidatorMiddleware.html#method.response_format)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// JSON, as `application/json` or `application/problem+json` for
+    /// [`ErrorShape::Problem`](enum.ErrorShape.html#variant.Problem) (default)
+    Json,
+    /// Messages of the errors, one per line, as `text/plain; charset=utf-8`
+    Text,
+    /// MessagePack, as `application/msgpack`, with the `msgpack` feature
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    /// The format preferred by the `Accept` header of the request, JSON when the request accepts
+    /// any format or none of them
+    Negotiated,
+}
+
+// `#[default]` on variants needs a newer compiler than the one supported
+#[allow(clippy::derivable_impls)]
+impl Default for ResponseFormat {
+    fn default() -> Self {
+        ResponseFormat::Json
+    }
+}
+
+/// Status code and body of error responses, globally and by location of the failing field
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// // Unknown resources get a bare 404, other errors a 422 with the serialized errors
+/// let policy = ResponsePolicy::new()
+///     .status(StatusCode::UnprocessableEntity)
+///     .for_location(Location::Path, StatusCode::NotFound, false);
+/// let validator_middleware = ValidatorMiddleware::new().response_policy(policy);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponsePolicy {
+    status: StatusCode,
+    include_errors: bool,
+    locations: HashMap<Location, (StatusCode, bool)>,
+}
+
+impl Default for ResponsePolicy {
+    fn default() -> Self {
+        ResponsePolicy {
+            status: StatusCode::BadRequest,
+            include_errors: true,
+            locations: HashMap::new(),
+        }
+    }
+}
+
+impl ResponsePolicy {
+    /// `400 Bad Request` with the serialized errors in the body, whatever the field
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Status code of error responses
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Send the serialized errors in the body, an empty body otherwise
+    pub fn include_errors(mut self, include_errors: bool) -> Self {
+        self.include_errors = include_errors;
+        self
+    }
+
+    /// Status code and body of error responses when the first failing field is at `location`
+    pub fn for_location(
+        mut self,
+        location: Location,
+        status: StatusCode,
+        include_errors: bool,
+    ) -> Self {
+        self.locations.insert(location, (status, include_errors));
+        self
+    }
+
+    /// Status code and whether the errors are sent for a failing field
+    fn response_for(&self, field: &HttpField<'_>) -> (StatusCode, bool) {
+        self.locations
+            .get(&field.location())
+            .copied()
+            .unwrap_or((self.status, self.include_errors))
+    }
+}
+
+pub(crate) type ErrorHook<T> = Arc<dyn Fn(&T) -> Response + Send + Sync + 'static>;
+pub(crate) type ErrorAggregator<T> =
+    Arc<dyn Fn(Vec<T>) -> serde_json::Result<serde_json::Value> + Send + Sync + 'static>;
+pub(crate) type ValidatorErrorHook =
+    Arc<dyn Fn(&HttpField<'_>, &ValidatorError) -> Response + Send + Sync + 'static>;
+
+/// Error of a field with the description of the rule which failed
+pub(crate) type FieldError<'a, T> = (&'a HttpField<'a>, Option<&'a str>, T);
+
+/// Error found by the middleware itself rather than by a validator, e.g. a body which doesn't
+/// parse, with the status of the response
+pub(crate) type Rejection = (StatusCode, HttpField<'static>, ValidationError);
+
+/// Errors found by the middleware itself as errors of its error type `T`, when it's
+/// `ValidationError` or `AnyError`, so they reach the hooks typed by `T`
+#[allow(clippy::type_complexity)]
+pub(crate) fn rule_errors<'a, T, E>(
+    errors: Vec<FieldError<'a, E>>,
+) -> Result<Vec<FieldError<'a, T>>, Vec<FieldError<'a, E>>>
+where
+    T: 'static,
+    E: Serialize + Send + Sync + 'static,
+{
+    let erased = TypeId::of::<T>() == TypeId::of::<AnyError>()
+        && TypeId::of::<E>() != TypeId::of::<AnyError>();
+    if !erased && TypeId::of::<T>() != TypeId::of::<E>() {
+        return Err(errors);
+    }
+    Ok(errors
+        .into_iter()
+        .map(|(field, description, err)| {
+            let err: Box<dyn Any> = if erased {
+                Box::new(AnyError::new(err))
+            } else {
+                Box::new(err)
+            };
+            let err = err.downcast::<T>().expect("errors are of the error type");
+            (field, description, *err)
+        })
+        .collect())
+}
+
+/// Fill the placeholders of a message override
+pub(crate) fn render_message(
+    template: &str,
+    field_name: &str,
+    params: Option<&serde_json::Value>,
+) -> String {
+    let mut message = template.replace("{field}", field_name);
+    if let Some(params) = params.and_then(serde_json::Value::as_object) {
+        for (name, value) in params {
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                other => other.to_string(),
+            };
+            message = message.replace(&format!("{{{}}}", name), &value);
+        }
+    }
+    message
+}
+
+pub(crate) fn truncate_strings(value: &mut serde_json::Value, max_length: usize) {
+    match value {
+        serde_json::Value::String(s) if s.chars().count() > max_length => {
+            *s = s.chars().take(max_length).collect::<String>() + "...";
+        }
+        serde_json::Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| truncate_strings(value, max_length)),
+        serde_json::Value::Object(map) => map
+            .values_mut()
+            .for_each(|value| truncate_strings(value, max_length)),
+        _ => {}
+    }
+}
+
+/// Format of error responses preferred by an `Accept` header, JSON when it accepts any format or
+/// none of the supported ones. Weights are read like
+/// [`require_accepts`](validators/fn.require_accepts.html) does, JSON then text win ties.
+pub(crate) fn negotiate_format(accept: &str) -> ResponseFormat {
+    let formats: &[(ResponseFormat, &[&str])] = &[
+        (
+            ResponseFormat::Json,
+            &["application/json", "application/problem+json"],
+        ),
+        (ResponseFormat::Text, &["text/plain"]),
+        #[cfg(feature = "msgpack")]
+        (
+            ResponseFormat::MessagePack,
+            &["application/msgpack", "application/x-msgpack"],
+        ),
+    ];
+    let mut preferred: Option<(ResponseFormat, f32)> = None;
+    for (format, media_types) in formats {
+        let weight = media_types
+            .iter()
+            .filter_map(|media_type| validators::accept_weight(accept, media_type))
+            .fold(0.0, f32::max);
+        if weight > 0.0 && !matches!(preferred, Some((_, preferred)) if weight <= preferred) {
+            preferred = Some((*format, weight));
+        }
+    }
+    preferred.map(|(format, _)| format).unwrap_or_default()
+}
+
+/// Messages of the errors of a JSON error body, whatever its shape: the `message` of error
+/// objects, the `reason` of the invalid parameters of problems, other values serialized
+pub(crate) fn error_messages(body: &serde_json::Value) -> Vec<String> {
+    match body {
+        serde_json::Value::String(message) => vec![message.clone()],
+        serde_json::Value::Array(errors) => errors.iter().flat_map(error_messages).collect(),
+        serde_json::Value::Object(fields) => {
+            if let Some(serde_json::Value::String(message)) = fields.get("message") {
+                vec![message.clone()]
+            } else if let Some(invalid_params) = fields.get("invalid-params") {
+                error_messages(invalid_params)
+            } else if let Some(reason) = fields.get("reason") {
+                error_messages(reason)
+            } else {
+                fields.values().flat_map(error_messages).collect()
+            }
+        }
+        value => vec![value.to_string()],
+    }
+}
+
+/// Settings of the error responses
+pub(crate) struct ResponseSettings<T> {
+    pub(crate) max_errors_per_field: Option<usize>,
+    pub(crate) max_error_message_length: Option<usize>,
+    pub(crate) message_overrides: HashMap<String, String>,
+    pub(crate) translations: i18n::Translations,
+    pub(crate) include_examples: bool,
+    pub(crate) error_shape: ErrorShape,
+    pub(crate) response_format: ResponseFormat,
+    pub(crate) response_policy: ResponsePolicy,
+    pub(crate) on_error: Option<ErrorHook<T>>,
+    pub(crate) aggregate_errors: Option<ErrorAggregator<T>>,
+    pub(crate) on_validator_error: Option<ValidatorErrorHook>,
+}
+
+impl<T> Default for ResponseSettings<T> {
+    fn default() -> Self {
+        ResponseSettings {
+            max_errors_per_field: None,
+            max_error_message_length: None,
+            message_overrides: HashMap::new(),
+            translations: i18n::Translations::new(),
+            include_examples: false,
+            error_shape: ErrorShape::default(),
+            response_format: ResponseFormat::default(),
+            response_policy: ResponsePolicy::default(),
+            on_error: None,
+            aggregate_errors: None,
+            on_validator_error: None,
+        }
+    }
+}
+
+impl<T> Clone for ResponseSettings<T> {
+    fn clone(&self) -> Self {
+        ResponseSettings {
+            max_errors_per_field: self.max_errors_per_field,
+            max_error_message_length: self.max_error_message_length,
+            message_overrides: self.message_overrides.clone(),
+            translations: self.translations.clone(),
+            include_examples: self.include_examples,
+            error_shape: self.error_shape,
+            response_format: self.response_format,
+            response_policy: self.response_policy.clone(),
+            on_error: self.on_error.clone(),
+            aggregate_errors: self.aggregate_errors.clone(),
+            on_validator_error: self.on_validator_error.clone(),
+        }
+    }
+}
+
+impl<T> ResponseSettings<T> {
+    /// Settings of `other` replace these ones, unless they're the defaults
+    pub(crate) fn merge(&mut self, other: ResponseSettings<T>) {
+        self.max_errors_per_field = other.max_errors_per_field.or(self.max_errors_per_field);
+        self.max_error_message_length = other
+            .max_error_message_length
+            .or(self.max_error_message_length);
+        self.message_overrides.extend(other.message_overrides);
+        if !other.translations.is_empty() {
+            self.translations = other.translations;
+        }
+        self.include_examples |= other.include_examples;
+        if other.error_shape != ErrorShape::default() {
+            self.error_shape = other.error_shape;
+        }
+        if other.response_format != ResponseFormat::default() {
+            self.response_format = other.response_format;
+        }
+        if other.response_policy != ResponsePolicy::default() {
+            self.response_policy = other.response_policy;
+        }
+        self.on_error = other.on_error.or(self.on_error.take());
+        self.aggregate_errors = other.aggregate_errors.or(self.aggregate_errors.take());
+        self.on_validator_error = other.on_validator_error.or(self.on_validator_error.take());
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Limit the number of errors reported for a single field (at least 1), useful when several
+    /// errors are reported for the same field so a pathological value can't produce a huge response
+    pub fn max_errors_per_field(mut self, max_errors: usize) -> Self {
+        self.response.max_errors_per_field = Some(max_errors.max(1));
+        self
+    }
+
+    /// Truncate every string contained in a serialized error to `max_length` characters (followed by `...`)
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new()
+    ///     .max_errors_per_field(3)
+    ///     .max_error_message_length(200);
+    /// ```
+    pub fn max_error_message_length(mut self, max_length: usize) -> Self {
+        self.response.max_error_message_length = Some(max_length);
+        self
+    }
+
+    /// Replace the message of serialized errors having the code `code`, e.g. the built-in
+    /// [`ValidationError`](validators/struct.ValidationError.html)s, by `template`. The template
+    /// can use the placeholders `{field}` and the parameters of the rule, e.g. `{min}` and `{max}`
+    /// for bounds or `{allowed}` for `one_of`. Errors without a `code` key, like strings, are
+    /// left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new()
+    ///     .with_message_override("required", "Please fill in {field}")
+    ///     .with_message_override("between", "{field} must be from {min} to {max}");
+    /// ```
+    pub fn with_message_override(mut self, code: &str, template: &str) -> Self {
+        self.response
+            .message_overrides
+            .insert(code.to_string(), template.to_string());
+        self
+    }
+
+    /// Translate the messages of serialized errors for requests accepting `locale`, a BCP 47 tag
+    /// like `fr` or `pt-BR`. `messages` are templates by error code using the placeholders of
+    /// [`with_message_override`](#method.with_message_override), see the [`i18n`](i18n/index.html)
+    /// module for the negotiation of the locale. Translated responses have a `Content-Language` header.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new()
+    ///     .with_translations("fr", vec![("required", "{field} est obligatoire")])
+    ///     .with_translations("de", vec![("required", "{field} ist erforderlich")]);
+    /// ```
+    pub fn with_translations<I, K, V>(mut self, locale: &str, messages: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.response.translations.add(locale, messages);
+        self
+    }
+
+    /// Add an `example` of a valid value to serialized errors, e.g. `2023-01-31` for dates. Built-in
+    /// validators give it as the `example` parameter of their errors, which is removed when
+    /// examples aren't included (default).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().include_examples(true);
+    /// // {"field": "since", "in": "query", "code": "date", "message": "...", "example": "2023-01-31"}
+    /// ```
+    pub fn include_examples(mut self, include_examples: bool) -> Self {
+        self.response.include_examples = include_examples;
+        self
+    }
+
+    /// Choose the shape of the body of error responses, see [`ErrorShape`](enum.ErrorShape.html)
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().error_shape(ErrorShape::GroupedByField);
+    /// ```
+    pub fn error_shape(mut self, error_shape: ErrorShape) -> Self {
+        self.response.error_shape = error_shape;
+        self
+    }
+
+    /// Choose the serialization format of error responses, see
+    /// [`ResponseFormat`](enum.ResponseFormat.html). The body built by
+    /// [`aggregate_errors`](struct.ValidatorMiddleware.html#method.aggregate_errors) is serialized
+    /// in the format too, responses built by your own hooks are sent as they are. Requests
+    /// rejected by the middleware itself, e.g. exceeding a limit or with a body which doesn't
+    /// parse, get a `ValidationError` in the same shape and format.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// // curl -H "Accept: text/plain" gets "field 'age' = 'abc' is not an integer"
+    /// let validator_middleware = ValidatorMiddleware::new().response_format(ResponseFormat::Negotiated);
+    /// ```
+    pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response.response_format = response_format;
+        self
+    }
+
+    /// Choose the status code of error responses and whether they include the serialized errors,
+    /// globally or by location of the failing field, see [`ResponsePolicy`](struct.ResponsePolicy.html)
+    pub fn response_policy(mut self, response_policy: ResponsePolicy) -> Self {
+        self.response.response_policy = response_policy;
+        self
+    }
+
+    /// Build the response sent when a validator fails yourself, e.g. to choose the status code
+    /// according to the error. When several errors are collected the hook receives the first one.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().on_error(|err: &CustomError| {
+    ///     Response::new(err.status_code).body_string(err.message.clone())
+    /// });
+    /// ```
+    pub fn on_error<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&T) -> Response + Send + Sync + 'static,
+    {
+        self.response.on_error = Some(Arc::new(hook));
+        self
+    }
+
+    /// Build the body of error responses yourself from every error of the request, in the order
+    /// they were found, instead of the [`ErrorShape`](enum.ErrorShape.html). Errors are given as
+    /// returned by validators: message overrides and
+    /// [`max_errors_per_field`](#method.max_errors_per_field) don't apply. The status code still
+    /// comes from the [`ResponsePolicy`](struct.ResponsePolicy.html).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new()
+    ///     .collect_all_errors(true)
+    ///     .aggregate_errors(|errors: Vec<CustomError>| ErrorReport {
+    ///         count: errors.len(),
+    ///         errors,
+    ///     });
+    /// ```
+    pub fn aggregate_errors<U, F>(mut self, aggregator: F) -> Self
+    where
+        U: Serialize,
+        F: Fn(Vec<T>) -> U + Send + Sync + 'static,
+    {
+        self.response.aggregate_errors = Some(Arc::new(move |errors| {
+            serde_json::to_value(aggregator(errors))
+        }));
+        self
+    }
+
+    /// Build the response sent when a validator can't check a value, see
+    /// [`add_fallible_validator`](struct.ValidatorMiddleware.html#method.add_fallible_validator).
+    /// It's the place to log these errors apart from invalid requests. Without hook the response
+    /// is a `500 Internal Server Error` naming the field.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().on_validator_error(|field, err| {
+    ///     log::error!("cannot validate {}: {}", field.name(), err);
+    ///     Response::new(StatusCode::ServiceUnavailable)
+    /// });
+    /// ```
+    pub fn on_validator_error<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&HttpField<'_>, &ValidatorError) -> Response + Send + Sync + 'static,
+    {
+        self.response.on_validator_error = Some(Arc::new(hook));
+        self
+    }
+
+    /// Build the response for a validator which couldn't check the value of `field`
+    #[cfg_attr(
+        not(any(feature = "log", feature = "tracing")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn validator_error_response(
+        &self,
+        req: &Request<State>,
+        field: &HttpField<'_>,
+        err: &ValidatorError,
+    ) -> Response {
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        events::validator_errored(req, field, err.as_ref());
+        match &self.response.on_validator_error {
+            Some(hook) => hook(field, err),
+            None => Response::new(StatusCode::InternalServerError).body_string(format!(
+                "cannot validate {} '{}'",
+                field.kind(),
+                field.name()
+            )),
+        }
+    }
+
+    /// Reject the request with errors found by the middleware itself, like errors of validators:
+    /// the `after_validation` hook gets them as failed fields, and they have the shape and format
+    /// of the middleware. Hooks typed by the error type of the middleware, `on_error` and
+    /// `aggregate_errors`, get them when it's `ValidationError` or `AnyError`. Without `status`, the
+    /// response policy gives it.
+    pub(crate) fn reject(
+        &self,
+        req: &mut Request<State>,
+        status: Option<StatusCode>,
+        errors: Vec<(HttpField<'static>, ValidationError)>,
+    ) -> Response {
+        self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+        let (fields, errors): (Vec<_>, Vec<_>) = errors.into_iter().unzip();
+        if let Some(hook) = &self.report.after_validation {
+            let failed: Vec<&HttpField<'static>> = fields.iter().collect();
+            let mut report = ValidationReport::from_request(req);
+            report.outcomes = field_outcomes(&[], &failed, &[]);
+            hook(&report, req);
+        }
+        let errors = fields
+            .iter()
+            .zip(errors)
+            .map(|(field, err)| (field, None, err))
+            .collect();
+        self.error_response(req, errors, status)
+    }
+
+    /// Reject the request with an error found by the middleware itself, with its status
+    pub(crate) fn reject_with(
+        &self,
+        req: &mut Request<State>,
+        (status, field, err): Rejection,
+    ) -> Response {
+        self.reject(req, Some(status), vec![(field, err)])
+    }
+
+    /// Build the response for the errors of one or several fields, with the description of the
+    /// failing rule for each error and the status of the first failing rule if it has one
+    #[cfg_attr(
+        not(any(feature = "log", feature = "tracing")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn error_response<E>(
+        &self,
+        req: &Request<State>,
+        errors: Vec<FieldError<'_, E>>,
+        status: Option<StatusCode>,
+    ) -> Response
+    where
+        E: Serialize + Send + Sync + 'static,
+    {
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        for (field, _, err) in &errors {
+            let mut error = serde_json::to_value(err).unwrap_or_default();
+            if let Some(decoded) = req.local::<DecodedValues>() {
+                decoded.redact(field, &mut error);
+            }
+            events::field_rejected(req, field, &error.to_string());
+        }
+        let errors = if self.response.on_error.is_some() || self.response.aggregate_errors.is_some()
+        {
+            match rule_errors::<T, E>(errors) {
+                Ok(errors) => return self.hooked_error_response(req, errors, status),
+                Err(errors) => errors,
+            }
+        } else {
+            errors
+        };
+        self.encode_field_errors(req, errors, status)
+    }
+
+    /// Give the errors to the `on_error` or `aggregate_errors` hook
+    pub(crate) fn hooked_error_response(
+        &self,
+        req: &Request<State>,
+        errors: Vec<FieldError<'_, T>>,
+        status: Option<StatusCode>,
+    ) -> Response {
+        if let (Some(hook), Some((_, _, err))) = (&self.response.on_error, errors.first()) {
+            return hook(err);
+        }
+        let aggregator = match &self.response.aggregate_errors {
+            Some(aggregator) => aggregator,
+            None => return self.encode_field_errors(req, errors, status),
+        };
+        let (status, include_errors) = self.error_status(&errors, status);
+        if !include_errors {
+            return Response::new(status);
+        }
+        let errors = errors.into_iter().map(|(_, _, err)| err).collect();
+        match aggregator(errors) {
+            Ok(body) => self.encode_errors(req, status, &body, "application/json"),
+            Err(err) => Response::new(StatusCode::InternalServerError)
+                .body_string(format!("cannot serialize validation errors : {:?}", err)),
+        }
+    }
+
+    /// Status of the response and whether it includes the errors, given by the response policy
+    /// of the first failing field unless the failing rule has a status
+    pub(crate) fn error_status<E>(
+        &self,
+        errors: &[FieldError<'_, E>],
+        status: Option<StatusCode>,
+    ) -> (StatusCode, bool) {
+        let (policy_status, include_errors) = match errors.first() {
+            Some((field, _, _)) => self.response.response_policy.response_for(field),
+            None => (
+                self.response.response_policy.status,
+                self.response.response_policy.include_errors,
+            ),
+        };
+        (status.unwrap_or(policy_status), include_errors)
+    }
+
+    /// Serialize the errors in the shape and format of the middleware
+    pub(crate) fn encode_field_errors<E: Serialize>(
+        &self,
+        req: &Request<State>,
+        errors: Vec<FieldError<'_, E>>,
+        status: Option<StatusCode>,
+    ) -> Response {
+        let (status, include_errors) = self.error_status(&errors, status);
+        if !include_errors {
+            return Response::new(status);
+        }
+
+        let locale = match header_values(req, "Accept-Language") {
+            Some(values) if !self.response.translations.is_empty() => values
+                .iter()
+                .find_map(|value| self.response.translations.negotiate(value.as_str())),
+            _ => None,
+        };
+        let translations = locale.map(|(_, messages)| messages);
+        let mut by_field: Vec<(&HttpField<'_>, Vec<serde_json::Value>)> = Vec::new();
+        for (field, description, err) in &errors {
+            let position = match by_field.iter().position(|(f, _)| f == field) {
+                Some(position) => position,
+                None => {
+                    by_field.push((field, Vec::new()));
+                    by_field.len() - 1
+                }
+            };
+            let field_errors = &mut by_field[position].1;
+            if !matches!(self.response.max_errors_per_field, Some(max_errors) if field_errors.len() >= max_errors)
+            {
+                let decoded = req.local::<DecodedValues>();
+                match self.serialize_error(field, *description, err, translations, decoded) {
+                    Ok(value) => field_errors.push(value),
+                    Err(err) => {
+                        return Response::new(StatusCode::InternalServerError).body_string(format!(
+                            "cannot serialize your {} validator for '{}' error : {:?}",
+                            field.kind(),
+                            field.name(),
+                            err
+                        ))
+                    }
+                }
+            }
+        }
+
+        let body = match self.response.error_shape {
+            ErrorShape::Problem => {
+                let invalid_params: Vec<serde_json::Value> = by_field
+                    .into_iter()
+                    .flat_map(|(field, field_errors)| {
+                        field_errors.into_iter().map(move |err| {
+                            let reason = match err.get("message") {
+                                Some(message) if message.is_string() => message.clone(),
+                                _ => err,
+                            };
+                            serde_json::json!({
+                                "name": field.name(),
+                                "in": field.location(),
+                                "reason": reason,
+                            })
+                        })
+                    })
+                    .collect();
+                let detail = invalid_params
+                    .first()
+                    .and_then(|invalid_param| invalid_param["reason"].as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                serde_json::json!({
+                    "type": "about:blank",
+                    "title": "Your request parameters didn't validate.",
+                    "status": u16::from(status),
+                    "detail": detail,
+                    "invalid-params": invalid_params,
+                })
+            }
+            ErrorShape::Plain if !self.collect_all_errors && by_field.len() == 1 => {
+                let mut field_errors = by_field.remove(0).1;
+                if field_errors.len() == 1 {
+                    field_errors.remove(0)
+                } else {
+                    serde_json::Value::Array(field_errors)
+                }
+            }
+            _ => serde_json::Value::Object(
+                by_field
+                    .into_iter()
+                    .map(|(field, field_errors)| {
+                        (
+                            field.name().to_string(),
+                            serde_json::Value::Array(field_errors),
+                        )
+                    })
+                    .collect(),
+            ),
+        };
+
+        let json_type = if self.response.error_shape == ErrorShape::Problem {
+            "application/problem+json"
+        } else {
+            "application/json"
+        };
+        let response = self.encode_errors(req, status, &body, json_type);
+        match locale {
+            Some((tag, _)) => response.set_header("Content-Language", tag),
+            None => response,
+        }
+    }
+
+    /// Serialize the body of an error response in the format of the middleware, `json_type` is
+    /// the `Content-Type` of JSON bodies
+    pub(crate) fn encode_errors(
+        &self,
+        req: &Request<State>,
+        status: StatusCode,
+        body: &serde_json::Value,
+        json_type: &str,
+    ) -> Response {
+        let format = match self.response.response_format {
+            ResponseFormat::Negotiated => header_values(req, "Accept")
+                .map(|values| {
+                    let values: Vec<&str> = values.iter().map(|value| value.as_str()).collect();
+                    negotiate_format(&values.join(","))
+                })
+                .unwrap_or_default(),
+            format => format,
+        };
+        let encoded = match format {
+            ResponseFormat::Text => Ok(Response::new(status)
+                .body_string(error_messages(body).join("\n"))
+                .set_header("Content-Type", "text/plain; charset=utf-8")),
+            #[cfg(feature = "msgpack")]
+            ResponseFormat::MessagePack => rmp_serde::to_vec_named(body)
+                .map(|bytes| {
+                    Response::new(status)
+                        .body(async_std::io::Cursor::new(bytes))
+                        .set_header("Content-Type", "application/msgpack")
+                })
+                .map_err(|err| err.to_string()),
+            ResponseFormat::Json | ResponseFormat::Negotiated => Response::new(status)
+                .body_json(body)
+                .map(|response| response.set_header("Content-Type", json_type))
+                .map_err(|err| err.to_string()),
+        };
+        encoded.unwrap_or_else(|err| {
+            Response::new(StatusCode::InternalServerError)
+                .body_string(format!("cannot serialize validation errors : {}", err))
+        })
+    }
+
+    pub(crate) fn serialize_error<E: Serialize>(
+        &self,
+        field: &HttpField<'_>,
+        description: Option<&str>,
+        err: &E,
+        translations: Option<&HashMap<String, String>>,
+        decoded: Option<&DecodedValues>,
+    ) -> serde_json::Result<serde_json::Value> {
+        let mut value = serde_json::to_value(err)?;
+        let template = value
+            .get("code")
+            .and_then(serde_json::Value::as_str)
+            .and_then(|code| {
+                translations
+                    .and_then(|messages| messages.get(code))
+                    .or_else(|| self.response.message_overrides.get(code))
+            });
+        if let Some(template) = template {
+            let message = render_message(template, field.name(), value.get("params"));
+            if let Some(current) = value.get_mut("message") {
+                *current = serde_json::Value::from(message);
+            }
+        }
+        // Errors with an empty `in` key, like built-in errors, get the location of the field
+        if let Some(location) = value.get_mut("in").filter(|location| location.is_null()) {
+            *location = serde_json::Value::from(field.location().as_str());
+        }
+        // The example given by built-in validators is only sent when examples are included
+        if let Some(object) = value.as_object_mut() {
+            let example = object
+                .get_mut("params")
+                .and_then(serde_json::Value::as_object_mut)
+                .and_then(|params| params.remove("example"));
+            if matches!(object.get("params"), Some(serde_json::Value::Object(params)) if params.is_empty())
+            {
+                object.remove("params");
+            }
+            if let (Some(example), true) = (example, self.response.include_examples) {
+                object.insert(String::from("example"), example);
+            }
+        }
+        if let Some(description) = description {
+            if let Some(empty) = value
+                .get_mut("description")
+                .filter(|description| description.is_null())
+            {
+                *empty = serde_json::Value::from(description);
+            }
+        }
+        if let Some(decoded) = decoded {
+            decoded.redact(field, &mut value);
+        }
+        if let Some(max_length) = self.response.max_error_message_length {
+            truncate_strings(&mut value, max_length);
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{is_number, is_required, CustomError};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request};
+
+    #[test]
+    fn validator_truncated_message() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().max_error_message_length(14);
+        validators.add_validator(HttpField::param("bar"), is_number);
+        inner
+            .at("/foo/:bar")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/foo/bar".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf[..]),
+            String::from(r#""field 'bar' = ...""#)
+        );
+    }
+
+    #[test]
+    fn validator_grouped_errors() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().error_shape(ErrorShape::GroupedByField);
+        validators.add_validator(HttpField::param("bar"), is_number);
+        inner
+            .at("/foo/:bar")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/foo/bar".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let errors: HashMap<String, Vec<String>> = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(
+            errors["bar"],
+            vec![String::from("field 'bar' = 'bar' is not a valid number")]
+        );
+    }
+
+    #[test]
+    fn validator_problem_details() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().error_shape(ErrorShape::Problem);
+        validators.add_validator(HttpField::param("bar"), validators::is_number);
+        inner
+            .at("/foo/:bar")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/foo/bar".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert_eq!(
+            res.header(&"Content-Type".into()).unwrap()[0].as_str(),
+            "application/problem+json"
+        );
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(problem["status"], 400);
+        assert_eq!(
+            problem["detail"],
+            "field 'bar' = 'bar' is not a valid number"
+        );
+        assert_eq!(
+            problem["invalid-params"],
+            serde_json::json!([{
+                "name": "bar",
+                "in": "path",
+                "reason": "field 'bar' = 'bar' is not a valid number",
+            }])
+        );
+    }
+
+    #[test]
+    fn validator_response_formats() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().response_format(ResponseFormat::Negotiated);
+        validators.add_validator(HttpField::param("bar"), validators::is_number);
+        inner
+            .at("/foo/:bar")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Get, "http://localhost/foo/bar".parse().unwrap());
+        req.insert_header("Accept", "text/html, text/plain;q=0.9, */*;q=0.8")
+            .unwrap();
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert_eq!(
+            res.header(&"Content-Type".into()).unwrap()[0].as_str(),
+            "text/plain; charset=utf-8"
+        );
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "field 'bar' = 'bar' is not a valid number"
+        );
+
+        let req = Request::new(Method::Get, "http://localhost/foo/bar".parse().unwrap());
+        let res = server.simulate(req).unwrap();
+        assert_eq!(
+            res.header(&"Content-Type".into()).unwrap()[0].as_str(),
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn response_format_negotiation() {
+        assert_eq!(negotiate_format("application/json"), ResponseFormat::Json);
+        assert_eq!(negotiate_format("text/plain"), ResponseFormat::Text);
+        assert_eq!(
+            negotiate_format("text/html, text/*;q=0.9, */*;q=0.8"),
+            ResponseFormat::Text
+        );
+        assert_eq!(
+            negotiate_format("text/plain;q=0.5, application/json"),
+            ResponseFormat::Json
+        );
+        assert_eq!(
+            negotiate_format("text/plain;q=0, */*"),
+            ResponseFormat::Json
+        );
+        assert_eq!(negotiate_format("image/png"), ResponseFormat::Json);
+        #[cfg(feature = "msgpack")]
+        assert_eq!(
+            negotiate_format("application/msgpack, application/json;q=0.5"),
+            ResponseFormat::MessagePack
+        );
+
+        let plain =
+            serde_json::json!({"field": "age", "code": "required", "message": "'age' is required"});
+        assert_eq!(error_messages(&plain), vec!["'age' is required"]);
+        let grouped =
+            serde_json::json!({"age": ["'age' is required"], "name": ["too long", "not a slug"]});
+        assert_eq!(
+            error_messages(&grouped),
+            vec!["'age' is required", "too long", "not a slug"]
+        );
+        let problem = serde_json::json!({
+            "type": "about:blank",
+            "status": 400,
+            "invalid-params": [{"name": "age", "in": "query", "reason": "'age' is required"}],
+        });
+        assert_eq!(error_messages(&problem), vec!["'age' is required"]);
+        assert_eq!(error_messages(&serde_json::json!([42])), vec!["42"]);
+    }
+
+    #[test]
+    fn validator_message_override() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .with_message_override("between", "{field} must be from {min} to {max}");
+        validators.add_validator(
+            HttpField::query_param("age"),
+            validators::is_integer_between(1, 120),
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/foo?age=200".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let error: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(error.message, "age must be from 1 to 120");
+    }
+
+    #[test]
+    fn error_examples() {
+        let field = HttpField::query_param("since");
+        let err = validators::is_date("since", Some("yesterday")).unwrap_err();
+
+        let validators = ValidatorMiddleware::<ValidationError, ()>::new().include_examples(true);
+        let value = validators
+            .serialize_error(&field, None, &err, None, None)
+            .unwrap();
+        assert_eq!(value["example"], "2023-01-31");
+        assert!(value.get("params").is_none());
+
+        let validators = ValidatorMiddleware::<ValidationError, ()>::new();
+        let value = validators
+            .serialize_error(&field, None, &err, None, None)
+            .unwrap();
+        assert!(value.get("example").is_none());
+    }
+
+    #[test]
+    fn translated_messages() {
+        let field = HttpField::query_param("age");
+        let err = validators::is_between(1.0, 120.0)("age", Some("200")).unwrap_err();
+        let validators = ValidatorMiddleware::<ValidationError, ()>::new()
+            .with_message_override("between", "age must be from {min} to {max}")
+            .with_translations(
+                "fr",
+                vec![("between", "{field} doit être entre {min} et {max}")],
+            );
+
+        let (locale, messages) = validators
+            .response
+            .translations
+            .negotiate("fr-CH, en;q=0.5")
+            .unwrap();
+        assert_eq!(locale, "fr");
+        let value = validators
+            .serialize_error(&field, None, &err, Some(messages), None)
+            .unwrap();
+        assert_eq!(value["message"], "age doit être entre 1 et 120");
+        let value = validators
+            .serialize_error(&field, None, &err, None, None)
+            .unwrap();
+        assert_eq!(value["message"], "age must be from 1 to 120");
+    }
+
+    #[test]
+    fn response_policies() {
+        let policy = ResponsePolicy::new()
+            .status(StatusCode::UnprocessableEntity)
+            .for_location(Location::Path, StatusCode::NotFound, false);
+        assert_eq!(
+            policy.response_for(&HttpField::param("id")),
+            (StatusCode::NotFound, false)
+        );
+        assert_eq!(
+            policy.response_for(&HttpField::query_param("page")),
+            (StatusCode::UnprocessableEntity, true)
+        );
+        assert_eq!(
+            ResponsePolicy::new().response_for(&HttpField::header("X-Id")),
+            (StatusCode::BadRequest, true)
+        );
+    }
+
+    #[test]
+    fn message_templates() {
+        let params = serde_json::json!({"min": "1", "max": 10});
+        assert_eq!(
+            render_message("{field} from {min} to {max} {other}", "age", Some(&params)),
+            "age from 1 to 10 {other}"
+        );
+        assert_eq!(render_message("{field}!", "age", None), "age!");
+    }
+
+    #[test]
+    fn validator_error_location() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(HttpField::query_param("page"), validators::is_integer);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/foo?page=a".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let err: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(err.location, Some(Location::Query));
+        assert_eq!(err.code, "integer");
+    }
+
+    #[test]
+    fn validator_structured_errors() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().error_shape(ErrorShape::GroupedByField);
+        validators.add_validator(
+            HttpField::query_param("slug"),
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some(value) if value.contains(' ') => Err(ValidationError::new(
+                    field_name,
+                    "slug",
+                    format!("'{}' is not a slug", field_name),
+                )),
+                _ => Ok(()),
+            },
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?slug=not%20a%20slug".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let errors: HashMap<String, Vec<ValidationError>> =
+            serde_json::from_slice(&buf[..]).unwrap();
+        let err = &errors["slug"][0];
+        assert_eq!(
+            (&err.field[..], err.location, &err.code[..]),
+            ("slug", Some(Location::Query), "slug")
+        );
+    }
+
+    #[test]
+    fn validator_collect_all_errors() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().collect_all_errors(true);
+        validators.add_validator(HttpField::query_param("age"), is_number);
+        validators.add_validator(HttpField::query_param("size"), is_number);
+        validators.add_validator(HttpField::query_param("size"), |field_name: &str, _| {
+            Err(format!("'{}' is never valid", field_name))
+        });
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?age=a&size=b".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let errors: HashMap<String, Vec<String>> = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(errors["age"].len(), 1);
+        assert_eq!(
+            errors["size"],
+            vec![
+                String::from("field 'size' = 'b' is not a valid number"),
+                String::from("'size' is never valid"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validator_max_errors_per_field() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .collect_all_errors(true)
+            .max_errors_per_field(2);
+        validators.add_validator(HttpField::query_param("age"), is_number);
+        for rule in 0..4 {
+            validators.add_validator(
+                HttpField::query_param("size"),
+                move |field_name: &str, _: Option<&str>| {
+                    Err(format!("'{}' fails rule {}", field_name, rule))
+                },
+            );
+        }
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?age=a&size=b".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let errors: HashMap<String, Vec<String>> = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(
+            errors["age"],
+            vec![String::from("field 'age' = 'a' is not a valid number")]
+        );
+        assert_eq!(
+            errors["size"],
+            vec![
+                String::from("'size' fails rule 0"),
+                String::from("'size' fails rule 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validator_error_order() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .collect_all_errors(true)
+            .error_shape(ErrorShape::Problem);
+        let names: Vec<String> = (0..12).rev().map(|n| format!("field{}", n)).collect();
+        for name in &names {
+            validators.add_validator(HttpField::query_param(name.clone()), is_number);
+        }
+        // A later validator of a field keeps the field at its first position
+        validators.add_validator(HttpField::query_param("field11"), is_number);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let query: Vec<String> = names.iter().map(|name| format!("{}=a", name)).collect();
+        let url = format!("http://localhost/foo?{}", query.join("&"));
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, url.parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        let reported: Vec<&str> = problem["invalid-params"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|invalid_param| invalid_param["name"].as_str().unwrap())
+            .collect();
+        let mut expected: Vec<&str> = names.iter().map(String::as_str).collect();
+        expected.insert(1, "field11");
+        assert_eq!(reported, expected);
+    }
+
+    #[test]
+    fn validator_aggregate_errors() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .collect_all_errors(true)
+            .aggregate_errors(|errors: Vec<CustomError>| {
+                serde_json::json!({
+                    "count": errors.len(),
+                    "messages": errors.into_iter().map(|err| err.message).collect::<Vec<_>>(),
+                })
+            });
+        validators.add_validator(HttpField::query_param("name"), is_required);
+        validators.add_validator(HttpField::query_param("city"), is_required);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(body["count"], 2);
+        assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn validator_on_error() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().on_error(|err: &String| {
+            Response::new(StatusCode::UnprocessableEntity).body_string(err.clone())
+        });
+        validators.add_validator(HttpField::param("bar"), is_number);
+        inner
+            .at("/foo/:bar")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(Method::Get, "http://localhost/foo/bar".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::UnprocessableEntity);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf[..]),
+            "field 'bar' = 'bar' is not a valid number"
+        );
+    }
+
+    #[test]
+    fn validator_rejections() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .max_body_size(16)
+            .response_format(ResponseFormat::Text);
+        validators.add_validator(HttpField::body_field("name"), validators::is_required);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .post(|_| async { Ok("foo") });
+        let mut hooked = ValidatorMiddleware::new().on_error(|err: &ValidationError| {
+            Response::new(StatusCode::UnprocessableEntity).body_string(err.code.clone())
+        });
+        hooked.add_validator(HttpField::body_field("name"), validators::is_required);
+        inner
+            .at("/bar")
+            .middleware(hooked)
+            .post(|_| async { Ok("bar") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (path, body, status, message) in &[
+            (
+                "foo",
+                "{\"name\": ",
+                StatusCode::BadRequest,
+                "cannot parse JSON body: ",
+            ),
+            (
+                "foo",
+                "{\"name\": \"0123456789\"}",
+                StatusCode::PayloadTooLarge,
+                "body is larger than 16 bytes",
+            ),
+            (
+                "bar",
+                "{\"name\": ",
+                StatusCode::UnprocessableEntity,
+                "json",
+            ),
+        ] {
+            let mut buf = Vec::new();
+            let url = format!("http://localhost/{}", path);
+            let mut req = Request::new(Method::Post, url.parse().unwrap());
+            req.set_body(*body);
+            let mut res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+            block_on(res.read_to_end(&mut buf)).unwrap();
+            assert!(String::from_utf8_lossy(&buf).starts_with(message));
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Compact rule strings, Laravel style.
+//!
+//! A rule string is a list of rules separated by `|`, each rule can take parameters after a `:`
+//! separated by `,`. For example `required|integer|between:1,100`.
+//!
+//! Available rules:
+//!
+//! - `required`: the field must be present
+//! - `integer`: the field must be an integer
+//! - `numeric`: the field must be a number
+//! - `boolean`: the field must be `true` or `false`
+//! - `between:min,max`: the field must be between `min` and `max`
+//! - `min:n`: the field must be at least `n`
+//! - `max:n`: the field must be at most `n`
+//! - `in:a,b,c`: the field must be one of the listed values
+//! - `in_ignore_case:a,b,c`: the field must be one of the listed values, ignoring ASCII case
+//! - `email`: the field must be an email address
+//! - `uuid`: the field must be a hyphenated UUID
+//! - `url`: the field must be an absolute `http` or `https` URL
+//! - `date`: the field must be an ISO-8601 date (`YYYY-MM-DD`)
+//! - `http_date`: the field must be an HTTP-date (`Sun, 06 Nov 1994 08:49:37 GMT`)
+//! - `past_http_date`: the field must be an HTTP-date which isn't in the future
+//! - `language`: the field must be a BCP 47 language tag (`en-GB`)
+//! - `currency`: the field must be an ISO 4217 currency code (`EUR`)
+//! - `country`: the field must be an ISO 3166-1 alpha-2 country code (`FR`)
+//! - `timezone`: the field must be an IANA time zone (`Europe/Paris`), with the `datetime` feature
+//!
+//! Like in Laravel, `between`, `min` and `max` compare the value itself when the rule string contains
+//! `integer` or `numeric`, otherwise they compare the length of the value.
+//!
+//! Your own rules can be added with a [`RuleRegistry`](struct.RuleRegistry.html), so rules stored in
+//! config files can reference them by name.
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::locale;
+use crate::validators::{self, ValidationError};
+use crate::{BoxedValidator, HttpField, ValidatorMiddleware};
+
+/// Build a validator from the parameters given to a rule, e.g. `["1", "100"]` for `between:1,100`
+pub type RuleFactory =
+    Box<dyn Fn(&[&str]) -> Result<BoxedValidator<ValidationError>, String> + Send + Sync + 'static>;
+
+/// Error returned when a rule string cannot be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleError {
+    /// The rule string contains an empty rule, e.g. `required||integer`
+    EmptyRule,
+    /// The rule is not known
+    UnknownRule(String),
+    /// The parameters given to a rule are invalid
+    InvalidParameters {
+        /// Name of the rule
+        rule: String,
+        /// Why the parameters are invalid
+        reason: String,
+    },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::EmptyRule => f.write_str("rule string contains an empty rule"),
+            RuleError::UnknownRule(rule) => write!(f, "unknown rule '{}'", rule),
+            RuleError::InvalidParameters { rule, reason } => {
+                write!(f, "invalid parameters for rule '{}': {}", rule, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Parse a rule string into a validator chaining every rule in order.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// validator_middleware.add_validator(
+///     HttpField::query_param("age"),
+///     rules("required|integer|between:1,100").unwrap(),
+/// );
+/// ```
+pub fn rules(spec: &str) -> Result<BoxedValidator<ValidationError>, RuleError> {
+    RuleRegistry::new().parse(spec)
+}
+
+/// Set of custom rules available in rule strings, in addition to the built-in ones
+#[derive(Default)]
+pub struct RuleRegistry {
+    custom: HashMap<String, RuleFactory>,
+}
+
+impl fmt::Debug for RuleRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("custom rules {:?}", self.custom.keys()))
+    }
+}
+
+impl RuleRegistry {
+    /// Create a registry containing only the built-in rules
+    pub fn new() -> Self {
+        RuleRegistry {
+            custom: HashMap::new(),
+        }
+    }
+
+    /// Register a custom rule. The factory receives the rule parameters and returns the validator,
+    /// or a message explaining why the parameters are invalid. A custom rule takes precedence over
+    /// a built-in rule with the same name.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut registry = RuleRegistry::new();
+    /// registry.register("slug", |_params| Ok(Box::new(is_slug)));
+    /// registry.register("tenant_exists", |params| {
+    ///     let region = params.first().ok_or_else(|| "expected a region".to_string())?;
+    ///     Ok(tenant_exists_in(region))
+    /// });
+    ///
+    /// validator_middleware.add_validator(HttpField::header("X-Tenant"), registry.parse("required|tenant_exists:eu")?);
+    /// ```
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F) -> &mut Self
+    where
+        F: Fn(&[&str]) -> Result<BoxedValidator<ValidationError>, String> + Send + Sync + 'static,
+    {
+        self.custom.insert(name.into(), Box::new(factory));
+        self
+    }
+
+    /// Parse a rule string into a validator chaining every rule in order
+    pub fn parse(&self, spec: &str) -> Result<BoxedValidator<ValidationError>, RuleError> {
+        let parsed = parse(spec)?;
+        let numeric = parsed
+            .iter()
+            .any(|(name, _)| *name == "integer" || *name == "numeric");
+        let chain = parsed
+            .into_iter()
+            .map(|(name, params)| match self.custom.get(name) {
+                Some(factory) => factory(&params).map_err(|reason| RuleError::InvalidParameters {
+                    rule: name.to_string(),
+                    reason,
+                }),
+                None => build(name, &params, numeric),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Box::new(
+            move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+                for validator in &chain {
+                    validator(field_name, field_value)?;
+                }
+                Ok(())
+            },
+        ))
+    }
+
+    /// Build a whole middleware from a list of fields and their rule string, typically loaded from a config file
+    pub fn middleware<I, S>(
+        &self,
+        rules: I,
+    ) -> Result<ValidatorMiddleware<ValidationError>, RuleError>
+    where
+        I: IntoIterator<Item = (HttpField<'static>, S)>,
+        S: AsRef<str>,
+    {
+        let mut middleware = ValidatorMiddleware::new();
+        for (field, spec) in rules {
+            middleware.add_validator(field, self.parse(spec.as_ref())?);
+        }
+        Ok(middleware)
+    }
+}
+
+fn parse(spec: &str) -> Result<Vec<(&str, Vec<&str>)>, RuleError> {
+    spec.split('|')
+        .map(|rule| {
+            let rule = rule.trim();
+            if rule.is_empty() {
+                return Err(RuleError::EmptyRule);
+            }
+            Ok(match rule.find(':') {
+                Some(idx) => (
+                    rule[..idx].trim(),
+                    rule[idx + 1..].split(',').map(str::trim).collect(),
+                ),
+                None => (rule, Vec::new()),
+            })
+        })
+        .collect()
+}
+
+fn build(
+    name: &str,
+    params: &[&str],
+    numeric: bool,
+) -> Result<BoxedValidator<ValidationError>, RuleError> {
+    match name {
+        "required" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_required))
+        }
+        "integer" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_integer))
+        }
+        "numeric" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_number))
+        }
+        "boolean" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_bool))
+        }
+        "email" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_email))
+        }
+        "uuid" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_uuid))
+        }
+        "url" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_url))
+        }
+        "date" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_date))
+        }
+        "http_date" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_http_date))
+        }
+        "past_http_date" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(validators::is_past_http_date))
+        }
+        "language" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(locale::is_language_tag))
+        }
+        "currency" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(locale::is_currency_code))
+        }
+        "country" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(locale::is_country_code))
+        }
+        #[cfg(feature = "datetime")]
+        "timezone" => {
+            expect_params(name, params, 0)?;
+            Ok(Box::new(locale::is_timezone))
+        }
+        "in" | "in_ignore_case" => {
+            if params.is_empty() {
+                return Err(RuleError::InvalidParameters {
+                    rule: name.to_string(),
+                    reason: String::from("expected at least 1 parameter"),
+                });
+            }
+            if name == "in" {
+                Ok(validators::one_of(params))
+            } else {
+                Ok(validators::one_of_ignore_case(params))
+            }
+        }
+        "between" => {
+            expect_params(name, params, 2)?;
+            if numeric {
+                Ok(validators::is_between(
+                    parse_param(name, params[0])?,
+                    parse_param(name, params[1])?,
+                ))
+            } else {
+                Ok(validators::is_length_between(
+                    parse_param(name, params[0])?,
+                    parse_param(name, params[1])?,
+                ))
+            }
+        }
+        "min" => {
+            expect_params(name, params, 1)?;
+            if numeric {
+                Ok(validators::is_min(parse_param(name, params[0])?))
+            } else {
+                Ok(validators::is_min_length(parse_param(name, params[0])?))
+            }
+        }
+        "max" => {
+            expect_params(name, params, 1)?;
+            if numeric {
+                Ok(validators::is_max(parse_param(name, params[0])?))
+            } else {
+                Ok(validators::is_max_length(parse_param(name, params[0])?))
+            }
+        }
+        other => Err(RuleError::UnknownRule(other.to_string())),
+    }
+}
+
+fn expect_params(rule: &str, params: &[&str], expected: usize) -> Result<(), RuleError> {
+    if params.len() != expected {
+        return Err(RuleError::InvalidParameters {
+            rule: rule.to_string(),
+            reason: format!("expected {} parameter(s), got {}", expected, params.len()),
+        });
+    }
+    Ok(())
+}
+
+fn parse_param<N: FromStr>(rule: &str, param: &str) -> Result<N, RuleError> {
+    param.parse().map_err(|_| RuleError::InvalidParameters {
+        rule: rule.to_string(),
+        reason: format!("'{}' is not a valid parameter", param),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_chain() {
+        let validator = rules("required|integer|between:1,100").unwrap();
+        assert!(validator("age", Some("42")).is_ok());
+        assert_eq!(validator("age", None).unwrap_err().code, "required");
+        assert_eq!(validator("age", Some("4.2")).unwrap_err().code, "integer");
+        assert_eq!(validator("age", Some("101")).unwrap_err().code, "between");
+
+        let validator = rules("required|currency").unwrap();
+        assert!(validator("currency", Some("EUR")).is_ok());
+        assert_eq!(
+            validator("currency", Some("euro")).unwrap_err().code,
+            "currency"
+        );
+
+        let validator = rules("required|in:asc,desc").unwrap();
+        assert!(validator("sort", Some("desc")).is_ok());
+        assert_eq!(validator("sort", Some("up")).unwrap_err().code, "in");
+        assert!(validator("sort", Some("DESC")).is_err());
+        assert!(rules("in_ignore_case:asc,desc").unwrap()("sort", Some("DESC")).is_ok());
+
+        let validator = rules("max:3").unwrap();
+        assert!(validator("name", Some("abc")).is_ok());
+        assert!(validator("name", Some("abcd")).is_err());
+    }
+
+    #[test]
+    fn rules_errors() {
+        assert_eq!(rules("required||integer").err(), Some(RuleError::EmptyRule));
+        assert_eq!(
+            rules("unknown").err(),
+            Some(RuleError::UnknownRule("unknown".into()))
+        );
+        assert!(rules("between:1").is_err());
+        assert!(rules("max:abc").is_err());
+    }
+
+    #[test]
+    fn custom_rules() {
+        let mut registry = RuleRegistry::new();
+        registry.register("slug", |_| {
+            Ok(Box::new(
+                |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+                    match field_value {
+                        Some(value) if value.contains(' ') => {
+                            Err(ValidationError::new(field_name, "slug", "not a slug"))
+                        }
+                        _ => Ok(()),
+                    }
+                },
+            ))
+        });
+
+        let validator = registry.parse("required|slug").unwrap();
+        assert!(validator("name", Some("my-slug")).is_ok());
+        assert_eq!(validator("name", Some("my slug")).unwrap_err().code, "slug");
+        assert!(rules("slug").is_err());
+    }
+}
@@ -0,0 +1,532 @@
+//! Run of the rules of the fields matched by a request
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use serde::Serialize;
+use tide::{Request, Response, StatusCode};
+
+use crate::field_map::FieldMap;
+use crate::field_rule::{Failure, FieldRule, RuleOutput};
+use crate::normalize::{rewrite_headers, DecodedValues, REDACTED};
+use crate::plan::{Plan, Target};
+use crate::report::field_outcomes;
+use crate::trace::{self, RuleTrace, StepOutcome, TraceStep};
+use crate::values::{header_values, RequestValues};
+use crate::{
+    Cost, ExecutionPolicy, FieldOutcome, HttpField, NormalizedValues, ParsedValues, Presence,
+    Severity, StepValues, ValidationReport, ValidationWarnings, ValidatorError,
+    ValidatorMiddleware, Warning,
+};
+
+/// Field matched by the request with the rules it runs in one pass, and how they run
+pub(crate) struct MatchedField<'p, T, State> {
+    pub(crate) field: Cow<'p, HttpField<'static>>,
+    pub(crate) rules: &'p [FieldRule<T, State>],
+    /// Cost of the rules run in this pass, every rule runs when it's `None`
+    pub(crate) pass: Option<Cost>,
+    pub(crate) policy: ExecutionPolicy,
+    pub(crate) presence: Option<Presence>,
+    /// Fields whose errors skip the rules of this field
+    pub(crate) depends_on: Cow<'p, [HttpField<'static>]>,
+}
+
+impl<T, State> MatchedField<'_, T, State> {
+    fn in_pass(&self, rule: &FieldRule<T, State>) -> bool {
+        self.pass.is_none() || self.pass == Some(rule.cost())
+    }
+}
+
+/// Validation of a request: the errors and warnings found so far, and what is reported about
+/// the fields
+pub(crate) struct Run<'p, T> {
+    pub(crate) errors: Vec<(Cow<'p, HttpField<'static>>, Option<&'p str>, T)>,
+    /// Status of the first failing rule, if it has one
+    pub(crate) status: Option<StatusCode>,
+    pub(crate) warnings: Vec<Warning>,
+    pub(crate) validated: Vec<Cow<'p, HttpField<'static>>>,
+    /// Value and passed rules of each field, only kept when outcomes are reported
+    pub(crate) admitted: Option<FieldMap<(Option<String>, Vec<String>)>>,
+    pub(crate) parsed: ParsedValues,
+    pub(crate) trace: Option<RuleTrace>,
+    /// Whether the client asked for the trace in a header
+    pub(crate) send_trace: bool,
+}
+
+impl<T> Run<'_, T> {
+    pub(crate) fn has_error(&self, field: &HttpField<'_>) -> bool {
+        self.errors
+            .iter()
+            .any(|(invalid, _, _)| invalid.as_ref() == field)
+    }
+
+    /// Outcome of each validated field, empty unless outcomes are reported
+    pub(crate) fn outcomes(&self) -> Vec<FieldOutcome> {
+        let admitted = match &self.admitted {
+            Some(admitted) => admitted,
+            None => return Vec::new(),
+        };
+        let validated: Vec<&HttpField<'static>> =
+            self.validated.iter().map(|field| field.as_ref()).collect();
+        let failed: Vec<&HttpField<'static>> = self
+            .errors
+            .iter()
+            .map(|(field, _, _)| field.as_ref())
+            .collect();
+        let mut outcomes = field_outcomes(&validated, &failed, &self.warnings);
+        for outcome in &mut outcomes {
+            if let Some((value, passed)) = admitted.get(&outcome.field) {
+                outcome.value = value.clone();
+                outcome.passed = passed.clone();
+            }
+        }
+        outcomes
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Start the validation of a request, tracing it when a sink or the client asks for it
+    pub(crate) fn start_run(&self, ctx: &Request<State>) -> Run<'_, T> {
+        let send_trace = cfg!(debug_assertions)
+            && self
+                .trace
+                .header
+                .as_deref()
+                .into_iter()
+                .any(|name| header_values(ctx, name).is_some());
+        let trace = if send_trace || self.trace.sink.is_some() {
+            Some(RuleTrace::default())
+        } else {
+            None
+        };
+        let report_outcomes =
+            self.report.after_validation.is_some() || self.report.stamp_validated_requests;
+        Run {
+            errors: Vec::new(),
+            status: None,
+            warnings: Vec::new(),
+            validated: Vec::new(),
+            admitted: if report_outcomes {
+                Some(FieldMap::new())
+            } else {
+                None
+            },
+            parsed: ParsedValues::default(),
+            trace,
+            send_trace,
+        }
+    }
+
+    /// Field matched by the request for the rules of a field declared as a pattern or with
+    /// wildcards, or of global validators
+    fn matched_field<'p>(
+        &'p self,
+        field: Cow<'p, HttpField<'static>>,
+        rules: &'p [FieldRule<T, State>],
+        pass: Option<Cost>,
+        scoped_rules: &[&'p Self],
+    ) -> MatchedField<'p, T, State> {
+        MatchedField {
+            policy: self.execution_policy_for(&field, scoped_rules),
+            presence: self.presence_for(&field, scoped_rules),
+            depends_on: match self.dependencies.get(field.as_ref()) {
+                Some(depends_on) => Cow::Borrowed(&depends_on[..]),
+                None => Cow::Borrowed(&[]),
+            },
+            field,
+            rules,
+            pass,
+        }
+    }
+
+    /// Run the rules of the fields of the plan in order until one fails, or all of them when
+    /// collecting every error. Patterns and wildcards run for the fields they match in the
+    /// request, expensive rules run after the cheap ones of every field when ordered by cost.
+    pub(crate) async fn run_plan<'p>(
+        &'p self,
+        run: &mut Run<'p, T>,
+        ctx: &mut Request<State>,
+        values: &mut RequestValues<'_, State>,
+        raw_body: &[u8],
+        plan: &'p Plan,
+        scoped_rules: &[&'p Self],
+    ) -> Result<(), Response> {
+        let passes: &[Option<Cost>] = if self.order_by_cost {
+            &[Some(Cost::Cheap), Some(Cost::Expensive)]
+        } else {
+            &[None]
+        };
+        for &pass in passes {
+            for planned in &plan.fields {
+                let rules = self.planned(planned).1;
+                if pass.is_some() && !rules.iter().any(|rule| Some(rule.cost()) == pass) {
+                    continue;
+                }
+                for field in self.planned_matches(planned, ctx, values) {
+                    let matched = match (&planned.target, &field) {
+                        (
+                            Target::Field {
+                                policy,
+                                presence,
+                                depends_on,
+                                ..
+                            },
+                            Cow::Borrowed(_),
+                        ) => MatchedField {
+                            field,
+                            rules,
+                            pass,
+                            policy: *policy,
+                            presence: *presence,
+                            depends_on: Cow::Borrowed(&depends_on[..]),
+                        },
+                        _ => self.matched_field(field, rules, pass, scoped_rules),
+                    };
+                    self.run_matched(run, ctx, values, raw_body, &matched)
+                        .await?;
+                    if !run.errors.is_empty() && !self.collect_all_errors {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Split the rules of the matched fields in two passes when ordered by cost, the cheap rules
+    /// of every field run before the expensive ones
+    pub(crate) fn by_cost<'p>(
+        &self,
+        matched: Vec<MatchedField<'p, T, State>>,
+    ) -> Vec<MatchedField<'p, T, State>> {
+        if !self.order_by_cost {
+            return matched;
+        }
+        let mut passes = Vec::with_capacity(matched.len() * 2);
+        for &pass in &[Cost::Cheap, Cost::Expensive] {
+            for matched in &matched {
+                if matched.rules.iter().any(|rule| rule.cost() == pass) {
+                    passes.push(MatchedField {
+                        field: matched.field.clone(),
+                        pass: Some(pass),
+                        depends_on: matched.depends_on.clone(),
+                        ..*matched
+                    });
+                }
+            }
+        }
+        passes
+    }
+
+    /// Run the rules of the matched fields until one fails, or all of them when collecting every
+    /// error
+    pub(crate) async fn run_fields<'p>(
+        &'p self,
+        run: &mut Run<'p, T>,
+        ctx: &mut Request<State>,
+        values: &mut RequestValues<'_, State>,
+        raw_body: &[u8],
+        matched_fields: &[MatchedField<'p, T, State>],
+    ) -> Result<(), Response> {
+        for matched in matched_fields {
+            self.run_matched(run, ctx, values, raw_body, matched)
+                .await?;
+            if !run.errors.is_empty() && !self.collect_all_errors {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the rules of a matched field, a validator which can't validate gives the response
+    async fn run_matched<'p>(
+        &'p self,
+        run: &mut Run<'p, T>,
+        ctx: &mut Request<State>,
+        values: &mut RequestValues<'_, State>,
+        raw_body: &[u8],
+        matched: &MatchedField<'p, T, State>,
+    ) -> Result<(), Response> {
+        if let Err(err) = self.run_field(run, ctx, values, raw_body, matched).await {
+            let trace = self.report_trace(run.trace.take(), run.send_trace);
+            let response = self.validator_error_response(ctx, &matched.field, &err);
+            return Err(trace::attach(response, trace));
+        }
+        Ok(())
+    }
+
+    /// Run the rules of a field, skipped when the field depends on an invalid field, is missing
+    /// from a partial update or is optional and missing
+    pub(crate) async fn run_field<'p>(
+        &'p self,
+        run: &mut Run<'p, T>,
+        ctx: &mut Request<State>,
+        values: &mut RequestValues<'_, State>,
+        raw_body: &[u8],
+        matched: &MatchedField<'p, T, State>,
+    ) -> Result<(), ValidatorError> {
+        let field = matched.field.as_ref();
+        if matched.policy == ExecutionPolicy::FailFast && run.has_error(field) {
+            return Ok(());
+        }
+        if matched
+            .depends_on
+            .iter()
+            .any(|depends_on| run.has_error(depends_on))
+        {
+            return Ok(());
+        }
+        // Fields missing from a partial update aren't updated, `null` deletes them
+        if let (true, HttpField::BodyField(path)) = (self.partial_updates, field) {
+            if !values.body.contains(path) {
+                return Ok(());
+            }
+        }
+        // Validated fields are only kept for the outcomes
+        if run.admitted.is_some()
+            && !run
+                .validated
+                .iter()
+                .any(|validated| validated.as_ref() == field)
+        {
+            run.validated.push(matched.field.clone());
+        }
+        let mut value = values.get(ctx, field);
+        let all_values = if matched
+            .rules
+            .iter()
+            .any(|rule| matched.in_pass(rule) && rule.reads_all_values())
+        {
+            values.all(ctx, field)
+        } else {
+            Vec::new()
+        };
+        if value.is_none() && matched.presence == Some(Presence::Optional) {
+            return Ok(());
+        }
+
+        // Values decoded by a codec stay out of the trace like out of errors and warnings
+        let decoded = ctx
+            .local::<DecodedValues>()
+            .filter(|decoded| decoded.get(field).is_some());
+        let to_redacted_value = |err: &T| {
+            let mut error = serde_json::to_value(err).unwrap_or_default();
+            if let Some(decoded) = decoded {
+                decoded.redact(field, &mut error);
+            }
+            error
+        };
+        let rules = matched
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| matched.in_pass(rule));
+        for (rule_index, rule) in rules {
+            if !rule.sample() {
+                self.record_skip(matched.rules, rule_index);
+                continue;
+            }
+            let applied = rule.apply(
+                ctx,
+                raw_body,
+                values.parts,
+                field.name(),
+                value.as_deref(),
+                &all_values,
+            );
+            let result = applied.resolve().await;
+            if let Some(trace) = &mut run.trace {
+                trace.steps.push(TraceStep {
+                    field: field.name().to_string(),
+                    location: field.location(),
+                    rule: rule_index,
+                    description: rule.description().map(String::from),
+                    name: rule.name().map(String::from),
+                    input: match decoded {
+                        Some(_) => value.as_ref().map(|_| String::from(REDACTED)),
+                        None => value.clone(),
+                    },
+                    outcome: match &result {
+                        Ok(_) => StepOutcome::Passed,
+                        Err(Failure::Invalid(err)) if rule.severity() == Severity::Warning => {
+                            StepOutcome::Warned(to_redacted_value(err))
+                        }
+                        Err(Failure::Invalid(err)) => StepOutcome::Failed(to_redacted_value(err)),
+                        Err(Failure::Errored(err)) => StepOutcome::Errored(match decoded {
+                            Some(decoded) => decoded.redact_text(field, err.to_string()),
+                            None => err.to_string(),
+                        }),
+                    },
+                });
+            }
+            if let (Some(admitted), Ok(_)) = (&mut run.admitted, &result) {
+                let (_, passed) = admitted.entry(field.clone());
+                passed.extend(rule.name().or_else(|| rule.description()).map(String::from));
+            }
+            match result {
+                Ok(Some(RuleOutput::Parsed(parsed))) => {
+                    run.parsed.0.insert(field.clone(), parsed);
+                }
+                Ok(Some(RuleOutput::Normalized(normalized))) => {
+                    values
+                        .normalized
+                        .0
+                        .insert(field.clone(), normalized.clone());
+                    value = Some(normalized);
+                }
+                Ok(None) => {}
+                Err(Failure::Errored(err)) => return Err(err),
+                Err(Failure::Invalid(err)) if rule.severity() == Severity::Warning => {
+                    self.record_failure(matched.rules, rule_index, true);
+                    run.warnings.push(Warning {
+                        field: field.clone(),
+                        error: to_redacted_value(&err),
+                    });
+                }
+                Err(Failure::Invalid(err)) => {
+                    self.record_failure(matched.rules, rule_index, false);
+                    if run.errors.is_empty() {
+                        run.status = rule.status();
+                    }
+                    run.errors
+                        .push((matched.field.clone(), rule.description(), err));
+                    if matched.policy == ExecutionPolicy::FailFast {
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(admitted) = &mut run.admitted {
+            admitted.entry(field.clone()).0 = value;
+        }
+        Ok(())
+    }
+
+    /// Run the rules of groups of fields, fields submitted in a previous step of a multi-step
+    /// form count as present
+    pub(crate) fn run_groups<'p>(
+        &'p self,
+        run: &mut Run<'p, T>,
+        ctx: &Request<State>,
+        values: &mut RequestValues<'_, State>,
+        scoped_rules: &[&'p Self],
+    ) {
+        let step_values = ctx.local::<StepValues>();
+        let groups = self.group_validators.iter().chain(
+            scoped_rules
+                .iter()
+                .flat_map(|rules| rules.group_validators.iter()),
+        );
+        for group in groups {
+            let group_values: Vec<(&str, Option<String>)> = group
+                .fields
+                .iter()
+                .map(|field| {
+                    let value = values.get(ctx, field).or_else(|| {
+                        step_values
+                            .and_then(|step_values| step_values.get(field.name()))
+                            .map(String::from)
+                    });
+                    (field.name(), value)
+                })
+                .collect();
+            let group_values: HashMap<&str, Option<&str>> = group_values
+                .iter()
+                .map(|(name, value)| (*name, value.as_deref()))
+                .collect();
+            if let Err(err) = (group.validate)(&group_values) {
+                run.errors
+                    .push((Cow::Borrowed(&group.fields[0]), None, err));
+                if !self.collect_all_errors {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Reject the request for the errors of the run, the `after_validation` hook gets the
+    /// outcomes of the fields
+    pub(crate) fn reject_run(
+        &self,
+        ctx: &mut Request<State>,
+        mut run: Run<'_, T>,
+        outcomes: Vec<FieldOutcome>,
+    ) -> Response {
+        self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+        if let Some(hook) = &self.report.after_validation {
+            let mut report = ValidationReport::from_request(ctx);
+            report.warnings.0.append(&mut run.warnings);
+            report.outcomes = outcomes;
+            hook(&report, ctx);
+        }
+        let (fields, errors): (Vec<_>, Vec<_>) = run
+            .errors
+            .into_iter()
+            .map(|(field, description, err)| (field, (description, err)))
+            .unzip();
+        let errors = fields
+            .iter()
+            .zip(errors)
+            .map(|(field, (description, err))| (field.as_ref(), description, err))
+            .collect();
+        let trace = self.report_trace(run.trace, run.send_trace);
+        trace::attach(self.error_response(ctx, errors, run.status), trace)
+    }
+
+    /// Store the values of the admitted request in its extensions for the handler: normalized
+    /// and parsed values, warnings and the fields validated by the middleware. Gives the values
+    /// of the warning header.
+    pub(crate) fn admit(
+        &self,
+        mut ctx: Request<State>,
+        run: Run<'_, T>,
+        normalized: NormalizedValues,
+        plan: &Plan,
+        outcomes: &[FieldOutcome],
+    ) -> (Request<State>, Vec<String>) {
+        if self.normalize.rewrite_headers {
+            rewrite_headers(&mut ctx, &normalized);
+        }
+        // Values of the middlewares stacked before this one are only copied to be merged with
+        // values this middleware normalized or parsed
+        if !normalized.0.is_empty() {
+            let mut all_normalized = ctx.local::<NormalizedValues>().cloned().unwrap_or_default();
+            all_normalized.0.extend(normalized.0);
+            ctx = ctx.set_local(all_normalized);
+        }
+        if !run.parsed.0.is_empty() {
+            let mut all_parsed_values = ctx.local::<ParsedValues>().cloned().unwrap_or_default();
+            all_parsed_values.0.extend(run.parsed.0);
+            ctx = ctx.set_local(all_parsed_values);
+        }
+        let mut warnings = run.warnings;
+        let warning_values: Vec<String> = match &self.report.warning_header {
+            Some(name) => {
+                let warn_value = name.eq_ignore_ascii_case("Warning");
+                warnings
+                    .iter()
+                    .map(|warning| warning.header_value(warn_value))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        if !warnings.is_empty() {
+            self.stats.warned.fetch_add(1, Ordering::Relaxed);
+            let mut all_warnings = ctx
+                .local::<ValidationWarnings>()
+                .cloned()
+                .unwrap_or_default();
+            all_warnings.0.append(&mut warnings);
+            ctx = ctx.set_local(all_warnings);
+        }
+        if self.report.stamp_validated_requests {
+            ctx = self.stamp(ctx, plan, outcomes);
+        }
+        (ctx, warning_values)
+    }
+}
@@ -0,0 +1,252 @@
+//! Time windows during which a route can be used, e.g. maintenance endpoints only usable
+//! between 02:00 and 04:00 UTC, or embargoed endpoints only usable after a launch date.
+//!
+//! The validator returned by [`within_time_window`](fn.within_time_window.html) ignores the value
+//! of its field, add it to any field of the route.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::schedule::{within_time_window, Schedule};
+//!
+//! let schedule = Schedule::new()
+//!     .allow_daily("02:00", "04:00")?
+//!     .deny_between(freeze_start, freeze_end);
+//! validator_middleware.add_validator(HttpField::param("job"), within_time_window(schedule));
+//! ```
+use std::fmt;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::validators::ValidationError;
+use crate::{BoxedValidator, ConfigError};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Source of the current time, replaced in tests with [`Schedule::clock`](struct.Schedule.html#method.clock)
+pub type Clock = Arc<dyn Fn() -> SystemTime + Send + Sync + 'static>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Window {
+    /// Seconds since midnight UTC, the start is included and the end excluded. The window spans
+    /// midnight when it ends before its start.
+    Daily(u64, u64),
+    Period(Option<SystemTime>, Option<SystemTime>),
+}
+
+impl Window {
+    fn contains(&self, time: SystemTime) -> bool {
+        match *self {
+            Window::Daily(start, end) => {
+                let seconds = time
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs() % SECONDS_PER_DAY)
+                    .unwrap_or_default();
+                if start <= end {
+                    start <= seconds && seconds < end
+                } else {
+                    start <= seconds || seconds < end
+                }
+            }
+            Window::Period(from, until) => {
+                from.into_iter().all(|from| from <= time)
+                    && until.into_iter().all(|until| time < until)
+            }
+        }
+    }
+}
+
+/// Windows during which requests are allowed or denied. A request is allowed when it arrives
+/// within one of the allowed windows, or at any time when there are none, and outside of every
+/// denied window.
+#[derive(Clone)]
+pub struct Schedule {
+    allowed: Vec<Window>,
+    denied: Vec<Window>,
+    clock: Clock,
+}
+
+impl fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Schedule")
+            .field("allowed", &self.allowed)
+            .field("denied", &self.denied)
+            .finish()
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::new()
+    }
+}
+
+impl Schedule {
+    /// Create a schedule allowing requests at any time, using the system clock
+    pub fn new() -> Self {
+        Schedule {
+            allowed: Vec::new(),
+            denied: Vec::new(),
+            clock: Arc::new(SystemTime::now),
+        }
+    }
+
+    /// Allow requests every day between two times of day in UTC, given as `HH:MM` or `HH:MM:SS`.
+    /// The end is excluded, a window ending before its start spans midnight, e.g. `22:00` to `02:00`.
+    pub fn allow_daily(mut self, start: &str, end: &str) -> Result<Self, ConfigError> {
+        self.allowed.push(Window::Daily(
+            parse_time_of_day(start)?,
+            parse_time_of_day(end)?,
+        ));
+        Ok(self)
+    }
+
+    /// Deny requests every day between two times of day in UTC, like
+    /// [`allow_daily`](#method.allow_daily)
+    pub fn deny_daily(mut self, start: &str, end: &str) -> Result<Self, ConfigError> {
+        self.denied.push(Window::Daily(
+            parse_time_of_day(start)?,
+            parse_time_of_day(end)?,
+        ));
+        Ok(self)
+    }
+
+    /// Allow requests from `from` (included) to `until` (excluded)
+    pub fn allow_between(mut self, from: SystemTime, until: SystemTime) -> Self {
+        self.allowed.push(Window::Period(Some(from), Some(until)));
+        self
+    }
+
+    /// Allow requests from `from`, e.g. the launch date of an embargoed endpoint
+    pub fn allow_after(mut self, from: SystemTime) -> Self {
+        self.allowed.push(Window::Period(Some(from), None));
+        self
+    }
+
+    /// Allow requests until `until` (excluded), e.g. the end of life of a deprecated endpoint
+    pub fn allow_before(mut self, until: SystemTime) -> Self {
+        self.allowed.push(Window::Period(None, Some(until)));
+        self
+    }
+
+    /// Deny requests from `from` (included) to `until` (excluded), e.g. during a release freeze
+    pub fn deny_between(mut self, from: SystemTime, until: SystemTime) -> Self {
+        self.denied.push(Window::Period(Some(from), Some(until)));
+        self
+    }
+
+    /// Use another source for the current time, e.g. a fixed time in tests
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let now = UNIX_EPOCH + Duration::from_secs(3 * 60 * 60);
+    /// let schedule = Schedule::new().allow_daily("02:00", "04:00")?.clock(move || now);
+    /// ```
+    pub fn clock<C>(mut self, clock: C) -> Self
+    where
+        C: Fn() -> SystemTime + Send + Sync + 'static,
+    {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Check if requests are allowed at `time`
+    pub fn is_open_at(&self, time: SystemTime) -> bool {
+        (self.allowed.is_empty() || self.allowed.iter().any(|window| window.contains(time)))
+            && !self.denied.iter().any(|window| window.contains(time))
+    }
+
+    /// Check if requests are allowed now, according to the clock of the schedule
+    pub fn is_open(&self) -> bool {
+        self.is_open_at((self.clock)())
+    }
+}
+
+/// Check if the request arrives while the schedule is open, the value of the field is ignored
+pub fn within_time_window(schedule: Schedule) -> BoxedValidator<ValidationError> {
+    Box::new(move |field_name: &str, _field_value: Option<&str>| {
+        if schedule.is_open() {
+            Ok(())
+        } else {
+            Err(ValidationError::new(
+                field_name,
+                "time_window",
+                format!("'{}' cannot be used at this time", field_name),
+            ))
+        }
+    })
+}
+
+fn parse_time_of_day(value: &str) -> Result<u64, ConfigError> {
+    let invalid = || ConfigError::InvalidTimeOfDay(value.to_string());
+    let parts = value
+        .split(':')
+        .map(|part| match part.len() {
+            2 if part.bytes().all(|b| b.is_ascii_digit()) => part.parse::<u64>().ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<u64>>>()
+        .ok_or_else(invalid)?;
+    match parts[..] {
+        [hours, minutes] | [hours, minutes, 0] if hours == 24 && minutes == 0 => {
+            Ok(SECONDS_PER_DAY)
+        }
+        [hours, minutes] if hours < 24 && minutes < 60 => Ok(hours * 3600 + minutes * 60),
+        [hours, minutes, seconds] if hours < 24 && minutes < 60 && seconds < 60 => {
+            Ok(hours * 3600 + minutes * 60 + seconds)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn daily_windows() {
+        let schedule = Schedule::new().allow_daily("02:00", "04:00").unwrap();
+        assert!(!schedule.is_open_at(at(SECONDS_PER_DAY + 3600)));
+        assert!(schedule.is_open_at(at(SECONDS_PER_DAY + 2 * 3600)));
+        assert!(schedule.is_open_at(at(SECONDS_PER_DAY + 4 * 3600 - 1)));
+        assert!(!schedule.is_open_at(at(SECONDS_PER_DAY + 4 * 3600)));
+
+        let schedule = Schedule::new().deny_daily("22:00", "02:00").unwrap();
+        assert!(!schedule.is_open_at(at(23 * 3600)));
+        assert!(!schedule.is_open_at(at(3600)));
+        assert!(schedule.is_open_at(at(12 * 3600)));
+
+        assert!(Schedule::new().allow_daily("00:00", "24:00").is_ok());
+        assert!(Schedule::new().allow_daily("02:00:30", "04:00").is_ok());
+        assert_eq!(
+            Schedule::new().allow_daily("2:00", "04:00").err(),
+            Some(ConfigError::InvalidTimeOfDay(String::from("2:00")))
+        );
+        assert!(Schedule::new().allow_daily("02:00", "24:01").is_err());
+        assert!(Schedule::new().allow_daily("02:60", "04:00").is_err());
+    }
+
+    #[test]
+    fn periods_and_clock() {
+        let launch = at(1_000_000);
+        let schedule = Schedule::new()
+            .allow_after(launch)
+            .deny_between(at(2_000_000), at(3_000_000));
+        assert!(!schedule.is_open_at(at(999_999)));
+        assert!(schedule.is_open_at(launch));
+        assert!(!schedule.is_open_at(at(2_500_000)));
+        assert!(schedule.is_open_at(at(3_000_000)));
+
+        let validator = within_time_window(schedule.clone().clock(move || launch));
+        assert!(validator("release", None).is_ok());
+        let validator = within_time_window(schedule.clock(|| at(0)));
+        assert_eq!(
+            validator("release", Some("v2")).unwrap_err().code,
+            "time_window"
+        );
+    }
+}
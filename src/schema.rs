@@ -0,0 +1,506 @@
+//! Validators generated from a JSON Schema, to share validation definitions with a frontend.
+//!
+//! [`ValidatorMiddleware::from_json_schema`](../struct.ValidatorMiddleware.html#method.from_json_schema)
+//! takes either the JSON Schema of the body, or a route schema whose members `querystring` (or
+//! `query`), `params`, `headers` and `body` are the JSON Schemas of each part of the request:
+//!
+//! ```json
+//! {
+//!   "querystring": {
+//!     "type": "object",
+//!     "properties": { "page": { "type": "integer", "minimum": 1 } }
+//!   },
+//!   "body": {
+//!     "type": "object",
+//!     "required": ["name"],
+//!     "properties": {
+//!       "name": { "type": "string", "minLength": 1, "maxLength": 50 },
+//!       "role": { "enum": ["admin", "member"] },
+//!       "address": { "type": "object", "properties": { "city": { "type": "string" } } }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Supported keywords are `properties`, `required`, `type` (`string`, `integer`, `number`,
+//! `boolean` and `object`), `minimum`, `maximum`, `minLength`, `maxLength`, `enum`, `format`
+//! (`email`, `uuid`, `uri`, `date`) and `pattern` with the `regex` feature. Properties of nested
+//! objects become dotted body fields, their `required` properties are only required when the
+//! object is present. Annotations like `title` or `default` are ignored, other keywords are
+//! rejected so a schema is never silently half applied. `additionalProperties` is only accepted
+//! when it's `true`, unknown fields are rejected with
+//! [`deny_unknown_fields`](../struct.ValidatorMiddleware.html#method.deny_unknown_fields).
+//!
+//! Values are checked as text like with the other validators: `{"age": "42"}` is a valid integer.
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::validators::{self, ValidationError};
+use crate::{BoxedValidator, HttpField, ValidatorMiddleware};
+
+/// Keywords which don't constrain values
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+    "example",
+    "nullable",
+];
+
+/// Error returned when a schema cannot be turned into validators
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    /// JSON Pointer of the invalid part of the schema, e.g. `/body/properties/age/minimum`
+    pub pointer: String,
+    /// Why it's invalid
+    pub reason: String,
+}
+
+impl SchemaError {
+    pub(crate) fn new(pointer: &str, reason: impl Into<String>) -> Self {
+        SchemaError {
+            pointer: pointer.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid schema at '{}': {}", self.pointer, self.reason)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl<State> ValidatorMiddleware<ValidationError, State>
+where
+    State: Send + Sync + 'static,
+{
+    /// Build a middleware from a JSON Schema loaded at startup, see the [`schema`](schema/index.html)
+    /// module for the supported schemas.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let schema: serde_json::Value = serde_json::from_str(include_str!("../schemas/create_user.json"))?;
+    /// let validator_middleware = ValidatorMiddleware::from_json_schema(&schema)?;
+    /// app.at("/users").middleware(validator_middleware).post(create_user);
+    /// ```
+    pub fn from_json_schema(schema: &Value) -> Result<Self, SchemaError> {
+        let mut middleware = ValidatorMiddleware::new();
+        let described = fields(schema)?;
+        for (field, validator) in described.fields {
+            middleware.add_validator(field, validator);
+        }
+        for (field, parent) in described.required_in {
+            let validator = required_in(field.name(), parent.name());
+            middleware.add_group_validator(&[field, parent], validator);
+        }
+        Ok(middleware)
+    }
+}
+
+/// Validators described by a schema
+#[derive(Default)]
+struct Described {
+    /// Validators of single fields
+    fields: Vec<(HttpField<'static>, BoxedValidator<ValidationError>)>,
+    /// Properties of nested objects which are required when the object, the second field, is
+    /// present
+    required_in: Vec<(HttpField<'static>, HttpField<'static>)>,
+}
+
+/// Group validator requiring `field` when the object `parent` is present
+fn required_in(
+    field: &str,
+    parent: &str,
+) -> impl Fn(&HashMap<&str, Option<&str>>) -> Result<(), ValidationError> + Send + Sync + 'static {
+    let field = field.to_string();
+    let parent = parent.to_string();
+    move |values: &HashMap<&str, Option<&str>>| {
+        let present = |name: &str| matches!(values.get(name), Some(Some(_)));
+        if present(&parent) && !present(&field) {
+            validators::is_required(&field, None)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Validators of every field described by a body or route schema
+fn fields(schema: &Value) -> Result<Described, SchemaError> {
+    let object = schema
+        .as_object()
+        .ok_or_else(|| SchemaError::new("", "expected an object"))?;
+    let is_route_schema = ["querystring", "query", "params", "headers", "body"]
+        .iter()
+        .any(|part| object.contains_key(*part))
+        && !object.contains_key("type")
+        && !object.contains_key("properties");
+    let mut fields = Described::default();
+    if !is_route_schema {
+        add_properties(schema, "", &HttpField::body_field, "", &mut fields)?;
+        return Ok(fields);
+    }
+    for (part, schema) in object {
+        let pointer = format!("/{}", part);
+        match part.as_str() {
+            "querystring" | "query" => {
+                add_properties(schema, &pointer, &HttpField::query_param, "", &mut fields)?
+            }
+            "params" => add_properties(schema, &pointer, &HttpField::param, "", &mut fields)?,
+            "headers" => add_properties(schema, &pointer, &HttpField::header, "", &mut fields)?,
+            "body" => add_properties(schema, &pointer, &HttpField::body_field, "", &mut fields)?,
+            other => {
+                return Err(SchemaError::new(
+                    &pointer,
+                    format!("unknown part of the request '{}'", other),
+                ))
+            }
+        }
+    }
+    Ok(fields)
+}
+
+/// Add the validators of the properties of an object schema, `prefix` is the dotted path of the object
+fn add_properties(
+    schema: &Value,
+    pointer: &str,
+    field: &dyn Fn(String) -> HttpField<'static>,
+    prefix: &str,
+    fields: &mut Described,
+) -> Result<(), SchemaError> {
+    let object = schema
+        .as_object()
+        .ok_or_else(|| SchemaError::new(pointer, "expected an object"))?;
+    for (keyword, value) in object {
+        let pointer = format!("{}/{}", pointer, keyword);
+        match keyword.as_str() {
+            "properties" | "required" => {}
+            "type" if value == "object" => {}
+            "additionalProperties" if value == true => {}
+            "additionalProperties" => {
+                return Err(SchemaError::new(
+                    &pointer,
+                    "only 'true' is supported, reject unknown fields with deny_unknown_fields",
+                ))
+            }
+            keyword if ANNOTATIONS.contains(&keyword) => {}
+            keyword => {
+                return Err(SchemaError::new(
+                    &pointer,
+                    format!("unsupported keyword '{}' for an object", keyword),
+                ))
+            }
+        }
+    }
+    let required = match object.get("required") {
+        None => Vec::new(),
+        Some(Value::Array(names)) => names
+            .iter()
+            .map(|name| {
+                name.as_str().ok_or_else(|| {
+                    SchemaError::new(&format!("{}/required", pointer), "expected property names")
+                })
+            })
+            .collect::<Result<Vec<&str>, _>>()?,
+        Some(_) => {
+            return Err(SchemaError::new(
+                &format!("{}/required", pointer),
+                "expected an array",
+            ))
+        }
+    };
+    let properties = match object.get("properties") {
+        None => return Ok(()),
+        Some(Value::Object(properties)) => properties,
+        Some(_) => {
+            return Err(SchemaError::new(
+                &format!("{}/properties", pointer),
+                "expected an object",
+            ))
+        }
+    };
+    for (name, property) in properties {
+        let pointer = format!("{}/properties/{}", pointer, name);
+        let path = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        // Properties of nested objects are only required when the object is present
+        let is_required = required.contains(&name.as_str());
+        if is_required && !prefix.is_empty() {
+            fields
+                .required_in
+                .push((field(path.clone()), field(prefix.to_string())));
+        }
+        let is_required = is_required && prefix.is_empty();
+        if property.get("type").and_then(Value::as_str) == Some("object") {
+            if is_required {
+                fields
+                    .fields
+                    .push((field(path.clone()), Box::new(validators::is_required)));
+            }
+            add_properties(property, &pointer, field, &path, fields)?;
+            continue;
+        }
+        let mut chain = Vec::new();
+        if is_required {
+            chain.push(Box::new(validators::is_required) as BoxedValidator<ValidationError>);
+        }
+        chain.extend(keywords(property, &pointer)?);
+        if !chain.is_empty() {
+            fields.fields.push((
+                field(path),
+                Box::new(
+                    move |field_name: &str,
+                          field_value: Option<&str>|
+                          -> Result<(), ValidationError> {
+                        for validator in &chain {
+                            validator(field_name, field_value)?;
+                        }
+                        Ok(())
+                    },
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Validators of the keywords of a property
+pub(crate) fn keywords(
+    property: &Value,
+    pointer: &str,
+) -> Result<Vec<BoxedValidator<ValidationError>>, SchemaError> {
+    let object = property
+        .as_object()
+        .ok_or_else(|| SchemaError::new(pointer, "expected an object"))?;
+    let number = |keyword: &str, value: &Value| {
+        value.as_f64().ok_or_else(|| {
+            SchemaError::new(&format!("{}/{}", pointer, keyword), "expected a number")
+        })
+    };
+    let length = |keyword: &str, value: &Value| {
+        value.as_u64().map(|length| length as usize).ok_or_else(|| {
+            SchemaError::new(
+                &format!("{}/{}", pointer, keyword),
+                "expected a positive integer",
+            )
+        })
+    };
+    // Check the type before the bounds, so `"a"` is reported as not an integer
+    let mut keywords: Vec<(&String, &Value)> = object.iter().collect();
+    keywords.sort_by_key(|(keyword, _)| *keyword != "type");
+    let mut chain: Vec<BoxedValidator<ValidationError>> = Vec::new();
+    for (keyword, value) in keywords {
+        let keyword_pointer = format!("{}/{}", pointer, keyword);
+        match (keyword.as_str(), value) {
+            ("type", Value::String(kind)) => match kind.as_str() {
+                "integer" => chain.push(Box::new(validators::is_integer)),
+                "number" => chain.push(Box::new(validators::is_number)),
+                "boolean" => chain.push(Box::new(validators::is_bool)),
+                "string" => {}
+                other => {
+                    return Err(SchemaError::new(
+                        &keyword_pointer,
+                        format!("unsupported type '{}'", other),
+                    ))
+                }
+            },
+            ("minimum", value) => chain.push(validators::is_min(number(keyword, value)?)),
+            ("maximum", value) => chain.push(validators::is_max(number(keyword, value)?)),
+            ("minLength", value) => chain.push(validators::is_min_length(length(keyword, value)?)),
+            ("maxLength", value) => chain.push(validators::is_max_length(length(keyword, value)?)),
+            ("enum", Value::Array(values)) => {
+                let allowed: Vec<String> = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::String(value) => value.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect();
+                let allowed: Vec<&str> = allowed.iter().map(String::as_str).collect();
+                chain.push(validators::one_of(&allowed));
+            }
+            ("format", Value::String(format)) => match format.as_str() {
+                "email" => chain.push(Box::new(validators::is_email)),
+                "uuid" => chain.push(Box::new(validators::is_uuid)),
+                "uri" => chain.push(Box::new(validators::is_url)),
+                "date" => chain.push(Box::new(validators::is_date)),
+                other => {
+                    return Err(SchemaError::new(
+                        &keyword_pointer,
+                        format!("unsupported format '{}'", other),
+                    ))
+                }
+            },
+            #[cfg(feature = "regex")]
+            ("pattern", value) => {
+                let pattern = value
+                    .as_str()
+                    .ok_or_else(|| SchemaError::new(&keyword_pointer, "expected a string"))?;
+                let regex = regex::Regex::new(pattern)
+                    .map_err(|err| SchemaError::new(&keyword_pointer, err.to_string()))?;
+                chain.push(validators::is_match(regex));
+            }
+            #[cfg(not(feature = "regex"))]
+            ("pattern", _) => {
+                return Err(SchemaError::new(
+                    &keyword_pointer,
+                    "'pattern' needs the regex feature",
+                ))
+            }
+            (keyword, _) if ANNOTATIONS.contains(&keyword) => {}
+            ("type", _) | ("enum", _) | ("format", _) => {
+                return Err(SchemaError::new(&keyword_pointer, "invalid value"))
+            }
+            (keyword, _) => {
+                return Err(SchemaError::new(
+                    &keyword_pointer,
+                    format!("unsupported keyword '{}'", keyword),
+                ))
+            }
+        }
+    }
+    Ok(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(
+        fields: &[(HttpField<'static>, BoxedValidator<ValidationError>)],
+        field: &HttpField<'static>,
+        value: Option<&str>,
+    ) -> Result<(), String> {
+        let (_, validator) = fields.iter().find(|(f, _)| f == field).unwrap();
+        validator(field.name(), value).map_err(|err| err.code)
+    }
+
+    #[test]
+    fn schema_fields() {
+        let schema = serde_json::json!({
+            "querystring": {
+                "type": "object",
+                "properties": { "page": { "type": "integer", "minimum": 1 } }
+            },
+            "body": {
+                "type": "object",
+                "required": ["name", "address"],
+                "properties": {
+                    "name": { "type": "string", "minLength": 1, "maxLength": 5, "title": "Name" },
+                    "role": { "enum": ["admin", "member"] },
+                    "address": {
+                        "type": "object",
+                        "required": ["city"],
+                        "properties": { "city": { "type": "string" } }
+                    }
+                }
+            }
+        });
+        let described = fields(&schema).unwrap();
+        let fields = described.fields;
+        let page = HttpField::query_param("page");
+        assert_eq!(validate(&fields, &page, Some("2")), Ok(()));
+        assert_eq!(validate(&fields, &page, Some("0")), Err("min".into()));
+        assert_eq!(validate(&fields, &page, Some("a")), Err("integer".into()));
+        let name = HttpField::body_field("name");
+        assert_eq!(validate(&fields, &name, None), Err("required".into()));
+        assert_eq!(
+            validate(&fields, &name, Some("Gribouille")),
+            Err("max".into())
+        );
+        let role = HttpField::body_field("role");
+        assert_eq!(validate(&fields, &role, Some("owner")), Err("in".into()));
+        assert_eq!(
+            validate(&fields, &HttpField::body_field("address"), None),
+            Err("required".into())
+        );
+        assert_eq!(
+            validate(&fields, &HttpField::body_field("address"), Some("{}")),
+            Ok(())
+        );
+        assert_eq!(
+            described.required_in,
+            vec![(
+                HttpField::body_field("address.city"),
+                HttpField::body_field("address")
+            )]
+        );
+
+        // A plain JSON Schema describes the body
+        let schema = serde_json::json!({"properties": {"age": {"type": "integer"}}});
+        let fields = super::fields(&schema).unwrap().fields;
+        assert_eq!(
+            validate(&fields, &HttpField::body_field("age"), Some("4.2")),
+            Err("integer".into())
+        );
+    }
+
+    #[test]
+    fn nested_required() {
+        // `city` is only required when the optional `address` is sent
+        let schema = serde_json::json!({
+            "properties": {
+                "address": {
+                    "type": "object",
+                    "required": ["city"],
+                    "properties": { "city": { "type": "string" } }
+                }
+            }
+        });
+        let described = fields(&schema).unwrap();
+        assert!(described.fields.is_empty());
+        let (field, parent) = &described.required_in[0];
+        let validator = required_in(field.name(), parent.name());
+        let values = |address, city| {
+            let mut values = HashMap::new();
+            values.insert("address", address);
+            values.insert("address.city", city);
+            values
+        };
+        assert!(validator(&values(None, None)).is_ok());
+        assert!(validator(&values(Some("{\"city\": \"Paris\"}"), Some("Paris"))).is_ok());
+        let err = validator(&values(Some("{}"), None)).unwrap_err();
+        assert_eq!(
+            (err.field.as_str(), err.code.as_str()),
+            ("address.city", "required")
+        );
+    }
+
+    #[test]
+    fn schema_errors() {
+        let schema = serde_json::json!({"body": {"properties": {"age": {"minimum": "one"}}}});
+        assert_eq!(
+            fields(&schema).err(),
+            Some(SchemaError::new(
+                "/body/properties/age/minimum",
+                "expected a number"
+            ))
+        );
+        let schema = serde_json::json!({"properties": {"tags": {"type": "array"}}});
+        assert!(fields(&schema).is_err());
+        let schema = serde_json::json!({"properties": {"age": {"multipleOf": 2}}});
+        assert!(fields(&schema).is_err());
+        let schema = serde_json::json!({"additionalProperties": false, "properties": {}});
+        assert_eq!(
+            fields(&schema).err().map(|err| err.pointer),
+            Some(String::from("/additionalProperties"))
+        );
+        let schema = serde_json::json!({"additionalProperties": true, "properties": {}});
+        assert!(fields(&schema).is_ok());
+    }
+}
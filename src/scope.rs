@@ -0,0 +1,833 @@
+//! Rules enforced for some requests only: by session, method or step of a form
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tide::http::Method;
+use tide::{Request, Response};
+
+use crate::body::Body;
+use crate::plan::Target;
+use crate::run::{MatchedField, Run};
+use crate::values::{matching_fields, RequestValues};
+use crate::{ExecutionPolicy, HttpField, Presence, ValidatorMiddleware};
+
+/// Values submitted in the previous steps of a multi-step form, by field name. Store them in the
+/// request extensions from your session middleware, group validators receive them for the
+/// fields missing from the request so rules of a step can be checked against earlier steps.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let mut values = StepValues::new();
+/// values.insert("start_date", "2020-05-01");
+/// let req = req.set_local(values);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepValues(HashMap<String, String>);
+
+impl StepValues {
+    /// Empty values, fill them with [`insert`](#method.insert)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember the value of a field
+    pub fn insert(&mut self, field_name: &str, value: &str) {
+        self.0.insert(field_name.to_string(), value.to_string());
+    }
+
+    /// Value of a field submitted in a previous step
+    pub fn get(&self, field_name: &str) -> Option<&str> {
+        self.0.get(field_name).map(|value| &value[..])
+    }
+}
+
+pub(crate) type RequestPredicate<State> =
+    Arc<dyn Fn(&Request<State>) -> bool + Send + Sync + 'static>;
+
+/// Rules which only apply to some requests: to a session, a method, a step of a form or the
+/// items of a JSON array body
+pub(crate) struct Scopes<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    #[cfg(feature = "sessions")]
+    pub(crate) session_rules: Vec<(RequestPredicate<State>, ValidatorMiddleware<T, State>)>,
+    pub(crate) step_field: Option<HttpField<'static>>,
+    pub(crate) steps: Vec<(String, ValidatorMiddleware<T, State>)>,
+    pub(crate) each_item: Option<Box<ValidatorMiddleware<T, State>>>,
+    pub(crate) method_rules: Vec<(Method, ValidatorMiddleware<T, State>)>,
+}
+
+impl<T, State> Default for Scopes<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Scopes {
+            #[cfg(feature = "sessions")]
+            session_rules: Vec::new(),
+            step_field: None,
+            steps: Vec::new(),
+            each_item: None,
+            method_rules: Vec::new(),
+        }
+    }
+}
+
+impl<T, State> Clone for Scopes<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        Scopes {
+            #[cfg(feature = "sessions")]
+            session_rules: self.session_rules.clone(),
+            step_field: self.step_field.clone(),
+            steps: self.steps.clone(),
+            each_item: self.each_item.clone(),
+            method_rules: self.method_rules.clone(),
+        }
+    }
+}
+
+impl<T, State> Scopes<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    pub(crate) fn merge(&mut self, other: Scopes<T, State>) {
+        #[cfg(feature = "sessions")]
+        self.session_rules.extend(other.session_rules);
+        self.step_field = other.step_field.or(self.step_field.take());
+        self.steps.extend(other.steps);
+        self.each_item = other.each_item.or(self.each_item.take());
+        self.method_rules.extend(other.method_rules);
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Use another set of rules when the session stored in the request extensions by your session
+    /// middleware matches `predicate`. Validators of `rules` replace the validators registered for the
+    /// same fields, fields only present in `rules` are validated too. The first matching rule set wins.
+    ///
+    /// Requires the `sessions` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut admin_rules = ValidatorMiddleware::new();
+    /// admin_rules.add_validator(HttpField::query_param("limit"), is_between(1.0, 10_000.0));
+    ///
+    /// let mut validator_middleware = ValidatorMiddleware::new()
+    ///     .when_session(|session: &Session| session.role == Role::Admin, admin_rules);
+    /// validator_middleware.add_validator(HttpField::query_param("limit"), is_between(1.0, 100.0));
+    /// ```
+    #[cfg(feature = "sessions")]
+    pub fn when_session<S, P>(mut self, predicate: P, rules: ValidatorMiddleware<T, State>) -> Self
+    where
+        S: Send + Sync + 'static,
+        P: Fn(&S) -> bool + Send + Sync + 'static,
+    {
+        let predicate: RequestPredicate<State> =
+            Arc::new(move |req: &Request<State>| match req.local::<S>() {
+                Some(session) => predicate(session),
+                None => false,
+            });
+        self.scopes.session_rules.push((predicate, rules));
+        self
+    }
+
+    /// Rules only enforced for requests using `method`, along with the rules of this middleware,
+    /// so a single middleware can validate every method of a route
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut create = ValidatorMiddleware::new();
+    /// create.add_validator(HttpField::body_field("name"), is_required);
+    ///
+    /// let mut validator_middleware = ValidatorMiddleware::new().for_method(Method::Post, create);
+    /// validator_middleware.add_validator_for(Method::Get, HttpField::query_param("limit"), is_integer);
+    /// app.at("/items").middleware(validator_middleware).get(list).post(create);
+    /// ```
+    pub fn for_method(mut self, method: Method, rules: ValidatorMiddleware<T, State>) -> Self {
+        self.scopes.method_rules.push((method, rules));
+        self
+    }
+
+    /// Apply the body field rules of `rules` to every item of a JSON array body, e.g. for bulk
+    /// endpoints. Paths are relative to the item and errors are reported on the path prefixed by
+    /// the index of the item, e.g. `2.email`. Optional fields, dependencies, execution policies and
+    /// the order of `rules` apply within each item, failures are counted by the
+    /// [status route](#method.validation_status_route) in the `each item` scope. Other fields of
+    /// `rules` are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut operation = ValidatorMiddleware::new();
+    /// operation.add_validator(HttpField::body_field("op"), one_of(&["add", "remove"]));
+    /// operation.add_validator(HttpField::body_field("email"), is_email);
+    ///
+    /// let validator_middleware = ValidatorMiddleware::new()
+    ///     .max_items(100)
+    ///     .each_item(operation)
+    ///     .collect_all_errors(true);
+    /// // Responds with {"2.email": [...], "5.op": [...]}
+    /// ```
+    pub fn each_item(mut self, rules: ValidatorMiddleware<T, State>) -> Self {
+        self.scopes.each_item = Some(Box::new(rules));
+        self
+    }
+
+    /// Read the active step of a multi-step form from `field`, e.g. a query parameter or a cookie.
+    /// Only the rules of the active step, added with [`step`](#method.step), are enforced along
+    /// with the rules of this middleware. When the step is missing or unknown no step rules are
+    /// enforced, validate `field` itself to reject it.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut address = ValidatorMiddleware::new();
+    /// address.add_validator(HttpField::body_field("city"), is_required);
+    /// let mut payment = ValidatorMiddleware::new();
+    /// payment.add_validator(HttpField::body_field("card"), is_required);
+    ///
+    /// let mut validator_middleware = ValidatorMiddleware::new()
+    ///     .step_field(HttpField::query_param("step"))
+    ///     .step("address", address)
+    ///     .step("payment", payment);
+    /// validator_middleware.add_validator(HttpField::query_param("step"), one_of(&["address", "payment"]));
+    /// ```
+    pub fn step_field(mut self, field: HttpField<'static>) -> Self {
+        self.scopes.step_field = Some(field);
+        self
+    }
+
+    /// Rules only enforced when the field given to [`step_field`](#method.step_field) is `name`.
+    /// Their group validators also receive the [`StepValues`](struct.StepValues.html) of the
+    /// previous steps for the fields missing from the request.
+    pub fn step(mut self, name: &str, rules: ValidatorMiddleware<T, State>) -> Self {
+        self.scopes.steps.push((name.to_string(), rules));
+        self
+    }
+
+    /// Add a validator only run for requests using `method`, see [`for_method`](#method.for_method)
+    pub fn add_validator_for<F>(
+        &mut self,
+        method: Method,
+        param_name: HttpField<'static>,
+        validator: F,
+    ) where
+        F: Fn(&str, Option<&str>) -> Result<(), T> + Send + Sync + 'static,
+    {
+        let position = match self
+            .scopes
+            .method_rules
+            .iter()
+            .position(|(m, _)| *m == method)
+        {
+            Some(position) => position,
+            None => {
+                self.scopes
+                    .method_rules
+                    .push((method, ValidatorMiddleware::new()));
+                self.scopes.method_rules.len() - 1
+            }
+        };
+        self.scopes.method_rules[position]
+            .1
+            .add_validator(param_name, validator);
+    }
+
+    /// Rules of the request method and of the step named `step`
+    pub(crate) fn scoped_rules(&self, method: Method, step: Option<&str>) -> Vec<&Self> {
+        let method_rules = self
+            .scopes
+            .method_rules
+            .iter()
+            .filter(|(rules_method, _)| *rules_method == method)
+            .map(|(_, rules)| rules);
+        let step_rules = self
+            .scopes
+            .steps
+            .iter()
+            .filter(|(name, _)| Some(&name[..]) == step)
+            .map(|(_, rules)| rules);
+        method_rules.chain(step_rules).collect()
+    }
+
+    /// Policy of a field, rules scoped to the method or step of the request take precedence
+    pub(crate) fn execution_policy_for(
+        &self,
+        field: &HttpField<'static>,
+        scoped_rules: &[&Self],
+    ) -> ExecutionPolicy {
+        scoped_rules
+            .iter()
+            .rev()
+            .chain(std::iter::once(&self))
+            .find_map(|rules| rules.field_policies.get(field))
+            .copied()
+            .or(self.execution_policy)
+            .unwrap_or(if self.collect_all_errors {
+                ExecutionPolicy::RunAll
+            } else {
+                ExecutionPolicy::FailFast
+            })
+    }
+
+    pub(crate) fn presence_for(
+        &self,
+        field: &HttpField<'static>,
+        scoped_rules: &[&Self],
+    ) -> Option<Presence> {
+        scoped_rules
+            .iter()
+            .rev()
+            .chain(std::iter::once(&self))
+            .find_map(|rules| rules.presence.get(field))
+            .copied()
+    }
+
+    /// Active step of a multi-step form, read from the step field
+    pub(crate) fn current_step(
+        &self,
+        ctx: &Request<State>,
+        values: &mut RequestValues<'_, State>,
+    ) -> Option<String> {
+        let field = self.scopes.step_field.as_ref()?;
+        values.raw(ctx, field)
+    }
+
+    /// Run the rules of the items of a JSON array body like the rules of the fields, errors are
+    /// reported at the path of the item, e.g. `2.email`. A validator which can't validate gives
+    /// the response.
+    pub(crate) async fn run_items<'p>(
+        &'p self,
+        run: &mut Run<'p, T>,
+        ctx: &mut Request<State>,
+        values: &mut RequestValues<'_, State>,
+        raw_body: &[u8],
+    ) -> Result<(), Response> {
+        let (item_rules, items) = match (&self.scopes.each_item, values.body) {
+            (Some(item_rules), Body::Json(serde_json::Value::Array(items))) => {
+                (item_rules, items.len())
+            }
+            _ => return Ok(()),
+        };
+        let plan = item_rules.item_plan(ctx);
+        let item_fields: Vec<_> = plan
+            .fields
+            .iter()
+            .filter_map(
+                |planned| match (&planned.target, item_rules.planned(planned)) {
+                    (
+                        Target::Field {
+                            presence,
+                            depends_on,
+                            ..
+                        },
+                        (Some(field), rules),
+                    ) => {
+                        let policy = item_rules
+                            .field_policies
+                            .get(field)
+                            .copied()
+                            .unwrap_or_else(|| self.execution_policy_for(field, &[]));
+                        Some((field, rules, policy, *presence, depends_on))
+                    }
+                    _ => None,
+                },
+            )
+            .collect();
+        let mut matched = Vec::new();
+        for index in 0..items {
+            for &(field, rules, policy, presence, depends_on) in &item_fields {
+                let item_field = match in_item(index, field) {
+                    Some(item_field) => item_field,
+                    None => continue,
+                };
+                let depends_on: Vec<HttpField<'static>> = depends_on
+                    .iter()
+                    .filter_map(|depends_on| in_item(index, depends_on))
+                    .collect();
+                let fields = matching_fields(ctx, &item_field, &mut values.query, values.body);
+                for field in fields {
+                    matched.push(MatchedField {
+                        field: Cow::Owned(field.into_owned()),
+                        rules,
+                        pass: None,
+                        policy,
+                        presence,
+                        depends_on: Cow::Owned(depends_on.clone()),
+                    });
+                }
+            }
+        }
+        let matched = self.by_cost(matched);
+        self.run_fields(run, ctx, values, raw_body, &matched).await
+    }
+}
+
+/// Body field of the item at `index` of a JSON array body
+fn in_item(index: usize, field: &HttpField<'_>) -> Option<HttpField<'static>> {
+    match field {
+        HttpField::BodyField(path) => Some(HttpField::body_field(match path.strip_prefix('/') {
+            Some(pointer) => format!("/{}/{}", index, pointer),
+            None => format!("{}.{}", index, path),
+        })),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{is_number, is_required};
+    use crate::{validators, ErrorShape, Location, ValidationContext, ValidationError};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use futures::future::BoxFuture;
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request};
+    use tide::Middleware;
+    use tide::Next;
+    use tide::StatusCode;
+
+    #[cfg(feature = "sessions")]
+    struct Session {
+        admin: bool,
+    }
+
+    /// Stands for a session middleware, the `X-Role` header tells who is logged in
+    #[cfg(feature = "sessions")]
+    struct Sessions;
+
+    #[cfg(feature = "sessions")]
+    impl Middleware<()> for Sessions {
+        fn handle<'a>(
+            &'a self,
+            ctx: tide::Request<()>,
+            next: Next<'a, ()>,
+        ) -> BoxFuture<'a, tide::Result> {
+            let ctx = match ctx.header(&"X-Role".parse().unwrap()) {
+                Some(role) => {
+                    let admin = role[0].as_str() == "admin";
+                    ctx.set_local(Session { admin })
+                }
+                None => ctx,
+            };
+            next.run(ctx)
+        }
+    }
+
+    #[cfg(feature = "sessions")]
+    #[test]
+    fn validator_when_session() {
+        let mut inner = tide::new();
+        let mut admin_rules = ValidatorMiddleware::new();
+        admin_rules.add_validator(
+            HttpField::query_param("limit"),
+            validators::is_between(1.0, 10_000.0),
+        );
+        let mut validators =
+            ValidatorMiddleware::new().when_session(|session: &Session| session.admin, admin_rules);
+        validators.add_validator(
+            HttpField::query_param("limit"),
+            validators::is_between(1.0, 100.0),
+        );
+        inner
+            .at("/foo")
+            .middleware(Sessions)
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (role, status) in [
+            (Some("admin"), StatusCode::Ok),
+            (Some("user"), StatusCode::BadRequest),
+            (None, StatusCode::BadRequest),
+        ]
+        .iter()
+        {
+            let mut req = Request::new(
+                Method::Get,
+                "http://localhost/foo?limit=500".parse().unwrap(),
+            );
+            if let Some(role) = role {
+                req.insert_header("X-Role", *role).unwrap();
+            }
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+    }
+
+    /// Stands for a session middleware storing the values of the previous steps
+    struct PreviousSteps;
+
+    impl Middleware<()> for PreviousSteps {
+        fn handle<'a>(
+            &'a self,
+            ctx: tide::Request<()>,
+            next: Next<'a, ()>,
+        ) -> BoxFuture<'a, tide::Result> {
+            let mut values = StepValues::new();
+            values.insert("start_date", "2020-05-01");
+            next.run(ctx.set_local(values))
+        }
+    }
+
+    #[test]
+    fn validator_steps() {
+        let mut inner = tide::new();
+        let mut dates = ValidatorMiddleware::new();
+        dates.add_validator(HttpField::query_param("end_date"), validators::is_required);
+        dates.add_group_validator(
+            &[
+                HttpField::query_param("start_date"),
+                HttpField::query_param("end_date"),
+            ],
+            |values: &HashMap<&str, Option<&str>>| match (values["start_date"], values["end_date"])
+            {
+                (Some(start), Some(end)) if end <= start => Err(validators::ValidationError::new(
+                    "end_date",
+                    "after",
+                    "'end_date' must be after 'start_date'",
+                )),
+                _ => Ok(()),
+            },
+        );
+        let validators = ValidatorMiddleware::new()
+            .step_field(HttpField::query_param("step"))
+            .step("dates", dates);
+        inner
+            .at("/foo")
+            .middleware(PreviousSteps)
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?step=name".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?step=dates".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?step=dates&end_date=2020-04-01"
+                .parse()
+                .unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?step=dates&end_date=2020-05-31"
+                .parse()
+                .unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+    }
+
+    #[test]
+    fn validator_skip_if() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .skip_if(|req: &tide::Request<()>| req.method() == Method::Options);
+        validators.add_validator(HttpField::header("X-Tenant"), validators::is_required);
+        inner
+            .at("/items")
+            .middleware(validators)
+            .get(|_| async { Ok("items") })
+            .method(Method::Options, |_| async { Ok("preflight") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let req = Request::new(Method::Options, "http://localhost/items".parse().unwrap());
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let req = Request::new(Method::Get, "http://localhost/items".parse().unwrap());
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn validator_global() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_global_validator(Location::Header, validators::is_max_length(16));
+        validators.add_global_validator(
+            Location::Query,
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some(value) if value.chars().any(char::is_control) => Err(ValidationError::new(
+                    field_name,
+                    "control",
+                    format!("'{}' contains control characters", field_name),
+                )),
+                _ => Ok(()),
+            },
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(Method::Get, "http://localhost/foo?q=cat".parse().unwrap());
+        req.insert_header("X-Short", "short").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+        req.insert_header("X-Long", "a value longer than 16 bytes")
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let req = Request::new(Method::Get, "http://localhost/foo?q=c%07t".parse().unwrap());
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn validator_each_item() {
+        let mut inner = tide::new();
+        let mut operation = ValidatorMiddleware::new();
+        operation.add_validator(HttpField::body_field("id"), is_number);
+        let validators = ValidatorMiddleware::new()
+            .max_items(3)
+            .each_item(operation)
+            .collect_all_errors(true);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .post(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"[{"id": 1}, {"id": 2}]"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"[{"id": 1}, {"id": "a"}, {"id": "b"}]"#);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "1.id": ["field '1.id' = 'a' is not a valid number"],
+                "2.id": ["field '2.id' = 'b' is not a valid number"],
+            })
+        );
+
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"[{"id": 1}, {"id": 2}, {"id": 3}, {"id": 4}]"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::PayloadTooLarge);
+    }
+
+    #[test]
+    fn validator_each_item_optional() {
+        let mut inner = tide::new();
+        let mut operation = ValidatorMiddleware::new();
+        operation.optional(HttpField::body_field("note"));
+        operation.add_validator(
+            HttpField::body_field("note"),
+            |_: &str, field_value: Option<&str>| match field_value {
+                Some(note) if note.len() <= 4 => Ok(()),
+                _ => Err(String::from("invalid note")),
+            },
+        );
+        let validators = ValidatorMiddleware::new()
+            .each_item(operation)
+            .collect_all_errors(true);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .post(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"[{"note": "ok"}, {}]"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(Method::Post, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"[{}, {"note": "too long"}]"#);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(body, serde_json::json!({"1.note": ["invalid note"]}));
+    }
+
+    #[test]
+    fn validator_for_method() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator_for(Method::Get, HttpField::query_param("limit"), is_number);
+        validators.add_validator_for(
+            Method::Post,
+            HttpField::query_param("dry_run"),
+            |field_name: &str, field_value: Option<&str>| match field_value {
+                Some("true") | Some("false") | None => Ok(()),
+                Some(_) => Err(format!("'{}' is not a boolean", field_name)),
+            },
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") })
+            .post(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let req = Request::new(Method::Get, "http://localhost/foo?limit=a".parse().unwrap());
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let req = Request::new(
+            Method::Post,
+            "http://localhost/foo?limit=a".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let req = Request::new(
+            Method::Post,
+            "http://localhost/foo?dry_run=yes".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn validator_partial_updates() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().partial_updates(true);
+        validators.add_validator(HttpField::body_field("name"), validators::is_required);
+        validators.add_validator(HttpField::body_field("age"), validators::is_integer);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .patch(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(Method::Patch, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"{"age": 4}"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let mut req = Request::new(Method::Patch, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"{"age": "four"}"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let mut req = Request::new(Method::Patch, "http://localhost/foo".parse().unwrap());
+        req.set_body(r#"{"name": null}"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn validator_execution_policy() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .collect_all_errors(true)
+            .error_shape(ErrorShape::GroupedByField);
+        for field in &["age", "size"] {
+            validators.add_validator(HttpField::query_param(*field), is_number);
+            validators.add_validator(HttpField::query_param(*field), |field_name: &str, _| {
+                Err(format!("'{}' is never valid", field_name))
+            });
+        }
+        validators.set_execution_policy(HttpField::query_param("size"), ExecutionPolicy::FailFast);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut buf = Vec::new();
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?age=a&size=b".parse().unwrap(),
+        );
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let errors: HashMap<String, Vec<String>> = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(errors["age"].len(), 2);
+        assert_eq!(
+            errors["size"],
+            vec![String::from("field 'size' = 'b' is not a valid number")]
+        );
+    }
+
+    #[test]
+    fn validator_when() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator_when(
+            HttpField::query_param("coupon"),
+            is_required,
+            |ctx: &ValidationContext<()>| ctx.query_param("promo").as_deref() == Some("true"),
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (url, status) in &[
+            ("http://localhost/foo", StatusCode::Ok),
+            ("http://localhost/foo?promo=false", StatusCode::Ok),
+            ("http://localhost/foo?promo=true", StatusCode::BadRequest),
+            (
+                "http://localhost/foo?promo=true&coupon=SPRING",
+                StatusCode::Ok,
+            ),
+        ] {
+            let req = Request::new(Method::Get, url.parse().unwrap());
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status, "{}", url);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+//! Validators rejecting the usual injection patterns, available with the `security` feature.
+//!
+//! Each check is a validator of its own. The [`hardening`](fn.hardening.html) preset adds the
+//! checks to every query parameter, header and cookie of the request, and each of them can be
+//! turned off. Path parameters can't be listed by the middleware, add the validators to them by
+//! name.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::security::{hardening, no_path_traversal};
+//!
+//! let mut validator_middleware = ValidatorMiddleware::new().preset(hardening().sql(false));
+//! validator_middleware.add_validator(HttpField::param("file"), no_path_traversal);
+//! ```
+use serde::Serialize;
+
+use crate::presets::Preset;
+use crate::validators::{self, ValidationError};
+use crate::{Location, ValidatorMiddleware};
+
+/// Fragments of SQL injections, matched in lowercase with whitespace collapsed
+const SQL_FRAGMENTS: &[&str] = &[
+    "' or '",
+    "' or 1",
+    "\" or \"",
+    " or 1=1",
+    "'--",
+    "';",
+    "union select",
+    "union all select",
+    "drop table",
+    "insert into",
+    "delete from",
+    "xp_cmdshell",
+    "sleep(",
+    "benchmark(",
+];
+
+/// Fragments of HTML or JavaScript injections, matched in lowercase without whitespace
+const SCRIPT_FRAGMENTS: &[&str] = &[
+    "<script",
+    "</script",
+    "javascript:",
+    "vbscript:",
+    "<iframe",
+    "<object",
+    "<embed",
+    "<svg",
+    "onerror=",
+    "onload=",
+    "onmouseover=",
+    "srcdoc=",
+];
+
+fn rejected(field_name: &str, code: &str, reason: &str) -> ValidationError {
+    ValidationError::new(
+        field_name,
+        code,
+        format!("field '{}' {}", field_name, reason),
+    )
+}
+
+/// Check the field doesn't contain a null byte, which truncates values in C libraries and file
+/// systems
+pub fn no_null_bytes(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if value.contains('\0') => Err(rejected(
+            field_name,
+            "null_byte",
+            "must not contain null bytes",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Check the field doesn't contain `\r` or `\n`, which split headers when the value is written
+/// in a response header
+pub fn no_crlf(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if value.contains(&['\r', '\n'][..]) => {
+            Err(rejected(field_name, "crlf", "must not contain line breaks"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check the field doesn't contain a `..` path segment, separated by `/` or `\`, even
+/// percent-encoded once or twice like `%2e%2e%2f` or `%252e%252e`
+pub fn no_path_traversal(
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<(), ValidationError> {
+    let value = match field_value {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    let once = String::from_utf8_lossy(&crate::percent_decode(value)).into_owned();
+    let twice = String::from_utf8_lossy(&crate::percent_decode(&once)).into_owned();
+    let traverses = |value: &str| value.split(&['/', '\\'][..]).any(|s| s == "..");
+    if traverses(value) || traverses(&once) || traverses(&twice) {
+        return Err(rejected(
+            field_name,
+            "path_traversal",
+            "must not contain '..' path segments",
+        ));
+    }
+    Ok(())
+}
+
+/// Check the field doesn't contain fragments of SQL injections like `' OR '1'='1` or
+/// `UNION SELECT`. Queries should be parameterized anyway, this check only spots probes.
+pub fn no_sql_injection(
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<(), ValidationError> {
+    let value = match field_value {
+        Some(value) => value.to_lowercase(),
+        None => return Ok(()),
+    };
+    let collapsed = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    if SQL_FRAGMENTS
+        .iter()
+        .any(|fragment| collapsed.contains(fragment))
+    {
+        return Err(rejected(
+            field_name,
+            "sql",
+            "contains a suspicious SQL fragment",
+        ));
+    }
+    Ok(())
+}
+
+/// Check the field doesn't contain fragments of script injections like `<script>`,
+/// `javascript:` URLs or event handlers like `onerror=`
+pub fn no_script(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    let value = match field_value {
+        Some(value) => value.to_lowercase(),
+        None => return Ok(()),
+    };
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if SCRIPT_FRAGMENTS
+        .iter()
+        .any(|fragment| compact.contains(fragment))
+    {
+        return Err(rejected(
+            field_name,
+            "script",
+            "contains a suspicious script fragment",
+        ));
+    }
+    Ok(())
+}
+
+/// Preset adding the security checks to every query parameter, header and cookie, see
+/// [`hardening`](fn.hardening.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hardening {
+    null_bytes: bool,
+    crlf: bool,
+    path_traversal: bool,
+    sql: bool,
+    script: bool,
+    max_length: Option<usize>,
+}
+
+/// Reject query parameters, headers and cookies which:
+///
+/// - contain null bytes, see [`no_null_bytes`](fn.no_null_bytes.html)
+/// - contain line breaks, see [`no_crlf`](fn.no_crlf.html)
+/// - contain `..` path segments, see [`no_path_traversal`](fn.no_path_traversal.html)
+/// - contain SQL fragments, see [`no_sql_injection`](fn.no_sql_injection.html)
+/// - contain script fragments, see [`no_script`](fn.no_script.html)
+/// - are longer than 8192 characters
+///
+/// Every check is enabled, turn them off one by one.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// // Search queries may contain SQL keywords
+/// let validator_middleware = ValidatorMiddleware::new().preset(hardening().sql(false).max_length(Some(2048)));
+/// ```
+pub fn hardening() -> Hardening {
+    Hardening {
+        null_bytes: true,
+        crlf: true,
+        path_traversal: true,
+        sql: true,
+        script: true,
+        max_length: Some(8192),
+    }
+}
+
+impl Hardening {
+    /// Reject null bytes
+    pub fn null_bytes(mut self, enabled: bool) -> Self {
+        self.null_bytes = enabled;
+        self
+    }
+
+    /// Reject line breaks
+    pub fn crlf(mut self, enabled: bool) -> Self {
+        self.crlf = enabled;
+        self
+    }
+
+    /// Reject `..` path segments
+    pub fn path_traversal(mut self, enabled: bool) -> Self {
+        self.path_traversal = enabled;
+        self
+    }
+
+    /// Reject SQL fragments
+    pub fn sql(mut self, enabled: bool) -> Self {
+        self.sql = enabled;
+        self
+    }
+
+    /// Reject script fragments
+    pub fn script(mut self, enabled: bool) -> Self {
+        self.script = enabled;
+        self
+    }
+
+    /// Maximum length of values in characters, `None` to accept any length
+    pub fn max_length(mut self, max_length: Option<usize>) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Run the enabled checks on a value
+    pub fn check(
+        &self,
+        field_name: &str,
+        field_value: Option<&str>,
+    ) -> Result<(), ValidationError> {
+        if let Some(max_length) = self.max_length {
+            validators::is_max_length(max_length)(field_name, field_value)?;
+        }
+        let checks: [(bool, fn(&str, Option<&str>) -> Result<(), ValidationError>); 5] = [
+            (self.null_bytes, no_null_bytes),
+            (self.crlf, no_crlf),
+            (self.path_traversal, no_path_traversal),
+            (self.sql, no_sql_injection),
+            (self.script, no_script),
+        ];
+        checks
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .try_for_each(|(_, check)| check(field_name, field_value))
+    }
+}
+
+impl<T, State> Preset<T, State> for Hardening
+where
+    T: Serialize + Send + Sync + From<ValidationError> + 'static,
+    State: Send + Sync + 'static,
+{
+    fn apply(self, middleware: &mut ValidatorMiddleware<T, State>) {
+        for location in &[Location::Query, Location::Header, Location::Cookie] {
+            middleware.add_global_validator(
+                *location,
+                move |field_name: &str, field_value: Option<&str>| {
+                    self.check(field_name, field_value).map_err(T::from)
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injection_checks() {
+        assert!(no_null_bytes("q", Some("cat")).is_ok());
+        assert_eq!(
+            no_null_bytes("q", Some("cat\0.jpg")).unwrap_err().code,
+            "null_byte"
+        );
+        assert_eq!(
+            no_crlf("next", Some("/\r\nSet-Cookie: a=b"))
+                .unwrap_err()
+                .code,
+            "crlf"
+        );
+
+        for value in &[
+            "../etc/passwd",
+            "a/../../b",
+            "..\\boot.ini",
+            "%2e%2e%2fetc",
+            "%252e%252e/",
+        ] {
+            assert_eq!(
+                no_path_traversal("file", Some(value)).unwrap_err().code,
+                "path_traversal",
+                "{}",
+                value
+            );
+        }
+        for value in &["report..pdf", "a/b/c", "..."] {
+            assert!(no_path_traversal("file", Some(value)).is_ok(), "{}", value);
+        }
+
+        for value in &[
+            "' OR '1'='1",
+            "1 UNION  SELECT password",
+            "x'; DROP TABLE users",
+        ] {
+            assert_eq!(
+                no_sql_injection("q", Some(value)).unwrap_err().code,
+                "sql",
+                "{}",
+                value
+            );
+        }
+        assert!(no_sql_injection("q", Some("rock and roll")).is_ok());
+        assert!(no_sql_injection("q", Some("O'Reilly books")).is_ok());
+
+        for value in &[
+            "<script>alert(1)</script>",
+            "JavaScript:alert(1)",
+            "<img src=x onerror = alert(1)>",
+        ] {
+            assert_eq!(
+                no_script("q", Some(value)).unwrap_err().code,
+                "script",
+                "{}",
+                value
+            );
+        }
+        assert!(no_script("q", Some("a < b")).is_ok());
+        assert!(no_script("q", None).is_ok());
+    }
+
+    #[test]
+    fn hardening_toggles() {
+        let preset = hardening();
+        assert!(preset.check("q", Some("cats")).is_ok());
+        assert_eq!(
+            preset
+                .check("q", Some("1 union select 2"))
+                .unwrap_err()
+                .code,
+            "sql"
+        );
+        assert!(preset
+            .sql(false)
+            .check("q", Some("1 union select 2"))
+            .is_ok());
+        let long = "a".repeat(9000);
+        assert!(preset.check("q", Some(&long)).is_err());
+        assert!(preset.max_length(None).check("q", Some(&long)).is_ok());
+        assert!(preset.check("Accept", Some("*/*")).is_ok());
+    }
+}
@@ -0,0 +1,471 @@
+//! Failure counters served by the validation status route
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tide::{Endpoint, Request, Response, StatusCode};
+
+use crate::{FieldRule, HttpField, Location, ValidatorMiddleware};
+
+/// Where a list of rules was added: the scope is `None` for the rules of the middleware itself,
+/// or names the session, method, step or item rules they were added to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum RuleSource {
+    Field {
+        scope: Option<String>,
+        field: HttpField<'static>,
+    },
+    Global {
+        scope: Option<String>,
+        location: Location,
+    },
+}
+
+impl RuleSource {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            RuleSource::Field { scope, field } => serde_json::json!({
+                "scope": scope,
+                "field": field.name(),
+                "location": field.kind(),
+            }),
+            RuleSource::Global { scope, location } => serde_json::json!({
+                "scope": scope,
+                "field": null,
+                "location": location,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ValidationStats {
+    pub(crate) requests: AtomicU64,
+    pub(crate) rejected: AtomicU64,
+    /// Accepted requests which validators added with `Severity::Warning` would have rejected
+    pub(crate) warned: AtomicU64,
+    /// Failures by source and by position of the rule in the source's rules
+    failures: Mutex<HashMap<(RuleSource, usize), u64>>,
+    /// Failures of validators added with `Severity::Warning`, counted like `failures`
+    warnings: Mutex<HashMap<(RuleSource, usize), u64>>,
+    /// Runs of sampled validators skipped by their sample rate, counted like `failures`
+    skipped: Mutex<HashMap<(RuleSource, usize), u64>>,
+}
+
+// Patterns are hashed by their source, their regex cache doesn't change the key
+#[cfg_attr(feature = "regex", allow(clippy::mutable_key_type))]
+impl ValidationStats {
+    fn record(
+        counters: &Mutex<HashMap<(RuleSource, usize), u64>>,
+        source: RuleSource,
+        rule_index: usize,
+    ) {
+        if let Ok(mut counters) = counters.lock() {
+            *counters.entry((source, rule_index)).or_insert(0) += 1;
+        }
+    }
+
+    fn counts(
+        counters: &Mutex<HashMap<(RuleSource, usize), u64>>,
+    ) -> HashMap<(RuleSource, usize), u64> {
+        counters
+            .lock()
+            .map(|counters| counters.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Create an endpoint serving the rules configuration of this middleware and its failure
+    /// counters as JSON, for dashboards and debugging. The rules configuration is the one at the
+    /// time this method is called, counters are live.
+    ///
+    /// Rules added for a session, a method, a step or the items of an array body are listed with a
+    /// `scope` naming them, rules added with
+    /// [`add_global_validator`](struct.ValidatorMiddleware.html#method.add_global_validator)
+    /// with a `null` field. `warnings` counts the failures of validators added with
+    /// [`Severity::Warning`](enum.Severity.html#variant.Warning) and `warned` the accepted requests
+    /// they would have rejected, to see what turning them into errors would change. `skipped`
+    /// counts the runs of validators added with
+    /// [`add_sampled_validator`](struct.ValidatorMiddleware.html#method.add_sampled_validator)
+    /// skipped by their sample rate.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut validator_middleware = ValidatorMiddleware::new();
+    /// validator_middleware.add_validator(HttpField::query_param("age"), is_number);
+    ///
+    /// app.at("/_validation").get(validator_middleware.validation_status_route());
+    /// app.at("/test").middleware(validator_middleware).get(endpoint);
+    /// ```
+    ///
+    /// Example of response:
+    ///
+    /// ```json
+    /// {
+    ///   "requests": 12,
+    ///   "rejected": 2,
+    ///   "warned": 0,
+    ///   "fields": [
+    ///     {
+    ///       "scope": null,
+    ///       "field": "age",
+    ///       "location": "query parameter",
+    ///       "rules": 1,
+    ///       "descriptions": [],
+    ///       "failures": [2],
+    ///       "warnings": [0],
+    ///       "skipped": [0]
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    // Patterns are hashed by their source, their regex cache doesn't change the key
+    #[cfg_attr(feature = "regex", allow(clippy::mutable_key_type))]
+    pub fn validation_status_route<S>(&self) -> impl Endpoint<S>
+    where
+        S: Send + Sync + 'static,
+    {
+        let stats = Arc::clone(&self.stats);
+        let rules: Vec<(RuleSource, usize, Vec<String>)> = self
+            .rule_sources()
+            .into_iter()
+            .map(|(source, validators)| {
+                let descriptions = validators
+                    .iter()
+                    .filter_map(FieldRule::description)
+                    .map(String::from)
+                    .collect();
+                (source, validators.len(), descriptions)
+            })
+            .collect();
+
+        move |_: Request<S>| {
+            let failures = ValidationStats::counts(&stats.failures);
+            let warnings = ValidationStats::counts(&stats.warnings);
+            let skipped = ValidationStats::counts(&stats.skipped);
+            let fields: Vec<serde_json::Value> = rules
+                .iter()
+                .map(|(source, rules_count, descriptions)| {
+                    let count = |counters: &HashMap<(RuleSource, usize), u64>| -> Vec<u64> {
+                        (0..*rules_count)
+                            .map(|index| {
+                                counters.get(&(source.clone(), index)).copied().unwrap_or(0)
+                            })
+                            .collect()
+                    };
+                    let mut entry = source.to_json();
+                    entry["rules"] = serde_json::json!(rules_count);
+                    entry["descriptions"] = serde_json::json!(descriptions);
+                    entry["failures"] = serde_json::json!(count(&failures));
+                    entry["warnings"] = serde_json::json!(count(&warnings));
+                    entry["skipped"] = serde_json::json!(count(&skipped));
+                    entry
+                })
+                .collect();
+            let body = serde_json::json!({
+                "requests": stats.requests.load(Ordering::Relaxed),
+                "rejected": stats.rejected.load(Ordering::Relaxed),
+                "warned": stats.warned.load(Ordering::Relaxed),
+                "fields": fields,
+            });
+            async move { Ok(Response::new(StatusCode::Ok).body_json(&body)?) }
+        }
+    }
+
+    /// Every list of rules of this middleware and of its session, method, step and item rules,
+    /// with where it was added
+    pub(crate) fn rule_sources(&self) -> Vec<(RuleSource, &[FieldRule<T, State>])> {
+        fn collect<'s, T, State>(
+            sources: &mut Vec<(RuleSource, &'s [FieldRule<T, State>])>,
+            scope: Option<String>,
+            rules: &'s ValidatorMiddleware<T, State>,
+        ) where
+            T: Serialize + Send + Sync + 'static,
+            State: Send + Sync + 'static,
+        {
+            for (field, validators) in &rules.validators {
+                let source = RuleSource::Field {
+                    scope: scope.clone(),
+                    field: field.clone(),
+                };
+                sources.push((source, &validators[..]));
+            }
+            for (location, validators) in &rules.global_validators {
+                let source = RuleSource::Global {
+                    scope: scope.clone(),
+                    location: *location,
+                };
+                sources.push((source, &validators[..]));
+            }
+        }
+
+        let mut sources = Vec::with_capacity(self.validators.len() + self.global_validators.len());
+        collect(&mut sources, None, self);
+        #[cfg(feature = "sessions")]
+        for (index, (_, rules)) in self.scopes.session_rules.iter().enumerate() {
+            collect(&mut sources, Some(format!("session {}", index)), rules);
+        }
+        for (method, rules) in &self.scopes.method_rules {
+            collect(&mut sources, Some(format!("method {}", method)), rules);
+        }
+        for (name, rules) in &self.scopes.steps {
+            collect(&mut sources, Some(format!("step {}", name)), rules);
+        }
+        if let Some(rules) = &self.scopes.each_item {
+            collect(&mut sources, Some(String::from("each item")), rules);
+        }
+        sources
+    }
+
+    /// Where `rules`, one of the lists of [`rule_sources`](#method.rule_sources), was added
+    pub(crate) fn rule_source(&self, rules: &[FieldRule<T, State>]) -> Option<RuleSource> {
+        self.rule_sources()
+            .into_iter()
+            .find(|(_, validators)| std::ptr::eq(*validators, rules))
+            .map(|(source, _)| source)
+    }
+
+    /// Count a failure of the rule at `rule_index` in `rules` for the status route
+    pub(crate) fn record_failure(
+        &self,
+        rules: &[FieldRule<T, State>],
+        rule_index: usize,
+        warning: bool,
+    ) {
+        let counters = if warning {
+            &self.stats.warnings
+        } else {
+            &self.stats.failures
+        };
+        if let Some(source) = self.rule_source(rules) {
+            ValidationStats::record(counters, source, rule_index);
+        }
+    }
+
+    /// Count a run of the sampled rule at `rule_index` in `rules` skipped by its sample rate
+    pub(crate) fn record_skip(&self, rules: &[FieldRule<T, State>], rule_index: usize) {
+        if let Some(source) = self.rule_source(rules) {
+            ValidationStats::record(&self.stats.skipped, source, rule_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    use crate::Severity;
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request};
+
+    #[test]
+    #[cfg_attr(feature = "regex", allow(clippy::mutable_key_type))]
+    fn validation_status_sources() {
+        let mut post_rules = ValidatorMiddleware::<String>::new();
+        post_rules.add_validator(HttpField::query_param("age"), is_number);
+        let mut validators = ValidatorMiddleware::new().for_method(Method::Post, post_rules);
+        validators.add_validator(HttpField::query_param("age"), is_number);
+        validators.add_global_validator(Location::Header, is_number);
+
+        let sources: Vec<RuleSource> = validators
+            .rule_sources()
+            .into_iter()
+            .map(|(source, _)| source)
+            .collect();
+        let base = RuleSource::Field {
+            scope: None,
+            field: HttpField::query_param("age"),
+        };
+        let post = RuleSource::Field {
+            scope: Some(format!("method {}", Method::Post)),
+            field: HttpField::query_param("age"),
+        };
+        let headers = RuleSource::Global {
+            scope: None,
+            location: Location::Header,
+        };
+        assert_eq!(sources, vec![base.clone(), headers, post.clone()]);
+
+        let post_rules =
+            &validators.scopes.method_rules[0].1.validators[&HttpField::query_param("age")];
+        validators.record_failure(post_rules, 0, false);
+        validators.record_failure(post_rules, 0, true);
+        let failures = ValidationStats::counts(&validators.stats.failures);
+        assert_eq!(failures.get(&(post.clone(), 0)), Some(&1));
+        assert_eq!(failures.get(&(base, 0)), None);
+        let warnings = ValidationStats::counts(&validators.stats.warnings);
+        assert_eq!(warnings.get(&(post, 0)), Some(&1));
+    }
+
+    #[test]
+    fn validator_status_route() {
+        let mut inner = tide::new();
+        let mut post_rules = ValidatorMiddleware::new();
+        post_rules.add_validator(
+            HttpField::query_param("age"),
+            |_: &str, field_value: Option<&str>| match field_value {
+                Some(age) if age.len() > 2 => Err("too old".to_string()),
+                _ => Ok(()),
+            },
+        );
+        let mut validators = ValidatorMiddleware::new().for_method(Method::Post, post_rules);
+        validators.add_validator(HttpField::query_param("age"), is_number);
+        validators.add_validator_with_severity(
+            HttpField::query_param("page_size"),
+            Severity::Warning,
+            |_: &str, field_value: Option<&str>| match field_value {
+                Some(_) => Err("deprecated".to_string()),
+                None => Ok(()),
+            },
+        );
+        inner
+            .at("/_validation")
+            .get(validators.validation_status_route());
+        inner
+            .at("/users")
+            .middleware(validators)
+            .get(|_: tide::Request<()>| async move { Ok("") })
+            .post(|_: tide::Request<()>| async move { Ok("") });
+
+        let mut server = make_server(inner).unwrap();
+        for (method, url, status) in [
+            (
+                Method::Get,
+                "http://localhost/users?age=a",
+                StatusCode::BadRequest,
+            ),
+            (
+                Method::Get,
+                "http://localhost/users?age=1&page_size=20",
+                StatusCode::Ok,
+            ),
+            (
+                Method::Post,
+                "http://localhost/users?age=100",
+                StatusCode::BadRequest,
+            ),
+        ]
+        .iter()
+        {
+            let req = Request::new(*method, url.parse().unwrap());
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+
+        let req = Request::new(Method::Get, "http://localhost/_validation".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        let mut buf = Vec::new();
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(status["requests"], 3);
+        assert_eq!(status["rejected"], 2);
+        assert_eq!(status["warned"], 1);
+        let fields = status["fields"].as_array().unwrap();
+        let field = |scope: serde_json::Value, name: &str| {
+            fields
+                .iter()
+                .find(|field| field["scope"] == scope && field["field"] == name)
+                .unwrap()
+        };
+        let age = field(serde_json::Value::Null, "age");
+        assert_eq!(age["failures"], serde_json::json!([1]));
+        let post_age = field(serde_json::json!(format!("method {}", Method::Post)), "age");
+        assert_eq!(post_age["failures"], serde_json::json!([1]));
+        let page_size = field(serde_json::Value::Null, "page_size");
+        assert_eq!(page_size["failures"], serde_json::json!([0]));
+        assert_eq!(page_size["warnings"], serde_json::json!([1]));
+    }
+
+    #[test]
+    fn validator_status_route_sampled() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_sampled_validator(
+            HttpField::query_param("q"),
+            0.5,
+            |_: &str, _: Option<&str>| Err(String::from("suspicious")),
+        );
+        inner
+            .at("/_validation")
+            .get(validators.validation_status_route());
+        inner
+            .at("/search")
+            .middleware(validators)
+            .get(|_: tide::Request<()>| async move { Ok("") });
+
+        let mut server = make_server(inner).unwrap();
+        let statuses: Vec<StatusCode> = (0..4)
+            .map(|_| {
+                let req = Request::new(Method::Get, "http://localhost/search?q=a".parse().unwrap());
+                server.simulate(req).unwrap().status()
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::Ok,
+                StatusCode::BadRequest,
+                StatusCode::Ok,
+                StatusCode::BadRequest
+            ]
+        );
+
+        let req = Request::new(Method::Get, "http://localhost/_validation".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        let mut buf = Vec::new();
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        let q = &status["fields"][0];
+        assert_eq!(q["failures"], serde_json::json!([2]));
+        assert_eq!(q["skipped"], serde_json::json!([2]));
+    }
+
+    #[test]
+    fn validator_status_route_items() {
+        let mut inner = tide::new();
+        let mut operation = ValidatorMiddleware::new();
+        operation.add_validator(HttpField::body_field("id"), is_number);
+        let validators = ValidatorMiddleware::new()
+            .each_item(operation)
+            .collect_all_errors(true);
+        inner
+            .at("/_validation")
+            .get(validators.validation_status_route());
+        inner
+            .at("/users")
+            .middleware(validators)
+            .post(|_: tide::Request<()>| async move { Ok("") });
+
+        let mut server = make_server(inner).unwrap();
+        let mut req = Request::new(Method::Post, "http://localhost/users".parse().unwrap());
+        req.set_body(r#"[{"id": "a"}, {"id": 1}, {"id": "b"}]"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let req = Request::new(Method::Get, "http://localhost/_validation".parse().unwrap());
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        let mut buf = Vec::new();
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(status["rejected"], 1);
+        let id = status["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|field| field["scope"] == "each item" && field["field"] == "id")
+            .unwrap();
+        assert_eq!(id["failures"], serde_json::json!([2]));
+    }
+}
@@ -0,0 +1,293 @@
+//! Helpers to test validators and middlewares, enable the `testing` feature in your
+//! `dev-dependencies`.
+//!
+//! - [`assert_valid!`](../macro.assert_valid.html) and [`assert_invalid!`](../macro.assert_invalid.html)
+//!   run a single validator, catching panics
+//! - [`TestRequest`](struct.TestRequest.html) sends a fake request through a whole
+//!   `ValidatorMiddleware`, without spinning up a server
+//! - [`snapshot`](fn.snapshot.html) and [`TestResponse::snapshot`](struct.TestResponse.html#method.snapshot)
+//!   render errors as stable pretty JSON, to compare them with snapshot testing tools like `insta`
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::testing::{snapshot, TestRequest};
+//! use tide_validator::{assert_invalid, assert_valid};
+//!
+//! assert_valid!(is_integer, "age", Some("42"));
+//! let err = assert_invalid!(is_integer, "age", Some("forty-two"));
+//! insta::assert_snapshot!(snapshot(&err));
+//!
+//! let res = TestRequest::get("/users/abc?page=2")
+//!     .route("/users/:id")
+//!     .header("X-Request-Id", "f7c1b4c2-5d43-4c3e-a9b1-1d4a8ea1e8d2")
+//!     .send(validator_middleware);
+//! assert!(!res.is_valid());
+//! insta::assert_snapshot!(res.snapshot());
+//! ```
+use futures::executor::block_on;
+use futures::io::AsyncReadExt;
+use http_service_mock::make_server;
+use serde::Serialize;
+use tide::http::{Method, Request};
+use tide::{Response, StatusCode};
+
+use crate::ValidatorMiddleware;
+
+/// Header set by the endpoint of the fake server, to know if the request went through the middleware
+const PASSED_HEADER: &str = "X-Tide-Validator-Passed";
+
+/// Check that a validator accepts a value, panicking with the error otherwise
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// assert_valid!(is_integer, "age", Some("42"));
+/// assert_valid!(is_integer, "age", None);
+/// ```
+#[macro_export]
+macro_rules! assert_valid {
+    ($validator:expr, $field_name:expr, $field_value:expr $(,)?) => {{
+        let field_value: Option<&str> = $field_value;
+        match $crate::validate_one(&$validator, $field_name, field_value) {
+            $crate::Outcome::Valid => {}
+            outcome => panic!(
+                "expected '{}' = {:?} to be valid, got {:?}",
+                $field_name, field_value, outcome
+            ),
+        }
+    }};
+}
+
+/// Check that a validator rejects a value and return the error, panicking otherwise
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// let err = assert_invalid!(is_integer, "age", Some("forty-two"));
+/// assert_eq!(err.code, "integer");
+/// ```
+#[macro_export]
+macro_rules! assert_invalid {
+    ($validator:expr, $field_name:expr, $field_value:expr $(,)?) => {{
+        let field_value: Option<&str> = $field_value;
+        match $crate::validate_one(&$validator, $field_name, field_value) {
+            $crate::Outcome::Invalid(err) => err,
+            outcome => panic!(
+                "expected '{}' = {:?} to be invalid, got {:?}",
+                $field_name, field_value, outcome
+            ),
+        }
+    }};
+}
+
+/// Render an error as pretty JSON with sorted keys, stable across runs
+pub fn snapshot<T: Serialize>(error: &T) -> String {
+    let value = serde_json::to_value(error).expect("cannot serialize the error");
+    serde_json::to_string_pretty(&value).expect("cannot serialize the error")
+}
+
+/// Fake request sent through a middleware with [`send`](#method.send)
+#[derive(Debug, Clone)]
+pub struct TestRequest {
+    method: Method,
+    url: String,
+    route: Option<String>,
+    headers: Vec<(String, String)>,
+    cookies: Vec<String>,
+    body: Option<Vec<u8>>,
+}
+
+impl TestRequest {
+    /// Create a request to `url`, a path with an optional query string like `/users/42?page=2`
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        TestRequest {
+            method,
+            url: url.into(),
+            route: None,
+            headers: Vec::new(),
+            cookies: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Create a `GET` request to `url`
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(Method::Get, url)
+    }
+
+    /// Create a `POST` request to `url`
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(Method::Post, url)
+    }
+
+    /// Route the middleware is mounted on, e.g. `/users/:id`, to fill path parameters.
+    /// Defaults to the path of the URL.
+    pub fn route(mut self, route: impl Into<String>) -> Self {
+        self.route = Some(route.into());
+        self
+    }
+
+    /// Add a header, repeat it to send several values
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add a cookie to the `Cookie` header
+    pub fn cookie(mut self, name: &str, value: &str) -> Self {
+        self.cookies.push(format!("{}={}", name, value));
+        self
+    }
+
+    /// Set the raw body
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Set a JSON body and its `Content-Type`
+    pub fn json<B: Serialize>(self, body: &B) -> Self {
+        let body = serde_json::to_vec(body).expect("cannot serialize the body");
+        self.header("Content-Type", "application/json").body(body)
+    }
+
+    /// Send the request through the middleware of an application without state
+    pub fn send<T>(self, middleware: ValidatorMiddleware<T>) -> TestResponse
+    where
+        T: Serialize + Send + Sync + 'static,
+    {
+        self.send_with_state((), middleware)
+    }
+
+    /// Send the request through the middleware of an application with `state`
+    pub fn send_with_state<T, State>(
+        self,
+        state: State,
+        middleware: ValidatorMiddleware<T, State>,
+    ) -> TestResponse
+    where
+        T: Serialize + Send + Sync + 'static,
+        State: Send + Sync + 'static,
+    {
+        let path = self.url.split('?').next().unwrap_or_default();
+        let mut app = tide::with_state(state);
+        app.at(self.route.as_deref().unwrap_or(path))
+            .middleware(middleware)
+            .method(self.method, |_| async {
+                Ok(Response::new(StatusCode::Ok).set_header(PASSED_HEADER, "true"))
+            });
+        let mut server = make_server(app).expect("cannot create the test server");
+
+        let url = format!("http://localhost{}", self.url)
+            .parse()
+            .expect("invalid test URL");
+        let mut req = Request::new(self.method, url);
+        for (name, value) in &self.headers {
+            req.append_header(name.as_str(), value.as_str())
+                .expect("invalid test header");
+        }
+        if !self.cookies.is_empty() {
+            req.insert_header("Cookie", self.cookies.join("; ").as_str())
+                .expect("invalid test cookie");
+        }
+        if let Some(body) = self.body {
+            req.set_body(body);
+        }
+
+        let mut res = server.simulate(req).expect("cannot send the test request");
+        let passed = res.header(&PASSED_HEADER.parse().unwrap()).is_some();
+        let mut body = Vec::new();
+        block_on(res.read_to_end(&mut body)).expect("cannot read the test response");
+        TestResponse {
+            status: res.status(),
+            passed,
+            body,
+        }
+    }
+}
+
+/// Response to a [`TestRequest`](struct.TestRequest.html)
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: StatusCode,
+    passed: bool,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    /// Status of the response
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Whether the request went through the middleware and reached the endpoint
+    pub fn is_valid(&self) -> bool {
+        self.passed
+    }
+
+    /// Body of the response
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Body of the response parsed as JSON, e.g. the errors of a rejected request
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("the response body isn't JSON")
+    }
+
+    /// Status followed by the body as pretty JSON with sorted keys, or the raw body when it isn't JSON
+    pub fn snapshot(&self) -> String {
+        match serde_json::from_slice::<serde_json::Value>(&self.body) {
+            Ok(body) => format!("{}\n{}", self.status as u16, snapshot(&body)),
+            Err(_) => format!("{}\n{}", self.status as u16, self.text()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validators::{is_integer, is_required, ValidationError};
+    use crate::HttpField;
+
+    #[test]
+    fn validator_assertions() {
+        assert_valid!(is_integer, "age", Some("42"));
+        assert_valid!(is_integer, "age", None);
+        let err: ValidationError = assert_invalid!(is_integer, "age", Some("forty-two"));
+        assert_eq!(err.code, "integer");
+
+        let rendered = snapshot(&err);
+        assert!(rendered.contains("\n  \"code\": \"integer\",\n"));
+        assert_eq!(rendered, snapshot(&err.clone()));
+    }
+
+    #[test]
+    #[should_panic(expected = "to be invalid")]
+    fn failed_assertion() {
+        let _: ValidationError = assert_invalid!(is_integer, "age", Some("42"));
+    }
+
+    #[test]
+    fn fake_requests() {
+        let middleware = || {
+            let mut middleware = ValidatorMiddleware::new();
+            middleware.add_validator(HttpField::param("id"), is_integer);
+            middleware.add_validator(HttpField::header("X-Tenant"), is_required);
+            middleware
+        };
+
+        let res = TestRequest::get("/users/42")
+            .route("/users/:id")
+            .header("X-Tenant", "acme")
+            .send(middleware());
+        assert!(res.is_valid());
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let res = TestRequest::get("/users/abc")
+            .route("/users/:id")
+            .send(middleware());
+        assert!(!res.is_valid());
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert!(res.snapshot().starts_with("400\n"));
+    }
+}
@@ -0,0 +1,293 @@
+//! Step by step trace of the rules run on a request, to answer "why was this request rejected?".
+//!
+//! Enable it with [`trace_rules`](../struct.ValidatorMiddleware.html#method.trace_rules) to log
+//! every trace, or with [`trace_header`](../struct.ValidatorMiddleware.html#method.trace_header)
+//! to let clients of debug builds ask for the trace of their own requests:
+//!
+//! ```sh
+//! curl -i -H 'X-Validation-Trace: 1' 'http://127.0.0.1:8080/search?page=0'
+//! # X-Validation-Trace: [{"field":"page","in":"query","rule":0,"input":"0","outcome":"passed"},
+//! #                      {"field":"page","in":"query","rule":1,"input":"0","outcome":"failed","error":{...}}]
+//! ```
+use std::sync::Arc;
+
+use serde::Serialize;
+use tide::Response;
+
+use crate::{Location, ValidatorMiddleware};
+
+/// Outcome of a rule, serialized as `outcome` with the `error` of failed rules
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "outcome", content = "error")]
+pub enum StepOutcome {
+    /// The value passed the rule
+    Passed,
+    /// The value is invalid, with the serialized error
+    Failed(serde_json::Value),
+    /// The value is invalid but the rule only warns, with the serialized error
+    Warned(serde_json::Value),
+    /// The rule couldn't check the value, see
+    /// [`add_fallible_validator`](../struct.ValidatorMiddleware.html#method.add_fallible_validator)
+    Errored(String),
+}
+
+/// A rule run on a field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceStep {
+    /// Name of the field
+    pub field: String,
+    /// Where the field is located in the request, serialized as `in`
+    #[serde(rename = "in")]
+    pub location: Location,
+    /// Index of the rule among the rules of the field, in the order they were added
+    pub rule: usize,
+    /// Description of the rule added with `add_described_validator`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Name of the [`Validator`](../trait.Validator.html) added with `add_named_validator`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Value given to the rule, `None` when the field is missing
+    pub input: Option<String>,
+    /// What the rule decided
+    #[serde(flatten)]
+    pub outcome: StepOutcome,
+}
+
+/// Rules run on a request, in the order they ran. Rules after the first failure of a field
+/// don't run unless every error is collected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct RuleTrace {
+    /// Every rule which ran
+    pub steps: Vec<TraceStep>,
+}
+
+impl RuleTrace {
+    /// The trace as JSON where non-ASCII characters are escaped, so it fits in a header value
+    pub fn to_header_value(&self) -> String {
+        escape_header_value(&serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+/// Escape the characters which can't be sent in a header value, non-ASCII and control characters
+/// except tabs, like in JSON strings: `é` is `\u00e9`
+pub(crate) fn escape_header_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_ascii() && (!c.is_ascii_control() || c == '\t') {
+            escaped.push(c);
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                escaped.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    escaped
+}
+
+/// Add the trace to the response in the header `name`, when the client asked for it
+pub(crate) fn attach(response: Response, trace: Option<(&str, RuleTrace)>) -> Response {
+    match trace {
+        Some((name, trace)) => response.set_header(name, trace.to_header_value()),
+        None => response,
+    }
+}
+
+pub(crate) type TraceSink = Arc<dyn Fn(&RuleTrace) + Send + Sync + 'static>;
+
+/// Where the rule traces go
+#[derive(Clone, Default)]
+pub(crate) struct TraceSettings {
+    pub(crate) sink: Option<TraceSink>,
+    pub(crate) header: Option<String>,
+}
+
+impl TraceSettings {
+    pub(crate) fn merge(&mut self, other: TraceSettings) {
+        self.sink = other.sink.or(self.sink.take());
+        self.header = other.header.or(self.header.take());
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Trace the rules run on every request and give the [`RuleTrace`](trace/struct.RuleTrace.html)
+    /// to `sink` once the request is validated, e.g. to log it. Traces contain the values of the
+    /// fields, keep them out of production logs.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().trace_rules(|trace| {
+    ///     log::debug!("validation trace: {}", serde_json::to_string(trace).unwrap());
+    /// });
+    /// ```
+    pub fn trace_rules<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&RuleTrace) + Send + Sync + 'static,
+    {
+        self.trace.sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Trace the rules run on requests carrying the header `name` and send the trace back as JSON
+    /// in the same header of the response. Only debug builds honor the header, so clients of
+    /// release builds can't inspect the rules and values of your service.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let validator_middleware = ValidatorMiddleware::new().trace_header("X-Validation-Trace");
+    /// ```
+    pub fn trace_header(mut self, name: &str) -> Self {
+        self.trace.header = Some(name.to_string());
+        self
+    }
+
+    /// Give the trace to the sink, and keep it with the header to send it in when the client
+    /// asked for it
+    pub(crate) fn report_trace(
+        &self,
+        trace: Option<RuleTrace>,
+        send_trace: bool,
+    ) -> Option<(&str, RuleTrace)> {
+        let trace = trace?;
+        if let Some(sink) = &self.trace.sink {
+            sink(&trace);
+        }
+        match &self.trace.header {
+            Some(name) if send_trace => Some((name.as_str(), trace)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{validators, HttpField};
+    use http_service_mock::make_server;
+    use std::sync::Mutex;
+    use tide::http::{Method, Request};
+    use tide::StatusCode;
+
+    #[test]
+    fn serialized_trace() {
+        let trace = RuleTrace {
+            steps: vec![
+                TraceStep {
+                    field: String::from("page"),
+                    location: Location::Query,
+                    rule: 0,
+                    description: None,
+                    name: None,
+                    input: Some(String::from("zéro")),
+                    outcome: StepOutcome::Passed,
+                },
+                TraceStep {
+                    field: String::from("page"),
+                    location: Location::Query,
+                    rule: 1,
+                    description: Some(String::from("a number")),
+                    name: None,
+                    input: Some(String::from("zéro")),
+                    outcome: StepOutcome::Failed(serde_json::json!("not a number")),
+                },
+            ],
+        };
+        assert_eq!(
+            serde_json::to_value(&trace).unwrap(),
+            serde_json::json!([
+                {"field": "page", "in": "query", "rule": 0, "input": "zéro", "outcome": "passed"},
+                {"field": "page", "in": "query", "rule": 1, "description": "a number",
+                 "input": "zéro", "outcome": "failed", "error": "not a number"},
+            ])
+        );
+        let header = trace.to_header_value();
+        assert!(header.is_ascii());
+        assert!(header.contains(r#""input":"z\u00e9ro""#));
+    }
+
+    #[test]
+    fn validator_trace() {
+        let traces = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&traces);
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .trace_rules(move |trace: &RuleTrace| sink.lock().unwrap().push(trace.clone()))
+            .trace_header("X-Validation-Trace");
+        validators.add_validator(HttpField::query_param("page"), validators::is_required);
+        validators.add_validator(HttpField::query_param("page"), validators::is_number);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(
+            Method::Get,
+            "http://localhost/foo?page=zero".parse().unwrap(),
+        );
+        req.insert_header("X-Validation-Trace", "1").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert!(res.header(&"X-Validation-Trace".into()).is_some());
+
+        let traces = traces.lock().unwrap();
+        let outcomes: Vec<&StepOutcome> =
+            traces[0].steps.iter().map(|step| &step.outcome).collect();
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0], &StepOutcome::Passed);
+        assert!(matches!(outcomes[1], StepOutcome::Failed(_)));
+        assert_eq!(traces[0].steps[1].input.as_deref(), Some("zero"));
+    }
+
+    #[test]
+    fn validator_trace_decoded_values() {
+        let traces = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&traces);
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new()
+            .trace_rules(move |trace: &RuleTrace| sink.lock().unwrap().push(trace.clone()))
+            .trace_header("X-Validation-Trace");
+        validators.add_codec(
+            HttpField::query_param("token"),
+            |_: &(), field_name: &str, value: &str| match value.strip_prefix("rev:") {
+                Some(encoded) => Ok(encoded.chars().rev().collect()),
+                None => Err(validators::ValidationError::new(
+                    field_name,
+                    "codec",
+                    "cannot be decoded",
+                )),
+            },
+        );
+        validators.add_validator(HttpField::query_param("token"), validators::is_number);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(
+            Method::Get,
+            "http://localhost/foo?token=rev:terc3s".parse().unwrap(),
+        );
+        req.insert_header("X-Validation-Trace", "1").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        let header = res.header(&"X-Validation-Trace".into()).unwrap();
+        assert!(!header[0].as_str().contains("s3cret"));
+
+        let traces = traces.lock().unwrap();
+        assert_eq!(traces[0].steps[0].input.as_deref(), Some("[redacted]"));
+        assert!(matches!(traces[0].steps[0].outcome, StepOutcome::Failed(_)));
+        let trace = serde_json::to_string(&traces[0]).unwrap();
+        assert!(!trace.contains("s3cret"));
+    }
+}
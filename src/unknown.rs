@@ -0,0 +1,199 @@
+//! Rejection of the query parameters and body fields which no rule uses
+
+use serde::Serialize;
+
+use crate::body::{path_segments, Body};
+use crate::plan::Plan;
+use crate::values::RequestValues;
+use crate::{HttpField, Location, ValidationError, ValidatorMiddleware};
+
+/// Locations where fields without rules are rejected
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DenyUnknown {
+    pub(crate) query: bool,
+    pub(crate) body_fields: bool,
+}
+
+impl DenyUnknown {
+    pub(crate) fn merge(&mut self, other: DenyUnknown) {
+        self.query |= other.query;
+        self.body_fields |= other.body_fields;
+    }
+}
+
+impl<T, State> ValidatorMiddleware<T, State>
+where
+    T: Serialize + Send + Sync + 'static,
+    State: Send + Sync + 'static,
+{
+    /// Reject requests with query parameters which have no validator, e.g. `?pgae=2` when only
+    /// `page` is validated. Each unknown parameter gets an `unknown_field` error, sent like errors
+    /// of validators with the status of the response policy. Parameters used by transformers,
+    /// group validators, resolvers and the rules of the request method or step are known too.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run,compile_fail
+    /// let mut validator_middleware = ValidatorMiddleware::new().deny_unknown_query_params();
+    /// validator_middleware.add_validator(HttpField::query_param("page"), is_integer);
+    /// // GET /items?pgae=2 -> 400 {"field": "pgae", "in": "query", "code": "unknown_field", ...}
+    /// ```
+    pub fn deny_unknown_query_params(mut self) -> Self {
+        self.deny_unknown.query = true;
+        self
+    }
+
+    /// Reject requests with query parameters or JSON body fields which have no validator, see
+    /// [`deny_unknown_query_params`](struct.ValidatorMiddleware.html#method.deny_unknown_query_params).
+    /// The top-level fields of JSON object bodies are checked when body fields are validated,
+    /// `HttpField::body_field("user.name")` makes `user` known. Headers aren't checked since
+    /// browsers and proxies add their own.
+    pub fn deny_unknown_fields(mut self) -> Self {
+        self.deny_unknown.query = true;
+        self.deny_unknown.body_fields = true;
+        self
+    }
+
+    /// Query parameters or JSON body fields of the request which no rule uses, sorted
+    pub(crate) fn unknown_fields(
+        &self,
+        location: Location,
+        names: Vec<&str>,
+        scoped_rules: &[&Self],
+        plan: &Plan,
+    ) -> Vec<String> {
+        let uses_name = |field: &HttpField<'_>, name: &str| match field {
+            HttpField::QueryParam(param) => location == Location::Query && param == name,
+            HttpField::QueryList(param, style) => {
+                location == Location::Query && style.param_name(param) == name
+            }
+            HttpField::BodyField(path) => {
+                location == Location::Body
+                    && path_segments(path)
+                        .first()
+                        .map(|(segment, _)| segment.as_ref())
+                        == Some(name)
+            }
+            #[cfg(feature = "regex")]
+            HttpField::QueryParamPattern(pattern) => {
+                location == Location::Query && pattern.is_match(name)
+            }
+            _ => false,
+        };
+        let mut unknown: Vec<String> = names
+            .into_iter()
+            .filter(|name| {
+                let mut fields = self
+                    .planned_fields(plan)
+                    .chain(self.scopes.step_field.iter())
+                    .chain(self.resolvers.iter().map(|resolver| &resolver.field))
+                    .chain(
+                        std::iter::once(self)
+                            .chain(scoped_rules.iter().copied())
+                            .flat_map(|rules| {
+                                rules.normalize.transformers.keys().chain(
+                                    rules
+                                        .group_validators
+                                        .iter()
+                                        .flat_map(|group| &group.fields),
+                                )
+                            }),
+                    );
+                !fields.any(|field| uses_name(field, name))
+            })
+            .map(String::from)
+            .collect();
+        unknown.sort();
+        unknown
+    }
+
+    /// `unknown_field` errors of the query parameters and JSON body fields no rule uses, when
+    /// they are denied
+    pub(crate) fn unknown_field_errors(
+        &self,
+        values: &mut RequestValues<'_, State>,
+        scoped_rules: &[&Self],
+        plan: &Plan,
+    ) -> Vec<(HttpField<'static>, ValidationError)> {
+        let mut unknown = Vec::new();
+        if self.deny_unknown.query {
+            let names = values
+                .query
+                .parsed()
+                .keys()
+                .map(|name| name.as_ref())
+                .collect();
+            let names = self.unknown_fields(Location::Query, names, scoped_rules, plan);
+            unknown.extend(names.into_iter().map(|name| {
+                let err = ValidationError::new(
+                    &name,
+                    "unknown_field",
+                    format!("query parameter '{}' is unknown", name),
+                );
+                (HttpField::query_param(name), err)
+            }));
+        }
+        if let (true, Body::Json(serde_json::Value::Object(fields))) =
+            (self.deny_unknown.body_fields, values.body)
+        {
+            let names = fields.keys().map(String::as_str).collect();
+            let names = self.unknown_fields(Location::Body, names, scoped_rules, plan);
+            unknown.extend(names.into_iter().map(|name| {
+                let err = ValidationError::new(
+                    &name,
+                    "unknown_field",
+                    format!("body field '{}' is unknown", name),
+                );
+                (HttpField::body_field(name), err)
+            }));
+        }
+        unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{validators, ValidationError};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use std::collections::HashMap;
+    use tide::http::{Method, Request};
+    use tide::StatusCode;
+
+    #[test]
+    fn validator_deny_unknown_fields() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new().deny_unknown_fields();
+        validators.add_validator(HttpField::query_param("page"), validators::is_integer);
+        validators.add_validator(HttpField::body_field("user.name"), validators::is_required);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .post(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(Method::Post, "http://localhost/foo?page=2".parse().unwrap());
+        req.set_body(r#"{"user": {"name": "Gribouille"}}"#);
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let mut buf = Vec::new();
+        let mut req = Request::new(
+            Method::Post,
+            "http://localhost/foo?pgae=2&page=2".parse().unwrap(),
+        );
+        req.set_body(r#"{"user": {"name": "Gribouille"}, "admin": true}"#);
+        let mut res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        block_on(res.read_to_end(&mut buf)).unwrap();
+        let errors: HashMap<String, Vec<ValidationError>> =
+            serde_json::from_slice(&buf[..]).unwrap();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors["pgae"][0].code, "unknown_field");
+        assert_eq!(errors["pgae"][0].location, Some(Location::Query));
+        assert_eq!(errors["admin"][0].message, "body field 'admin' is unknown");
+    }
+}
@@ -0,0 +1,1433 @@
+//! Ready-made validators for the most common checks.
+//!
+//! Simple checks are plain functions you can pass directly to `add_validator`, checks which need
+//! a configuration are constructors returning a boxed validator.
+//!
+//! Available validators: `is_required`, `is_empty`, `is_number`, `is_integer`, `is_integer_between`,
+//! `is_bool`, `is_between`, `is_min`, `is_max`, `is_length_between`, `is_min_length`,
+//! `is_max_length`, `one_of`, `one_of_ignore_case`, `is_email`, `is_uuid`, `is_url`, `is_date`,
+//! `is_http_date`, `is_past_http_date`, `prefer`, `is_fetch_site`, `is_fetch_mode`, `is_fetch_dest`,
+//! `is_count_between`, `require_content_type`, `require_accepts` and `is_match` with the `regex`
+//! feature.
+//!
+//! ```rust,no_run,compile_fail
+//! use tide_validator::validators::{is_integer, is_between, one_of};
+//!
+//! validator_middleware.add_validator(HttpField::query_param("page"), is_integer);
+//! validator_middleware.add_validator(HttpField::query_param("page"), is_between(1.0, 100.0));
+//! validator_middleware.add_validator(HttpField::query_param("sort"), one_of(&["asc", "desc"]));
+//! ```
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use tide::http::Url;
+
+use crate::{is_token, BoxedValidator, Location};
+
+/// Error returned by the built-in validators
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationError {
+    /// Name of the field which is invalid
+    pub field: String,
+    /// Where the field is located in the request, serialized as `in`. Validators don't know it,
+    /// the middleware fills it before sending the error.
+    #[serde(rename = "in", default)]
+    pub location: Option<Location>,
+    /// Machine readable code of the rule which failed, it's the rule name used in rule strings (e.g. `integer`)
+    pub code: String,
+    /// Human readable message explaining why the value is invalid
+    pub message: String,
+    /// Description of what the validator expects, filled by the middleware for validators
+    /// added with `add_described_validator`
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Parameters of the rule, e.g. `min` and `max` for bounds, available as placeholders in
+    /// [message overrides](../struct.ValidatorMiddleware.html#method.with_message_override).
+    /// Built-in validators add an `example` of a valid value, see
+    /// [`include_examples`](../struct.ValidatorMiddleware.html#method.include_examples).
+    /// Boxed to keep errors small.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Box<BTreeMap<String, String>>>,
+}
+
+impl ValidationError {
+    /// Create a new error for the field `field` failing the rule `code`
+    pub fn new(field: &str, code: impl Into<String>, message: impl Into<String>) -> Self {
+        ValidationError {
+            field: field.to_string(),
+            location: None,
+            code: code.into(),
+            message: message.into(),
+            description: None,
+            params: None,
+        }
+    }
+
+    fn with_params(self, params: &[(&str, String)]) -> Self {
+        params
+            .iter()
+            .fold(self, |err, (name, value)| err.with_param(name, value))
+    }
+
+    /// Add a parameter of the rule
+    pub fn with_param(mut self, name: &str, value: impl ToString) -> Self {
+        self.params
+            .get_or_insert_with(Default::default)
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Value of a parameter of the rule
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.as_ref()?.get(name).map(String::as_str)
+    }
+
+    /// Set where the field is located in the request
+    pub fn with_location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for String {
+    fn from(err: ValidationError) -> Self {
+        err.message
+    }
+}
+
+/// Force the field to be present in the request
+pub fn is_required(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    if field_value.is_none() {
+        Err(ValidationError::new(
+            field_name,
+            "required",
+            format!("'{}' is required", field_name),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Check if the field is missing or empty, mostly useful negated with
+/// [`combinators::not`](../combinators/fn.not.html)
+pub fn is_empty(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        None | Some("") => Ok(()),
+        Some(value) => Err(ValidationError::new(
+            field_name,
+            "empty",
+            format!("field '{}' = '{}' must be empty", field_name, value),
+        )),
+    }
+}
+
+/// Check if the field is a finite number, integer or decimal
+pub fn is_number(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    if let Some(field_value) = field_value {
+        if parse_number(field_value).is_none() {
+            return Err(ValidationError::new(
+                field_name,
+                "numeric",
+                format!(
+                    "field '{}' = '{}' is not a valid number",
+                    field_name, field_value
+                ),
+            )
+            .with_param("example", "4.2"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if the field is an integer
+pub fn is_integer(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    if let Some(field_value) = field_value {
+        if field_value.parse::<i64>().is_err() {
+            return Err(ValidationError::new(
+                field_name,
+                "integer",
+                format!(
+                    "field '{}' = '{}' is not a valid integer",
+                    field_name, field_value
+                ),
+            )
+            .with_param("example", "42"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if the field is `true` or `false`
+pub fn is_bool(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        None | Some("true") | Some("false") => Ok(()),
+        Some(other) => Err(ValidationError::new(
+            field_name,
+            "boolean",
+            format!(
+                "field '{}' = '{}' is not a valid boolean",
+                field_name, other
+            ),
+        )
+        .with_param("example", "true")),
+    }
+}
+
+/// Normalizer mapping the usual spellings of booleans to `true` or `false`: `y`, `yes`, `on` and
+/// `1` are `true`, `n`, `no`, `off` and `0` are `false`, whatever the case. Add it with
+/// [`add_normalizer`](../struct.ValidatorMiddleware.html#method.add_normalizer).
+pub fn normalize_bool(
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<Option<String>, ValidationError> {
+    let value = match field_value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let normalized = match value.to_ascii_lowercase().as_str() {
+        "true" | "t" | "y" | "yes" | "on" | "1" => "true",
+        "false" | "f" | "n" | "no" | "off" | "0" => "false",
+        _ => return is_bool(field_name, field_value).map(|_| None),
+    };
+    Ok(Some(normalized.to_string()))
+}
+
+/// Normalizer writing phone numbers with digits only, prefixed with `+` for international
+/// numbers: `+33 1 23 45 67 89` and `0033.1.23.45.67.89` become `+33123456789`. Spaces, dots,
+/// dashes and parentheses are separators, numbers have from 4 to 15 digits as in E.164.
+pub fn normalize_phone(
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<Option<String>, ValidationError> {
+    let value = match field_value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let (international, number) = match value.trim().strip_prefix('+') {
+        Some(number) => (true, number),
+        None => match value.trim().strip_prefix("00") {
+            Some(number) => (true, number),
+            None => (false, value.trim()),
+        },
+    };
+    let valid = number
+        .chars()
+        .all(|c| c.is_ascii_digit() || " .-()".contains(c));
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    if !valid || digits.len() < 4 || digits.len() > 15 {
+        return Err(ValidationError::new(
+            field_name,
+            "phone",
+            format!(
+                "field '{}' = '{}' is not a valid phone number",
+                field_name, value
+            ),
+        )
+        .with_param("example", "+33123456789"));
+    }
+    Ok(Some(if international {
+        format!("+{}", digits)
+    } else {
+        digits
+    }))
+}
+
+/// Check if the field is a hyphenated UUID, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`
+pub fn is_uuid(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if !is_hyphenated_uuid(value) => Err(ValidationError::new(
+            field_name,
+            "uuid",
+            format!("field '{}' = '{}' is not a valid UUID", field_name, value),
+        )
+        .with_param("example", "67e55044-10b1-426f-9247-bb680e5fe0c8")),
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is an email address like `user@example.com`. Only the structure is checked:
+/// a local part, a single `@` and a domain made of at least two labels.
+pub fn is_email(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if !is_email_address(value) => Err(ValidationError::new(
+            field_name,
+            "email",
+            format!(
+                "field '{}' = '{}' is not a valid email address",
+                field_name, value
+            ),
+        )
+        .with_param("example", "user@example.com")),
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is an absolute `http` or `https` URL
+pub fn is_url(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if !matches!(Url::parse(value), Ok(url) if (url.scheme() == "http" || url.scheme() == "https") && url.has_host()) => {
+            Err(ValidationError::new(
+                field_name,
+                "url",
+                format!("field '{}' = '{}' is not a valid URL", field_name, value),
+            )
+            .with_param("example", "https://example.com"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is an ISO-8601 calendar date, e.g. `2020-02-29`
+pub fn is_date(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if !is_iso_date(value) => Err(ValidationError::new(
+            field_name,
+            "date",
+            format!(
+                "field '{}' = '{}' is not a valid date (YYYY-MM-DD)",
+                field_name, value
+            ),
+        )
+        .with_param("example", "2023-01-31")),
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is an HTTP-date (RFC 7231), e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, see
+/// [`parse_http_date`](fn.parse_http_date.html)
+pub fn is_http_date(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if parse_http_date(value).is_none() => Err(ValidationError::new(
+            field_name,
+            "http_date",
+            format!(
+                "field '{}' = '{}' is not a valid HTTP date",
+                field_name, value
+            ),
+        )
+        .with_param("example", "Sun, 06 Nov 1994 08:49:37 GMT")),
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is an HTTP-date which isn't in the future, as expected for the
+/// conditional headers `If-Modified-Since` and `If-Unmodified-Since`
+pub fn is_past_http_date(
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<(), ValidationError> {
+    past_http_date::<ValidationError>()(field_name, field_value).map(|_| ())
+}
+
+/// Parser for [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser) giving the
+/// `SystemTime` of conditional headers, checked like [`is_past_http_date`](fn.is_past_http_date.html)
+///
+/// ```rust,no_run,compile_fail
+/// validator_middleware.add_parser(HttpField::header("If-Modified-Since"), past_http_date::<ValidationError>());
+/// // ... then in the endpoint
+/// let since: Option<&SystemTime> = req.validated::<SystemTime>("If-Modified-Since");
+/// ```
+pub fn past_http_date<T>(
+) -> impl Fn(&str, Option<&str>) -> Result<Option<SystemTime>, T> + Send + Sync + 'static
+where
+    T: From<ValidationError>,
+{
+    |field_name: &str, field_value: Option<&str>| {
+        let value = match field_value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        is_http_date(field_name, field_value)?;
+        match parse_http_date(value) {
+            Some(date) if date <= SystemTime::now() => Ok(Some(date)),
+            _ => Err(ValidationError::new(
+                field_name,
+                "past_http_date",
+                format!("field '{}' = '{}' is in the future", field_name, value),
+            )
+            .into()),
+        }
+    }
+}
+
+/// Parse an HTTP-date (RFC 7231): the preferred IMF-fixdate `Sun, 06 Nov 1994 08:49:37 GMT` or
+/// the obsolete formats `Sunday, 06-Nov-94 08:49:37 GMT` and `Sun Nov  6 08:49:37 1994`
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    const LONG_DAYS: [&str; 7] = [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let digits = |value: &str, len: usize| -> Option<u32> {
+        if value.len() == len && value.bytes().all(|b| b.is_ascii_digit()) {
+            value.parse().ok()
+        } else {
+            None
+        }
+    };
+    let month = |name: &str| MONTHS.iter().position(|month| *month == name);
+
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let (year, month, day, time) = match parts.as_slice() {
+        [weekday, day, month_name, year, time, "GMT"]
+            if DAYS.contains(&weekday.strip_suffix(',')?) =>
+        {
+            (digits(year, 4)?, month(month_name)?, digits(day, 2)?, *time)
+        }
+        [weekday, date, time, "GMT"] if LONG_DAYS.contains(&weekday.strip_suffix(',')?) => {
+            let mut date = date.split('-');
+            let (day, month_name, year) = (date.next()?, date.next()?, date.next()?);
+            // Two digit years in the past 50 years
+            let year = match digits(year, 2)? {
+                year if year < 70 => 2000 + year,
+                year => 1900 + year,
+            };
+            (year, month(month_name)?, digits(day, 2)?, *time)
+        }
+        [weekday, month_name, day, time, year] if DAYS.contains(weekday) => {
+            let day = digits(day, 1).or_else(|| digits(day, 2))?;
+            (digits(year, 4)?, month(month_name)?, day, *time)
+        }
+        _ => return None,
+    };
+    let month = month as u32 + 1;
+    if day < 1 || day > days_in_month(i64::from(year), month)? {
+        return None;
+    }
+    let mut time = time.split(':');
+    let (hours, minutes, seconds) = (
+        digits(time.next()?, 2)?,
+        digits(time.next()?, 2)?,
+        digits(time.next()?, 2)?,
+    );
+    if time.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    // Days since the epoch of the civil date
+    let (year, month) = if month <= 2 {
+        (i64::from(year) - 1, month + 9)
+    } else {
+        (i64::from(year), month - 3)
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = i64::from((153 * month + 2) / 5 + day - 1);
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds =
+        days * 86_400 + i64::from(hours) * 3_600 + i64::from(minutes) * 60 + i64::from(seconds);
+    if seconds >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+    }
+}
+
+/// Values of the `Sec-Fetch-Site` header
+pub const FETCH_SITES: &[&str] = &["cross-site", "same-origin", "same-site", "none"];
+
+/// Values of the `Sec-Fetch-Mode` header
+pub const FETCH_MODES: &[&str] = &["cors", "navigate", "no-cors", "same-origin", "websocket"];
+
+/// Values of the `Sec-Fetch-Dest` header
+pub const FETCH_DESTS: &[&str] = &[
+    "audio",
+    "audioworklet",
+    "document",
+    "embed",
+    "empty",
+    "font",
+    "frame",
+    "iframe",
+    "image",
+    "manifest",
+    "object",
+    "paintworklet",
+    "report",
+    "script",
+    "serviceworker",
+    "sharedworker",
+    "style",
+    "track",
+    "video",
+    "webidentity",
+    "worker",
+    "xslt",
+];
+
+fn is_fetch_metadata(
+    code: &str,
+    known: &[&str],
+    field_name: &str,
+    field_value: Option<&str>,
+) -> Result<(), ValidationError> {
+    match field_value {
+        Some(value) if !known.contains(&value) => Err(ValidationError::new(
+            field_name,
+            code,
+            format!(
+                "field '{}' = '{}' must be one of: {}",
+                field_name,
+                value,
+                known.join(", ")
+            ),
+        )
+        .with_param("allowed", known.join(", "))
+        .with_param("example", known[0])),
+        _ => Ok(()),
+    }
+}
+
+/// Check if the field is a `Sec-Fetch-Site` value sent by browsers, e.g. `same-origin`
+pub fn is_fetch_site(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    is_fetch_metadata("fetch_site", FETCH_SITES, field_name, field_value)
+}
+
+/// Check if the field is a `Sec-Fetch-Mode` value sent by browsers, e.g. `cors`
+pub fn is_fetch_mode(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    is_fetch_metadata("fetch_mode", FETCH_MODES, field_name, field_value)
+}
+
+/// Check if the field is a `Sec-Fetch-Dest` value sent by browsers, e.g. `document`
+pub fn is_fetch_dest(field_name: &str, field_value: Option<&str>) -> Result<(), ValidationError> {
+    is_fetch_metadata("fetch_dest", FETCH_DESTS, field_name, field_value)
+}
+
+/// Media type or media range with its parameters, e.g. `text/html; charset=utf-8` or `*/*;q=0.8`
+struct MediaType<'a> {
+    kind: &'a str,
+    subtype: &'a str,
+    params: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> MediaType<'a> {
+    fn parse(value: &'a str) -> Option<Self> {
+        let mut items = split_unquoted(value, ';').into_iter();
+        let (kind, subtype) = items.next()?.trim().split_once('/')?;
+        if !is_token(kind) || !is_token(subtype) {
+            return None;
+        }
+        let params = items
+            .map(|param| {
+                let (name, value) = param.trim().split_once('=')?;
+                Some((name.trim(), value.trim().trim_matches('"')))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(MediaType {
+            kind,
+            subtype,
+            params,
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&'a str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    /// Whether the media type is in `range`: same type and subtype unless they're `*` in the range,
+    /// and every parameter of the range except its weight has the same value
+    fn is_in(&self, range: &MediaType<'_>) -> bool {
+        (range.kind == "*" || range.kind.eq_ignore_ascii_case(self.kind))
+            && (range.subtype == "*" || range.subtype.eq_ignore_ascii_case(self.subtype))
+            && range
+                .params
+                .iter()
+                .filter(|(name, _)| !name.eq_ignore_ascii_case("q"))
+                .all(|(name, value)| {
+                    self.param(name)
+                        .into_iter()
+                        .any(|own| own.eq_ignore_ascii_case(value))
+                })
+    }
+
+    /// Precedence of a media range, more specific ranges override less specific ones. The weight
+    /// isn't a parameter of the range, `text/*;q=0` is less specific than `text/html`.
+    fn specificity(&self) -> usize {
+        let params = self
+            .params
+            .iter()
+            .filter(|(name, _)| !name.eq_ignore_ascii_case("q"))
+            .count();
+        (self.kind != "*") as usize + (self.subtype != "*") as usize + params
+    }
+}
+
+/// Check if the media type of the body is one of the allowed ones, to register on
+/// [`HttpField::ContentType`](../enum.HttpField.html#variant.ContentType). Parameters of the
+/// request are ignored unless an allowed media type lists them, e.g. `text/plain; charset=utf-8`
+/// only allows UTF-8 text. Allowed types can be ranges like `image/*`.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// validator_middleware.add_validator(HttpField::content_type(), is_required);
+/// validator_middleware.add_validator(HttpField::content_type(), require_content_type(&["application/json"]));
+/// ```
+pub fn require_content_type(allowed: &[&str]) -> BoxedValidator<ValidationError> {
+    let allowed: Vec<String> = allowed.iter().map(|value| value.to_string()).collect();
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            let value = match field_value {
+                Some(value) => value,
+                None => return Ok(()),
+            };
+            let is_allowed = MediaType::parse(value).into_iter().any(|media_type| {
+                allowed
+                    .iter()
+                    .filter_map(|allowed| MediaType::parse(allowed))
+                    .any(|allowed| media_type.is_in(&allowed))
+            });
+            if is_allowed {
+                Ok(())
+            } else {
+                Err(ValidationError::new(
+                    field_name,
+                    "content_type",
+                    format!(
+                        "field '{}' = '{}' must be one of: {}",
+                        field_name,
+                        value,
+                        allowed.join(", ")
+                    ),
+                )
+                .with_param("allowed", allowed.join(", "))
+                .with_param("example", allowed.first().cloned().unwrap_or_default()))
+            }
+        },
+    )
+}
+
+/// Check if the client accepts responses of `media_type`, to register on
+/// [`HttpField::Accept`](../enum.HttpField.html#variant.Accept). The most specific range
+/// including the media type gives its weight, which must not be `q=0`. Clients without an
+/// `Accept` header accept everything.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// // Rejects `Accept: text/html` but not `Accept: text/html, */*;q=0.1`
+/// validator_middleware.add_validator(HttpField::accept(), require_accepts("application/json"));
+/// ```
+pub fn require_accepts(media_type: &str) -> BoxedValidator<ValidationError> {
+    let media_type = media_type.to_string();
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            let value = match field_value {
+                Some(value) => value,
+                None => return Ok(()),
+            };
+            match accept_weight(value, &media_type) {
+                Some(weight) if weight > 0.0 => Ok(()),
+                _ => Err(ValidationError::new(
+                    field_name,
+                    "accept",
+                    format!(
+                        "field '{}' = '{}' must accept '{}'",
+                        field_name, value, media_type
+                    ),
+                )
+                .with_param("media_type", &media_type)
+                .with_param("example", &media_type)),
+            }
+        },
+    )
+}
+
+/// Weight an `Accept` header gives to `media_type`, the weight of the most specific range
+/// including it. `None` when no range includes it or when the header doesn't parse.
+pub(crate) fn accept_weight(accept: &str, media_type: &str) -> Option<f32> {
+    let offered = MediaType::parse(media_type)?;
+    split_unquoted(accept, ',')
+        .into_iter()
+        .filter(|range| !range.trim().is_empty())
+        .map(MediaType::parse)
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .filter(|range| offered.is_in(range))
+        .max_by_key(MediaType::specificity)
+        .and_then(|range| {
+            range
+                .param("q")
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())
+        })
+}
+
+/// Preferences sent in a `Prefer` header (RFC 7240), e.g. `return=minimal; wait=10`.
+/// Names are lowercase and only the first occurrence of a preference is kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preferences(Vec<(String, Option<String>)>);
+
+impl Preferences {
+    /// Whether the client sent the preference `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.0
+            .iter()
+            .any(|(pref, _)| pref.eq_ignore_ascii_case(name))
+    }
+
+    /// Value of the preference `name`, e.g. `Some("minimal")` for `return`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(pref, _)| pref.eq_ignore_ascii_case(name))
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    /// Seconds the client is willing to wait, given by the `wait` preference
+    pub fn wait(&self) -> Option<u64> {
+        self.get("wait")?.parse().ok()
+    }
+
+    /// Preferences with their value, in the order of the header
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+    }
+}
+
+/// Parser for [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser) checking the
+/// `Prefer` header is well formed and only contains `supported` preferences. Values of the
+/// preferences defined by RFC 7240 are checked: `return` is `minimal` or `representation`,
+/// `handling` is `strict` or `lenient`, `wait` is a number of seconds and `respond-async` has no value.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// validator_middleware.add_parser(HttpField::header("Prefer"), prefer::<ValidationError>(&["return", "wait"]));
+/// // ... then in the endpoint
+/// let minimal = req.validated::<Preferences>("Prefer").map_or(false, |prefs| prefs.get("return") == Some("minimal"));
+/// ```
+pub fn prefer<T>(
+    supported: &[&str],
+) -> impl Fn(&str, Option<&str>) -> Result<Option<Preferences>, T> + Send + Sync + 'static
+where
+    T: From<ValidationError>,
+{
+    let supported: Vec<String> = supported
+        .iter()
+        .map(|name| name.to_ascii_lowercase())
+        .collect();
+    move |field_name: &str, field_value: Option<&str>| {
+        let value = match field_value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let prefs = parse_prefer(value).ok_or_else(|| {
+            ValidationError::new(
+                field_name,
+                "prefer",
+                format!(
+                    "field '{}' = '{}' is not a valid Prefer header",
+                    field_name, value
+                ),
+            )
+            .with_param("example", "return=minimal")
+        })?;
+        for (name, pref_value) in prefs.iter() {
+            if !supported.iter().any(|supported| supported == name) {
+                return Err(ValidationError::new(
+                    field_name,
+                    "unsupported_preference",
+                    format!(
+                        "preference '{}' is not supported, supported preferences: {}",
+                        name,
+                        supported.join(", ")
+                    ),
+                )
+                .with_param("supported", supported.join(", "))
+                .into());
+            }
+            let valid = match (name, pref_value) {
+                ("return", value) => matches!(value, Some("minimal") | Some("representation")),
+                ("handling", value) => matches!(value, Some("strict") | Some("lenient")),
+                ("wait", Some(value)) => value.parse::<u64>().is_ok(),
+                ("wait", None) => false,
+                ("respond-async", value) => value.is_none(),
+                _ => true,
+            };
+            if !valid {
+                return Err(ValidationError::new(
+                    field_name,
+                    "prefer",
+                    format!(
+                        "preference '{}' has an invalid value '{}'",
+                        name,
+                        pref_value.unwrap_or_default()
+                    ),
+                )
+                .into());
+            }
+        }
+        Ok(Some(prefs))
+    }
+}
+
+/// Parse a `Prefer` header (RFC 7240) like `return=minimal, wait=10; foo="bar"`, `None` when it's
+/// malformed. Parameters after `;` are checked but not kept.
+pub fn parse_prefer(value: &str) -> Option<Preferences> {
+    let is_token = |value: &str| {
+        !value.is_empty()
+            && value
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+    };
+    let name_value = |item: &str| -> Option<(String, Option<String>)> {
+        let mut name_value = item.splitn(2, '=');
+        let name = name_value.next()?.trim();
+        if !is_token(name) {
+            return None;
+        }
+        let value = match name_value.next().map(str::trim) {
+            None => None,
+            Some(value) if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') => {
+                let mut chars = value[1..value.len() - 1].chars();
+                let mut unquoted = String::new();
+                while let Some(c) = chars.next() {
+                    unquoted.push(if c == '\\' { chars.next()? } else { c });
+                }
+                Some(unquoted)
+            }
+            Some(value) if is_token(value) => Some(value.to_string()),
+            Some(_) => return None,
+        };
+        Some((name.to_ascii_lowercase(), value))
+    };
+
+    let mut prefs = Preferences::default();
+    for pref in split_unquoted(value, ',') {
+        if pref.trim().is_empty() {
+            continue;
+        }
+        let mut items = split_unquoted(pref, ';').into_iter();
+        let (name, value) = name_value(items.next()?)?;
+        for param in items {
+            if !param.trim().is_empty() {
+                name_value(param)?;
+            }
+        }
+        if !prefs.contains(&name) {
+            prefs.0.push((name, value));
+        }
+    }
+    if prefs.0.is_empty() {
+        return None;
+    }
+    Some(prefs)
+}
+
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let (mut items, mut start, mut quoted, mut escaped) = (Vec::new(), 0, false, false);
+    for (idx, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                items.push(&value[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items
+}
+
+/// Check if the field is one of the allowed values, the error lists them
+pub fn one_of(allowed: &[&str]) -> BoxedValidator<ValidationError> {
+    allowed_values(allowed, false)
+}
+
+/// Check if the field is one of the allowed values ignoring ASCII case, e.g. `DESC` for `desc`.
+/// The error lists them.
+pub fn one_of_ignore_case(allowed: &[&str]) -> BoxedValidator<ValidationError> {
+    allowed_values(allowed, true)
+}
+
+fn allowed_values(allowed: &[&str], ignore_case: bool) -> BoxedValidator<ValidationError> {
+    let allowed: Vec<String> = allowed.iter().map(|value| value.to_string()).collect();
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            let matches = |allowed: &String, value: &str| {
+                if ignore_case {
+                    allowed.eq_ignore_ascii_case(value)
+                } else {
+                    allowed == value
+                }
+            };
+            match field_value {
+                Some(value) if !allowed.iter().any(|allowed| matches(allowed, value)) => {
+                    Err(ValidationError::new(
+                        field_name,
+                        "in",
+                        format!(
+                            "field '{}' = '{}' must be one of: {}",
+                            field_name,
+                            value,
+                            allowed.join(", ")
+                        ),
+                    )
+                    .with_param("allowed", allowed.join(", "))
+                    .with_param("example", allowed.first().cloned().unwrap_or_default()))
+                }
+                _ => Ok(()),
+            }
+        },
+    )
+}
+
+/// Check if the field matches a regular expression, available with the `regex` feature.
+/// Anchor your expression with `^` and `$` to match the whole value.
+#[cfg(feature = "regex")]
+pub fn is_match(regex: regex::Regex) -> BoxedValidator<ValidationError> {
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            match field_value {
+                Some(value) if !regex.is_match(value) => Err(ValidationError::new(
+                    field_name,
+                    "regex",
+                    format!(
+                        "field '{}' = '{}' must match {}",
+                        field_name,
+                        value,
+                        regex.as_str()
+                    ),
+                )),
+                _ => Ok(()),
+            }
+        },
+    )
+}
+
+/// Parser for [`add_parser`](../struct.ValidatorMiddleware.html#method.add_parser) checking the
+/// field can be parsed into `V`, e.g. `parsed::<u32, ValidationError>()` rejects `-1` and `a`
+pub fn parsed<V, T>() -> impl Fn(&str, Option<&str>) -> Result<Option<V>, T> + Send + Sync + 'static
+where
+    V: FromStr,
+    T: From<ValidationError>,
+{
+    |field_name: &str, field_value: Option<&str>| match field_value {
+        Some(value) => value.parse::<V>().map(Some).map_err(|_| {
+            let type_name = std::any::type_name::<V>();
+            let type_name = type_name.rsplit("::").next().unwrap_or(type_name);
+            ValidationError::new(
+                field_name,
+                "parse",
+                format!(
+                    "field '{}' = '{}' is not a valid {}",
+                    field_name, value, type_name
+                ),
+            )
+            .into()
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Check if the field is an integer between `min` and `max` (inclusive)
+pub fn is_integer_between(min: i64, max: i64) -> BoxedValidator<ValidationError> {
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            is_integer(field_name, field_value)?;
+            match field_value.and_then(|value| value.parse::<i64>().ok()) {
+                Some(n) if n < min || n > max => Err(ValidationError::new(
+                    field_name,
+                    "between",
+                    format!(
+                        "field '{}' = '{}' must be between {} and {}",
+                        field_name, n, min, max
+                    ),
+                )
+                .with_param("min", min)
+                .with_param("max", max)
+                .with_param("example", min)),
+                _ => Ok(()),
+            }
+        },
+    )
+}
+
+/// Check if the field is a number between `min` and `max` (inclusive)
+pub fn is_between(min: f64, max: f64) -> BoxedValidator<ValidationError> {
+    number_check(
+        "between",
+        move |n| n >= min && n <= max,
+        move || format!("between {} and {}", min, max),
+        vec![
+            ("min", min.to_string()),
+            ("max", max.to_string()),
+            ("example", min.to_string()),
+        ],
+    )
+}
+
+/// Check if the field is a number greater than or equal to `min`
+pub fn is_min(min: f64) -> BoxedValidator<ValidationError> {
+    number_check(
+        "min",
+        move |n| n >= min,
+        move || format!("at least {}", min),
+        vec![("min", min.to_string()), ("example", min.to_string())],
+    )
+}
+
+/// Check if the field is a number lower than or equal to `max`
+pub fn is_max(max: f64) -> BoxedValidator<ValidationError> {
+    number_check(
+        "max",
+        move |n| n <= max,
+        move || format!("at most {}", max),
+        vec![("max", max.to_string()), ("example", max.to_string())],
+    )
+}
+
+/// Validator for [`add_multi_validator`](../struct.ValidatorMiddleware.html#method.add_multi_validator)
+/// checking the field has between `min` and `max` values (inclusive), e.g. the items of a
+/// [query list](../enum.HttpField.html#variant.QueryList). A missing field has no values.
+pub fn is_count_between(
+    min: usize,
+    max: usize,
+) -> impl Fn(&str, Option<&[&str]>) -> Result<(), ValidationError> + Send + Sync + 'static {
+    move |field_name: &str, values: Option<&[&str]>| {
+        let count = values.map_or(0, |values| values.len());
+        if count < min || count > max {
+            return Err(ValidationError::new(
+                field_name,
+                "count",
+                format!(
+                    "field '{}' must have between {} and {} values, got {}",
+                    field_name, min, max, count
+                ),
+            )
+            .with_param("min", min)
+            .with_param("max", max));
+        }
+        Ok(())
+    }
+}
+
+/// Check if the field length (in characters) is between `min` and `max` (inclusive)
+pub fn is_length_between(min: usize, max: usize) -> BoxedValidator<ValidationError> {
+    length_check(
+        "between",
+        move |len| len >= min && len <= max,
+        move || format!("between {} and {} characters long", min, max),
+        vec![("min", min.to_string()), ("max", max.to_string())],
+    )
+}
+
+/// Check if the field is at least `min` characters long
+pub fn is_min_length(min: usize) -> BoxedValidator<ValidationError> {
+    length_check(
+        "min",
+        move |len| len >= min,
+        move || format!("at least {} characters long", min),
+        vec![("min", min.to_string())],
+    )
+}
+
+/// Check if the field is at most `max` characters long
+pub fn is_max_length(max: usize) -> BoxedValidator<ValidationError> {
+    length_check(
+        "max",
+        move |len| len <= max,
+        move || format!("at most {} characters long", max),
+        vec![("max", max.to_string())],
+    )
+}
+
+fn is_email_address(value: &str) -> bool {
+    let (local, domain) = match value.rfind('@') {
+        Some(at) => (&value[..at], &value[at + 1..]),
+        None => return false,
+    };
+    let labels: Vec<&str> = domain.split('.').collect();
+    !local.is_empty()
+        && local.len() <= 64
+        && !local.contains('@')
+        && !local.chars().any(|c| c.is_whitespace() || c.is_control())
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+fn is_iso_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    if bytes.len() != 10
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 4 || i == 7 || b.is_ascii_digit())
+    {
+        return false;
+    }
+    let (year, month, day): (u32, u32, u32) =
+        match (value[..4].parse(), value[5..7].parse(), value[8..].parse()) {
+            (Ok(year), Ok(month), Ok(day)) => (year, month, day),
+            _ => return false,
+        };
+    match days_in_month(i64::from(year), month) {
+        Some(days_in_month) => day >= 1 && day <= days_in_month,
+        None => false,
+    }
+}
+
+fn days_in_month(year: i64, month: u32) -> Option<u32> {
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => Some(31),
+        4 | 6 | 9 | 11 => Some(30),
+        2 if leap_year => Some(29),
+        2 => Some(28),
+        _ => None,
+    }
+}
+
+fn is_hyphenated_uuid(value: &str) -> bool {
+    value.len() == 36
+        && value.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn number_check<C, D>(
+    code: &'static str,
+    check: C,
+    describe: D,
+    params: Vec<(&'static str, String)>,
+) -> BoxedValidator<ValidationError>
+where
+    C: Fn(f64) -> bool + Send + Sync + 'static,
+    D: Fn() -> String + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            if let Some(field_value) = field_value {
+                match parse_number(field_value) {
+                    Some(n) if check(n) => {}
+                    Some(_) => {
+                        return Err(ValidationError::new(
+                            field_name,
+                            code,
+                            format!(
+                                "field '{}' = '{}' must be {}",
+                                field_name,
+                                field_value,
+                                describe()
+                            ),
+                        )
+                        .with_params(&params))
+                    }
+                    None => {
+                        return Err(ValidationError::new(
+                            field_name,
+                            code,
+                            format!(
+                                "field '{}' = '{}' is not a valid number",
+                                field_name, field_value
+                            ),
+                        ))
+                    }
+                }
+            }
+            Ok(())
+        },
+    )
+}
+
+fn length_check<C, D>(
+    code: &'static str,
+    check: C,
+    describe: D,
+    params: Vec<(&'static str, String)>,
+) -> BoxedValidator<ValidationError>
+where
+    C: Fn(usize) -> bool + Send + Sync + 'static,
+    D: Fn() -> String + Send + Sync + 'static,
+{
+    Box::new(
+        move |field_name: &str, field_value: Option<&str>| -> Result<(), ValidationError> {
+            if let Some(field_value) = field_value {
+                if !check(field_value.chars().count()) {
+                    return Err(ValidationError::new(
+                        field_name,
+                        code,
+                        format!(
+                            "field '{}' = '{}' must be {}",
+                            field_name,
+                            field_value,
+                            describe()
+                        ),
+                    )
+                    .with_params(&params));
+                }
+            }
+            Ok(())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_checks() {
+        assert!(is_number("n", Some("4.2")).is_ok());
+        assert!(is_number("n", Some("NaN")).is_err());
+        assert!(is_integer("n", Some("4.2")).is_err());
+        assert_eq!(
+            parsed::<u8, ValidationError>()("n", Some("42")),
+            Ok(Some(42))
+        );
+        assert_eq!(parsed::<u8, ValidationError>()("n", None), Ok(None));
+        assert_eq!(
+            parsed::<u8, ValidationError>()("n", Some("256"))
+                .unwrap_err()
+                .message,
+            "field 'n' = '256' is not a valid u8"
+        );
+        assert!(is_between(1.0, 10.0)("n", Some("10")).is_ok());
+        let err = is_between(1.0, 10.0)("n", Some("11")).unwrap_err();
+        assert_eq!(
+            (err.param("min"), err.param("max")),
+            (Some("1"), Some("10"))
+        );
+        assert_eq!(
+            is_between(1.0, 10.0)("n", Some("11")).unwrap_err().message,
+            "field 'n' = '11' must be between 1 and 10"
+        );
+        assert!(is_between(1.0, 10.0)("n", None).is_ok());
+    }
+
+    #[test]
+    fn normalizers() {
+        assert_eq!(normalize_bool("b", Some("Yes")), Ok(Some("true".into())));
+        assert_eq!(normalize_bool("b", Some("0")), Ok(Some("false".into())));
+        assert_eq!(normalize_bool("b", None), Ok(None));
+        assert_eq!(
+            normalize_bool("b", Some("maybe")).unwrap_err().code,
+            "boolean"
+        );
+
+        assert_eq!(
+            normalize_phone("tel", Some("+33 1 23 45 67 89")),
+            Ok(Some("+33123456789".into()))
+        );
+        assert_eq!(
+            normalize_phone("tel", Some("0033.1.23.45.67.89")),
+            Ok(Some("+33123456789".into()))
+        );
+        assert_eq!(
+            normalize_phone("tel", Some("(555) 010-4477")),
+            Ok(Some("5550104477".into()))
+        );
+        assert_eq!(
+            normalize_phone("tel", Some("555-CALL-NOW"))
+                .unwrap_err()
+                .code,
+            "phone"
+        );
+        assert!(normalize_phone("tel", Some("+1 234 567 890 123 456")).is_err());
+    }
+
+    #[test]
+    fn fetch_metadata_checks() {
+        assert!(is_fetch_site("Sec-Fetch-Site", Some("same-origin")).is_ok());
+        assert!(is_fetch_site("Sec-Fetch-Site", None).is_ok());
+        assert_eq!(
+            is_fetch_site("Sec-Fetch-Site", Some("Same-Origin"))
+                .unwrap_err()
+                .code,
+            "fetch_site"
+        );
+        assert!(is_fetch_mode("Sec-Fetch-Mode", Some("navigate")).is_ok());
+        assert!(is_fetch_mode("Sec-Fetch-Mode", Some("document")).is_err());
+        assert!(is_fetch_dest("Sec-Fetch-Dest", Some("document")).is_ok());
+        assert!(is_fetch_dest("Sec-Fetch-Dest", Some("navigate")).is_err());
+    }
+
+    #[test]
+    fn content_negotiation() {
+        let json = require_content_type(&["application/json", "text/plain; charset=utf-8"]);
+        assert!(json("Content-Type", None).is_ok());
+        assert!(json("Content-Type", Some("application/json")).is_ok());
+        assert!(json("Content-Type", Some("Application/JSON; charset=utf-8")).is_ok());
+        assert!(json("Content-Type", Some("text/plain; charset=\"UTF-8\"")).is_ok());
+        assert!(json("Content-Type", Some("text/plain")).is_err());
+        assert!(json("Content-Type", Some("text/html")).is_err());
+        assert_eq!(
+            json("Content-Type", Some("json")).unwrap_err().code,
+            "content_type"
+        );
+        assert!(require_content_type(&["image/*"])("Content-Type", Some("image/png")).is_ok());
+
+        let accepts = require_accepts("application/json");
+        assert!(accepts("Accept", None).is_ok());
+        assert!(accepts("Accept", Some("application/json")).is_ok());
+        assert!(accepts("Accept", Some("text/html, application/*;q=0.5")).is_ok());
+        assert!(accepts("Accept", Some("text/html, */*;q=0.1")).is_ok());
+        assert!(accepts("Accept", Some("*/*, application/json;q=0")).is_err());
+        assert!(accepts("Accept", Some("text/html")).is_err());
+        assert_eq!(
+            accepts("Accept", Some("application/json;q=zero"))
+                .unwrap_err()
+                .code,
+            "accept"
+        );
+        assert!(accepts("Accept", Some("nonsense")).is_err());
+
+        // The weight doesn't make a range more specific
+        let html = require_accepts("text/html");
+        assert!(html("Accept", Some("text/html, text/*;q=0")).is_ok());
+        assert!(html("Accept", Some("text/*;q=0, text/html")).is_ok());
+        assert!(html("Accept", Some("text/*, text/html;q=0")).is_err());
+        assert_eq!(
+            accept_weight("text/html, text/*;q=0", "text/plain"),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn prefer_header() {
+        let prefs =
+            parse_prefer(r#"return=minimal; foo="a;b", wait=10, RETURN=representation"#).unwrap();
+        assert_eq!(
+            prefs.iter().collect::<Vec<_>>(),
+            vec![("return", Some("minimal")), ("wait", Some("10"))]
+        );
+        assert_eq!(prefs.wait(), Some(10));
+        assert!(parse_prefer("return=a b").is_none());
+        assert!(parse_prefer("").is_none());
+
+        let parser = prefer::<ValidationError>(&["return", "wait", "respond-async"]);
+        assert!(parser("Prefer", Some("respond-async, wait=5")).is_ok());
+        assert_eq!(parser("Prefer", None), Ok(None));
+        assert_eq!(
+            parser("Prefer", Some("return=full")).unwrap_err().code,
+            "prefer"
+        );
+        assert_eq!(
+            parser("Prefer", Some("handling=strict")).unwrap_err().code,
+            "unsupported_preference"
+        );
+    }
+
+    #[test]
+    fn format_checks() {
+        assert!(is_email("email", Some("john.doe+tag@example.co.uk")).is_ok());
+        assert!(is_email("email", Some("john@localhost")).is_err());
+        assert!(is_email("email", Some("john doe@example.com")).is_err());
+        assert!(is_url("url", Some("https://example.com/path?q=1")).is_ok());
+        assert!(is_url("url", Some("javascript:alert(1)")).is_err());
+        assert!(is_date("date", Some("2020-02-29")).is_ok());
+        assert!(is_date("date", Some("2019-02-29")).is_err());
+        assert!(is_date("date", Some("2020-1-01")).is_err());
+        assert!(one_of(&["asc", "desc"])("sort", Some("asc")).is_ok());
+        assert_eq!(
+            one_of(&["asc", "desc"])("sort", Some("up"))
+                .unwrap_err()
+                .message,
+            "field 'sort' = 'up' must be one of: asc, desc"
+        );
+        assert!(one_of(&["asc", "desc"])("sort", Some("DESC")).is_err());
+        assert!(one_of_ignore_case(&["asc", "desc"])("sort", Some("DESC")).is_ok());
+        let err = one_of_ignore_case(&["asc", "desc"])("sort", Some("up")).unwrap_err();
+        assert_eq!(err.code, "in");
+        assert_eq!(err.param("allowed"), Some("asc, desc"));
+        assert!(is_integer_between(1, 10)("n", Some("11")).is_err());
+        assert!(is_count_between(1, 2)("ids", Some(&["1", "2"])).is_ok());
+        assert_eq!(
+            is_count_between(1, 2)("ids", None).unwrap_err().code,
+            "count"
+        );
+        assert!(is_count_between(1, 2)("ids", Some(&["1", "2", "3"])).is_err());
+    }
+
+    #[test]
+    fn http_dates() {
+        let date = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(date));
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(date)
+        );
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(date));
+        assert_eq!(
+            parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"),
+            Some(UNIX_EPOCH)
+        );
+        assert!(parse_http_date("Sun, 06 Nov 1994 08:49:37").is_none());
+        assert!(parse_http_date("Sun, 31 Nov 1994 08:49:37 GMT").is_none());
+        assert!(parse_http_date("Sun, 06 Nov 1994 24:00:00 GMT").is_none());
+        assert!(parse_http_date("1994-11-06T08:49:37Z").is_none());
+
+        assert!(
+            is_past_http_date("If-Modified-Since", Some("Sun, 06 Nov 1994 08:49:37 GMT")).is_ok()
+        );
+        assert_eq!(
+            is_past_http_date("If-Modified-Since", Some("Fri, 01 Jan 9999 00:00:00 GMT"))
+                .unwrap_err()
+                .code,
+            "past_http_date"
+        );
+        assert_eq!(
+            is_past_http_date("If-Modified-Since", Some("yesterday"))
+                .unwrap_err()
+                .code,
+            "http_date"
+        );
+    }
+
+    #[test]
+    fn uuid_checks() {
+        assert!(is_uuid("id", Some("67e55044-10b1-426f-9247-bb680e5fe0c8")).is_ok());
+        assert!(is_uuid("id", Some("67E55044-10B1-426F-9247-BB680E5FE0C8")).is_ok());
+        assert!(is_uuid("id", Some("67e5504410b1426f9247bb680e5fe0c8")).is_err());
+        assert!(is_uuid("id", Some("67e55044-10b1-426f-9247-bb680e5fe0cg")).is_err());
+    }
+
+    #[test]
+    fn length_checks() {
+        assert!(is_max_length(3)("s", Some("été")).is_ok());
+        assert_eq!(is_min_length(4)("s", Some("abc")).unwrap_err().code, "min");
+    }
+}
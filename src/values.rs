@@ -0,0 +1,634 @@
+//! Values of the fields of a request, read when a rule needs them
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tide::http::headers::HeaderValue;
+use tide::Request;
+
+use crate::body::Body;
+use crate::{multipart, HttpField, ListStyle, Location, NormalizedValues};
+
+/// Source of the values of [`HttpField::Custom`](enum.HttpField.html#variant.Custom) fields,
+/// registered with [`value_provider`](struct.ValidatorMiddleware.html#method.value_provider).
+/// Closures taking the request and the field name are providers.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// struct Claims;
+///
+/// impl ValueProvider<()> for Claims {
+///     fn get(&self, req: &Request<()>, name: &str) -> Option<String> {
+///         let claims = req.local::<JwtClaims>()?;
+///         match name {
+///             "jwt.sub" => Some(claims.sub.clone()),
+///             "jwt.scope" => Some(claims.scope.join(" ")),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let mut validator_middleware = ValidatorMiddleware::new().value_provider(Claims);
+/// validator_middleware.add_validator(HttpField::custom("jwt.sub"), is_required);
+/// ```
+pub trait ValueProvider<State>: Send + Sync + 'static {
+    /// Value of the custom field `name`, `None` when the provider doesn't have it
+    fn get(&self, req: &Request<State>, name: &str) -> Option<String>;
+}
+
+impl<State, F> ValueProvider<State> for F
+where
+    F: Fn(&Request<State>, &str) -> Option<String> + Send + Sync + 'static,
+{
+    fn get(&self, req: &Request<State>, name: &str) -> Option<String> {
+        self(req, name)
+    }
+}
+
+pub(crate) fn field_value<State>(
+    ctx: &Request<State>,
+    field: &HttpField<'_>,
+    query_parameters: &mut QueryParameters<'_>,
+    body: Body<'_>,
+    parts: &[multipart::Part<'_>],
+    providers: &[Arc<dyn ValueProvider<State>>],
+) -> Option<String>
+where
+    State: Send + Sync + 'static,
+{
+    // Repeated query parameters and headers give their last value, taken without collecting
+    // every value
+    match field {
+        HttpField::BodyField(path) => body.value(path),
+        HttpField::QueryParam(param_name) => query_parameters
+            .parsed()
+            .get(param_name.as_ref())
+            .and_then(|values| values.last())
+            .map(|value| value.to_string()),
+        HttpField::Header(_) | HttpField::ContentType => header_values(ctx, field.name())
+            .and_then(|values| values.last())
+            .map(|value| value.as_str().to_string()),
+        HttpField::Accept => accept_value(ctx),
+        _ => field_values(ctx, field, query_parameters, body, parts, providers).pop(),
+    }
+}
+
+/// Media ranges of every `Accept` header line, a list header whose lines are a single value
+pub(crate) fn accept_value<State>(ctx: &Request<State>) -> Option<String> {
+    header_values(ctx, "Accept").map(|values| {
+        let values: Vec<&str> = values.iter().map(HeaderValue::as_str).collect();
+        values.join(", ")
+    })
+}
+
+/// Values of a header, found by comparing `name` case-insensitively to the raw header names
+pub(crate) fn header_values<'r, State>(
+    req: &'r Request<State>,
+    name: &str,
+) -> Option<&'r Vec<HeaderValue>> {
+    req.iter()
+        .find(|(header_name, _)| header_name.as_str().eq_ignore_ascii_case(name))
+        .map(|(_, values)| values)
+}
+
+/// Every value of a field: repeated query parameters, the values of a header or the items of
+/// a JSON array
+pub(crate) fn field_values<State>(
+    ctx: &Request<State>,
+    field: &HttpField<'_>,
+    query_parameters: &mut QueryParameters<'_>,
+    body: Body<'_>,
+    parts: &[multipart::Part<'_>],
+    providers: &[Arc<dyn ValueProvider<State>>],
+) -> Vec<String>
+where
+    State: Send + Sync + 'static,
+{
+    match field {
+        HttpField::Param(param_name) => ctx.param::<String>(param_name).ok().into_iter().collect(),
+        HttpField::QueryParam(param_name) => query_parameters
+            .parsed()
+            .get(param_name.as_ref())
+            .map(|values| values.iter().map(|value| value.to_string()).collect())
+            .unwrap_or_default(),
+        HttpField::QueryList(name, style) => {
+            let values = match query_parameters
+                .parsed()
+                .get(style.param_name(name).as_ref())
+            {
+                Some(values) => values,
+                None => return Vec::new(),
+            };
+            match style {
+                ListStyle::Comma => values
+                    .iter()
+                    .filter(|value| !value.is_empty())
+                    .flat_map(|value| value.split(','))
+                    .map(String::from)
+                    .collect(),
+                ListStyle::Repeated | ListStyle::Brackets => {
+                    values.iter().map(|value| value.to_string()).collect()
+                }
+            }
+        }
+        HttpField::Header(_) | HttpField::ContentType => header_values(ctx, field.name())
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_str().to_string())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        HttpField::Accept => accept_value(ctx).into_iter().collect(),
+        HttpField::Cookie(cookie_name) => ctx
+            .cookie(cookie_name)
+            .map(|c| c.value().to_string())
+            .into_iter()
+            .collect(),
+        HttpField::BodyField(path) => body.values(path),
+        HttpField::MatrixParam(segment, param_name) => {
+            matrix_params(ctx.uri().path(), segment, param_name)
+        }
+        HttpField::Multipart(name) => parts
+            .iter()
+            .filter(|part| part.name == name)
+            .filter_map(|part| part.value().map(String::from))
+            .collect(),
+        HttpField::Custom(name) => providers
+            .iter()
+            .find_map(|provider| provider.get(ctx, name))
+            .into_iter()
+            .collect(),
+        HttpField::PeerAddr => ctx.peer_addr().map(String::from).into_iter().collect(),
+        // Patterns are replaced by the fields they match before reading values
+        HttpField::HeaderPattern(_) | HttpField::QueryParamPattern(_) => Vec::new(),
+    }
+}
+
+/// Headers or query parameters matching the pattern `field` and body fields matching a path with
+/// wildcards or several XML elements, the field itself when it's not a pattern
+#[cfg_attr(not(feature = "regex"), allow(unused_variables))]
+pub(crate) fn matching_fields<'f, State>(
+    ctx: &Request<State>,
+    field: &'f HttpField<'static>,
+    query_parameters: &mut QueryParameters<'_>,
+    body: Body<'_>,
+) -> Vec<Cow<'f, HttpField<'static>>> {
+    match field {
+        HttpField::BodyField(path) => match body.expand(path) {
+            Some(paths) => paths
+                .into_iter()
+                .map(|path| Cow::Owned(HttpField::body_field(path)))
+                .collect(),
+            None => vec![Cow::Borrowed(field)],
+        },
+        #[cfg(feature = "regex")]
+        HttpField::HeaderPattern(pattern) => ctx
+            .iter()
+            .filter(|(name, _)| pattern.is_match(name.as_str()))
+            .map(|(name, _)| Cow::Owned(HttpField::header(name.as_str().to_string())))
+            .collect(),
+        #[cfg(feature = "regex")]
+        HttpField::QueryParamPattern(pattern) => {
+            let mut names: Vec<&str> = query_parameters
+                .parsed()
+                .keys()
+                .map(|name| name.as_ref())
+                .filter(|name| pattern.is_match(name))
+                .collect();
+            names.sort_unstable();
+            names
+                .into_iter()
+                .map(|name| Cow::Owned(HttpField::query_param(name.to_string())))
+                .collect()
+        }
+        field => vec![Cow::Borrowed(field)],
+    }
+}
+
+/// Fields at `location` present in the request, for global validators
+pub(crate) fn present_fields<State>(
+    ctx: &Request<State>,
+    location: Location,
+    query_parameters: &mut QueryParameters<'_>,
+    body: Body<'_>,
+) -> Vec<HttpField<'static>> {
+    match location {
+        Location::Header => ctx
+            .iter()
+            .map(|(name, _)| HttpField::header(name.as_str().to_string()))
+            .collect(),
+        Location::Query => {
+            let mut names: Vec<&str> = query_parameters
+                .parsed()
+                .keys()
+                .map(|name| name.as_ref())
+                .collect();
+            names.sort_unstable();
+            names
+                .into_iter()
+                .map(|name| HttpField::query_param(name.to_string()))
+                .collect()
+        }
+        Location::Cookie => header_values(ctx, "Cookie")
+            .into_iter()
+            .flatten()
+            .flat_map(|value| value.as_str().split(';'))
+            .filter_map(|pair| pair.split('=').next())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| HttpField::cookie(name.to_string()))
+            .collect(),
+        Location::Body => body
+            .names()
+            .into_iter()
+            .map(HttpField::body_field)
+            .collect(),
+        Location::Path | Location::Custom => Vec::new(),
+    }
+}
+
+/// Values of the matrix parameter `param_name` of the path segments `segment`, a parameter
+/// without `=` has an empty value
+pub(crate) fn matrix_params(path: &str, segment: &str, param_name: &str) -> Vec<String> {
+    path.split('/')
+        .filter_map(|path_segment| {
+            let mut parts = path_segment.split(';');
+            match parts.next() {
+                Some(name) if name == segment => Some(parts),
+                _ => None,
+            }
+        })
+        .flatten()
+        .filter_map(|param| {
+            let mut name_value = param.splitn(2, '=');
+            match name_value.next() {
+                Some(name) if name == param_name => {
+                    Some(name_value.next().unwrap_or_default().to_string())
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Query parameters of a request, parsed on first use
+pub(crate) struct QueryParameters<'q> {
+    url: Option<&'q tide::http::Url>,
+    parsed: Option<HashMap<Cow<'q, str>, Vec<Cow<'q, str>>>>,
+}
+
+impl<'q> QueryParameters<'q> {
+    /// Parameters of `url`, `None` when the request has no query string
+    pub(crate) fn new(url: Option<&'q tide::http::Url>) -> Self {
+        QueryParameters { url, parsed: None }
+    }
+
+    pub(crate) fn parsed(&mut self) -> &HashMap<Cow<'q, str>, Vec<Cow<'q, str>>> {
+        let url = self.url;
+        self.parsed
+            .get_or_insert_with(|| url.map(parse_query).unwrap_or_default())
+    }
+}
+
+/// Values of the fields of a request for one run of the middleware, normalized values first.
+/// `normalized` only holds the values normalized by this middleware, those of the middlewares
+/// stacked before it are read from the request.
+pub(crate) struct RequestValues<'r, State> {
+    pub(crate) query: QueryParameters<'r>,
+    pub(crate) body: Body<'r>,
+    pub(crate) parts: &'r [multipart::Part<'r>],
+    providers: &'r [Arc<dyn ValueProvider<State>>],
+    pub(crate) normalized: NormalizedValues,
+}
+
+impl<'r, State> RequestValues<'r, State>
+where
+    State: Send + Sync + 'static,
+{
+    pub(crate) fn new(
+        query: QueryParameters<'r>,
+        body: Body<'r>,
+        parts: &'r [multipart::Part<'r>],
+        providers: &'r [Arc<dyn ValueProvider<State>>],
+        normalized: NormalizedValues,
+    ) -> Self {
+        RequestValues {
+            query,
+            body,
+            parts,
+            providers,
+            normalized,
+        }
+    }
+
+    /// Value of the field as sent
+    pub(crate) fn raw(&mut self, ctx: &Request<State>, field: &HttpField<'_>) -> Option<String> {
+        field_value(
+            ctx,
+            field,
+            &mut self.query,
+            self.body,
+            self.parts,
+            self.providers,
+        )
+    }
+
+    /// Value of the field, the normalized one if the field was normalized
+    pub(crate) fn get(
+        &mut self,
+        ctx: &Request<State>,
+        field: &HttpField<'static>,
+    ) -> Option<String> {
+        match self.normalized(ctx, field) {
+            Some(normalized) => Some(normalized.to_string()),
+            None => self.raw(ctx, field),
+        }
+    }
+
+    /// Normalized value of the field, by this middleware or one stacked before it
+    pub(crate) fn normalized<'v>(
+        &'v self,
+        ctx: &'v Request<State>,
+        field: &HttpField<'static>,
+    ) -> Option<&'v str> {
+        self.normalized.get(field).or_else(|| {
+            ctx.local::<NormalizedValues>()
+                .and_then(|normalized| normalized.get(field))
+        })
+    }
+
+    /// Every value of the field as sent
+    pub(crate) fn all(&mut self, ctx: &Request<State>, field: &HttpField<'_>) -> Vec<String> {
+        field_values(
+            ctx,
+            field,
+            &mut self.query,
+            self.body,
+            self.parts,
+            self.providers,
+        )
+    }
+}
+
+/// Query parameters by name, with the values of repeated parameters in order. Names and values
+/// borrow the URL unless they are percent-encoded.
+pub(crate) fn parse_query(url: &tide::http::Url) -> HashMap<Cow<'_, str>, Vec<Cow<'_, str>>> {
+    let mut query_parameters: HashMap<Cow<'_, str>, Vec<Cow<'_, str>>> = HashMap::new();
+    for (name, value) in url.query_pairs() {
+        query_parameters.entry(name).or_default().push(value);
+    }
+    query_parameters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::is_number;
+    use crate::{validators, ValidatedRequestExt, ValidationError, ValidatorMiddleware};
+    use async_std::io::prelude::*;
+    use futures::executor::block_on;
+    use http_service_mock::make_server;
+    use tide::http::{Method, Request};
+    use tide::StatusCode;
+
+    #[test]
+    fn matrix_parameters() {
+        let path = "/items;limit=10;offset=20/tags;color=red;color=blue;all";
+        assert_eq!(matrix_params(path, "items", "limit"), vec!["10"]);
+        assert_eq!(matrix_params(path, "tags", "color"), vec!["red", "blue"]);
+        assert_eq!(matrix_params(path, "tags", "all"), vec![""]);
+        assert!(matrix_params(path, "tags", "limit").is_empty());
+        assert!(matrix_params(path, "users", "limit").is_empty());
+    }
+
+    #[test]
+    fn validator_multi_values() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_multi_validator(
+            HttpField::query_param("id"),
+            |field_name: &str, values: Option<&[&str]>| match values {
+                Some(ids) if ids.len() > 2 => Err(format!("at most 2 '{}' allowed", field_name)),
+                _ => Ok(()),
+            },
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?id=1&id=2".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?id=1&id=2&id=3".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn validator_each_value() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_each_validator(HttpField::header("X-Forwarded-For"), is_number);
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (values, status) in &[
+            (&["1", "2"][..], StatusCode::Ok),
+            (&["1", "nope"][..], StatusCode::BadRequest),
+            (&["nope", "2"][..], StatusCode::BadRequest),
+            (&[][..], StatusCode::Ok),
+        ] {
+            let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+            for value in values.iter() {
+                req.append_header("X-Forwarded-For", value).unwrap();
+            }
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+    }
+
+    #[test]
+    fn validator_query_list() {
+        let mut inner = tide::new();
+        for (path, style) in &[
+            ("/repeated", ListStyle::Repeated),
+            ("/brackets", ListStyle::Brackets),
+            ("/comma", ListStyle::Comma),
+        ] {
+            let ids = HttpField::query_list("ids", *style);
+            let mut validators = ValidatorMiddleware::new();
+            validators.add_multi_validator(ids.clone(), validators::is_count_between(1, 3));
+            validators.add_list_parser(ids, validators::parsed::<u64, ValidationError>());
+            inner
+                .at(path)
+                .middleware(validators)
+                .get(|req: tide::Request<()>| async move {
+                    let ids = req.validated::<Vec<u64>>("ids").unwrap();
+                    Ok(format!("{:?}", ids))
+                });
+        }
+
+        let mut server = make_server(inner).unwrap();
+
+        for (url, status, body) in &[
+            ("/repeated?ids=1&ids=2", StatusCode::Ok, "[1, 2]"),
+            ("/brackets?ids[]=1&ids[]=2&ids=3", StatusCode::Ok, "[1, 2]"),
+            ("/brackets?ids%5B%5D=4", StatusCode::Ok, "[4]"),
+            ("/comma?ids=1,2,3", StatusCode::Ok, "[1, 2, 3]"),
+            ("/comma?ids=1,2&ids=3", StatusCode::Ok, "[1, 2, 3]"),
+            ("/comma?ids=1,2,3,4", StatusCode::BadRequest, ""),
+            ("/comma?ids=", StatusCode::BadRequest, ""),
+            ("/brackets?ids=1", StatusCode::BadRequest, ""),
+            ("/repeated?ids=1&ids=two", StatusCode::BadRequest, ""),
+        ] {
+            let req = Request::new(
+                Method::Get,
+                format!("http://localhost{}", url).parse().unwrap(),
+            );
+            let mut res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status, "{}", url);
+            if *status == StatusCode::Ok {
+                let mut buf = Vec::new();
+                block_on(res.read_to_end(&mut buf)).unwrap();
+                assert_eq!(String::from_utf8_lossy(&buf), *body);
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_query_parameters() {
+        let url: tide::http::Url = "http://localhost/foo?id=1&name=a+b&id=2".parse().unwrap();
+        let query_parameters = parse_query(&url);
+        assert_eq!(query_parameters["id"], vec!["1", "2"]);
+        assert_eq!(query_parameters["name"], vec!["a b"]);
+    }
+
+    #[test]
+    fn validator_custom_fields() {
+        let mut inner = tide::new();
+        let mut validators =
+            ValidatorMiddleware::new().value_provider(|req: &tide::Request<()>, name: &str| {
+                match name {
+                    "geo.country" => req
+                        .header(&"X-Geo-Country".parse().unwrap())
+                        .and_then(|values| values.last())
+                        .map(|value| value.as_str().to_lowercase()),
+                    _ => None,
+                }
+            });
+        validators.add_validator(HttpField::custom("geo.country"), validators::is_required);
+        validators.add_validator(
+            HttpField::custom("geo.country"),
+            validators::one_of(&["fr", "be"]),
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (country, status) in &[
+            (Some("FR"), StatusCode::Ok),
+            (Some("US"), StatusCode::BadRequest),
+            (None, StatusCode::BadRequest),
+        ] {
+            let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+            if let Some(country) = country {
+                req.insert_header("X-Geo-Country", country).unwrap();
+            }
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+    }
+
+    #[test]
+    fn validator_accept_lines() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(
+            HttpField::accept(),
+            validators::require_accepts("application/json"),
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        for (lines, status) in &[
+            (&["text/html", "application/json;q=0.5"][..], StatusCode::Ok),
+            (&["application/json", "*/*;q=0"][..], StatusCode::Ok),
+            (&["*/*", "application/json;q=0"][..], StatusCode::BadRequest),
+        ] {
+            let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+            for line in lines.iter() {
+                req.append_header("Accept", *line).unwrap();
+            }
+            let res = server.simulate(req).unwrap();
+            assert_eq!(res.status(), *status);
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn validator_field_patterns() {
+        let mut inner = tide::new();
+        let mut validators = ValidatorMiddleware::new();
+        validators.add_validator(
+            HttpField::header_pattern(regex::Regex::new("(?i)^x-internal-").unwrap()),
+            validators::is_uuid,
+        );
+        validators.add_validator(
+            HttpField::query_param_pattern(regex::Regex::new("^filter_").unwrap()),
+            validators::is_integer,
+        );
+        inner
+            .at("/foo")
+            .middleware(validators)
+            .get(|_| async { Ok("foo") });
+
+        let mut server = make_server(inner).unwrap();
+
+        let mut req = Request::new(
+            Method::Get,
+            "http://localhost/foo?filter_age=4&sort=name"
+                .parse()
+                .unwrap(),
+        );
+        req.insert_header("X-Internal-Trace", "67e55044-10b1-426f-9247-bb680e5fe0c8")
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let req = Request::new(
+            Method::Get,
+            "http://localhost/foo?filter_age=four".parse().unwrap(),
+        );
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+
+        let mut req = Request::new(Method::Get, "http://localhost/foo".parse().unwrap());
+        req.insert_header("X-Internal-Trace", "not-a-uuid").unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+    }
+}
@@ -0,0 +1,178 @@
+//! XML bodies, parsed into a tree in which body fields are selected with XPath-like paths
+/// Element of an XML document, copied from the body
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    /// Text of the element and its descendants, trimmed
+    text: String,
+    children: Vec<Element>,
+}
+
+/// Parse an XML body into a document node whose only child is the root element
+pub(crate) fn parse(body: &[u8]) -> Option<Element> {
+    let body = std::str::from_utf8(body).ok()?;
+    let document = roxmltree::Document::parse(body).ok()?;
+    Some(Element {
+        name: String::new(),
+        attributes: Vec::new(),
+        text: String::new(),
+        children: vec![element(document.root_element())],
+    })
+}
+
+fn element(node: roxmltree::Node<'_, '_>) -> Element {
+    let text: String = node
+        .descendants()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .collect();
+    Element {
+        name: node.tag_name().name().to_string(),
+        attributes: node
+            .attributes()
+            .iter()
+            .map(|attribute| (attribute.name().to_string(), attribute.value().to_string()))
+            .collect(),
+        text: text.trim().to_string(),
+        children: node
+            .children()
+            .filter(|node| node.is_element())
+            .map(element)
+            .collect(),
+    }
+}
+
+/// Elements or attributes selected by `path` in `document`, with the path selecting only them and
+/// their text, `None` when they are missing
+pub(crate) fn select(document: &Element, path: &str) -> Vec<(String, Option<String>)> {
+    let (start, path, absolute) = match path.strip_prefix('/') {
+        Some(path) => (Some(document), path, true),
+        None => (document.children.first(), path, false),
+    };
+    let steps: Vec<&str> = path.split('/').collect();
+    let mut selected = Vec::new();
+    select_from(start, &steps, &mut Vec::new(), &mut |concrete, value| {
+        let concrete = concrete.join("/");
+        let concrete = if absolute {
+            format!("/{}", concrete)
+        } else {
+            concrete
+        };
+        selected.push((concrete, value));
+    });
+    selected
+}
+
+fn select_from(
+    node: Option<&Element>,
+    steps: &[&str],
+    concrete: &mut Vec<String>,
+    selected: &mut dyn FnMut(&[String], Option<String>),
+) {
+    let (step, rest) = match steps.split_first() {
+        Some(first) => first,
+        None => return selected(concrete, node.map(|node| node.text.clone())),
+    };
+    if let Some(attribute) = step.strip_prefix('@') {
+        if rest.is_empty() {
+            concrete.push(step.to_string());
+            let value = node.and_then(|node| {
+                node.attributes
+                    .iter()
+                    .find(|(name, _)| name == attribute)
+                    .map(|(_, value)| value.clone())
+            });
+            selected(concrete, value);
+            concrete.pop();
+        }
+        return;
+    }
+    if *step == "text()" {
+        return select_from(node, rest, concrete, selected);
+    }
+
+    let (name, index) = match step.find('[') {
+        Some(bracket) => (
+            &step[..bracket],
+            step[bracket + 1..]
+                .strip_suffix(']')
+                .and_then(|index| index.parse::<usize>().ok()),
+        ),
+        None => (*step, None),
+    };
+    let children: Vec<&Element> = node
+        .map(|node| {
+            node.children
+                .iter()
+                .filter(|child| name == "*" || child.name == name)
+                .enumerate()
+                .filter(|(i, _)| index.is_none() || index == Some(i + 1))
+                .map(|(_, child)| child)
+                .collect()
+        })
+        .unwrap_or_default();
+    if children.is_empty() {
+        concrete.push(step.to_string());
+        select_from(None, rest, concrete, selected);
+        concrete.pop();
+        return;
+    }
+    let siblings = node.map(|node| &node.children[..]).unwrap_or_default();
+    for child in children {
+        let same_name = siblings.iter().filter(|sibling| sibling.name == child.name);
+        let position = same_name
+            .clone()
+            .position(|sibling| std::ptr::eq(sibling, child))
+            .unwrap_or_default();
+        concrete.push(if same_name.count() > 1 {
+            format!("{}[{}]", child.name, position + 1)
+        } else {
+            child.name.clone()
+        });
+        select_from(Some(child), rest, concrete, selected);
+        concrete.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDER: &[u8] = br#"<?xml version="1.0"?>
+<order id="42">
+  <customer><name>Gribouille</name><email>cat@example.com</email></customer>
+  <item sku="A1"><quantity>2</quantity></item>
+  <item><quantity>two</quantity></item>
+</order>"#;
+
+    #[test]
+    fn selectors() {
+        let document = parse(ORDER).unwrap();
+        let value = |path| {
+            let mut selected = select(&document, path);
+            assert!(selected.len() <= 1, "{}", path);
+            selected.pop().and_then(|(_, value)| value)
+        };
+        assert_eq!(value("/order/customer/name"), Some("Gribouille".into()));
+        assert_eq!(value("customer/email"), Some("cat@example.com".into()));
+        assert_eq!(value("/order/@id"), Some("42".into()));
+        assert_eq!(value("/order/item[2]/quantity/text()"), Some("two".into()));
+        assert_eq!(value("/order/customer/phone"), None);
+        assert_eq!(value("/order/item[3]/quantity"), None);
+        assert_eq!(value("/invoice/@id"), None);
+
+        assert_eq!(
+            select(&document, "/order/item/@sku"),
+            vec![
+                (
+                    String::from("/order/item[1]/@sku"),
+                    Some(String::from("A1"))
+                ),
+                (String::from("/order/item[2]/@sku"), None),
+            ]
+        );
+        assert_eq!(select(&document, "*").len(), 3);
+        assert!(parse(b"<order></item>").is_none());
+    }
+}
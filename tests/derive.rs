@@ -0,0 +1,61 @@
+#![cfg(feature = "derive")]
+use async_std::io::prelude::*;
+use futures::executor::block_on;
+use http_service_mock::make_server;
+use tide::http::{Method, Request};
+use tide::StatusCode;
+use tide_validator::validators::ValidationError;
+use tide_validator::Validate;
+
+#[allow(dead_code)]
+#[derive(Validate)]
+struct ListParams {
+    #[validate(query, range(min = 1, max = 100))]
+    page: u32,
+    #[validate(header = "X-Request-Id", uuid)]
+    request_id: Option<String>,
+    #[validate(one_of("asc", "desc"))]
+    order: Option<String>,
+}
+
+#[test]
+fn derive_validate() {
+    let mut inner = tide::new();
+    inner
+        .at("/items")
+        .middleware(ListParams::validator_middleware())
+        .get(|_| async { Ok("items") });
+
+    let mut server = make_server(inner).unwrap();
+
+    let req = Request::new(
+        Method::Get,
+        "http://localhost/items?page=2&order=asc".parse().unwrap(),
+    );
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+
+    let req = Request::new(Method::Get, "http://localhost/items".parse().unwrap());
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+
+    // Overflows the u32 type of the field
+    let mut buf = Vec::new();
+    let req = Request::new(
+        Method::Get,
+        "http://localhost/items?page=4294967296".parse().unwrap(),
+    );
+    let mut res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    block_on(res.read_to_end(&mut buf)).unwrap();
+    let error: ValidationError = serde_json::from_slice(&buf[..]).unwrap();
+    assert_eq!(error.code, "integer");
+
+    let mut req = Request::new(
+        Method::Get,
+        "http://localhost/items?page=2".parse().unwrap(),
+    );
+    req.insert_header("X-Request-Id", "nope").unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use async_std::io::prelude::*;
+use async_std::io::Read;
+use futures::executor::block_on;
+use http_service_mock::make_server;
+use tide::http::{Method, Request};
+use tide::StatusCode;
+use tide_validator::validators::ValidationError;
+
+#[path = "../examples/full_api/app.rs"]
+mod app;
+
+const USER_ID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+const SESSION: &str = "0123456789abcdef";
+
+fn get(url: &str, headers: &[(&str, &str)]) -> Request {
+    let mut req = Request::new(Method::Get, url.parse().unwrap());
+    for (name, value) in headers {
+        req.insert_header(*name, *value).unwrap();
+    }
+    req
+}
+
+fn post(url: &str, content_type: &str, body: &str) -> Request {
+    let mut req = Request::new(Method::Post, url.parse().unwrap());
+    req.insert_header("Content-Type", content_type).unwrap();
+    req.set_body(body);
+    req
+}
+
+fn read_json<D: serde::de::DeserializeOwned>(res: impl Read + Unpin) -> D {
+    serde_json::from_str(&read_string(res)).unwrap()
+}
+
+fn read_string(mut res: impl Read + Unpin) -> String {
+    let mut buf = Vec::new();
+    block_on(res.read_to_end(&mut buf)).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn params_headers_and_cookies() {
+    let mut server = make_server(app::app()).unwrap();
+    let url = format!("http://localhost/users/{}", USER_ID);
+    let session = format!("session={}", SESSION);
+
+    let res = server
+        .simulate(get(
+            &url,
+            &[("X-Request-Id", USER_ID), ("Cookie", &session)],
+        ))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    let res = server
+        .simulate(get("http://localhost/users/42", &[("Cookie", &session)]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let error: ValidationError = read_json(res);
+    assert_eq!((error.field.as_str(), error.code.as_str()), ("id", "uuid"));
+
+    let res = server
+        .simulate(get(&url, &[("X-Request-Id", "nope"), ("Cookie", &session)]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let error: ValidationError = read_json(res);
+    assert_eq!(error.field, "X-Request-Id");
+
+    // Missing and too short cookies
+    let res = server.simulate(get(&url, &[])).unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let error: ValidationError = read_json(res);
+    assert_eq!(
+        (error.field.as_str(), error.code.as_str()),
+        ("session", "required")
+    );
+
+    let res = server
+        .simulate(get(&url, &[("Cookie", "session=short")]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+}
+
+#[test]
+fn query_params_grouped_by_field() {
+    let mut server = make_server(app::app()).unwrap();
+
+    let res = server
+        .simulate(get("http://localhost/search?q=cats&page=2&sort=asc", &[]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    let res = server
+        .simulate(get("http://localhost/search?page=0&sort=up", &[]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let errors: HashMap<String, Vec<ValidationError>> = read_json(res);
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors["q"][0].code, "required");
+    assert_eq!(errors["page"][0].code, "between");
+    assert_eq!(errors["sort"][0].code, "in");
+
+    let res = server
+        .simulate(get("http://localhost/search?q=cats&debug=1", &[]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    assert_eq!(read_string(res), "unknown query parameters: debug");
+}
+
+#[test]
+fn body_fields_as_problem_details() {
+    let mut server = make_server(app::app()).unwrap();
+
+    let res = server
+        .simulate(post(
+            "http://localhost/users",
+            "application/json",
+            r#"{"name": "Gribouille", "email": "cat@example.com", "address": {"country": "FR"}}"#,
+        ))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+    let user: serde_json::Value = read_json(res);
+    assert_eq!(user["name"], "Gribouille");
+
+    let res = server
+        .simulate(post(
+            "http://localhost/users",
+            "application/json",
+            r#"{"name": "Gribouille", "email": "nope", "address": {"country": "XX"}}"#,
+        ))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    assert_eq!(
+        res.header(&"Content-Type".into()).unwrap()[0].as_str(),
+        "application/problem+json"
+    );
+    let problem: serde_json::Value = read_json(res);
+    assert_eq!(problem["status"], 400);
+    let invalid_params = problem["invalid-params"].as_array().unwrap();
+    assert!(invalid_params
+        .iter()
+        .all(|invalid_param| invalid_param["in"] == "body"));
+    assert!(invalid_params
+        .iter()
+        .any(|invalid_param| invalid_param["name"] == "email"));
+
+    // Malformed and unexpected bodies never reach the validators
+    let res = server
+        .simulate(post(
+            "http://localhost/users",
+            "application/json",
+            "{not json",
+        ))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    assert!(read_string(res).starts_with("cannot parse JSON body"));
+
+    let res = server
+        .simulate(post(
+            "http://localhost/users",
+            "application/json",
+            r#"{"name": "Gribouille", "email": "cat@example.com", "admin": true}"#,
+        ))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    assert_eq!(read_string(res), "unknown body fields: admin");
+}
+
+#[test]
+fn matrix_params() {
+    let mut server = make_server(app::app()).unwrap();
+
+    for (url, status) in &[
+        ("http://localhost/catalog/items;limit=10", StatusCode::Ok),
+        ("http://localhost/catalog/items", StatusCode::Ok),
+        (
+            "http://localhost/catalog/items;limit=100",
+            StatusCode::BadRequest,
+        ),
+        (
+            "http://localhost/catalog/items;limit=ten",
+            StatusCode::BadRequest,
+        ),
+    ] {
+        assert_eq!(
+            server.simulate(get(url, &[])).unwrap().status(),
+            *status,
+            "{}",
+            url
+        );
+    }
+}
+
+#[test]
+fn multipart_files() {
+    let mut server = make_server(app::app()).unwrap();
+    let body = |title: &str, filename: &str, content: &str| {
+        format!(
+            "--XyZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\n{}\r\n\
+             --XyZ\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"{}\"\r\n\
+             Content-Type: image/png\r\n\r\n{}\r\n--XyZ--\r\n",
+            title, filename, content
+        )
+    };
+    let content_type = "multipart/form-data; boundary=XyZ";
+
+    for (body, code) in &[
+        (body("Holidays", "cat.png", "PNG"), None),
+        (body("Holidays", "cat.gif", "GIF"), Some("extension")),
+        (
+            body("Holidays", "cat.png", &"X".repeat(2048)),
+            Some("file_size"),
+        ),
+    ] {
+        let res = server
+            .simulate(post("http://localhost/avatars", content_type, body))
+            .unwrap();
+        match code {
+            None => assert_eq!(res.status(), StatusCode::Ok),
+            Some(code) => {
+                assert_eq!(res.status(), StatusCode::BadRequest);
+                let error: ValidationError = read_json(res);
+                assert_eq!(error.code, *code);
+            }
+        }
+    }
+
+    let res = server
+        .simulate(post("http://localhost/avatars", content_type, "nope"))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    assert_eq!(read_string(res), "cannot parse multipart body");
+}
+
+#[test]
+fn custom_fields_and_validator_errors() {
+    let mut server = make_server(app::app()).unwrap();
+
+    let res = server
+        .simulate(get("http://acme.localhost/tenant", &[]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    let res = server
+        .simulate(get("http://initech.localhost/tenant", &[]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let error: ValidationError = read_json(res);
+    assert_eq!(
+        (error.field.as_str(), error.code.as_str()),
+        ("tenant", "tenant")
+    );
+
+    let res = server
+        .simulate(get("http://localhost/tenant", &[]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let error: ValidationError = read_json(res);
+    assert_eq!(error.code, "required");
+
+    let res = server
+        .simulate(get("http://maintenance.localhost/tenant", &[]))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ServiceUnavailable);
+    assert_eq!(
+        read_string(res),
+        "cannot check 'tenant': tenant directory is unavailable"
+    );
+}
@@ -0,0 +1,337 @@
+//! Derive macro for [tide-validator](https://docs.rs/tide-validator), enable it with the `derive`
+//! feature of `tide-validator` instead of depending on this crate directly.
+//!
+//! Each field of the struct is an input of the route, described with a `#[validate(...)]` attribute:
+//!
+//! - where the field is: `param`, `query` (default), `header`, `cookie` or `body`, optionally
+//!   with its name when it isn't the name of the struct field, e.g. `header = "X-Token"`
+//! - the checks: `required`, `integer`, `numeric`, `boolean`, `email`, `uuid`, `url`, `date`,
+//!   `range(min = 1, max = 100)`, `length(min = 1, max = 64)`, `one_of("asc", "desc")`
+//!   and `rules = "required|integer"`
+//!
+//! Fields which aren't an `Option` are required, integer, float and boolean fields are checked
+//! according to their type.
+//!
+//! ```rust,no_run,compile_fail
+//! #[derive(Validate)]
+//! struct ListParams {
+//!     #[validate(query, range(min = 1, max = 100))]
+//!     page: u32,
+//!     #[validate(header = "X-Request-Id", uuid)]
+//!     request_id: Option<String>,
+//! }
+//!
+//! app.at("/items").middleware(ListParams::validator_middleware()).get(endpoint);
+//! ```
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Lit, Meta, NestedMeta,
+    PathArguments, Type,
+};
+
+/// Implement `tide_validator::Validate` from the `#[validate(...)]` attributes of the fields
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn derive_validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "Validate can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "Validate can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut registrations = Vec::new();
+    for field in fields {
+        let name = field
+            .ident
+            .as_ref()
+            .map(|ident| ident.to_string())
+            .unwrap_or_default();
+        let field_input = FieldInput::parse(name, field)?;
+        registrations.extend(field_input.registrations());
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::tide_validator::Validate for #ident #ty_generics #where_clause {
+            fn validator_middleware<State>() -> ::tide_validator::ValidatorMiddleware<
+                ::tide_validator::validators::ValidationError,
+                State,
+            >
+            where
+                State: Send + Sync + 'static,
+            {
+                let mut middleware = ::tide_validator::ValidatorMiddleware::new();
+                #(middleware.add_validator(#registrations);)*
+                middleware
+            }
+        }
+    })
+}
+
+/// A field of the struct with its location and its checks
+struct FieldInput {
+    http_field: TokenStream2,
+    validators: Vec<TokenStream2>,
+}
+
+impl FieldInput {
+    fn parse(name: String, field: &syn::Field) -> syn::Result<Self> {
+        let (optional, ty) = match option_inner(&field.ty) {
+            Some(inner) => (true, inner),
+            None => (false, &field.ty),
+        };
+
+        let mut location = (String::from("query"), name);
+        let mut validators = Vec::new();
+        if !optional {
+            validators.push(quote!(::tide_validator::validators::is_required));
+        }
+        if let Some(validator) = type_validator(ty) {
+            validators.push(validator);
+        }
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("validate"))
+        {
+            let items = match attr.parse_meta()? {
+                Meta::List(list) => list.nested,
+                meta => return Err(syn::Error::new_spanned(meta, "expected #[validate(...)]")),
+            };
+            for item in items {
+                match item {
+                    NestedMeta::Meta(Meta::Path(path)) => {
+                        let ident = path_name(&path);
+                        if is_location(&ident) {
+                            location.0 = ident;
+                        } else {
+                            validators.push(simple_validator(&ident, &path)?);
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(name_value)) => {
+                        let ident = path_name(&name_value.path);
+                        let value = match &name_value.lit {
+                            Lit::Str(value) => value.value(),
+                            lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                        };
+                        if is_location(&ident) {
+                            location = (ident, value);
+                        } else if ident == "rules" {
+                            let message = format!("invalid rule string '{}'", value);
+                            validators.push(quote! {
+                                ::tide_validator::rules::rules(#value).expect(#message)
+                            });
+                        } else {
+                            return Err(syn::Error::new_spanned(
+                                name_value.path,
+                                "unknown validate attribute",
+                            ));
+                        }
+                    }
+                    NestedMeta::Meta(Meta::List(list)) => {
+                        validators.push(list_validator(&list)?);
+                    }
+                    NestedMeta::Lit(lit) => {
+                        return Err(syn::Error::new_spanned(lit, "unexpected literal"))
+                    }
+                }
+            }
+        }
+
+        let field_name = location.1;
+        let http_field = match location.0.as_str() {
+            "param" => quote!(::tide_validator::HttpField::param(#field_name)),
+            "header" => quote!(::tide_validator::HttpField::header(#field_name)),
+            "cookie" => quote!(::tide_validator::HttpField::cookie(#field_name)),
+            "body" => quote!(::tide_validator::HttpField::body_field(#field_name)),
+            _ => quote!(::tide_validator::HttpField::query_param(#field_name)),
+        };
+        Ok(FieldInput {
+            http_field,
+            validators,
+        })
+    }
+
+    fn registrations(self) -> Vec<TokenStream2> {
+        let http_field = self.http_field;
+        self.validators
+            .into_iter()
+            .map(|validator| quote!(#http_field, #validator))
+            .collect()
+    }
+}
+
+fn is_location(ident: &str) -> bool {
+    matches!(ident, "param" | "query" | "header" | "cookie" | "body")
+}
+
+fn path_name(path: &syn::Path) -> String {
+    path.get_ident()
+        .map(|ident| ident.to_string())
+        .unwrap_or_default()
+}
+
+fn simple_validator(ident: &str, path: &syn::Path) -> syn::Result<TokenStream2> {
+    let validator = match ident {
+        "required" => quote!(is_required),
+        "integer" => quote!(is_integer),
+        "numeric" => quote!(is_number),
+        "boolean" => quote!(is_bool),
+        "email" => quote!(is_email),
+        "uuid" => quote!(is_uuid),
+        "url" => quote!(is_url),
+        "date" => quote!(is_date),
+        _ => return Err(syn::Error::new_spanned(path, "unknown validator")),
+    };
+    Ok(quote!(::tide_validator::validators::#validator))
+}
+
+fn list_validator(list: &syn::MetaList) -> syn::Result<TokenStream2> {
+    let ident = path_name(&list.path);
+    match ident.as_str() {
+        "range" | "length" => {
+            let (mut min, mut max) = (None, None);
+            for item in &list.nested {
+                match item {
+                    NestedMeta::Meta(Meta::NameValue(bound)) if bound.path.is_ident("min") => {
+                        min = Some(bound.lit.clone())
+                    }
+                    NestedMeta::Meta(Meta::NameValue(bound)) if bound.path.is_ident("max") => {
+                        max = Some(bound.lit.clone())
+                    }
+                    other => return Err(syn::Error::new_spanned(other, "expected min or max")),
+                }
+            }
+            if ident == "range" {
+                let (min, max) = (number(min)?, number(max)?);
+                Ok(match (min, max) {
+                    (Some(min), Some(max)) => {
+                        quote!(::tide_validator::validators::is_between(#min, #max))
+                    }
+                    (Some(min), None) => quote!(::tide_validator::validators::is_min(#min)),
+                    (None, Some(max)) => quote!(::tide_validator::validators::is_max(#max)),
+                    (None, None) => {
+                        return Err(syn::Error::new_spanned(list, "expected min or max"))
+                    }
+                })
+            } else {
+                let (min, max) = (length(min)?, length(max)?);
+                Ok(match (min, max) {
+                    (Some(min), Some(max)) => {
+                        quote!(::tide_validator::validators::is_length_between(#min, #max))
+                    }
+                    (Some(min), None) => {
+                        quote!(::tide_validator::validators::is_min_length(#min))
+                    }
+                    (None, Some(max)) => {
+                        quote!(::tide_validator::validators::is_max_length(#max))
+                    }
+                    (None, None) => {
+                        return Err(syn::Error::new_spanned(list, "expected min or max"))
+                    }
+                })
+            }
+        }
+        "one_of" => {
+            let values = list
+                .nested
+                .iter()
+                .map(|item| match item {
+                    NestedMeta::Lit(Lit::Str(value)) => Ok(value.value()),
+                    other => Err(syn::Error::new_spanned(other, "expected a string")),
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            Ok(quote!(::tide_validator::validators::one_of(&[#(#values),*])))
+        }
+        _ => Err(syn::Error::new_spanned(&list.path, "unknown validator")),
+    }
+}
+
+fn number(lit: Option<Lit>) -> syn::Result<Option<f64>> {
+    match lit {
+        Some(Lit::Int(value)) => value.base10_parse().map(Some),
+        Some(Lit::Float(value)) => value.base10_parse().map(Some),
+        Some(other) => Err(syn::Error::new_spanned(other, "expected a number")),
+        None => Ok(None),
+    }
+}
+
+fn length(lit: Option<Lit>) -> syn::Result<Option<usize>> {
+    match lit {
+        Some(Lit::Int(value)) => value.base10_parse().map(Some),
+        Some(other) => Err(syn::Error::new_spanned(other, "expected an integer")),
+        None => Ok(None),
+    }
+}
+
+/// Inner type of `Option<T>`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Check the value can be parsed into the type of the field
+fn type_validator(ty: &Type) -> Option<TokenStream2> {
+    let ident = match ty {
+        Type::Path(path) => path.path.get_ident()?.to_string(),
+        _ => return None,
+    };
+    let (code, expected) = match ident.as_str() {
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128"
+        | "usize" => ("integer", "a valid integer"),
+        "f32" | "f64" => ("numeric", "a valid number"),
+        "bool" => ("boolean", "a valid boolean"),
+        _ => return None,
+    };
+    Some(quote! {
+        |field_name: &str, field_value: Option<&str>| -> Result<(), ::tide_validator::validators::ValidationError> {
+            match field_value {
+                Some(value) if value.parse::<#ty>().is_err() => {
+                    Err(::tide_validator::validators::ValidationError::new(
+                        field_name,
+                        #code,
+                        format!("field '{}' = '{}' is not {}", field_name, value, #expected),
+                    ))
+                }
+                _ => Ok(()),
+            }
+        }
+    })
+}